@@ -1,16 +1,17 @@
 use crate::error::ContractError;
 use crate::helpers::{send_tokens, GenericBalance};
+use crate::manager::is_reserved_for_other;
 use crate::state::{Config, CwCroncat};
 use cosmwasm_std::{
-    has_coins, Addr, Coin, Deps, DepsMut, Env, MessageInfo, Response, StdError, StdResult, Storage,
-    SubMsg, Uint64,
+    has_coins, Addr, BlockInfo, Coin, Deps, DepsMut, Env, Event, MessageInfo, Response, StdError,
+    StdResult, Storage, SubMsg, Uint64,
 };
 use cw20::Balance;
 use std::ops::Div;
 
 use crate::ContractError::AgentNotRegistered;
 use cw_croncat_core::msg::{AgentTaskResponse, GetAgentIdsResponse};
-use cw_croncat_core::types::{Agent, AgentResponse, AgentStatus};
+use cw_croncat_core::types::{Agent, AgentResponse, AgentStatus, SlotType};
 
 impl<'a> CwCroncat<'a> {
     /// Get a single agent details
@@ -33,6 +34,7 @@ impl<'a> CwCroncat<'a> {
             balance: a.balance,
             total_tasks_executed: a.total_tasks_executed,
             last_missed_slot: a.last_missed_slot,
+            missed_slots: a.missed_slots,
             register_start: a.register_start,
         };
 
@@ -55,6 +57,7 @@ impl<'a> CwCroncat<'a> {
     }
 
     /// Get a list of agent addresses
+    /// Both queues are in order, so an index in pending is the agent's position for nomination
     pub(crate) fn query_get_agent_ids(&self, deps: Deps) -> StdResult<GetAgentIdsResponse> {
         let active: Vec<Addr> = self.agent_active_queue.load(deps.storage)?;
         let pending: Vec<Addr> = self.agent_pending_queue.load(deps.storage)?;
@@ -62,6 +65,43 @@ impl<'a> CwCroncat<'a> {
         Ok(GetAgentIdsResponse { active, pending })
     }
 
+    /// Task hashes in the slot proxy_call executes next, block slots first, that the agent can execute now
+    /// Only active agents execute, so pending ones get nothing even after their nomination period,
+    /// and tasks reserved for another preferred agent are left out until their grace period ends
+    pub(crate) fn query_get_eligible_tasks(
+        &self,
+        deps: Deps,
+        env: Env,
+        agent_id: Addr,
+    ) -> StdResult<Vec<String>> {
+        let c: Config = self.config.load(deps.storage)?;
+        let active = self.agent_active_queue.load(deps.storage)?;
+        if c.paused || !active.contains(&agent_id) {
+            return Ok(vec![]);
+        }
+
+        let (slot_id, slot_kind) =
+            match self.get_current_slot_items(&env.block, deps.storage, Some(1)) {
+                (Some(block_id), _) => (block_id, SlotType::Block),
+                (None, Some(time_id)) => (time_id, SlotType::Cron),
+                (None, None) => return Ok(vec![]),
+            };
+        let hashes = match slot_kind {
+            SlotType::Block => self.block_slots.may_load(deps.storage, slot_id)?,
+            SlotType::Cron => self.time_slots.may_load(deps.storage, slot_id)?,
+        };
+
+        let mut eligible = vec![];
+        for hash in hashes.unwrap_or_default() {
+            if let Some(task) = self.tasks.may_load(deps.storage, hash.clone())? {
+                if !is_reserved_for_other(&c, &task, &agent_id, slot_id, &slot_kind, &env.block) {
+                    eligible.push(String::from_utf8(hash).unwrap_or_default());
+                }
+            }
+        }
+        Ok(eligible)
+    }
+
     // TODO: Change this to solid round-table implementation. Setup this simple version for PoC
     /// Get how many tasks an agent can execute
     pub(crate) fn query_get_agent_tasks(
@@ -203,6 +243,7 @@ impl<'a> CwCroncat<'a> {
                             balance: GenericBalance::default(),
                             total_tasks_executed: 0,
                             last_missed_slot: 0,
+                            missed_slots: 0,
                             // REF: https://github.com/CosmWasm/cosmwasm/blob/main/packages/std/src/types.rs#L57
                             register_start: env.block.time,
                         })
@@ -250,6 +291,63 @@ impl<'a> CwCroncat<'a> {
         Ok(Response::new().add_attribute("method", "update_agent"))
     }
 
+    /// Counts an overdue slot as missed by every active agent but the executing one, once per slot
+    /// Agents missing more than agents_eject_threshold slots in a row are moved to the end of
+    /// the pending queue, returns an "agent_ejected" event for each of them
+    pub(crate) fn track_missed_slot(
+        &self,
+        storage: &mut dyn Storage,
+        c: &Config,
+        executor: &Addr,
+        slot_id: u64,
+        slot_kind: &SlotType,
+        block: &BlockInfo,
+    ) -> StdResult<Vec<Event>> {
+        let overdue = match slot_kind {
+            SlotType::Block => slot_id < block.height,
+            SlotType::Cron => slot_id.saturating_add(c.slot_granularity) < block.time.nanos(),
+        };
+        if !overdue {
+            return Ok(vec![]);
+        }
+
+        let mut active: Vec<Addr> = self.agent_active_queue.load(storage)?;
+        let mut ejected: Vec<(Addr, u64)> = vec![];
+        for agent_id in active.iter().filter(|a| *a != executor) {
+            let mut agent = match self.agents.may_load(storage, agent_id.clone())? {
+                Some(agent) => agent,
+                None => continue,
+            };
+            if agent.last_missed_slot == slot_id {
+                continue;
+            }
+            agent.last_missed_slot = slot_id;
+            agent.missed_slots = agent.missed_slots.saturating_add(1);
+            if agent.missed_slots > c.agents_eject_threshold {
+                ejected.push((agent_id.clone(), agent.missed_slots));
+            }
+            self.agents.save(storage, agent_id.clone(), &agent)?;
+        }
+        if ejected.is_empty() {
+            return Ok(vec![]);
+        }
+
+        active.retain(|a| !ejected.iter().any(|(id, _)| id == a));
+        self.agent_active_queue.save(storage, &active)?;
+        let mut pending: Vec<Addr> = self.agent_pending_queue.load(storage)?;
+        pending.extend(ejected.iter().map(|(id, _)| id.clone()));
+        self.agent_pending_queue.save(storage, &pending)?;
+
+        Ok(ejected
+            .into_iter()
+            .map(|(agent_id, missed_slots)| {
+                Event::new("agent_ejected")
+                    .add_attribute("agent_id", agent_id)
+                    .add_attribute("missed_slots", missed_slots.to_string())
+            })
+            .collect())
+    }
+
     /// Allows an agent to withdraw all rewards, paid to the specified payable account id.
     pub(crate) fn withdraw_balances(
         &self,
@@ -265,6 +363,9 @@ impl<'a> CwCroncat<'a> {
         // This will send all token balances to Agent
         let (messages, balances) = send_tokens(&agent.payable_account_id, &agent.balance)?;
         let mut config = self.config.load(storage)?;
+        config
+            .surplus_balance
+            .saturating_minus_tokens(Balance::from(balances.native.clone()));
         config
             .available_balance
             .minus_tokens(Balance::from(balances.native));
@@ -409,11 +510,13 @@ impl<'a> CwCroncat<'a> {
 mod tests {
     use super::*;
     use crate::error::ContractError;
+    use crate::helpers::test_helpers::{settings_msg, SettingsUpdate};
     use crate::helpers::CwTemplateContract;
+    use crate::state::default_execution_grace_blocks;
     use cosmwasm_std::testing::{mock_env, mock_info, MOCK_CONTRACT_ADDR};
     use cosmwasm_std::{coin, coins, from_slice, Addr, BlockInfo, CosmosMsg, Empty, StakingMsg};
     use cw_croncat_core::msg::{ExecuteMsg, InstantiateMsg, QueryMsg, TaskRequest, TaskResponse};
-    use cw_croncat_core::types::{Action, Boundary, Interval};
+    use cw_croncat_core::types::{Action, Boundary, FailMode, Interval};
     use cw_multi_test::{App, AppBuilder, AppResponse, Contract, ContractWrapper, Executor};
 
     pub fn contract_template() -> Box<dyn Contract<Empty>> {
@@ -499,6 +602,7 @@ mod tests {
                 &QueryMsg::GetTasks {
                     from_index: None,
                     limit: None,
+                    order_by: None,
                 },
             )
             .unwrap();
@@ -522,6 +626,15 @@ mod tests {
                         end: None,
                     },
                     stop_on_fail: false,
+                    allow_sponsored_refill: false,
+                    refund_split: None,
+                    preferred_agent: None,
+                    fail_mode: FailMode::StopOnFirstFail,
+                    agent_fee_override: None,
+                    on_success_task: None,
+                    label: None,
+                    memo: None,
+                    return_surplus: false,
                     actions: vec![Action {
                         msg,
                         gas_limit: Some(150_000),
@@ -556,6 +669,15 @@ mod tests {
                         end: None,
                     },
                     stop_on_fail: false,
+                    allow_sponsored_refill: false,
+                    refund_split: None,
+                    preferred_agent: None,
+                    fail_mode: FailMode::StopOnFirstFail,
+                    agent_fee_override: None,
+                    on_success_task: None,
+                    label: None,
+                    memo: None,
+                    return_surplus: false,
                     actions: vec![Action {
                         msg,
                         gas_limit: Some(150_000),
@@ -584,12 +706,21 @@ mod tests {
             contract_addr.clone(),
             &ExecuteMsg::CreateTask {
                 task: TaskRequest {
-                    interval: Interval::Cron(format!("* {} * * * *", num_minutes)),
+                    interval: Interval::Cron(format!("0 {} * * * *", num_minutes)),
                     boundary: Boundary {
                         start: None,
                         end: None,
                     },
                     stop_on_fail: false,
+                    allow_sponsored_refill: false,
+                    refund_split: None,
+                    preferred_agent: None,
+                    fail_mode: FailMode::StopOnFirstFail,
+                    agent_fee_override: None,
+                    on_success_task: None,
+                    label: None,
+                    memo: None,
+                    return_surplus: false,
                     actions: vec![Action {
                         msg,
                         gas_limit: Some(150_000),
@@ -625,6 +756,15 @@ mod tests {
                     end: None,
                 },
                 stop_on_fail: false,
+                allow_sponsored_refill: false,
+                refund_split: None,
+                preferred_agent: None,
+                fail_mode: FailMode::StopOnFirstFail,
+                agent_fee_override: None,
+                on_success_task: None,
+                label: None,
+                memo: None,
+                return_surplus: false,
                 actions: vec![Action {
                     msg: msg.clone(),
                     gas_limit: Some(150_000),
@@ -742,17 +882,10 @@ mod tests {
         );
 
         // Test Can't register if contract is paused
-        let payload_1 = ExecuteMsg::UpdateSettings {
+        let payload_1 = settings_msg(SettingsUpdate {
             paused: Some(true),
-            owner_id: None,
-            // treasury_id: None,
-            agent_fee: None,
-            min_tasks_per_agent: None,
-            agents_eject_threshold: None,
-            gas_price: None,
-            proxy_callback_gas: None,
-            slot_granularity: None,
-        };
+            ..Default::default()
+        });
 
         app.execute_contract(
             Addr::unchecked(ADMIN),
@@ -772,17 +905,10 @@ mod tests {
         );
 
         // Test wallet rejected if doesnt have enough funds
-        let payload_2 = ExecuteMsg::UpdateSettings {
+        let payload_2 = settings_msg(SettingsUpdate {
             paused: Some(false),
-            owner_id: None,
-            // treasury_id: None,
-            agent_fee: None,
-            min_tasks_per_agent: None,
-            agents_eject_threshold: None,
-            gas_price: None,
-            proxy_callback_gas: None,
-            slot_granularity: None,
-        };
+            ..Default::default()
+        });
 
         app.execute_contract(
             Addr::unchecked(ADMIN),
@@ -866,6 +992,49 @@ mod tests {
         assert_eq!(1, num_pending_agents);
     }
 
+    #[test]
+    fn query_agent_queue() {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+
+        register_agent_exec(&mut app, &contract_addr, AGENT1, AGENT_BENEFICIARY);
+        register_agent_exec(&mut app, &contract_addr, AGENT2, AGENT_BENEFICIARY);
+        register_agent_exec(&mut app, &contract_addr, AGENT3, AGENT_BENEFICIARY);
+
+        let (agent_ids, _, _) = get_agent_ids(&app, &contract_addr);
+        assert_eq!(
+            GetAgentIdsResponse {
+                active: vec![Addr::unchecked(AGENT1)],
+                pending: vec![Addr::unchecked(AGENT2), Addr::unchecked(AGENT3)],
+            },
+            agent_ids
+        );
+        assert_eq!(
+            AgentStatus::Active,
+            get_stored_agent_status(&mut app, &contract_addr, AGENT1)
+        );
+        assert_eq!(
+            AgentStatus::Pending,
+            get_stored_agent_status(&mut app, &contract_addr, AGENT2)
+        );
+        assert_eq!(
+            AgentStatus::Pending,
+            get_stored_agent_status(&mut app, &contract_addr, AGENT3)
+        );
+
+        // Unknown agents aren't an error
+        let unknown: Option<AgentResponse> = app
+            .wrap()
+            .query_wasm_smart(
+                &contract_addr,
+                &QueryMsg::GetAgent {
+                    account_id: Addr::unchecked(AGENT4),
+                },
+            )
+            .unwrap();
+        assert_eq!(None, unknown);
+    }
+
     #[test]
     fn update_agent() {
         let (mut app, cw_template_contract) = proper_instantiate();
@@ -1352,4 +1521,200 @@ mod tests {
             .query_wasm_smart(contract_addr.clone(), &msg_agent_tasks);
         println!("aloha query_task_res {:?}", query_task_res);
     }
+
+    #[test]
+    fn query_get_eligible_tasks() {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+        let eligible_tasks = |app: &App, agent: &str| -> Vec<String> {
+            app.wrap()
+                .query_wasm_smart(
+                    contract_addr.clone(),
+                    &QueryMsg::GetEligibleTasks {
+                        agent_id: Addr::unchecked(agent),
+                    },
+                )
+                .unwrap()
+        };
+        let task_hash = |res: AppResponse| -> String {
+            res.events
+                .into_iter()
+                .flat_map(|e| e.attributes)
+                .find(|a| a.key == "task_hash")
+                .unwrap()
+                .value
+        };
+
+        // AGENT1 is active, AGENT2 waits in the pending queue
+        register_agent_exec(&mut app, &contract_addr, AGENT1, AGENT_BENEFICIARY);
+        register_agent_exec(&mut app, &contract_addr, AGENT2, AGENT_BENEFICIARY);
+        let (ids, _, _) = get_agent_ids(&app, &contract_addr);
+        assert_eq!(vec![Addr::unchecked(AGENT2)], ids.pending);
+        assert!(eligible_tasks(&app, AGENT1).is_empty());
+
+        // Immediate tasks are due in the current block slot, one is reserved for AGENT3
+        let open_hash = task_hash(add_task_exec(&mut app, &contract_addr, PARTICIPANT0));
+        let reserved_hash = task_hash(
+            app.execute_contract(
+                Addr::unchecked(PARTICIPANT1),
+                contract_addr.clone(),
+                &ExecuteMsg::CreateTask {
+                    task: TaskRequest {
+                        interval: Interval::Immediate,
+                        boundary: Boundary {
+                            start: None,
+                            end: None,
+                        },
+                        stop_on_fail: false,
+                        allow_sponsored_refill: false,
+                        refund_split: None,
+                        preferred_agent: Some(Addr::unchecked(AGENT3)),
+                        fail_mode: FailMode::StopOnFirstFail,
+                        agent_fee_override: None,
+                        on_success_task: None,
+                        label: None,
+                        memo: None,
+                        return_surplus: false,
+                        actions: vec![Action {
+                            msg: StakingMsg::Delegate {
+                                validator: "you".to_string(),
+                                amount: coin(4, NATIVE_DENOM),
+                            }
+                            .into(),
+                            gas_limit: Some(150_000),
+                        }],
+                        rules: None,
+                    },
+                },
+                &coins(1, NATIVE_DENOM),
+            )
+            .unwrap(),
+        );
+
+        // the active agent gets the open task, pending & unknown agents get nothing
+        assert_eq!(vec![open_hash.clone()], eligible_tasks(&app, AGENT1));
+        assert!(eligible_tasks(&app, AGENT2).is_empty());
+        assert!(eligible_tasks(&app, AGENT3).is_empty());
+
+        // once the grace period is over any active agent can take the reserved task
+        app.update_block(|block| block.height += default_execution_grace_blocks());
+        assert_eq!(vec![open_hash, reserved_hash], eligible_tasks(&app, AGENT1));
+    }
+
+    #[test]
+    fn track_missed_slot_ejects_agent() -> StdResult<()> {
+        let mut deps = cosmwasm_std::testing::mock_dependencies_with_balances(&[
+            (MOCK_CONTRACT_ADDR, &[coin(6000, NATIVE_DENOM)]),
+            (AGENT0, &[coin(600, NATIVE_DENOM)]),
+            (AGENT1, &[coin(600, NATIVE_DENOM)]),
+        ]);
+        let mut contract = CwCroncat::default();
+        contract
+            .instantiate(
+                deps.as_mut(),
+                mock_env(),
+                mock_info(ADMIN, &[]),
+                InstantiateMsg {
+                    denom: NATIVE_DENOM.to_string(),
+                    owner_id: None,
+                    agent_nomination_duration: None,
+                },
+            )
+            .unwrap();
+        contract_register_agent(AGENT0, &mut contract, deps.as_mut()).unwrap();
+        contract_register_agent(AGENT1, &mut contract, deps.as_mut()).unwrap();
+        // both agents active, any of them can miss slots
+        contract.agent_active_queue.save(
+            &mut deps.storage,
+            &vec![Addr::unchecked(AGENT0), Addr::unchecked(AGENT1)],
+        )?;
+        contract
+            .agent_pending_queue
+            .save(&mut deps.storage, &vec![])?;
+        let mut c = contract.config.load(&deps.storage)?;
+        c.agents_eject_threshold = 2;
+        contract.config.save(&mut deps.storage, &c)?;
+
+        let block = mock_env().block;
+        let agent0 = Addr::unchecked(AGENT0);
+        let agent1 = Addr::unchecked(AGENT1);
+        let missed_slots = |deps: &cosmwasm_std::OwnedDeps<_, _, _>| {
+            contract
+                .agents
+                .load(&deps.storage, agent1.clone())
+                .unwrap()
+                .missed_slots
+        };
+
+        // a due slot isn't missed by anyone
+        let events = contract.track_missed_slot(
+            &mut deps.storage,
+            &c,
+            &agent0,
+            block.height,
+            &SlotType::Block,
+            &block,
+        )?;
+        assert!(events.is_empty());
+        assert_eq!(0, missed_slots(&deps));
+
+        // AGENT0 executes overdue slots, AGENT1 missed them, each slot counts once
+        for slot_id in [block.height - 5, block.height - 5, block.height - 4] {
+            let events = contract.track_missed_slot(
+                &mut deps.storage,
+                &c,
+                &agent0,
+                slot_id,
+                &SlotType::Block,
+                &block,
+            )?;
+            assert!(events.is_empty());
+        }
+        assert_eq!(2, missed_slots(&deps));
+        assert_eq!(
+            0,
+            contract
+                .agents
+                .load(&deps.storage, agent0.clone())?
+                .missed_slots
+        );
+
+        // executing again resets the streak
+        let agent = contract.agents.load(&deps.storage, agent1.clone())?;
+        contract.send_base_agent_reward(&mut deps.storage, agent, mock_info(AGENT1, &[]), None);
+        assert_eq!(0, missed_slots(&deps));
+
+        // going past agents_eject_threshold ejects the agent back to pending
+        for slot_id in [block.height - 3, block.height - 2] {
+            let events = contract.track_missed_slot(
+                &mut deps.storage,
+                &c,
+                &agent0,
+                slot_id,
+                &SlotType::Block,
+                &block,
+            )?;
+            assert!(events.is_empty());
+        }
+        let events = contract.track_missed_slot(
+            &mut deps.storage,
+            &c,
+            &agent0,
+            block.height - 1,
+            &SlotType::Block,
+            &block,
+        )?;
+        assert_eq!(
+            vec![Event::new("agent_ejected")
+                .add_attribute("agent_id", AGENT1)
+                .add_attribute("missed_slots", "3")],
+            events
+        );
+        let ids = contract.query_get_agent_ids(deps.as_ref())?;
+        assert_eq!(vec![agent0.clone()], ids.active);
+        assert_eq!(vec![agent1.clone()], ids.pending);
+        assert_eq!(3, missed_slots(&deps));
+
+        Ok(())
+    }
 }
@@ -163,12 +163,14 @@ mod tests {
     use cosmwasm_std::testing::{
         mock_dependencies_with_balance, mock_env, mock_info, MockStorage, MOCK_CONTRACT_ADDR,
     };
+    use cosmwasm_std::Uint128;
     use cosmwasm_std::{
         coin, coins, from_slice, Addr, BlockInfo, Coin, CosmosMsg, Empty, StakingMsg,
     };
     use cw_croncat_core::types::{Agent, SlotType, Task};
 
     use cw_croncat_core::msg::{ExecuteMsg, InstantiateMsg, QueryMsg, TaskRequest, TaskResponse};
+    use cw_croncat_core::types::MsgKind;
     use cw_croncat_core::types::{Action, Boundary, Interval};
     use cw_multi_test::{App, AppBuilder, AppResponse, Contract, ContractWrapper, Executor};
 
@@ -185,15 +187,32 @@ mod tests {
         Config {
             paused: false,
             owner_id: Addr::unchecked(ADMIN),
-            // treasury_id: None,
+            treasury_id: None,
+            treasury_fee: 0,
             min_tasks_per_agent: 3,
             agent_active_indices: Vec::<(SlotType, u32, u32)>::with_capacity(0),
             agents_eject_threshold: 600, // how many slots an agent can miss before being ejected. 10 * 60 = 1hr
             available_balance: GenericBalance::default(),
             staked_balance: GenericBalance::default(),
+            protocol_balance: GenericBalance::default(),
+            surplus_balance: GenericBalance::default(),
             agent_fee: Coin::new(5, NATIVE_DENOM.clone()), // TODO: CHANGE AMOUNT HERE!!! 0.0005 Juno (2000 tasks = 1 Juno)
             gas_price: 1,
             proxy_callback_gas: 3,
+            max_action_gas: 3_000_000,
+            default_gas_limit: 150_000,
+            max_tasks_per_owner: None,
+            max_tasks_per_slot: None,
+            max_failure_streak: None,
+            refund_on_remove: true,
+            min_refill_amount: None,
+            dust_threshold: Uint128::zero(),
+            execution_grace_blocks: 10,
+            execution_grace_seconds: 60,
+            min_task_deposit: None,
+            max_boundary_horizon: None,
+            reject_duplicate_actions: false,
+            allowed_msg_kinds: MsgKind::all(),
             slot_granularity: 60_000_000_000,
             native_denom: NATIVE_DENOM.to_owned(),
             cw20_whitelist: vec![],
@@ -1,14 +1,19 @@
 use crate::error::ContractError;
 use crate::helpers::GenericBalance;
+use crate::state::{
+    default_action_gas_limit, default_execution_grace_blocks, default_execution_grace_seconds,
+    default_max_action_gas, default_max_failure_streak, default_refund_on_remove,
+};
 use crate::state::{Config, CwCroncat};
 #[cfg(not(feature = "library"))]
 use cosmwasm_std::{
     to_binary, Binary, Coin, Deps, DepsMut, Env, MessageInfo, Reply, Response, StdResult,
 };
-use cw2::set_contract_version;
+use cosmwasm_std::{Order, Uint128};
+use cw2::{get_contract_version, set_contract_version};
 use cw20::Balance;
-use cw_croncat_core::msg::{ExecuteMsg, InstantiateMsg, QueryMsg};
-use cw_croncat_core::types::SlotType;
+use cw_croncat_core::msg::{ExecuteMsg, InstantiateMsg, MigrateMsg, QueryMsg};
+use cw_croncat_core::types::{MsgKind, SlotType};
 
 // version info for migration info
 const CONTRACT_NAME: &str = "crates.io:cw-croncat";
@@ -40,15 +45,32 @@ impl<'a> CwCroncat<'a> {
         let config = Config {
             paused: false,
             owner_id: owner_acct,
-            // treasury_id: None,
+            treasury_id: None,
+            treasury_fee: 0,
             min_tasks_per_agent: 3,
             agent_active_indices: vec![(SlotType::Block, 0, 0), (SlotType::Cron, 0, 0)],
             agents_eject_threshold: 600, // how many slots an agent can miss before being ejected. 10 * 60 = 1hr
+            surplus_balance: available_balance.clone(),
             available_balance,
             staked_balance: GenericBalance::default(),
+            protocol_balance: GenericBalance::default(),
             agent_fee: Coin::new(5, msg.denom.clone()), // TODO: CHANGE AMOUNT HERE!!! 0.0005 Juno (2000 tasks = 1 Juno)
             gas_price: 1,
             proxy_callback_gas: 3,
+            max_action_gas: default_max_action_gas(),
+            default_gas_limit: default_action_gas_limit(),
+            max_tasks_per_owner: None,
+            max_tasks_per_slot: None,
+            max_failure_streak: default_max_failure_streak(),
+            refund_on_remove: default_refund_on_remove(),
+            min_refill_amount: None,
+            dust_threshold: Uint128::zero(),
+            execution_grace_blocks: default_execution_grace_blocks(),
+            execution_grace_seconds: default_execution_grace_seconds(),
+            min_task_deposit: None,
+            max_boundary_horizon: None,
+            reject_duplicate_actions: false,
+            allowed_msg_kinds: MsgKind::all(),
             slot_granularity: 60_000_000_000,
             native_denom: msg.denom,
             cw20_whitelist: vec![],
@@ -80,6 +102,7 @@ impl<'a> CwCroncat<'a> {
             //         .unwrap_or_else(|| Addr::unchecked(""))
             //         .to_string(),
             // )
+            .add_attribute("treasury_fee", config.treasury_fee.to_string())
             .add_attribute(
                 "min_tasks_per_agent",
                 config.min_tasks_per_agent.to_string(),
@@ -100,6 +123,8 @@ impl<'a> CwCroncat<'a> {
             .add_attribute("agent_fee", config.agent_fee.to_string())
             .add_attribute("gas_price", config.gas_price.to_string())
             .add_attribute("proxy_callback_gas", config.proxy_callback_gas.to_string())
+            .add_attribute("max_action_gas", config.max_action_gas.to_string())
+            .add_attribute("default_gas_limit", config.default_gas_limit.to_string())
             .add_attribute("slot_granularity", config.slot_granularity.to_string()))
     }
 
@@ -110,12 +135,41 @@ impl<'a> CwCroncat<'a> {
         info: MessageInfo,
         msg: ExecuteMsg,
     ) -> Result<Response, ContractError> {
+        // Task actions could call back in while slots & tasks are mid-execution
+        let touches_tasks = matches!(
+            msg,
+            ExecuteMsg::CreateTask { .. }
+                | ExecuteMsg::RemoveTask { .. }
+                | ExecuteMsg::RemoveTasks { .. }
+                | ExecuteMsg::AdminRemoveTask { .. }
+                | ExecuteMsg::CompactSlots {}
+                | ExecuteMsg::RefillTaskBalance { .. }
+                | ExecuteMsg::ClaimEndedTask { .. }
+                | ExecuteMsg::UpdateTaskGas { .. }
+                | ExecuteMsg::TransferTaskOwnership { .. }
+                | ExecuteMsg::ProxyCall {}
+        );
+        if touches_tasks
+            && self
+                .proxy_in_progress
+                .may_load(deps.storage)?
+                .unwrap_or_default()
+        {
+            return Err(ContractError::ReentrantCall {});
+        }
+
         match msg {
             ExecuteMsg::UpdateSettings { .. } => self.update_settings(deps, info, msg),
             ExecuteMsg::MoveBalances {
                 balances,
                 account_id,
             } => self.move_balances(deps, info, env, balances, account_id),
+            ExecuteMsg::OwnerWithdraw { amount } => self.owner_withdraw(deps, info, amount),
+            ExecuteMsg::DepositFunds {} => self.deposit_funds(deps, info),
+            ExecuteMsg::AdminRemoveTask { task_hash } => {
+                self.admin_remove_task(deps, info, task_hash)
+            }
+            ExecuteMsg::CompactSlots {} => self.compact_slots(deps, info),
 
             ExecuteMsg::RegisterAgent { payable_account_id } => {
                 self.register_agent(deps, info, env, payable_account_id)
@@ -128,8 +182,25 @@ impl<'a> CwCroncat<'a> {
             ExecuteMsg::CheckInAgent {} => self.accept_nomination_agent(deps, info, env),
 
             ExecuteMsg::CreateTask { task } => self.create_task(deps, info, env, task),
-            ExecuteMsg::RemoveTask { task_hash } => self.remove_task(deps, task_hash),
-            ExecuteMsg::RefillTaskBalance { task_hash } => self.refill_task(deps, info, task_hash),
+            ExecuteMsg::RemoveTask { task_hash } => self.owner_remove_task(deps, info, task_hash),
+            ExecuteMsg::RemoveTasks { task_hashes } => {
+                self.owner_remove_tasks(deps, info, task_hashes)
+            }
+            ExecuteMsg::RefillTaskBalance {
+                task_hash,
+                allow_new_denoms,
+            } => self.refill_task(deps, info, task_hash, allow_new_denoms.unwrap_or_default()),
+            ExecuteMsg::ClaimEndedTask { task_hash } => {
+                self.claim_ended_task(deps, info, env, task_hash)
+            }
+            ExecuteMsg::UpdateTaskGas {
+                task_hash,
+                gas_limits,
+            } => self.update_task_gas(deps, info, task_hash, gas_limits),
+            ExecuteMsg::TransferTaskOwnership {
+                task_hash,
+                new_owner,
+            } => self.transfer_task_ownership(deps, info, task_hash, new_owner),
             ExecuteMsg::ProxyCall {} => self.proxy_call(deps, info, env),
         }
     }
@@ -146,20 +217,131 @@ impl<'a> CwCroncat<'a> {
             QueryMsg::GetAgentTasks { account_id } => {
                 to_binary(&self.query_get_agent_tasks(deps, env, account_id)?)
             }
+            QueryMsg::GetEligibleTasks { agent_id } => {
+                to_binary(&self.query_get_eligible_tasks(deps, env, agent_id)?)
+            }
 
-            QueryMsg::GetTasks { from_index, limit } => {
-                to_binary(&self.query_get_tasks(deps, from_index, limit)?)
+            QueryMsg::GetTasks {
+                from_index,
+                limit,
+                order_by,
+            } => to_binary(&self.query_get_tasks(deps, env, from_index, limit, order_by)?),
+            QueryMsg::GetTasksFrom { start_after, limit } => {
+                to_binary(&self.query_get_tasks_from(deps, env, start_after, limit)?)
             }
+            QueryMsg::GetTasksPage { start_after, limit } => {
+                to_binary(&self.query_get_tasks_page(deps, env, start_after, limit)?)
+            }
+            QueryMsg::GetTasksByHashPrefix { prefix, limit } => {
+                to_binary(&self.query_get_tasks_by_hash_prefix(deps, env, prefix, limit)?)
+            }
+            QueryMsg::GetTasksWithRules { from_index, limit } => {
+                to_binary(&self.query_get_tasks_with_rules(deps, env, from_index, limit)?)
+            }
+            QueryMsg::GetTasksByDenom {
+                denom,
+                from_index,
+                limit,
+            } => to_binary(&self.query_get_tasks_by_denom(deps, env, denom, from_index, limit)?),
+            QueryMsg::GetTasksUnderGas {
+                max_gas,
+                from_index,
+                limit,
+            } => to_binary(&self.query_get_tasks_under_gas(deps, env, max_gas, from_index, limit)?),
+            QueryMsg::GetTasksByBoundaryEnd {
+                start,
+                end,
+                from_index,
+                limit,
+            } => to_binary(
+                &self.query_get_tasks_by_boundary_end(deps, env, start, end, from_index, limit)?,
+            ),
+            QueryMsg::GetTasksCreatedBetween {
+                start,
+                end,
+                from_index,
+                limit,
+            } => to_binary(
+                &self.query_get_tasks_created_between(deps, env, start, end, from_index, limit)?,
+            ),
             QueryMsg::GetTasksByOwner { owner_id } => {
-                to_binary(&self.query_get_tasks_by_owner(deps, owner_id)?)
+                to_binary(&self.query_get_tasks_by_owner(deps, env, owner_id)?)
+            }
+            QueryMsg::GetTasksForAgent {
+                agent_id,
+                from_index,
+                limit,
+            } => {
+                to_binary(&self.query_get_tasks_for_agent(deps, env, agent_id, from_index, limit)?)
+            }
+            QueryMsg::GetTaskCount {} => to_binary(&self.query_get_task_count(deps)?),
+            QueryMsg::GetOwnerTaskCount { owner_id } => {
+                to_binary(&self.query_get_owner_task_count(deps, owner_id)?)
+            }
+            QueryMsg::GetOwnerSummary { owner_id } => {
+                to_binary(&self.query_owner_summary(deps, env, owner_id)?)
+            }
+            QueryMsg::GetTasksByNextExecution { limit } => {
+                to_binary(&self.query_get_tasks_by_next_execution(deps, env, limit)?)
+            }
+            QueryMsg::GetTask { task_hash } => {
+                to_binary(&self.query_get_task(deps, env, task_hash)?)
+            }
+            QueryMsg::GetTasksByHashes { task_hashes } => {
+                to_binary(&self.query_get_tasks_by_hashes(deps, env, task_hashes)?)
+            }
+            QueryMsg::GetTaskRaw { task_hash } => {
+                to_binary(&self.query_get_task_raw(deps, env, task_hash)?)
             }
-            QueryMsg::GetTask { task_hash } => to_binary(&self.query_get_task(deps, task_hash)?),
             QueryMsg::GetTaskHash { task } => to_binary(&self.query_get_task_hash(*task)?),
+            QueryMsg::GetTaskSchedule { task_hash } => {
+                to_binary(&self.query_get_task_schedule(deps, task_hash)?)
+            }
+            QueryMsg::GetTaskCost { task_hash } => {
+                to_binary(&self.query_get_task_cost(deps, task_hash)?)
+            }
+            QueryMsg::CheckTaskRules { task_hash } => {
+                to_binary(&self.query_check_task_rules(deps, task_hash)?)
+            }
             QueryMsg::ValidateInterval { interval } => {
-                to_binary(&self.query_validate_interval(interval)?)
+                to_binary(&self.query_validate_interval(deps, env, interval)?)
+            }
+            QueryMsg::ValidateTask {
+                task,
+                funds,
+                owner_id,
+            } => to_binary(&self.query_validate_task(deps, env, *task, funds, owner_id)?),
+            QueryMsg::GetSlotHashes {
+                slot,
+                from_index,
+                limit,
+                due_only,
+            } => to_binary(&self.query_slot_tasks(
+                deps,
+                slot,
+                from_index,
+                limit,
+                due_only.then_some(&env.block),
+            )?),
+            QueryMsg::GetTaskHashes { slots } => {
+                to_binary(&self.query_get_task_hashes(deps, slots)?)
             }
-            QueryMsg::GetSlotHashes { slot } => to_binary(&self.query_slot_tasks(deps, slot)?),
+            QueryMsg::GetAllSlots {
+                slot_type,
+                from_index,
+                limit,
+            } => to_binary(&self.query_get_all_slots(deps, slot_type, from_index, limit)?),
             QueryMsg::GetSlotIds {} => to_binary(&self.query_slot_ids(deps)?),
+            QueryMsg::GetSlotStats {} => to_binary(&self.query_slot_stats(deps)?),
+            QueryMsg::GetSlotGas { slot_type, slot_id } => {
+                to_binary(&self.query_slot_gas(deps, slot_type, slot_id)?)
+            }
+            QueryMsg::GetHorizon {} => to_binary(&self.query_horizon(deps, env)?),
+            QueryMsg::GetStuckTasks {} => to_binary(&self.query_stuck_tasks(deps, env)?),
+            QueryMsg::GetDenoms {} => to_binary(&self.query_get_denoms(deps)?),
+            QueryMsg::GetBalancerInvariant {} => to_binary(&self.query_balancer_invariant(deps)?),
+            QueryMsg::GetDenomBalances {} => to_binary(&self.query_denom_balances(deps)?),
+            QueryMsg::GetFeeSchedule {} => to_binary(&self.query_fee_schedule(deps)?),
         }
     }
 
@@ -177,14 +359,50 @@ impl<'a> CwCroncat<'a> {
 
         // If contract_addr matches THIS contract, it is the proxy callback
         // proxy_callback is also responsible for handling reply modes: "handle_failure", "handle_success"
-        if item.contract_addr.is_some() && item.contract_addr.unwrap() == env.contract.address {
-            return self.proxy_callback(deps, env, msg, item.task_hash.unwrap());
+        if item.contract_addr.as_ref() == Some(&env.contract.address) {
+            return self.proxy_callback(deps, env, msg, item);
         }
 
         // NOTE: Currently only handling proxy callbacks
         // Responds with the reply ID if nothing was found in queue
         Ok(Response::new().add_attribute("reply_id", msg.id.to_string()))
     }
+
+    /// Brings stored state to the current shape & bumps the contract version
+    /// Fields added since the stored version deserialize to their defaults (see Config & Task),
+    /// so everything is loaded & saved back, which also rebuilds the task owner index
+    pub fn migrate(
+        &self,
+        deps: DepsMut,
+        _env: Env,
+        _msg: MigrateMsg,
+    ) -> Result<Response, ContractError> {
+        let previous = get_contract_version(deps.storage)?;
+        if previous.contract != CONTRACT_NAME {
+            return Err(ContractError::CustomError {
+                val: format!("Can't migrate from {}", previous.contract),
+            });
+        }
+
+        let config: Config = self.config.load(deps.storage)?;
+        self.config.save(deps.storage, &config)?;
+
+        let tasks = self
+            .tasks
+            .range(deps.storage, None, None, Order::Ascending)
+            .collect::<StdResult<Vec<_>>>()?;
+        for (hash, task) in tasks.iter() {
+            self.tasks.save(deps.storage, hash.clone(), task)?;
+        }
+        self.task_total.save(deps.storage, &(tasks.len() as u64))?;
+
+        set_contract_version(deps.storage, CONTRACT_NAME, CONTRACT_VERSION)?;
+        Ok(Response::new()
+            .add_attribute("method", "migrate")
+            .add_attribute("previous_version", previous.version)
+            .add_attribute("version", CONTRACT_VERSION)
+            .add_attribute("migrated_tasks", tasks.len().to_string()))
+    }
 }
 
 #[cfg(test)]
@@ -192,14 +410,19 @@ mod tests {
     use super::*;
     use crate::helpers::test_helpers::mock_init;
     use crate::state::QueueItem;
+    use cosmwasm_std::testing::mock_dependencies;
     use cosmwasm_std::testing::{
         mock_dependencies_with_balance, mock_env, mock_info, MOCK_CONTRACT_ADDR,
     };
+    use cosmwasm_std::BankMsg;
     use cosmwasm_std::{
         coin, coins, from_binary, Addr, Binary, Event, Reply, SubMsgResponse, SubMsgResult,
     };
     use cw_croncat_core::msg::{GetConfigResponse, QueryMsg};
     use cw_croncat_core::types::SlotType;
+    use cw_croncat_core::types::{Action, Boundary, FailMode, Interval, Rule, Task};
+    use cw_storage_plus::{Item, Map};
+    use serde::{Deserialize, Serialize};
 
     #[test]
     fn configure() {
@@ -272,6 +495,9 @@ mod tests {
                     prev_idx: None,
                     task_hash: Some(task_hash.clone()),
                     contract_addr: None,
+                    slot_id: None,
+                    action_idx: 0,
+                    failed_actions: vec![],
                 },
             )
             .unwrap();
@@ -304,6 +530,9 @@ mod tests {
                     prev_idx: None,
                     task_hash: Some(task_hash),
                     contract_addr: Some(Addr::unchecked(MOCK_CONTRACT_ADDR)),
+                    slot_id: None,
+                    action_idx: 0,
+                    failed_actions: vec![],
                 },
             )
             .unwrap();
@@ -324,4 +553,155 @@ mod tests {
             .unwrap();
         assert!(queue_item4.is_none());
     }
+
+    #[test]
+    fn migrate_from_old_state() -> StdResult<()> {
+        // Config & Task as stored by the first release
+        #[derive(Serialize, Deserialize)]
+        struct OldConfig {
+            paused: bool,
+            owner_id: Addr,
+            min_tasks_per_agent: u64,
+            agent_active_indices: Vec<(SlotType, u32, u32)>,
+            agents_eject_threshold: u64,
+            agent_nomination_duration: u16,
+            agent_fee: Coin,
+            gas_price: u32,
+            proxy_callback_gas: u32,
+            slot_granularity: u64,
+            cw20_whitelist: Vec<Addr>,
+            native_denom: String,
+            available_balance: GenericBalance,
+            staked_balance: GenericBalance,
+        }
+        #[derive(Serialize, Deserialize)]
+        struct OldTask {
+            owner_id: Addr,
+            interval: Interval,
+            boundary: Boundary,
+            stop_on_fail: bool,
+            total_deposit: Vec<Coin>,
+            actions: Vec<Action>,
+            rules: Option<Vec<Rule>>,
+        }
+
+        let mut deps = mock_dependencies();
+        let store = CwCroncat::default();
+        Item::<OldConfig>::new("config").save(
+            deps.as_mut().storage,
+            &OldConfig {
+                paused: false,
+                owner_id: Addr::unchecked("owner"),
+                min_tasks_per_agent: 3,
+                agent_active_indices: vec![(SlotType::Block, 0, 0), (SlotType::Cron, 0, 0)],
+                agents_eject_threshold: 600,
+                agent_nomination_duration: 360,
+                agent_fee: coin(5, "atom"),
+                gas_price: 1,
+                proxy_callback_gas: 3,
+                slot_granularity: 60_000_000_000,
+                cw20_whitelist: vec![],
+                native_denom: "atom".to_string(),
+                available_balance: GenericBalance::default(),
+                staked_balance: GenericBalance::default(),
+            },
+        )?;
+        let old_task = OldTask {
+            owner_id: Addr::unchecked("alice"),
+            interval: Interval::Block(5),
+            boundary: Boundary {
+                start: None,
+                end: None,
+            },
+            stop_on_fail: true,
+            total_deposit: coins(37, "atom"),
+            actions: vec![Action {
+                msg: BankMsg::Send {
+                    to_address: "bob".to_string(),
+                    amount: coins(1, "atom"),
+                }
+                .into(),
+                gas_limit: Some(150_000),
+            }],
+            rules: None,
+        };
+        let task = Task {
+            owner_id: old_task.owner_id.clone(),
+            interval: old_task.interval.clone(),
+            boundary: old_task.boundary,
+            stop_on_fail: old_task.stop_on_fail,
+            allow_sponsored_refill: false,
+            refund_split: None,
+            preferred_agent: None,
+            fail_mode: FailMode::StopOnFirstFail,
+            agent_fee_override: None,
+            on_success_task: None,
+            label: None,
+            memo: None,
+            return_surplus: false,
+            total_deposit: old_task.total_deposit.clone(),
+            actions: old_task.actions.clone(),
+            rules: None,
+            last_executed_slot: None,
+            executions_count: 0,
+            failure_streak: 0,
+            created_at_height: 0,
+            created_at_time: Default::default(),
+        };
+        Map::<Vec<u8>, OldTask>::new("tasks").save(
+            deps.as_mut().storage,
+            task.to_hash_vec(),
+            &old_task,
+        )?;
+        store.task_total.save(deps.as_mut().storage, &1)?;
+        set_contract_version(deps.as_mut().storage, CONTRACT_NAME, "0.0.1")?;
+
+        let res = store
+            .migrate(deps.as_mut(), mock_env(), MigrateMsg {})
+            .unwrap();
+        assert!(res
+            .attributes
+            .iter()
+            .any(|a| a.key == "migrated_tasks" && a.value == "1"));
+        assert_eq!(
+            CONTRACT_VERSION,
+            get_contract_version(deps.as_ref().storage)?.version
+        );
+
+        // new settings get their defaults, existing ones are kept
+        let config: Config = store.config.load(deps.as_ref().storage)?;
+        assert_eq!(coin(5, "atom"), config.agent_fee);
+        assert_eq!(Addr::unchecked("owner"), config.owner_id);
+        assert_eq!(3_000_000, config.max_action_gas);
+        assert_eq!(Some(10), config.max_failure_streak);
+        assert!(config.refund_on_remove);
+        assert_eq!(MsgKind::all(), config.allowed_msg_kinds);
+        assert_eq!(None, config.treasury_id);
+
+        // the task is still there, under the same hash & found by its owner
+        assert_eq!(
+            task,
+            store
+                .tasks
+                .load(deps.as_ref().storage, task.to_hash_vec())?
+        );
+        assert_eq!(
+            1,
+            store.query_get_owner_task_count(deps.as_ref(), Addr::unchecked("alice"))?
+        );
+
+        // only the same contract can be migrated
+        set_contract_version(deps.as_mut().storage, "crates.io:other", "1.0.0")?;
+        let res_err = store
+            .migrate(deps.as_mut(), mock_env(), MigrateMsg {})
+            .unwrap_err();
+        assert_eq!(
+            ContractError::CustomError {
+                val: "Can't migrate from crates.io:other".to_string()
+            },
+            res_err
+        );
+
+        Ok(())
+    }
 }
@@ -1,4 +1,4 @@
-use cosmwasm_std::StdError;
+use cosmwasm_std::{Coin, StdError};
 use thiserror::Error;
 
 #[derive(Error, Debug, PartialEq)]
@@ -30,6 +30,66 @@ pub enum ContractError {
     #[error("Can't attach deposit")]
     AttachedDeposit {},
 
+    #[error("Boundary start must not be after end")]
+    InvalidBoundary {},
+
+    #[error("Boundary end is in the past")]
+    BoundaryInPast {},
+
+    #[error("Boundary is further ahead than the max boundary horizon")]
+    BoundaryBeyondHorizon {},
+
+    #[error("Task cost overflows")]
+    CostOverflow {},
+
+    #[error("Task must have at least one action")]
+    NoActions {},
+
+    #[error("Task contains duplicate actions")]
+    DuplicateActions {},
+
+    #[error("Interval time is in the past")]
+    IntervalInPast {},
+
+    #[error("Memo must be at most {max} characters")]
+    MemoTooLong { max: usize },
+
+    #[error("Chained task {task_hash} not found")]
+    ChainedTaskNotFound { task_hash: String },
+
+    #[error("Invalid rules: {reason}")]
+    InvalidRules { reason: String },
+
+    #[error("Task chain has a cycle")]
+    TaskChainCycle {},
+
+    #[error("Reentrant call during task execution")]
+    ReentrantCall {},
+
+    #[error("Owner reached the limit of {max} tasks")]
+    MaxTasksPerOwner { max: u64 },
+
+    #[error("Slot {slot_id} reached the limit of {max} tasks")]
+    MaxTasksPerSlot { slot_id: u64, max: u64 },
+
+    #[error("Refill must be at least {min}")]
+    RefillBelowMinimum { min: Coin },
+
+    #[error("Task deposit must be at least {min}")]
+    DepositBelowMinimum { min: Coin },
+
+    #[error("Gas price must be between 1 and {max}")]
+    InvalidGasPrice { max: u32 },
+
+    #[error("Agent fee must be at most {max}")]
+    InvalidAgentFee { max: Coin },
+
+    #[error("Denom {denom} is not accepted, use {native_denom}")]
+    DenomAlias { denom: String, native_denom: String },
+
+    #[error("Refill denom {denom} isn't in the task deposit")]
+    RefillNewDenom { denom: String },
+
     #[error("Custom Error val: {val:?}")]
     CustomError { val: String },
     // Add any other custom errors you like here.
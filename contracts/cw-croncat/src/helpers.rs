@@ -1,14 +1,18 @@
 use crate::state::Config;
 use crate::ContractError::AgentNotRegistered;
 use crate::{ContractError, CwCroncat};
-use cosmwasm_std::{to_binary, Addr, BankMsg, CosmosMsg, Env, StdResult, Storage, SubMsg, WasmMsg};
+use cosmwasm_std::{
+    to_binary, Addr, BankMsg, Coin, CosmosMsg, Env, Event, StdResult, Storage, SubMsg, WasmMsg,
+};
 use cw20::{Cw20CoinVerified, Cw20ExecuteMsg};
 use cw_croncat_core::msg::ExecuteMsg;
 use cw_croncat_core::types::AgentStatus;
 pub use cw_croncat_core::types::{GenericBalance, Task};
 use schemars::JsonSchema;
+use serde::de::{Deserializer, IgnoredAny, MapAccess, Visitor};
 use serde::{Deserialize, Serialize};
 use std::cmp;
+use std::fmt;
 use std::ops::Div;
 
 pub(crate) fn vect_difference<T: std::clone::Clone + std::cmp::PartialEq>(
@@ -64,6 +68,82 @@ pub(crate) fn has_cw_coins(coins: &[Cw20CoinVerified], required: &Cw20CoinVerifi
         .unwrap_or(false)
 }
 
+/// Finds a funds denom that only differs from `denom` in case or a micro "u" prefix
+/// e.g. "ATOM" or "uatom" for "atom", those would be tallied apart from the real denom
+pub(crate) fn find_denom_alias(funds: &[Coin], denom: &str) -> Option<String> {
+    let base = |d: &str| {
+        let d = d.to_ascii_lowercase();
+        d.strip_prefix('u').map(str::to_string).unwrap_or(d)
+    };
+    funds
+        .iter()
+        .find(|f| f.denom != denom && base(&f.denom) == base(denom))
+        .map(|f| f.denom.clone())
+}
+
+/// Drops zero amount coins, some clients attach them & they'd bloat deposits and refunds
+pub(crate) fn non_zero_coins(funds: &[Coin]) -> Vec<Coin> {
+    funds
+        .iter()
+        .filter(|f| !f.amount.is_zero())
+        .cloned()
+        .collect()
+}
+
+/// Number of top level entries of a json object, e.g. a query msg of an unknown schema
+/// serde-json-wasm can't deserialize maps directly, only through `deserialize_any`
+pub(crate) struct JsonObjectLen(pub usize);
+
+impl<'de> Deserialize<'de> for JsonObjectLen {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        struct LenVisitor;
+
+        impl<'de> Visitor<'de> for LenVisitor {
+            type Value = JsonObjectLen;
+
+            fn expecting(&self, formatter: &mut fmt::Formatter) -> fmt::Result {
+                formatter.write_str("a json object")
+            }
+
+            fn visit_map<A: MapAccess<'de>>(self, mut map: A) -> Result<Self::Value, A::Error> {
+                let mut len = 0;
+                while map.next_entry::<IgnoredAny, IgnoredAny>()?.is_some() {
+                    len += 1;
+                }
+                Ok(JsonObjectLen(len))
+            }
+        }
+
+        deserializer.deserialize_any(LenVisitor)
+    }
+}
+
+/// Event for a change of available_balance caused by a task
+/// `delta` lists added coins, then removed ones prefixed with "-", e.g. "5atom,-30juno"
+pub(crate) fn balance_changed_event(
+    task_hash: &str,
+    added: &[Coin],
+    removed: &[Coin],
+    available_balance: &GenericBalance,
+) -> Event {
+    let delta = added
+        .iter()
+        .map(|c| c.to_string())
+        .chain(removed.iter().map(|c| format!("-{}", c)))
+        .collect::<Vec<_>>()
+        .join(",");
+    let available = available_balance
+        .native
+        .iter()
+        .map(|c| c.to_string())
+        .collect::<Vec<_>>()
+        .join(",");
+    Event::new("balance_changed")
+        .add_attribute("task_hash", task_hash)
+        .add_attribute("delta", delta)
+        .add_attribute("available_balance", available)
+}
+
 impl<'a> CwCroncat<'a> {
     pub fn get_agent_status(
         &self,
@@ -191,14 +271,134 @@ impl CwTemplateContract {
 #[cfg(test)]
 pub mod test_helpers {
     use cosmwasm_std::{
-        coins,
+        coin, coins,
         testing::{mock_env, mock_info},
-        DepsMut, Empty, Response, StdResult,
+        Addr, Coin, DepsMut, Empty, Response, StakingMsg, StdResult, Uint128,
     };
-    use cw_croncat_core::msg::InstantiateMsg;
+    use cw_croncat_core::msg::{ExecuteMsg, InstantiateMsg, TaskRequest};
+    use cw_croncat_core::types::{Action, Boundary, BoundaryHorizon, FailMode, Interval, MsgKind};
+    use cw_multi_test::{App, AppResponse, Executor};
 
     use crate::CwCroncat;
 
+    pub const ANYONE: &str = "cosmos1t5u0jfg3ljsjrh2m9e47d4ny2hea7eehxrzdgd";
+    pub const NATIVE_DENOM: &str = "atom";
+
+    /// Immediate task delegating 1 atom, without a boundary or any of the options
+    /// Tests override what they need with struct update syntax
+    pub fn task_request() -> TaskRequest {
+        TaskRequest {
+            interval: Interval::Immediate,
+            boundary: Boundary {
+                start: None,
+                end: None,
+            },
+            stop_on_fail: false,
+            allow_sponsored_refill: false,
+            refund_split: None,
+            preferred_agent: None,
+            fail_mode: FailMode::StopOnFirstFail,
+            agent_fee_override: None,
+            on_success_task: None,
+            label: None,
+            memo: None,
+            return_surplus: false,
+            actions: vec![Action {
+                msg: StakingMsg::Delegate {
+                    validator: "you".to_string(),
+                    amount: coin(1, NATIVE_DENOM),
+                }
+                .into(),
+                gas_limit: Some(150_000),
+            }],
+            rules: None,
+        }
+    }
+
+    /// task_hash attribute of a CreateTask response
+    pub fn task_hash_attr(res: &AppResponse) -> String {
+        res.events
+            .iter()
+            .flat_map(|e| e.attributes.iter())
+            .find(|a| a.key == "task_hash")
+            .unwrap()
+            .value
+            .clone()
+    }
+
+    /// Creates the task as ANYONE with 10 atom attached, returns its hash
+    pub fn create_task_hash(app: &mut App, contract_addr: &Addr, task: TaskRequest) -> String {
+        let res = app
+            .execute_contract(
+                Addr::unchecked(ANYONE),
+                contract_addr.clone(),
+                &ExecuteMsg::CreateTask { task },
+                &coins(10, NATIVE_DENOM),
+            )
+            .unwrap();
+        task_hash_attr(&res)
+    }
+
+    /// Fields of ExecuteMsg::UpdateSettings, every setting is left unchanged by default
+    #[derive(Default)]
+    pub struct SettingsUpdate {
+        pub owner_id: Option<Addr>,
+        pub slot_granularity: Option<u64>,
+        pub paused: Option<bool>,
+        pub agent_fee: Option<Coin>,
+        pub gas_price: Option<u32>,
+        pub proxy_callback_gas: Option<u32>,
+        pub max_action_gas: Option<u64>,
+        pub default_gas_limit: Option<u64>,
+        pub max_tasks_per_owner: Option<Option<u64>>,
+        pub max_tasks_per_slot: Option<Option<u64>>,
+        pub max_failure_streak: Option<Option<u64>>,
+        pub refund_on_remove: Option<bool>,
+        pub min_refill_amount: Option<Option<Uint128>>,
+        pub dust_threshold: Option<Uint128>,
+        pub execution_grace_blocks: Option<u64>,
+        pub execution_grace_seconds: Option<u64>,
+        pub min_task_deposit: Option<Option<Coin>>,
+        pub max_boundary_horizon: Option<Option<BoundaryHorizon>>,
+        pub reject_duplicate_actions: Option<bool>,
+        pub allowed_msg_kinds: Option<Vec<MsgKind>>,
+        pub min_tasks_per_agent: Option<u64>,
+        pub agents_eject_threshold: Option<u64>,
+        pub treasury_id: Option<Option<Addr>>,
+        pub treasury_fee: Option<u64>,
+    }
+
+    /// UpdateSettings changing only the given settings, e.g.
+    /// `settings_msg(SettingsUpdate { paused: Some(true), ..Default::default() })`
+    pub fn settings_msg(settings: SettingsUpdate) -> ExecuteMsg {
+        ExecuteMsg::UpdateSettings {
+            owner_id: settings.owner_id,
+            slot_granularity: settings.slot_granularity,
+            paused: settings.paused,
+            agent_fee: settings.agent_fee,
+            gas_price: settings.gas_price,
+            proxy_callback_gas: settings.proxy_callback_gas,
+            max_action_gas: settings.max_action_gas,
+            default_gas_limit: settings.default_gas_limit,
+            max_tasks_per_owner: settings.max_tasks_per_owner,
+            max_tasks_per_slot: settings.max_tasks_per_slot,
+            max_failure_streak: settings.max_failure_streak,
+            refund_on_remove: settings.refund_on_remove,
+            min_refill_amount: settings.min_refill_amount,
+            dust_threshold: settings.dust_threshold,
+            execution_grace_blocks: settings.execution_grace_blocks,
+            execution_grace_seconds: settings.execution_grace_seconds,
+            min_task_deposit: settings.min_task_deposit,
+            max_boundary_horizon: settings.max_boundary_horizon,
+            reject_duplicate_actions: settings.reject_duplicate_actions,
+            allowed_msg_kinds: settings.allowed_msg_kinds,
+            min_tasks_per_agent: settings.min_tasks_per_agent,
+            agents_eject_threshold: settings.agents_eject_threshold,
+            treasury_id: settings.treasury_id,
+            treasury_fee: settings.treasury_fee,
+        }
+    }
+
     pub fn mock_init(store: &CwCroncat, deps: DepsMut<Empty>) -> StdResult<Response> {
         let msg = InstantiateMsg {
             denom: "atom".to_string(),
@@ -13,7 +13,7 @@ pub use crate::error::ContractError;
 pub use crate::state::CwCroncat;
 use cosmwasm_std::entry_point;
 use cosmwasm_std::{Binary, Deps, DepsMut, Env, MessageInfo, Reply, Response, StdResult};
-pub use cw_croncat_core::msg::{ExecuteMsg, InstantiateMsg, QueryMsg};
+pub use cw_croncat_core::msg::{ExecuteMsg, InstantiateMsg, MigrateMsg, QueryMsg};
 
 #[cfg(not(feature = "library"))]
 pub mod entry {
@@ -48,6 +48,12 @@ pub mod entry {
         s.query(deps, env, msg)
     }
 
+    #[entry_point]
+    pub fn migrate(deps: DepsMut, env: Env, msg: MigrateMsg) -> Result<Response, ContractError> {
+        let s = CwCroncat::default();
+        s.migrate(deps, env, msg)
+    }
+
     #[entry_point]
     pub fn reply(deps: DepsMut, env: Env, msg: Reply) -> Result<Response, ContractError> {
         let s = CwCroncat::default();
@@ -1,16 +1,45 @@
 use crate::error::ContractError;
 use crate::state::{Config, CwCroncat, QueueItem};
 use cosmwasm_std::{
-    Addr, DepsMut, Empty, Env, MessageInfo, Reply, Response, StdResult, Storage, SubMsg,
+    Addr, BankMsg, BlockInfo, Coin, DepsMut, Env, MessageInfo, Reply, Response, Storage, SubMsg,
+    Uint128,
 };
 use cw20::Balance;
-use cw_croncat_core::types::{Agent, SlotType};
+use cw_croncat_core::types::{Action, Agent, FailMode, GenericBalance, SlotType, Task};
+
+/// Tasks with a preferred agent are reserved for it, until the grace period after their slot passed
+/// Block slots wait `execution_grace_blocks` from the config, cron slots `execution_grace_seconds`
+pub(crate) fn is_reserved_for_other(
+    c: &Config,
+    task: &Task,
+    agent: &Addr,
+    slot_id: u64,
+    slot_kind: &SlotType,
+    block: &BlockInfo,
+) -> bool {
+    let preferred_agent = match &task.preferred_agent {
+        Some(preferred_agent) => preferred_agent,
+        None => return false,
+    };
+    let grace_over = match slot_kind {
+        SlotType::Block => block.height >= slot_id.saturating_add(c.execution_grace_blocks),
+        SlotType::Cron => {
+            block.time.seconds()
+                >= (slot_id / 1_000_000_000).saturating_add(c.execution_grace_seconds)
+        }
+    };
+    preferred_agent != agent && !grace_over
+}
 
 impl<'a> CwCroncat<'a> {
     /// Executes a task based on the current task slot
     /// Computes whether a task should continue further or not
     /// Makes a cross-contract call with the task configuration
     /// Called directly by a registered agent
+    ///
+    /// Catch-up policy: the earliest due slot is executed first, however far in the past it is.
+    /// A task that missed several slots still runs only once, its callback then schedules it
+    /// from the current block height & time, so missed executions are skipped rather than replayed
     pub fn proxy_call(
         &mut self,
         deps: DepsMut,
@@ -52,26 +81,29 @@ impl<'a> CwCroncat<'a> {
         let slot = self.get_current_slot_items(&env.block, deps.storage, Some(1));
         // Give preference for block-based slots
         let slot_id: u64;
+        let slot_kind: SlotType;
         let some_hash: Option<Vec<u8>>;
         if slot.0.is_none() {
             // See if there are cron (time-based) tasks to execute
             if slot.1.is_none() {
-                self.send_base_agent_reward(deps.storage, agent, info);
+                self.send_base_agent_reward(deps.storage, agent, info, None);
                 return Err(ContractError::CustomError {
                     val: "No Tasks For Slot".to_string(),
                 });
             } else {
                 slot_id = slot.1.unwrap();
+                slot_kind = SlotType::Cron;
                 // There aren't block tasks but there are cron tasks
                 some_hash = self.pop_slot_item(deps.storage, &slot_id, &SlotType::Cron);
             }
         } else {
             // There are block tasks (which we prefer to execute before time-based ones at this point)
             slot_id = slot.0.unwrap();
+            slot_kind = SlotType::Block;
             some_hash = self.pop_slot_item(deps.storage, &slot.0.unwrap(), &SlotType::Block);
         }
         if some_hash.is_none() {
-            self.send_base_agent_reward(deps.storage, agent, info);
+            self.send_base_agent_reward(deps.storage, agent, info, None);
             return Err(ContractError::CustomError {
                 val: "No Tasks For Slot".to_string(),
             });
@@ -83,7 +115,7 @@ impl<'a> CwCroncat<'a> {
         let some_task = self.tasks.may_load(deps.storage, hash.clone())?;
         if some_task.is_none() {
             // NOTE: This could should never get reached, however we cover just in case
-            self.send_base_agent_reward(deps.storage, agent, info);
+            self.send_base_agent_reward(deps.storage, agent, info, None);
             return Err(ContractError::NoTaskFound {});
         }
 
@@ -94,6 +126,12 @@ impl<'a> CwCroncat<'a> {
 
         let task = some_task.unwrap();
 
+        if is_reserved_for_other(&c, &task, &info.sender, slot_id, &slot_kind, &env.block) {
+            return Err(ContractError::CustomError {
+                val: "Task is reserved for its preferred agent".to_string(),
+            });
+        }
+
         // TODO: Bring this back!
         // // Fee breakdown:
         // // - Used Gas: Task Txn Fee Cost
@@ -163,60 +201,81 @@ impl<'a> CwCroncat<'a> {
         //     }
         // }
 
-        // Setup submessages for actions for this task
-        // Each submessage in storage, computes & stores the "next" reply to allow for chained message processing.
-        let mut sub_msgs: Vec<SubMsg<Empty>> = vec![];
+        // Only the first action is sent here, each reply chains the next one
+        // so the task fail_mode can decide whether the remaining actions still run
         let next_idx = self.rq_next_id(deps.storage)?;
-        let actions = task.clone().actions;
         let self_addr = env.contract.address;
+        let sub_msg = task
+            .actions
+            .first()
+            .map(|action| action_sub_msg(action, next_idx));
 
-        // Add submessages for all actions
-        for action in actions {
-            let sub_msg: SubMsg = SubMsg::reply_always(action.msg, next_idx);
-            if let Some(gas_limit) = action.gas_limit {
-                sub_msgs.push(sub_msg.with_gas_limit(gas_limit));
-            } else {
-                sub_msgs.push(sub_msg);
-            }
-        }
-
-        // Keep track for later scheduling
-        self.rq_push(
+        // Other active agents let this slot go overdue, the ones missing too many get ejected
+        let eject_events = self.track_missed_slot(
             deps.storage,
-            QueueItem {
-                prev_idx: None,
-                task_hash: Some(hash),
-                contract_addr: Some(self_addr),
-            },
+            &c,
+            &info.sender,
+            slot_id,
+            &slot_kind,
+            &env.block,
         )?;
 
+        // Pay the agent for this execution, treasury cut (if any) is sent out directly
+        let treasury_msg =
+            self.send_base_agent_reward(deps.storage, agent, info.clone(), Some(&task));
+
+        // Only a sent action gets a reply, so without one there's no callback to clear the guard
+        if sub_msg.is_some() {
+            // Guard against actions calling back in, until the callback reschedules
+            self.proxy_in_progress.save(deps.storage, &true)?;
+
+            // Keep track for later scheduling
+            self.rq_push(
+                deps.storage,
+                QueueItem {
+                    prev_idx: None,
+                    task_hash: Some(hash),
+                    contract_addr: Some(self_addr),
+                    slot_id: Some(slot_id),
+                    action_idx: 0,
+                    failed_actions: vec![],
+                },
+            )?;
+        }
+
         // TODO: Add supported msgs if not a SubMessage?
         // Add the messages, reply handler responsible for task rescheduling
-        let final_res = Response::new()
+        let mut final_res = Response::new()
             .add_attribute("method", "proxy_call")
             .add_attribute("agent", info.sender)
             .add_attribute("slot_id", slot_id.to_string())
-            .add_attribute("slot_kind", format!("{:?}", SlotType::Block))
+            .add_attribute("slot_kind", slot_kind.to_string())
             .add_attribute("task_hash", task.to_hash())
             // .add_attributes(rule_responses)
-            .add_submessages(sub_msgs);
+            .add_submessages(sub_msg)
+            .add_events(eject_events);
+        if let Some(treasury_msg) = treasury_msg {
+            final_res = final_res.add_submessage(treasury_msg);
+        }
 
         Ok(final_res)
     }
 
     /// Logic executed on the completion of a proxy call
-    /// Reschedule next task
+    /// Runs the next action, or reschedules the task once the actions are done
     pub(crate) fn proxy_callback(
         &self,
         deps: DepsMut,
         env: Env,
         msg: Reply,
-        task_hash: Vec<u8>,
+        item: QueueItem,
     ) -> Result<Response, ContractError> {
         let mut response = Response::new().add_attribute("method", "proxy_callback");
+        let task_hash = item.task_hash.unwrap_or_default();
+        let slot_id = item.slot_id;
 
         // check if reply had failure
-        let mut reply_submsg_failed = false;
+        let mut action_failed = false;
         if msg.result.is_ok() {
             for e in msg.result.unwrap().events {
                 for a in e.attributes {
@@ -224,16 +283,68 @@ impl<'a> CwCroncat<'a> {
                         && a.clone().key == "mode"
                         && a.clone().value == "handle_failure"
                     {
-                        reply_submsg_failed = true;
+                        action_failed = true;
                     }
                 }
             }
         } else if msg.result.is_err() {
-            reply_submsg_failed = true;
+            action_failed = true;
+        }
+        let mut failed_actions = item.failed_actions;
+        if action_failed {
+            failed_actions.push(item.action_idx);
+        }
+
+        // Chain the next action, unless the task stops on its first failure
+        if let Some(mut task) = self.tasks.may_load(deps.storage, task_hash.clone())? {
+            // What a successful action sent left the contract, so it's no longer part of the deposit
+            // A failed one was reverted along with its funds
+            let sent = match task.actions.get(item.action_idx as usize) {
+                Some(action) if !action_failed => action.to_sent_amounts(),
+                _ => vec![],
+            };
+            if !sent.is_empty() {
+                let mut deposit = GenericBalance {
+                    native: task.total_deposit.clone(),
+                    cw20: vec![],
+                };
+                deposit.saturating_minus_tokens(Balance::from(sent.clone()));
+                task.total_deposit = deposit.native;
+                self.tasks.save(deps.storage, task_hash.clone(), &task)?;
+                let mut c: Config = self.config.load(deps.storage)?;
+                c.available_balance
+                    .saturating_minus_tokens(Balance::from(sent));
+                self.config.save(deps.storage, &c)?;
+            }
+            let next_action = item.action_idx as usize + 1;
+            let stop = action_failed && task.fail_mode == FailMode::StopOnFirstFail;
+            if let (Some(action), false) = (task.actions.get(next_action), stop) {
+                let next_idx = self.rq_push(
+                    deps.storage,
+                    QueueItem {
+                        prev_idx: Some(msg.id),
+                        task_hash: Some(task_hash),
+                        contract_addr: item.contract_addr,
+                        slot_id,
+                        action_idx: next_action as u64,
+                        failed_actions,
+                    },
+                )?;
+                return Ok(response
+                    .add_attribute("action_idx", next_action.to_string())
+                    .add_submessage(action_sub_msg(action, next_idx)));
+            }
+        }
+
+        self.proxy_in_progress.save(deps.storage, &false)?;
+        let reply_submsg_failed = !failed_actions.is_empty();
+        if reply_submsg_failed {
+            let failed: Vec<String> = failed_actions.iter().map(|idx| idx.to_string()).collect();
+            response = response.add_attribute("failed_actions", failed.join(","));
         }
 
         // reschedule next!
-        if let Some(task) = self.tasks.may_load(deps.storage, task_hash)? {
+        if let Some(mut task) = self.tasks.may_load(deps.storage, task_hash)? {
             let task_hash = task.to_hash();
             // TODO: How can we compute gas & fees paid on this txn?
             // let out_of_funds = call_total_balance > task.total_deposit;
@@ -252,11 +363,53 @@ impl<'a> CwCroncat<'a> {
                 return Ok(response);
             }
 
+            // Keep execution history for successful runs, failed ones extend the failure streak
+            if reply_submsg_failed {
+                task.failure_streak = task.failure_streak.saturating_add(1);
+            } else {
+                task.executions_count = task.executions_count.saturating_add(1);
+                task.last_executed_slot = slot_id;
+                task.failure_streak = 0;
+            }
+            self.tasks.save(deps.storage, task.to_hash_vec(), &task)?;
+
+            // A successful run triggers its chained task in the current block slot
+            if !reply_submsg_failed {
+                if let Some(child_hash) = task.on_success_task.clone() {
+                    response =
+                        self.trigger_chained_task(deps.storage, &env, &child_hash, response)?;
+                }
+            }
+
+            // A task failing every run (e.g. its target contract is gone) would only drain its deposit
+            // on agent fees, so it ends & gets refunded after too many failures in a row
+            let mut c: Config = self.config.load(deps.storage)?;
+            if matches!(c.max_failure_streak, Some(max) if task.failure_streak >= max) {
+                if let Ok(resp) = self.remove_task(deps, task_hash.clone()) {
+                    response = response
+                        .add_attributes(resp.attributes)
+                        .add_submessages(resp.messages)
+                        .add_events(resp.events);
+                }
+                response = response
+                    .add_attribute("ended_task", task_hash)
+                    .add_attribute("failure_streak", task.failure_streak.to_string());
+                return Ok(response);
+            }
+
             // Parse interval into a future timestamp, then convert to a slot
-            let (next_id, slot_kind) = task.interval.next(env, task.boundary);
+            // The next slot is computed from the current block, not the slot that just ran,
+            // so a task executed late (after missing slots) moves forward instead of catching up
+            // Once, Delay & At tasks are done after their single run
+            let (next_id, slot_kind) = if task.interval.is_one_shot() {
+                (0, SlotType::Cron)
+            } else {
+                task.interval.next(env, task.boundary)
+            };
 
             // If the next interval comes back 0, then this task should not schedule again
-            if next_id == 0 {
+            // Same once the deposit can't cover what the actions send in one more run
+            if next_id == 0 || !self.has_action_funds(&c, &task) {
                 let rt = self.remove_task(deps, task_hash.clone());
                 if let Ok(..) = rt {
                     let resp = rt.unwrap();
@@ -269,34 +422,36 @@ impl<'a> CwCroncat<'a> {
                 return Ok(response);
             }
 
-            response = response.add_attribute("slot_id", next_id.to_string());
-            response = response.add_attribute("slot_kind", format!("{:?}", slot_kind));
-
-            // Get previous task hashes in slot, add as needed
-            let update_vec_data = |d: Option<Vec<Vec<u8>>>| -> StdResult<Vec<Vec<u8>>> {
-                match d {
-                    // has some data, simply push new hash
-                    Some(data) => {
-                        let mut s = data;
-                        s.push(task.to_hash_vec());
-                        Ok(s)
-                    }
-                    // No data, push new vec & hash
-                    None => Ok(vec![task.to_hash_vec()]),
-                }
-            };
-
-            // Based on slot kind, put into block or cron slots
-            match slot_kind {
-                SlotType::Block => {
-                    self.block_slots
-                        .update(deps.storage, next_id, update_vec_data)?;
-                }
-                SlotType::Cron => {
-                    self.time_slots
-                        .update(deps.storage, next_id, update_vec_data)?;
+            // Send back what the actions won't need for the next run
+            // What this run's actions sent already came out of the deposit, so it isn't counted
+            if task.return_surplus && !reply_submsg_failed {
+                let surplus = self.action_surplus(&c, &task);
+                if !surplus.is_empty() {
+                    let mut deposit = GenericBalance {
+                        native: task.total_deposit.clone(),
+                        cw20: vec![],
+                    };
+                    deposit.minus_tokens(Balance::from(surplus.clone()));
+                    task.total_deposit = deposit.native;
+                    self.tasks.save(deps.storage, task.to_hash_vec(), &task)?;
+                    c.available_balance
+                        .minus_tokens(Balance::from(surplus.clone()));
+                    self.config.save(deps.storage, &c)?;
+
+                    let coins_total: String = surplus.iter().map(|a| a.to_string()).collect();
+                    response = response
+                        .add_attribute("returned_surplus", coins_total)
+                        .add_message(BankMsg::Send {
+                            to_address: task.owner_id.to_string(),
+                            amount: surplus,
+                        });
                 }
             }
+
+            response = response.add_attribute("slot_id", next_id.to_string());
+            response = response.add_attribute("slot_kind", slot_kind.to_string());
+
+            self.schedule_task(deps.storage, &task, next_id, &slot_kind)?;
         } else {
             return Err(ContractError::NoTaskFound {});
         }
@@ -304,30 +459,86 @@ impl<'a> CwCroncat<'a> {
         Ok(response)
     }
 
+    /// Schedules a chained task into the current block slot, unless it's gone or already in a slot
+    /// A child that is already scheduled runs then, triggering it again would run it twice
+    fn trigger_chained_task(
+        &self,
+        storage: &mut dyn Storage,
+        env: &Env,
+        task_hash: &str,
+        response: Response,
+    ) -> Result<Response, ContractError> {
+        let task = match self
+            .tasks
+            .may_load(storage, task_hash.as_bytes().to_vec())?
+        {
+            Some(task) => task,
+            None => return Ok(response),
+        };
+        if self.task_slot(storage, &task.to_hash_vec())?.is_some() {
+            return Ok(response);
+        }
+        self.schedule_task(storage, &task, env.block.height, &SlotType::Block)?;
+        Ok(response.add_attribute("triggered_task", task_hash))
+    }
+
     /// Internal management of agent reward
     /// Used in cases where there are empty slots or failed txns
     /// Keep the agent profitable, as this will be a business expense
+    /// If a treasury is set, its cut of the agent fee is returned as a payout msg
+    /// Executed tasks pay their own agent fee, see `task_agent_fee`
     pub(crate) fn send_base_agent_reward(
         &self,
         storage: &mut dyn Storage,
         mut agent: Agent,
         message: MessageInfo,
-    ) {
+        task: Option<&Task>,
+    ) -> Option<SubMsg> {
         let mut config: Config = self.config.load(storage).unwrap();
 
-        let agent_base_fee = config.agent_fee.clone();
+        let agent_base_fee = match task {
+            Some(task) => self.task_agent_fee(&config, task),
+            None => config.agent_fee.clone(),
+        };
         let coin = vec![agent_base_fee.clone()];
         let add_native: Balance = Balance::from(coin);
 
-        agent.balance.add_tokens(add_native.clone());
+        // Split the agent fee, treasury gets its percentage & the agent the rest
+        let treasury_amount = match config.treasury_id {
+            Some(_) => agent_base_fee
+                .amount
+                .multiply_ratio(config.treasury_fee, 100u64),
+            None => Uint128::zero(),
+        };
+        let agent_amount = agent_base_fee.amount.saturating_sub(treasury_amount);
+        agent.balance.add_tokens(Balance::from(vec![Coin::new(
+            agent_amount.u128(),
+            agent_base_fee.denom.clone(),
+        )]));
         agent.total_tasks_executed = agent.total_tasks_executed.saturating_add(1);
         println!("{:?}", add_native);
         println!("{:?}", config.available_balance.native);
 
+        let mut treasury_msg: Option<SubMsg> = None;
         if !config.available_balance.native.is_empty()
             && config.available_balance.native.first().unwrap().amount >= agent_base_fee.amount
         {
+            // Fees aren't taken from the task deposit, protocol funds pay them first, then the surplus
+            let rest = config
+                .protocol_balance
+                .saturating_minus_tokens(add_native.clone());
+            config
+                .surplus_balance
+                .saturating_minus_tokens(Balance::from(rest.native));
             config.available_balance.minus_tokens(add_native);
+            if let Some(treasury_id) = config.treasury_id.clone() {
+                if !treasury_amount.is_zero() {
+                    treasury_msg = Some(SubMsg::new(BankMsg::Send {
+                        to_address: treasury_id.into(),
+                        amount: vec![Coin::new(treasury_amount.u128(), agent_base_fee.denom)],
+                    }));
+                }
+            }
         }
 
         self.config
@@ -338,21 +549,45 @@ impl<'a> CwCroncat<'a> {
         if agent.last_missed_slot != 0 {
             agent.last_missed_slot = 0;
         }
+        agent.missed_slots = 0;
         self.agents.save(storage, message.sender, &agent).unwrap();
+
+        treasury_msg
+    }
+}
+
+/// Submessage for a task action, replies go to the queue item at `reply_idx`
+fn action_sub_msg(action: &Action, reply_idx: u64) -> SubMsg {
+    let sub_msg: SubMsg = SubMsg::reply_always(action.msg.clone(), reply_idx);
+    match action.gas_limit {
+        Some(gas_limit) => sub_msg.with_gas_limit(gas_limit),
+        None => sub_msg,
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use cosmwasm_std::testing::{mock_dependencies_with_balances, mock_env, mock_info};
     use cosmwasm_std::{
-        coin, coins, to_binary, Addr, BlockInfo, CosmosMsg, Empty, StakingMsg, WasmMsg,
+        coin, coins, to_binary, Addr, Binary, BlockInfo, CosmosMsg, Empty, Order, ReplyOn,
+        StakingMsg, StdResult, Uint128, WasmMsg,
+    };
+    use cw_multi_test::{
+        App, AppBuilder, AppResponse, BankSudo, Contract, ContractWrapper, Executor, SudoMsg,
     };
-    use cw_multi_test::{App, AppBuilder, Contract, ContractWrapper, Executor};
     // use cw20::Balance;
+    use crate::helpers::test_helpers::{settings_msg, SettingsUpdate};
     use crate::helpers::CwTemplateContract;
-    use cw_croncat_core::msg::{ExecuteMsg, InstantiateMsg, TaskRequest};
-    use cw_croncat_core::types::{Action, Boundary, BoundarySpec, Interval};
+    use crate::state::default_execution_grace_blocks;
+    use cw_croncat_core::msg::{
+        ExecuteMsg, GetSlotHashesResponse, GetSlotIdsResponse, InstantiateMsg, QueryMsg,
+        TaskRequest, TaskResponse,
+    };
+    use cw_croncat_core::msg::{
+        GetBalancerInvariantResponse, GetBalancesResponse, GetConfigResponse, GetTaskCostResponse,
+    };
+    use cw_croncat_core::types::{Action, AgentResponse, Boundary, BoundarySpec, Interval};
 
     pub fn contract_template() -> Box<dyn Contract<Empty>> {
         let contract = ContractWrapper::new(
@@ -364,10 +599,64 @@ mod tests {
         Box::new(contract)
     }
 
+    pub fn cw20_template() -> Box<dyn Contract<Empty>> {
+        let contract = ContractWrapper::new(
+            cw20_base::contract::execute,
+            cw20_base::contract::instantiate,
+            cw20_base::contract::query,
+        );
+        Box::new(contract)
+    }
+
+    // A task target that calls back into the manager (with the funds it received) to create a task
+    pub fn reentrant_template() -> Box<dyn Contract<Empty>> {
+        let contract = ContractWrapper::new(
+            |_deps, _env, info: MessageInfo, _msg: Empty| -> StdResult<Response> {
+                let create_task_msg = ExecuteMsg::CreateTask {
+                    task: TaskRequest {
+                        interval: Interval::Immediate,
+                        boundary: Boundary {
+                            start: None,
+                            end: None,
+                        },
+                        stop_on_fail: false,
+                        allow_sponsored_refill: false,
+                        refund_split: None,
+                        preferred_agent: None,
+                        fail_mode: FailMode::StopOnFirstFail,
+                        agent_fee_override: None,
+                        on_success_task: None,
+                        label: None,
+                        memo: None,
+                        return_surplus: false,
+                        actions: vec![Action {
+                            msg: StakingMsg::Delegate {
+                                validator: "you".to_string(),
+                                amount: coin(1, NATIVE_DENOM),
+                            }
+                            .into(),
+                            gas_limit: None,
+                        }],
+                        rules: None,
+                    },
+                };
+                Ok(Response::new().add_message(WasmMsg::Execute {
+                    contract_addr: info.sender.to_string(),
+                    msg: to_binary(&create_task_msg)?,
+                    funds: info.funds,
+                }))
+            },
+            |_deps, _env, _info, _msg: Empty| -> StdResult<Response> { Ok(Response::new()) },
+            |_deps, _env, _msg: Empty| -> StdResult<Binary> { Ok(Binary::default()) },
+        );
+        Box::new(contract)
+    }
+
     const ADMIN: &str = "cosmos1sjllsnramtg3ewxqwwrwjxfgc4n4ef9u0tvx7u";
     const ANYONE: &str = "cosmos1t5u0jfg3ljsjrh2m9e47d4ny2hea7eehxrzdgd";
     const AGENT0: &str = "cosmos1a7uhnpqthunr2rzj0ww0hwurpn42wyun6c5puz";
     const AGENT1_BENEFICIARY: &str = "cosmos1t5u0jfg3ljsjrh2m9e47d4ny2hea7eehxrzdgd";
+    const TREASURY: &str = "cosmos1kqzfrlwmvyunfd4ucmp8mg2whl0mwdxvtxgx5s";
     const NATIVE_DENOM: &str = "atom";
 
     fn mock_app() -> App {
@@ -418,6 +707,21 @@ mod tests {
         (app, cw_template_contract)
     }
 
+    // Tasks can't target the manager itself, so successful actions go to a cw20 contract
+    fn instantiate_cw20(app: &mut App) -> Addr {
+        let cw20_id = app.store_code(cw20_template());
+        let msg = cw20_base::msg::InstantiateMsg {
+            name: "Test Token".to_string(),
+            symbol: "TEST".to_string(),
+            decimals: 6,
+            initial_balances: vec![],
+            mint: None,
+            marketing: None,
+        };
+        app.instantiate_contract(cw20_id, Addr::unchecked(ADMIN), &msg, &[], "cw20", None)
+            .unwrap()
+    }
+
     pub fn add_little_time(block: &mut BlockInfo) {
         // block.time = block.time.plus_seconds(360);
         block.time = block.time.plus_seconds(19);
@@ -448,6 +752,15 @@ mod tests {
                     end: None,
                 },
                 stop_on_fail: false,
+                allow_sponsored_refill: false,
+                refund_split: None,
+                preferred_agent: None,
+                fail_mode: FailMode::StopOnFirstFail,
+                agent_fee_override: None,
+                on_success_task: None,
+                label: None,
+                memo: None,
+                return_surplus: false,
                 actions: vec![Action {
                     msg,
                     gas_limit: Some(150_000),
@@ -475,17 +788,10 @@ mod tests {
         );
 
         // Create task paused
-        let change_settings_msg = ExecuteMsg::UpdateSettings {
+        let change_settings_msg = settings_msg(SettingsUpdate {
             paused: Some(true),
-            owner_id: None,
-            // treasury_id: None,
-            agent_fee: None,
-            min_tasks_per_agent: None,
-            agents_eject_threshold: None,
-            gas_price: None,
-            proxy_callback_gas: None,
-            slot_granularity: None,
-        };
+            ..Default::default()
+        });
         app.execute_contract(
             Addr::unchecked(ADMIN),
             contract_addr.clone(),
@@ -511,17 +817,10 @@ mod tests {
         app.execute_contract(
             Addr::unchecked(ADMIN),
             contract_addr.clone(),
-            &ExecuteMsg::UpdateSettings {
+            &settings_msg(SettingsUpdate {
                 paused: Some(false),
-                owner_id: None,
-                // treasury_id: None,
-                agent_fee: None,
-                min_tasks_per_agent: None,
-                agents_eject_threshold: None,
-                gas_price: None,
-                proxy_callback_gas: None,
-                slot_granularity: None,
-            },
+                ..Default::default()
+            }),
             &vec![],
         )
         .unwrap();
@@ -547,6 +846,22 @@ mod tests {
         app.execute_contract(Addr::unchecked(AGENT0), contract_addr.clone(), &msg, &[])
             .unwrap();
 
+        // NoTasksForSlot
+        let res_err = app
+            .execute_contract(
+                Addr::unchecked(AGENT0),
+                contract_addr.clone(),
+                &proxy_call_msg,
+                &[],
+            )
+            .unwrap_err();
+        assert_eq!(
+            ContractError::CustomError {
+                val: "No Tasks For Slot".to_string()
+            },
+            res_err.downcast().unwrap()
+        );
+
         // create task, so any slot actually exists
         let res = app
             .execute_contract(
@@ -567,22 +882,6 @@ mod tests {
         }
         assert!(has_created_hash);
 
-        // NoTasksForSlot
-        let res_err = app
-            .execute_contract(
-                Addr::unchecked(AGENT0),
-                contract_addr.clone(),
-                &proxy_call_msg,
-                &vec![],
-            )
-            .unwrap_err();
-        assert_eq!(
-            ContractError::CustomError {
-                val: "No Tasks For Slot".to_string()
-            },
-            res_err.downcast().unwrap()
-        );
-
         // NOTE: Unless there's a way to fake a task getting removed but hash remains in slot,
         // this coverage is not mockable. There literally shouldn't be any code that allows
         // this scenario to happen since all slot/task removal cases are covered
@@ -619,21 +918,366 @@ mod tests {
     // TODO: TestCov: Agent balance updated (send_base_agent_reward)
     // TODO: TestCov: Total balance updated
     #[test]
-    fn proxy_call_success() -> StdResult<()> {
+    fn proxy_call_delay_and_at_run_once() -> StdResult<()> {
+        let created_at = mock_app().block_info().time;
+        for interval in [
+            Interval::Delay(60),
+            Interval::At(created_at.plus_seconds(60)),
+        ] {
+            let (mut app, cw_template_contract) = proper_instantiate();
+            let contract_addr = cw_template_contract.addr();
+            let proxy_call_msg = ExecuteMsg::ProxyCall {};
+            let cw20_addr = instantiate_cw20(&mut app);
+
+            let action = Action {
+                msg: CosmosMsg::Wasm(WasmMsg::Execute {
+                    contract_addr: cw20_addr.to_string(),
+                    msg: to_binary(&cw20::Cw20ExecuteMsg::IncreaseAllowance {
+                        spender: ANYONE.to_string(),
+                        amount: Uint128::new(10),
+                        expires: None,
+                    })?,
+                    funds: coins(1, NATIVE_DENOM),
+                }),
+                gas_limit: Some(250_000),
+            };
+            let create_task_msg = |interval| ExecuteMsg::CreateTask {
+                task: TaskRequest {
+                    interval,
+                    boundary: Boundary {
+                        start: None,
+                        end: None,
+                    },
+                    stop_on_fail: false,
+                    allow_sponsored_refill: false,
+                    refund_split: None,
+                    preferred_agent: None,
+                    fail_mode: FailMode::StopOnFirstFail,
+                    agent_fee_override: None,
+                    on_success_task: None,
+                    label: None,
+                    memo: None,
+                    return_surplus: false,
+                    actions: vec![action.clone()],
+                    rules: None,
+                },
+            };
+            // a time that already passed is rejected
+            let res_err = app
+                .execute_contract(
+                    Addr::unchecked(ADMIN),
+                    contract_addr.clone(),
+                    &create_task_msg(Interval::At(created_at.minus_seconds(1))),
+                    &coins(10, NATIVE_DENOM),
+                )
+                .unwrap_err();
+            assert_eq!(
+                ContractError::IntervalInPast {},
+                res_err.downcast().unwrap()
+            );
+
+            let res = app
+                .execute_contract(
+                    Addr::unchecked(ADMIN),
+                    contract_addr.clone(),
+                    &create_task_msg(interval),
+                    &coins(10, NATIVE_DENOM),
+                )
+                .unwrap();
+            let task_hash = res
+                .events
+                .iter()
+                .flat_map(|e| e.attributes.iter())
+                .find(|a| a.key == "task_hash")
+                .unwrap()
+                .value
+                .clone();
+
+            // scheduled 60 seconds after creation
+            let slot_ids: GetSlotIdsResponse = app
+                .wrap()
+                .query_wasm_smart(contract_addr.clone(), &QueryMsg::GetSlotIds {})
+                .unwrap();
+            assert_eq!(vec![created_at.plus_seconds(60).nanos()], slot_ids.time_ids);
+
+            app.execute_contract(
+                Addr::unchecked(AGENT0),
+                contract_addr.clone(),
+                &ExecuteMsg::RegisterAgent {
+                    payable_account_id: Some(Addr::unchecked(AGENT1_BENEFICIARY)),
+                },
+                &[],
+            )
+            .unwrap();
+
+            // not yet
+            app.update_block(add_little_time);
+            let res_err = app
+                .execute_contract(
+                    Addr::unchecked(AGENT0),
+                    contract_addr.clone(),
+                    &proxy_call_msg,
+                    &[],
+                )
+                .unwrap_err();
+            assert_eq!(
+                ContractError::CustomError {
+                    val: "No Tasks For Slot".to_string()
+                },
+                res_err.downcast().unwrap()
+            );
+
+            app.update_block(|block| {
+                block.time = created_at.plus_seconds(60);
+                block.height += 1;
+            });
+            let res = app
+                .execute_contract(
+                    Addr::unchecked(AGENT0),
+                    contract_addr.clone(),
+                    &proxy_call_msg,
+                    &[],
+                )
+                .unwrap();
+            assert!(res
+                .events
+                .iter()
+                .flat_map(|e| e.attributes.iter())
+                .any(|a| a.key == "ended_task" && a.value == task_hash));
+
+            // terminal after one run
+            let task: Option<TaskResponse> = app
+                .wrap()
+                .query_wasm_smart(contract_addr.clone(), &QueryMsg::GetTask { task_hash })
+                .unwrap();
+            assert!(task.is_none());
+            let slot_ids: GetSlotIdsResponse = app
+                .wrap()
+                .query_wasm_smart(contract_addr.clone(), &QueryMsg::GetSlotIds {})
+                .unwrap();
+            assert!(slot_ids.time_ids.is_empty());
+            assert!(slot_ids.block_ids.is_empty());
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn proxy_call_once_runs_once() -> StdResult<()> {
         let (mut app, cw_template_contract) = proper_instantiate();
         let contract_addr = cw_template_contract.addr();
-        let proxy_call_msg = ExecuteMsg::ProxyCall {};
-        let task_id_str =
-            "9c1b6c9d91a5960b9c8580f3606bca18a9ceb8ed628f68a1c7022ef130c5c2d6".to_string();
+        let cw20_addr = instantiate_cw20(&mut app);
 
-        // Doing this msg since its the easiest to guarantee success in reply
-        let msg = CosmosMsg::Wasm(WasmMsg::Execute {
-            contract_addr: contract_addr.to_string(),
-            msg: to_binary(&ExecuteMsg::WithdrawReward {})?,
-            funds: coins(1, NATIVE_DENOM),
-        });
+        let res = app
+            .execute_contract(
+                Addr::unchecked(ADMIN),
+                contract_addr.clone(),
+                &ExecuteMsg::CreateTask {
+                    task: TaskRequest {
+                        interval: Interval::Once,
+                        boundary: Boundary {
+                            start: None,
+                            end: None,
+                        },
+                        stop_on_fail: false,
+                        allow_sponsored_refill: false,
+                        refund_split: None,
+                        preferred_agent: None,
+                        fail_mode: FailMode::StopOnFirstFail,
+                        agent_fee_override: None,
+                        on_success_task: None,
+                        label: None,
+                        memo: None,
+                        return_surplus: false,
+                        actions: vec![Action {
+                            msg: CosmosMsg::Wasm(WasmMsg::Execute {
+                                contract_addr: cw20_addr.to_string(),
+                                msg: to_binary(&cw20::Cw20ExecuteMsg::IncreaseAllowance {
+                                    spender: ANYONE.to_string(),
+                                    amount: Uint128::new(10),
+                                    expires: None,
+                                })?,
+                                funds: vec![],
+                            }),
+                            gas_limit: Some(250_000),
+                        }],
+                        rules: None,
+                    },
+                },
+                &coins(10, NATIVE_DENOM),
+            )
+            .unwrap();
+        let task_hash = res
+            .events
+            .iter()
+            .flat_map(|e| e.attributes.iter())
+            .find(|a| a.key == "task_hash")
+            .unwrap()
+            .value
+            .clone();
+        app.execute_contract(
+            Addr::unchecked(AGENT0),
+            contract_addr.clone(),
+            &ExecuteMsg::RegisterAgent {
+                payable_account_id: Some(Addr::unchecked(AGENT1_BENEFICIARY)),
+            },
+            &[],
+        )
+        .unwrap();
 
-        let create_task_msg = ExecuteMsg::CreateTask {
+        let task: Option<TaskResponse> = app
+            .wrap()
+            .query_wasm_smart(
+                contract_addr.clone(),
+                &QueryMsg::GetTask {
+                    task_hash: task_hash.clone(),
+                },
+            )
+            .unwrap();
+        let next_block = app.block_info().height + 1;
+        assert_eq!(Some((SlotType::Block, next_block)), task.unwrap().next_slot);
+
+        app.update_block(add_little_time);
+        let res = app
+            .execute_contract(
+                Addr::unchecked(AGENT0),
+                contract_addr.clone(),
+                &ExecuteMsg::ProxyCall {},
+                &[],
+            )
+            .unwrap();
+        assert!(res
+            .events
+            .iter()
+            .flat_map(|e| e.attributes.iter())
+            .any(|a| a.key == "ended_task" && a.value == task_hash));
+
+        // Ended & refunded after its single run, not rescheduled
+        let task: Option<TaskResponse> = app
+            .wrap()
+            .query_wasm_smart(contract_addr.clone(), &QueryMsg::GetTask { task_hash })
+            .unwrap();
+        assert!(task.is_none());
+        let slot_ids: GetSlotIdsResponse = app
+            .wrap()
+            .query_wasm_smart(contract_addr.clone(), &QueryMsg::GetSlotIds {})
+            .unwrap();
+        assert!(slot_ids.time_ids.is_empty());
+        assert!(slot_ids.block_ids.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn proxy_callback_one_shot_refund_modes() -> StdResult<()> {
+        for refund_on_remove in [true, false] {
+            let (mut app, cw_template_contract) = proper_instantiate();
+            let contract_addr = cw_template_contract.addr();
+            let cw20_addr = instantiate_cw20(&mut app);
+
+            app.execute_contract(
+                Addr::unchecked(ADMIN),
+                contract_addr.clone(),
+                &settings_msg(SettingsUpdate {
+                    refund_on_remove: Some(refund_on_remove),
+                    treasury_id: Some(Some(Addr::unchecked(TREASURY))),
+                    ..Default::default()
+                }),
+                &[],
+            )
+            .unwrap();
+            app.execute_contract(
+                Addr::unchecked(ANYONE),
+                contract_addr.clone(),
+                &ExecuteMsg::CreateTask {
+                    task: TaskRequest {
+                        interval: Interval::Once,
+                        boundary: Boundary {
+                            start: None,
+                            end: None,
+                        },
+                        stop_on_fail: false,
+                        allow_sponsored_refill: false,
+                        refund_split: None,
+                        preferred_agent: None,
+                        fail_mode: FailMode::StopOnFirstFail,
+                        agent_fee_override: None,
+                        on_success_task: None,
+                        label: None,
+                        memo: None,
+                        return_surplus: false,
+                        actions: vec![Action {
+                            msg: CosmosMsg::Wasm(WasmMsg::Execute {
+                                contract_addr: cw20_addr.to_string(),
+                                msg: to_binary(&cw20::Cw20ExecuteMsg::IncreaseAllowance {
+                                    spender: ANYONE.to_string(),
+                                    amount: Uint128::new(10),
+                                    expires: None,
+                                })?,
+                                funds: vec![],
+                            }),
+                            gas_limit: Some(250_000),
+                        }],
+                        rules: None,
+                    },
+                },
+                &coins(37, NATIVE_DENOM),
+            )
+            .unwrap();
+            app.execute_contract(
+                Addr::unchecked(AGENT0),
+                contract_addr.clone(),
+                &ExecuteMsg::RegisterAgent {
+                    payable_account_id: Some(Addr::unchecked(AGENT1_BENEFICIARY)),
+                },
+                &[],
+            )
+            .unwrap();
+
+            // The run ends the task, its leftover goes to the owner or the treasury
+            app.update_block(add_little_time);
+            let res = app
+                .execute_contract(
+                    Addr::unchecked(AGENT0),
+                    contract_addr.clone(),
+                    &ExecuteMsg::ProxyCall {},
+                    &[],
+                )
+                .unwrap();
+            let attribute = |key: &str| {
+                res.events
+                    .iter()
+                    .flat_map(|e| e.attributes.iter())
+                    .find(|a| a.key == key)
+                    .map(|a| a.value.clone())
+            };
+            let leftover = Some(format!("37{}", NATIVE_DENOM));
+            let treasury_balance = app.wrap().query_balance(TREASURY, NATIVE_DENOM)?;
+            let owner_balance = app.wrap().query_balance(ANYONE, NATIVE_DENOM)?;
+            if refund_on_remove {
+                assert_eq!(leftover, attribute("refund_amount"));
+                assert_eq!(None, attribute("treasury_amount"));
+                assert_eq!(Uint128::zero(), treasury_balance.amount);
+                assert_eq!(Uint128::new(100), owner_balance.amount);
+            } else {
+                assert_eq!(leftover, attribute("treasury_amount"));
+                assert_eq!(None, attribute("refund_amount"));
+                assert_eq!(Uint128::new(37), treasury_balance.amount);
+                assert_eq!(Uint128::new(63), owner_balance.amount);
+            }
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn proxy_call_preferred_agent() -> StdResult<()> {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+        let proxy_call_msg = ExecuteMsg::ProxyCall {};
+        let cw20_addr = instantiate_cw20(&mut app);
+
+        let create_task_msg = |preferred_agent: &str| ExecuteMsg::CreateTask {
             task: TaskRequest {
                 interval: Interval::Immediate,
                 boundary: Boundary {
@@ -641,58 +1285,568 @@ mod tests {
                     end: None,
                 },
                 stop_on_fail: false,
+                allow_sponsored_refill: false,
+                refund_split: None,
+                preferred_agent: Some(Addr::unchecked(preferred_agent)),
+                fail_mode: FailMode::StopOnFirstFail,
+                agent_fee_override: None,
+                on_success_task: None,
+                label: None,
+                memo: None,
+                return_surplus: false,
                 actions: vec![Action {
-                    msg,
+                    msg: CosmosMsg::Wasm(WasmMsg::Execute {
+                        contract_addr: cw20_addr.to_string(),
+                        msg: to_binary(&cw20::Cw20ExecuteMsg::IncreaseAllowance {
+                            spender: ANYONE.to_string(),
+                            amount: Uint128::new(10),
+                            expires: None,
+                        })
+                        .unwrap(),
+                        funds: coins(1, NATIVE_DENOM),
+                    }),
                     gas_limit: Some(250_000),
                 }],
                 rules: None,
             },
         };
+        let task_hash = |res: AppResponse| {
+            res.events
+                .iter()
+                .flat_map(|e| e.attributes.iter())
+                .find(|a| a.key == "task_hash")
+                .unwrap()
+                .value
+                .clone()
+        };
+        app.execute_contract(
+            Addr::unchecked(AGENT0),
+            contract_addr.clone(),
+            &ExecuteMsg::RegisterAgent {
+                payable_account_id: Some(Addr::unchecked(AGENT1_BENEFICIARY)),
+            },
+            &[],
+        )
+        .unwrap();
 
-        // create a task
+        // the preferred agent can execute right away
         let res = app
             .execute_contract(
                 Addr::unchecked(ADMIN),
                 contract_addr.clone(),
-                &create_task_msg,
+                &create_task_msg(AGENT0),
                 &coins(10, NATIVE_DENOM),
             )
             .unwrap();
-        // Assert task hash is returned as part of event attributes
-        let mut has_created_hash: bool = false;
-        for e in res.events {
-            for a in e.attributes {
-                if a.key == "task_hash" && a.value == task_id_str.clone() {
-                    has_created_hash = true;
-                }
-            }
-        }
-        assert!(has_created_hash);
-
-        // quick agent register
-        let msg = ExecuteMsg::RegisterAgent {
-            payable_account_id: Some(Addr::unchecked(AGENT1_BENEFICIARY)),
-        };
-        app.execute_contract(Addr::unchecked(AGENT0), contract_addr.clone(), &msg, &[])
-            .unwrap();
+        let preferred_hash = task_hash(res);
+        app.execute_contract(
+            Addr::unchecked(AGENT0),
+            contract_addr.clone(),
+            &proxy_call_msg,
+            &[],
+        )
+        .unwrap();
         app.execute_contract(
-            Addr::unchecked(contract_addr.clone()),
+            Addr::unchecked(ADMIN),
             contract_addr.clone(),
-            &msg,
+            &ExecuteMsg::RemoveTask {
+                task_hash: preferred_hash,
+            },
             &[],
         )
         .unwrap();
 
-        // might need block advancement?!
+        // other agents wait for the grace period
         app.update_block(add_little_time);
-
-        // execute proxy_call
-        let res = app
+        app.execute_contract(
+            Addr::unchecked(ADMIN),
+            contract_addr.clone(),
+            &create_task_msg(AGENT1_BENEFICIARY),
+            &coins(10, NATIVE_DENOM),
+        )
+        .unwrap();
+        app.update_block(|block| block.height += default_execution_grace_blocks() - 1);
+        let res_err = app
             .execute_contract(
                 Addr::unchecked(AGENT0),
                 contract_addr.clone(),
                 &proxy_call_msg,
-                &vec![],
+                &[],
+            )
+            .unwrap_err();
+        assert_eq!(
+            ContractError::CustomError {
+                val: "Task is reserved for its preferred agent".to_string()
+            },
+            res_err.downcast().unwrap()
+        );
+
+        app.update_block(add_little_time);
+        app.execute_contract(
+            Addr::unchecked(AGENT0),
+            contract_addr.clone(),
+            &proxy_call_msg,
+            &[],
+        )
+        .unwrap();
+
+        Ok(())
+    }
+
+    #[test]
+    fn proxy_call_execution_grace_blocks() -> StdResult<()> {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+        let proxy_call_msg = ExecuteMsg::ProxyCall {};
+        let cw20_addr = instantiate_cw20(&mut app);
+
+        app.execute_contract(
+            Addr::unchecked(ADMIN),
+            contract_addr.clone(),
+            &settings_msg(SettingsUpdate {
+                execution_grace_blocks: Some(3),
+                execution_grace_seconds: Some(30),
+                ..Default::default()
+            }),
+            &[],
+        )
+        .unwrap();
+        let config: GetConfigResponse = app
+            .wrap()
+            .query_wasm_smart(&contract_addr, &QueryMsg::GetConfig {})?;
+        assert_eq!(3, config.execution_grace_blocks);
+        assert_eq!(30, config.execution_grace_seconds);
+
+        let create_task_msg = |preferred_agent: &str, allowance: u128| ExecuteMsg::CreateTask {
+            task: TaskRequest {
+                interval: Interval::Immediate,
+                boundary: Boundary {
+                    start: None,
+                    end: None,
+                },
+                stop_on_fail: false,
+                allow_sponsored_refill: false,
+                refund_split: None,
+                preferred_agent: Some(Addr::unchecked(preferred_agent)),
+                fail_mode: FailMode::StopOnFirstFail,
+                agent_fee_override: None,
+                on_success_task: None,
+                label: None,
+                memo: None,
+                return_surplus: false,
+                actions: vec![Action {
+                    msg: CosmosMsg::Wasm(WasmMsg::Execute {
+                        contract_addr: cw20_addr.to_string(),
+                        msg: to_binary(&cw20::Cw20ExecuteMsg::IncreaseAllowance {
+                            spender: ANYONE.to_string(),
+                            amount: Uint128::new(allowance),
+                            expires: None,
+                        })
+                        .unwrap(),
+                        funds: coins(1, NATIVE_DENOM),
+                    }),
+                    gas_limit: Some(250_000),
+                }],
+                rules: None,
+            },
+        };
+        app.execute_contract(
+            Addr::unchecked(AGENT0),
+            contract_addr.clone(),
+            &ExecuteMsg::RegisterAgent {
+                payable_account_id: Some(Addr::unchecked(AGENT1_BENEFICIARY)),
+            },
+            &[],
+        )
+        .unwrap();
+
+        // within the grace period only the preferred agent executes
+        app.execute_contract(
+            Addr::unchecked(ADMIN),
+            contract_addr.clone(),
+            &create_task_msg(AGENT0, 10),
+            &coins(10, NATIVE_DENOM),
+        )
+        .unwrap();
+        app.execute_contract(
+            Addr::unchecked(AGENT0),
+            contract_addr.clone(),
+            &proxy_call_msg,
+            &[],
+        )
+        .unwrap();
+
+        app.update_block(add_little_time);
+        app.execute_contract(
+            Addr::unchecked(ADMIN),
+            contract_addr.clone(),
+            &create_task_msg(AGENT1_BENEFICIARY, 20),
+            &coins(10, NATIVE_DENOM),
+        )
+        .unwrap();
+        app.update_block(|block| block.height += 2);
+        let res_err = app
+            .execute_contract(
+                Addr::unchecked(AGENT0),
+                contract_addr.clone(),
+                &proxy_call_msg,
+                &[],
+            )
+            .unwrap_err();
+        assert_eq!(
+            ContractError::CustomError {
+                val: "Task is reserved for its preferred agent".to_string()
+            },
+            res_err.downcast().unwrap()
+        );
+
+        // after it, any active agent does
+        app.update_block(|block| block.height += 1);
+        app.execute_contract(
+            Addr::unchecked(AGENT0),
+            contract_addr.clone(),
+            &proxy_call_msg,
+            &[],
+        )
+        .unwrap();
+
+        Ok(())
+    }
+
+    #[test]
+    fn is_reserved_for_other_execution_grace_seconds() -> StdResult<()> {
+        let mut deps = mock_dependencies_with_balances(&[]);
+        let store = CwCroncat::default();
+        store
+            .instantiate(
+                deps.as_mut(),
+                mock_env(),
+                mock_info(ADMIN, &[]),
+                InstantiateMsg {
+                    denom: NATIVE_DENOM.to_string(),
+                    owner_id: None,
+                    agent_nomination_duration: None,
+                },
+            )
+            .unwrap();
+        let res = store
+            .create_task(
+                deps.as_mut(),
+                mock_info(ADMIN, &coins(10, NATIVE_DENOM)),
+                mock_env(),
+                TaskRequest {
+                    interval: Interval::Immediate,
+                    boundary: Boundary {
+                        start: None,
+                        end: None,
+                    },
+                    stop_on_fail: false,
+                    allow_sponsored_refill: false,
+                    refund_split: None,
+                    preferred_agent: Some(Addr::unchecked(AGENT0)),
+                    fail_mode: FailMode::StopOnFirstFail,
+                    agent_fee_override: None,
+                    on_success_task: None,
+                    label: None,
+                    memo: None,
+                    return_surplus: false,
+                    actions: vec![Action {
+                        msg: StakingMsg::Delegate {
+                            validator: "you".to_string(),
+                            amount: coin(1, NATIVE_DENOM),
+                        }
+                        .into(),
+                        gas_limit: Some(150_000),
+                    }],
+                    rules: None,
+                },
+            )
+            .unwrap();
+        let task_hash = res
+            .attributes
+            .iter()
+            .find(|a| a.key == "task_hash")
+            .unwrap()
+            .value
+            .clone();
+        let task = store.tasks.load(&deps.storage, task_hash.into_bytes())?;
+        let mut c = store.config.load(&deps.storage)?;
+        c.execution_grace_seconds = 30;
+
+        let slot_id = mock_env().block.time.nanos();
+        let reserved_after = |seconds: u64| {
+            let mut block = mock_env().block;
+            block.time = block.time.plus_seconds(seconds);
+            is_reserved_for_other(
+                &c,
+                &task,
+                &Addr::unchecked(ANYONE),
+                slot_id,
+                &SlotType::Cron,
+                &block,
+            )
+        };
+        assert!(reserved_after(29));
+        assert!(!reserved_after(30));
+
+        Ok(())
+    }
+
+    #[test]
+    fn proxy_call_balancer_invariant() -> StdResult<()> {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+        let cw20_addr = instantiate_cw20(&mut app);
+        let invariant = |app: &App| -> StdResult<GetBalancerInvariantResponse> {
+            app.wrap()
+                .query_wasm_smart(&contract_addr, &QueryMsg::GetBalancerInvariant {})
+        };
+
+        // The instantiate funds are surplus
+        let res = invariant(&app)?;
+        assert!(res.in_balance);
+        assert_eq!(res.available_balance, res.surplus_balance);
+        let surplus = res.surplus_balance.native[0].amount.u128();
+
+        app.execute_contract(
+            Addr::unchecked(ADMIN),
+            contract_addr.clone(),
+            &ExecuteMsg::DepositFunds {},
+            &coins(10, NATIVE_DENOM),
+        )
+        .unwrap();
+        app.execute_contract(
+            Addr::unchecked(ADMIN),
+            contract_addr.clone(),
+            &ExecuteMsg::CreateTask {
+                task: TaskRequest {
+                    interval: Interval::Immediate,
+                    boundary: Boundary {
+                        start: None,
+                        end: None,
+                    },
+                    stop_on_fail: false,
+                    allow_sponsored_refill: false,
+                    refund_split: None,
+                    preferred_agent: None,
+                    fail_mode: FailMode::StopOnFirstFail,
+                    agent_fee_override: None,
+                    on_success_task: None,
+                    label: None,
+                    memo: None,
+                    return_surplus: false,
+                    actions: vec![Action {
+                        msg: CosmosMsg::Wasm(WasmMsg::Execute {
+                            contract_addr: cw20_addr.to_string(),
+                            msg: to_binary(&cw20::Cw20ExecuteMsg::IncreaseAllowance {
+                                spender: ANYONE.to_string(),
+                                amount: Uint128::new(10),
+                                expires: None,
+                            })?,
+                            funds: vec![],
+                        }),
+                        gas_limit: Some(250_000),
+                    }],
+                    rules: None,
+                },
+            },
+            &coins(30, NATIVE_DENOM),
+        )
+        .unwrap();
+        app.execute_contract(
+            Addr::unchecked(AGENT0),
+            contract_addr.clone(),
+            &ExecuteMsg::RegisterAgent {
+                payable_account_id: Some(Addr::unchecked(AGENT1_BENEFICIARY)),
+            },
+            &[],
+        )
+        .unwrap();
+        assert!(invariant(&app)?.in_balance);
+
+        // The agent fee is paid from the protocol funds, the task deposit is untouched
+        app.update_block(add_little_time);
+        app.execute_contract(
+            Addr::unchecked(AGENT0),
+            contract_addr.clone(),
+            &ExecuteMsg::ProxyCall {},
+            &[],
+        )
+        .unwrap();
+        let res = invariant(&app)?;
+        assert!(res.in_balance);
+        assert_eq!(coins(surplus, NATIVE_DENOM), res.surplus_balance.native);
+        assert_eq!(coins(5, NATIVE_DENOM), res.protocol_balance.native);
+        assert_eq!(coins(30, NATIVE_DENOM), res.tasks_balance.native);
+
+        Ok(())
+    }
+
+    #[test]
+    fn proxy_call_task_without_actions() -> StdResult<()> {
+        let mut deps = mock_dependencies_with_balances(&[(AGENT0, &coins(100, NATIVE_DENOM))]);
+        let mut store = CwCroncat::default();
+        store
+            .instantiate(
+                deps.as_mut(),
+                mock_env(),
+                mock_info(ADMIN, &coins(6, NATIVE_DENOM)),
+                InstantiateMsg {
+                    denom: NATIVE_DENOM.to_string(),
+                    owner_id: None,
+                    agent_nomination_duration: None,
+                },
+            )
+            .unwrap();
+        store
+            .register_agent(deps.as_mut(), mock_info(AGENT0, &[]), mock_env(), None)
+            .unwrap();
+        let res = store
+            .create_task(
+                deps.as_mut(),
+                mock_info(ADMIN, &coins(10, NATIVE_DENOM)),
+                mock_env(),
+                TaskRequest {
+                    interval: Interval::Immediate,
+                    boundary: Boundary {
+                        start: None,
+                        end: None,
+                    },
+                    stop_on_fail: false,
+                    allow_sponsored_refill: false,
+                    refund_split: None,
+                    preferred_agent: None,
+                    fail_mode: FailMode::StopOnFirstFail,
+                    agent_fee_override: None,
+                    on_success_task: None,
+                    label: None,
+                    memo: None,
+                    return_surplus: false,
+                    actions: vec![Action {
+                        msg: StakingMsg::Delegate {
+                            validator: "you".to_string(),
+                            amount: coin(1, NATIVE_DENOM),
+                        }
+                        .into(),
+                        gas_limit: Some(150_000),
+                    }],
+                    rules: None,
+                },
+            )
+            .unwrap();
+        let task_hash = res
+            .attributes
+            .iter()
+            .find(|a| a.key == "task_hash")
+            .unwrap()
+            .value
+            .clone();
+
+        // Tasks stored before actions were required can have none
+        let mut task = store
+            .tasks
+            .load(&deps.storage, task_hash.clone().into_bytes())?;
+        task.actions = vec![];
+        store
+            .tasks
+            .save(&mut deps.storage, task_hash.into_bytes(), &task)?;
+
+        // Nothing is sent, so nothing would ever reply & clear the reentrancy guard
+        let res = store
+            .proxy_call(deps.as_mut(), mock_info(AGENT0, &[]), mock_env())
+            .unwrap();
+        assert!(res.messages.iter().all(|m| m.reply_on == ReplyOn::Never));
+        assert!(!store
+            .proxy_in_progress
+            .may_load(&deps.storage)?
+            .unwrap_or_default());
+
+        Ok(())
+    }
+
+    #[test]
+    fn proxy_call_success() -> StdResult<()> {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+        let proxy_call_msg = ExecuteMsg::ProxyCall {};
+        let task_id_str =
+            "48f7b3346544d96be5f4e1edf0349b6ac99f997ed8ff22f2394be0af5bb10f85".to_string();
+
+        let cw20_addr = instantiate_cw20(&mut app);
+
+        // Doing this msg since its the easiest to guarantee success in reply
+        let msg = CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: cw20_addr.to_string(),
+            msg: to_binary(&cw20::Cw20ExecuteMsg::IncreaseAllowance {
+                spender: ANYONE.to_string(),
+                amount: Uint128::new(10),
+                expires: None,
+            })?,
+            funds: coins(1, NATIVE_DENOM),
+        });
+
+        let create_task_msg = ExecuteMsg::CreateTask {
+            task: TaskRequest {
+                interval: Interval::Immediate,
+                boundary: Boundary {
+                    start: None,
+                    end: None,
+                },
+                stop_on_fail: false,
+                allow_sponsored_refill: false,
+                refund_split: None,
+                preferred_agent: None,
+                fail_mode: FailMode::StopOnFirstFail,
+                agent_fee_override: None,
+                on_success_task: None,
+                label: None,
+                memo: None,
+                return_surplus: false,
+                actions: vec![Action {
+                    msg,
+                    gas_limit: Some(250_000),
+                }],
+                rules: None,
+            },
+        };
+
+        // create a task
+        let res = app
+            .execute_contract(
+                Addr::unchecked(ADMIN),
+                contract_addr.clone(),
+                &create_task_msg,
+                &coins(10, NATIVE_DENOM),
+            )
+            .unwrap();
+        // Assert task hash is returned as part of event attributes
+        let mut has_created_hash: bool = false;
+        for e in res.events {
+            for a in e.attributes {
+                if a.key == "task_hash" && a.value == task_id_str.clone() {
+                    has_created_hash = true;
+                }
+            }
+        }
+        assert!(has_created_hash);
+
+        // quick agent register
+        let msg = ExecuteMsg::RegisterAgent {
+            payable_account_id: Some(Addr::unchecked(AGENT1_BENEFICIARY)),
+        };
+        app.execute_contract(Addr::unchecked(AGENT0), contract_addr.clone(), &msg, &[])
+            .unwrap();
+
+        // might need block advancement?!
+        app.update_block(add_little_time);
+
+        // execute proxy_call
+        let res = app
+            .execute_contract(
+                Addr::unchecked(AGENT0),
+                contract_addr.clone(),
+                &proxy_call_msg,
+                &vec![],
             )
             .unwrap();
         let mut has_required_attributes: bool = true;
@@ -701,8 +1855,8 @@ mod tests {
         let attributes = vec![
             ("method", "proxy_call"),
             ("agent", AGENT0),
-            ("slot_id", "12346"),
-            ("slot_kind", "Block"),
+            ("slot_id", "12345"),
+            ("slot_kind", "block"),
             ("task_hash", task_id_str.as_str().clone()),
         ];
 
@@ -717,8 +1871,8 @@ mod tests {
                         attr_value = Some(a.clone().value);
                     }
                     if e.ty == "wasm"
-                        && a.clone().key == "method"
-                        && a.clone().value == "withdraw_agent_balance"
+                        && a.clone().key == "action"
+                        && a.clone().value == "increase_allowance"
                     {
                         has_submsg_method = true;
                     }
@@ -773,6 +1927,15 @@ mod tests {
                     end: Some(BoundarySpec::Height(12347)),
                 },
                 stop_on_fail: true,
+                allow_sponsored_refill: false,
+                refund_split: None,
+                preferred_agent: None,
+                fail_mode: FailMode::StopOnFirstFail,
+                agent_fee_override: None,
+                on_success_task: None,
+                label: None,
+                memo: None,
+                return_surplus: false,
                 actions: vec![Action {
                     msg,
                     gas_limit: Some(250_000),
@@ -807,13 +1970,6 @@ mod tests {
         };
         app.execute_contract(Addr::unchecked(AGENT0), contract_addr.clone(), &msg, &[])
             .unwrap();
-        app.execute_contract(
-            Addr::unchecked(contract_addr.clone()),
-            contract_addr.clone(),
-            &msg,
-            &[],
-        )
-        .unwrap();
 
         // might need block advancement?!
         app.update_block(add_little_time);
@@ -832,8 +1988,8 @@ mod tests {
         let mut has_reply_success: bool = false;
         let attributes = vec![
             ("method", "remove_task"), // the last method
-            ("slot_id", "12346"),
-            ("slot_kind", "Block"),
+            ("slot_id", "12345"),
+            ("slot_kind", "block"),
             ("task_hash", task_id_str.as_str().clone()),
         ];
 
@@ -896,6 +2052,15 @@ mod tests {
                     end: Some(BoundarySpec::Height(12347)),
                 },
                 stop_on_fail: false,
+                allow_sponsored_refill: false,
+                refund_split: None,
+                preferred_agent: None,
+                fail_mode: FailMode::StopOnFirstFail,
+                agent_fee_override: None,
+                on_success_task: None,
+                label: None,
+                memo: None,
+                return_surplus: false,
                 actions: vec![Action {
                     msg,
                     gas_limit: Some(250_000),
@@ -985,12 +2150,18 @@ mod tests {
         let contract_addr = cw_template_contract.addr();
         let proxy_call_msg = ExecuteMsg::ProxyCall {};
         let task_id_str =
-            "9c1b6c9d91a5960b9c8580f3606bca18a9ceb8ed628f68a1c7022ef130c5c2d6".to_string();
+            "48f7b3346544d96be5f4e1edf0349b6ac99f997ed8ff22f2394be0af5bb10f85".to_string();
+
+        let cw20_addr = instantiate_cw20(&mut app);
 
         // Doing this msg since its the easiest to guarantee success in reply
         let msg = CosmosMsg::Wasm(WasmMsg::Execute {
-            contract_addr: contract_addr.to_string(),
-            msg: to_binary(&ExecuteMsg::WithdrawReward {})?,
+            contract_addr: cw20_addr.to_string(),
+            msg: to_binary(&cw20::Cw20ExecuteMsg::IncreaseAllowance {
+                spender: ANYONE.to_string(),
+                amount: Uint128::new(10),
+                expires: None,
+            })?,
             funds: coins(1, NATIVE_DENOM),
         });
 
@@ -1002,6 +2173,15 @@ mod tests {
                     end: None,
                 },
                 stop_on_fail: false,
+                allow_sponsored_refill: false,
+                refund_split: None,
+                preferred_agent: None,
+                fail_mode: FailMode::StopOnFirstFail,
+                agent_fee_override: None,
+                on_success_task: None,
+                label: None,
+                memo: None,
+                return_surplus: false,
                 actions: vec![Action {
                     msg,
                     gas_limit: Some(250_000),
@@ -1036,13 +2216,6 @@ mod tests {
         };
         app.execute_contract(Addr::unchecked(AGENT0), contract_addr.clone(), &msg, &[])
             .unwrap();
-        app.execute_contract(
-            Addr::unchecked(contract_addr.clone()),
-            contract_addr.clone(),
-            &msg,
-            &[],
-        )
-        .unwrap();
 
         // might need block advancement?!
         app.update_block(add_little_time);
@@ -1062,7 +2235,7 @@ mod tests {
         let attributes = vec![
             ("method", "proxy_callback"),
             ("slot_id", "12347"),
-            ("slot_kind", "Block"),
+            ("slot_kind", "block"),
             ("task_hash", task_id_str.as_str().clone()),
         ];
 
@@ -1077,8 +2250,8 @@ mod tests {
                         attr_value = Some(a.clone().value);
                     }
                     if e.ty == "wasm"
-                        && a.clone().key == "method"
-                        && a.clone().value == "withdraw_agent_balance"
+                        && a.clone().key == "action"
+                        && a.clone().value == "increase_allowance"
                     {
                         has_submsg_method = true;
                     }
@@ -1117,12 +2290,18 @@ mod tests {
         let contract_addr = cw_template_contract.addr();
         let proxy_call_msg = ExecuteMsg::ProxyCall {};
         let task_id_str =
-            "0309be13444499606658e996ed79c3334bf258bbea573ca880f2e8d70bb536b3".to_string();
+            "60a92296a2fb7ab636e9c954935011b16205b2665823d0887430c8c5f2d13fa7".to_string();
+
+        let cw20_addr = instantiate_cw20(&mut app);
 
         // Doing this msg since its the easiest to guarantee success in reply
         let msg = CosmosMsg::Wasm(WasmMsg::Execute {
-            contract_addr: contract_addr.to_string(),
-            msg: to_binary(&ExecuteMsg::WithdrawReward {})?,
+            contract_addr: cw20_addr.to_string(),
+            msg: to_binary(&cw20::Cw20ExecuteMsg::IncreaseAllowance {
+                spender: ANYONE.to_string(),
+                amount: Uint128::new(10),
+                expires: None,
+            })?,
             funds: coins(1, NATIVE_DENOM),
         });
 
@@ -1134,6 +2313,15 @@ mod tests {
                     end: None,
                 },
                 stop_on_fail: false,
+                allow_sponsored_refill: false,
+                refund_split: None,
+                preferred_agent: None,
+                fail_mode: FailMode::StopOnFirstFail,
+                agent_fee_override: None,
+                on_success_task: None,
+                label: None,
+                memo: None,
+                return_surplus: false,
                 actions: vec![Action {
                     msg,
                     gas_limit: Some(250_000),
@@ -1168,13 +2356,6 @@ mod tests {
         };
         app.execute_contract(Addr::unchecked(AGENT0), contract_addr.clone(), &msg, &[])
             .unwrap();
-        app.execute_contract(
-            Addr::unchecked(contract_addr.clone()),
-            contract_addr.clone(),
-            &msg,
-            &[],
-        )
-        .unwrap();
 
         // might need block advancement?!
         app.update_block(add_one_duration_of_time);
@@ -1194,7 +2375,7 @@ mod tests {
         let attributes = vec![
             ("method", "proxy_callback"),
             ("slot_id", "1571797860000000000"),
-            ("slot_kind", "Cron"),
+            ("slot_kind", "cron"),
             ("task_hash", task_id_str.as_str().clone()),
         ];
 
@@ -1209,8 +2390,8 @@ mod tests {
                         attr_value = Some(a.clone().value);
                     }
                     if e.ty == "wasm"
-                        && a.clone().key == "method"
-                        && a.clone().value == "withdraw_agent_balance"
+                        && a.clone().key == "action"
+                        && a.clone().value == "increase_allowance"
                     {
                         has_submsg_method = true;
                     }
@@ -1244,30 +2425,26 @@ mod tests {
     }
 
     #[test]
-    fn proxy_call_several_tasks() -> StdResult<()> {
+    fn proxy_callback_execution_history() -> StdResult<()> {
         let (mut app, cw_template_contract) = proper_instantiate();
         let contract_addr = cw_template_contract.addr();
         let proxy_call_msg = ExecuteMsg::ProxyCall {};
+        let task_id_str =
+            "48f7b3346544d96be5f4e1edf0349b6ac99f997ed8ff22f2394be0af5bb10f85".to_string();
+
+        let cw20_addr = instantiate_cw20(&mut app);
 
         // Doing this msg since its the easiest to guarantee success in reply
         let msg = CosmosMsg::Wasm(WasmMsg::Execute {
-            contract_addr: contract_addr.to_string(),
-            msg: to_binary(&ExecuteMsg::WithdrawReward {})?,
+            contract_addr: cw20_addr.to_string(),
+            msg: to_binary(&cw20::Cw20ExecuteMsg::IncreaseAllowance {
+                spender: ANYONE.to_string(),
+                amount: Uint128::new(10),
+                expires: None,
+            })?,
             funds: coins(1, NATIVE_DENOM),
         });
 
-        let msg2 = CosmosMsg::Wasm(WasmMsg::Execute {
-            contract_addr: contract_addr.to_string(),
-            msg: to_binary(&ExecuteMsg::WithdrawReward {})?,
-            funds: coins(2, NATIVE_DENOM),
-        });
-
-        let msg3 = CosmosMsg::Wasm(WasmMsg::Execute {
-            contract_addr: contract_addr.to_string(),
-            msg: to_binary(&ExecuteMsg::WithdrawReward {})?,
-            funds: coins(3, NATIVE_DENOM),
-        });
-
         let create_task_msg = ExecuteMsg::CreateTask {
             task: TaskRequest {
                 interval: Interval::Immediate,
@@ -1276,6 +2453,1385 @@ mod tests {
                     end: None,
                 },
                 stop_on_fail: false,
+                allow_sponsored_refill: false,
+                refund_split: None,
+                preferred_agent: None,
+                fail_mode: FailMode::StopOnFirstFail,
+                agent_fee_override: None,
+                on_success_task: None,
+                label: None,
+                memo: None,
+                return_surplus: false,
+                actions: vec![Action {
+                    msg,
+                    gas_limit: Some(250_000),
+                }],
+                rules: None,
+            },
+        };
+
+        // create a task
+        app.execute_contract(
+            Addr::unchecked(ADMIN),
+            contract_addr.clone(),
+            &create_task_msg,
+            &coins(10, NATIVE_DENOM),
+        )
+        .unwrap();
+
+        // quick agent register
+        let msg = ExecuteMsg::RegisterAgent {
+            payable_account_id: Some(Addr::unchecked(AGENT1_BENEFICIARY)),
+        };
+        app.execute_contract(Addr::unchecked(AGENT0), contract_addr.clone(), &msg, &[])
+            .unwrap();
+
+        // no history before the first execution
+        let task: Option<TaskResponse> = app
+            .wrap()
+            .query_wasm_smart(
+                &contract_addr,
+                &QueryMsg::GetTask {
+                    task_hash: task_id_str.clone(),
+                },
+            )
+            .unwrap();
+        let task = task.unwrap();
+        assert_eq!(0, task.executions_count);
+        assert_eq!(None, task.last_executed_slot);
+
+        // execute the task twice, once per block
+        app.update_block(add_little_time);
+        app.execute_contract(
+            Addr::unchecked(AGENT0),
+            contract_addr.clone(),
+            &proxy_call_msg,
+            &[],
+        )
+        .unwrap();
+        app.update_block(add_little_time);
+        app.execute_contract(
+            Addr::unchecked(AGENT0),
+            contract_addr.clone(),
+            &proxy_call_msg,
+            &[],
+        )
+        .unwrap();
+
+        let task: Option<TaskResponse> = app
+            .wrap()
+            .query_wasm_smart(
+                &contract_addr,
+                &QueryMsg::GetTask {
+                    task_hash: task_id_str.clone(),
+                },
+            )
+            .unwrap();
+        let task = task.unwrap();
+        assert_eq!(2, task.executions_count);
+        assert_eq!(Some(12347), task.last_executed_slot);
+
+        Ok(())
+    }
+
+    #[test]
+    fn proxy_callback_fail_mode() -> StdResult<()> {
+        for (fail_mode, expected_allowance) in [
+            (FailMode::StopOnFirstFail, 10u128),
+            (FailMode::ContinueOnFail, 20u128),
+        ] {
+            let (mut app, cw_template_contract) = proper_instantiate();
+            let contract_addr = cw_template_contract.addr();
+            let cw20_addr = instantiate_cw20(&mut app);
+
+            // Allowance increases always succeed, the transfer fails as the contract holds no tokens
+            let increase_allowance = Action {
+                msg: CosmosMsg::Wasm(WasmMsg::Execute {
+                    contract_addr: cw20_addr.to_string(),
+                    msg: to_binary(&cw20::Cw20ExecuteMsg::IncreaseAllowance {
+                        spender: ANYONE.to_string(),
+                        amount: Uint128::new(10),
+                        expires: None,
+                    })?,
+                    funds: vec![],
+                }),
+                gas_limit: Some(250_000),
+            };
+            let transfer = Action {
+                msg: CosmosMsg::Wasm(WasmMsg::Execute {
+                    contract_addr: cw20_addr.to_string(),
+                    msg: to_binary(&cw20::Cw20ExecuteMsg::Transfer {
+                        recipient: ANYONE.to_string(),
+                        amount: Uint128::new(10),
+                    })?,
+                    funds: vec![],
+                }),
+                gas_limit: Some(250_000),
+            };
+            let create_task_msg = ExecuteMsg::CreateTask {
+                task: TaskRequest {
+                    interval: Interval::Immediate,
+                    boundary: Boundary {
+                        start: None,
+                        end: None,
+                    },
+                    stop_on_fail: false,
+                    allow_sponsored_refill: false,
+                    refund_split: None,
+                    preferred_agent: None,
+                    fail_mode,
+                    agent_fee_override: None,
+                    on_success_task: None,
+                    label: None,
+                    memo: None,
+                    return_surplus: false,
+                    actions: vec![increase_allowance.clone(), transfer, increase_allowance],
+                    rules: None,
+                },
+            };
+            app.execute_contract(
+                Addr::unchecked(ADMIN),
+                contract_addr.clone(),
+                &create_task_msg,
+                &coins(30, NATIVE_DENOM),
+            )
+            .unwrap();
+
+            let msg = ExecuteMsg::RegisterAgent {
+                payable_account_id: Some(Addr::unchecked(AGENT1_BENEFICIARY)),
+            };
+            app.execute_contract(Addr::unchecked(AGENT0), contract_addr.clone(), &msg, &[])
+                .unwrap();
+
+            app.update_block(add_little_time);
+            let res = app
+                .execute_contract(
+                    Addr::unchecked(AGENT0),
+                    contract_addr.clone(),
+                    &ExecuteMsg::ProxyCall {},
+                    &[],
+                )
+                .unwrap();
+
+            // The failed middle action is recorded, whatever the mode
+            let failed_actions: Vec<String> = res
+                .events
+                .iter()
+                .flat_map(|e| e.attributes.iter())
+                .filter(|a| a.key == "failed_actions")
+                .map(|a| a.value.clone())
+                .collect();
+            assert_eq!(vec!["1".to_string()], failed_actions);
+
+            // Only ContinueOnFail runs the last action
+            let allowance: cw20::AllowanceResponse = app.wrap().query_wasm_smart(
+                &cw20_addr,
+                &cw20::Cw20QueryMsg::Allowance {
+                    owner: contract_addr.to_string(),
+                    spender: ANYONE.to_string(),
+                },
+            )?;
+            assert_eq!(Uint128::new(expected_allowance), allowance.allowance);
+
+            // The callback finished, so another proxy call goes through
+            let in_progress: bool = app
+                .wrap()
+                .query_wasm_raw(&contract_addr, b"proxy_in_progress".as_slice())?
+                .map(|v| cosmwasm_std::from_slice(&v))
+                .transpose()?
+                .unwrap_or(false);
+            assert!(!in_progress);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn proxy_callback_on_success_task() -> StdResult<()> {
+        for parent_succeeds in [true, false] {
+            let (mut app, cw_template_contract) = proper_instantiate();
+            let contract_addr = cw_template_contract.addr();
+            let cw20_addr = instantiate_cw20(&mut app);
+
+            // Allowance increases always succeed, the transfer fails as the contract holds no tokens
+            let action = |succeeds: bool| -> StdResult<Action> {
+                let msg = if succeeds {
+                    cw20::Cw20ExecuteMsg::IncreaseAllowance {
+                        spender: ANYONE.to_string(),
+                        amount: Uint128::new(10),
+                        expires: None,
+                    }
+                } else {
+                    cw20::Cw20ExecuteMsg::Transfer {
+                        recipient: ANYONE.to_string(),
+                        amount: Uint128::new(10),
+                    }
+                };
+                Ok(Action {
+                    msg: CosmosMsg::Wasm(WasmMsg::Execute {
+                        contract_addr: cw20_addr.to_string(),
+                        msg: to_binary(&msg)?,
+                        funds: vec![],
+                    }),
+                    gas_limit: Some(250_000),
+                })
+            };
+            let create_task_msg = |interval, action, on_success_task| ExecuteMsg::CreateTask {
+                task: TaskRequest {
+                    interval,
+                    boundary: Boundary {
+                        start: None,
+                        end: None,
+                    },
+                    stop_on_fail: false,
+                    allow_sponsored_refill: false,
+                    refund_split: None,
+                    preferred_agent: None,
+                    fail_mode: FailMode::StopOnFirstFail,
+                    agent_fee_override: None,
+                    on_success_task,
+                    label: None,
+                    memo: None,
+                    return_surplus: false,
+                    actions: vec![action],
+                    rules: None,
+                },
+            };
+
+            // The chained task must exist
+            let res_err = app
+                .execute_contract(
+                    Addr::unchecked(ADMIN),
+                    contract_addr.clone(),
+                    &create_task_msg(
+                        Interval::Immediate,
+                        action(parent_succeeds)?,
+                        Some("not_a_task".to_string()),
+                    ),
+                    &coins(30, NATIVE_DENOM),
+                )
+                .unwrap_err();
+            assert_eq!(
+                ContractError::ChainedTaskNotFound {
+                    task_hash: "not_a_task".to_string()
+                },
+                res_err.downcast().unwrap()
+            );
+
+            // Only the owner's own tasks can be chained
+            let res = app
+                .execute_contract(
+                    Addr::unchecked(ANYONE),
+                    contract_addr.clone(),
+                    &create_task_msg(Interval::Block(1_000), action(true)?, None),
+                    &coins(30, NATIVE_DENOM),
+                )
+                .unwrap();
+            let other_hash = res
+                .events
+                .iter()
+                .flat_map(|e| e.attributes.iter())
+                .find(|a| a.key == "task_hash")
+                .unwrap()
+                .value
+                .clone();
+            let res_err = app
+                .execute_contract(
+                    Addr::unchecked(ADMIN),
+                    contract_addr.clone(),
+                    &create_task_msg(
+                        Interval::Immediate,
+                        action(parent_succeeds)?,
+                        Some(other_hash),
+                    ),
+                    &coins(30, NATIVE_DENOM),
+                )
+                .unwrap_err();
+            assert_eq!(ContractError::Unauthorized {}, res_err.downcast().unwrap());
+
+            // The child only runs on its own far in the future
+            let res = app
+                .execute_contract(
+                    Addr::unchecked(ADMIN),
+                    contract_addr.clone(),
+                    &create_task_msg(Interval::Block(1_000), action(true)?, None),
+                    &coins(30, NATIVE_DENOM),
+                )
+                .unwrap();
+            let child_hash = res
+                .events
+                .iter()
+                .flat_map(|e| e.attributes.iter())
+                .find(|a| a.key == "task_hash")
+                .unwrap()
+                .value
+                .clone();
+            app.execute_contract(
+                Addr::unchecked(ADMIN),
+                contract_addr.clone(),
+                &create_task_msg(
+                    Interval::Immediate,
+                    action(parent_succeeds)?,
+                    Some(child_hash.clone()),
+                ),
+                &coins(30, NATIVE_DENOM),
+            )
+            .unwrap();
+
+            let msg = ExecuteMsg::RegisterAgent {
+                payable_account_id: Some(Addr::unchecked(AGENT1_BENEFICIARY)),
+            };
+            app.execute_contract(Addr::unchecked(AGENT0), contract_addr.clone(), &msg, &[])
+                .unwrap();
+
+            app.update_block(add_little_time);
+            let res = app
+                .execute_contract(
+                    Addr::unchecked(AGENT0),
+                    contract_addr.clone(),
+                    &ExecuteMsg::ProxyCall {},
+                    &[],
+                )
+                .unwrap();
+            let triggered: Vec<String> = res
+                .events
+                .iter()
+                .flat_map(|e| e.attributes.iter())
+                .filter(|a| a.key == "triggered_task")
+                .map(|a| a.value.clone())
+                .collect();
+
+            let slot_hashes: GetSlotHashesResponse = app.wrap().query_wasm_smart(
+                &contract_addr,
+                &QueryMsg::GetSlotHashes {
+                    slot: Some((SlotType::Block, app.block_info().height)),
+                    from_index: None,
+                    limit: None,
+                    due_only: false,
+                },
+            )?;
+            // The child already has its own slot, so even a successful parent doesn't add another
+            assert!(triggered.is_empty());
+            assert!(!slot_hashes.block_task_hash.contains(&child_hash));
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn trigger_chained_task_unscheduled_child() -> StdResult<()> {
+        let mut deps = mock_dependencies_with_balances(&[]);
+        let store = CwCroncat::default();
+        store
+            .instantiate(
+                deps.as_mut(),
+                mock_env(),
+                mock_info(ADMIN, &coins(6, NATIVE_DENOM)),
+                InstantiateMsg {
+                    denom: NATIVE_DENOM.to_string(),
+                    owner_id: None,
+                    agent_nomination_duration: None,
+                },
+            )
+            .unwrap();
+        let res = store
+            .create_task(
+                deps.as_mut(),
+                mock_info(ADMIN, &coins(10, NATIVE_DENOM)),
+                mock_env(),
+                TaskRequest {
+                    interval: Interval::Block(1_000),
+                    boundary: Boundary {
+                        start: None,
+                        end: None,
+                    },
+                    stop_on_fail: false,
+                    allow_sponsored_refill: false,
+                    refund_split: None,
+                    preferred_agent: None,
+                    fail_mode: FailMode::StopOnFirstFail,
+                    agent_fee_override: None,
+                    on_success_task: None,
+                    label: None,
+                    memo: None,
+                    return_surplus: false,
+                    actions: vec![Action {
+                        msg: StakingMsg::Delegate {
+                            validator: "you".to_string(),
+                            amount: coin(1, NATIVE_DENOM),
+                        }
+                        .into(),
+                        gas_limit: Some(150_000),
+                    }],
+                    rules: None,
+                },
+            )
+            .unwrap();
+        let child_hash = res
+            .attributes
+            .iter()
+            .find(|a| a.key == "task_hash")
+            .unwrap()
+            .value
+            .clone();
+        let triggered = |res: &Response| {
+            res.attributes
+                .iter()
+                .any(|a| a.key == "triggered_task" && a.value == child_hash)
+        };
+
+        // Already in its own slot
+        let env = mock_env();
+        let res = store
+            .trigger_chained_task(&mut deps.storage, &env, &child_hash, Response::new())
+            .unwrap();
+        assert!(!triggered(&res));
+        assert!(store
+            .block_slots
+            .may_load(&deps.storage, env.block.height)?
+            .is_none());
+
+        // Once out of every slot, it's scheduled into the current block
+        let slot_ids: Vec<u64> = store
+            .block_slots
+            .keys(&deps.storage, None, None, Order::Ascending)
+            .collect::<StdResult<_>>()?;
+        for slot_id in slot_ids {
+            store.block_slots.remove(&mut deps.storage, slot_id);
+        }
+        let res = store
+            .trigger_chained_task(&mut deps.storage, &env, &child_hash, Response::new())
+            .unwrap();
+        assert!(triggered(&res));
+        assert_eq!(
+            Some(vec![child_hash.clone().into_bytes()]),
+            store
+                .block_slots
+                .may_load(&deps.storage, env.block.height)?
+        );
+
+        // and not a second time
+        let res = store
+            .trigger_chained_task(&mut deps.storage, &env, &child_hash, Response::new())
+            .unwrap();
+        assert!(!triggered(&res));
+
+        Ok(())
+    }
+
+    #[test]
+    fn proxy_callback_return_surplus() -> StdResult<()> {
+        // Actions send 10juno a run, after one a 35juno deposit has 15juno beyond the next run,
+        // a 20juno one has none
+        for (deposit, expected_surplus) in [(35, Some(15)), (20, None)] {
+            let (mut app, cw_template_contract) = proper_instantiate();
+            let contract_addr = cw_template_contract.addr();
+            let cw20_addr = instantiate_cw20(&mut app);
+            app.sudo(SudoMsg::Bank(BankSudo::Mint {
+                to_address: ADMIN.to_string(),
+                amount: coins(100, "juno"),
+            }))
+            .unwrap();
+
+            let create_task_msg = ExecuteMsg::CreateTask {
+                task: TaskRequest {
+                    interval: Interval::Immediate,
+                    boundary: Boundary {
+                        start: None,
+                        end: None,
+                    },
+                    stop_on_fail: false,
+                    allow_sponsored_refill: false,
+                    refund_split: None,
+                    preferred_agent: None,
+                    fail_mode: FailMode::StopOnFirstFail,
+                    agent_fee_override: None,
+                    on_success_task: None,
+                    label: None,
+                    memo: None,
+                    return_surplus: true,
+                    actions: vec![Action {
+                        msg: CosmosMsg::Wasm(WasmMsg::Execute {
+                            contract_addr: cw20_addr.to_string(),
+                            msg: to_binary(&cw20::Cw20ExecuteMsg::IncreaseAllowance {
+                                spender: ANYONE.to_string(),
+                                amount: Uint128::new(10),
+                                expires: None,
+                            })?,
+                            funds: coins(10, "juno"),
+                        }),
+                        gas_limit: Some(250_000),
+                    }],
+                    rules: None,
+                },
+            };
+            let res = app
+                .execute_contract(
+                    Addr::unchecked(ADMIN),
+                    contract_addr.clone(),
+                    &create_task_msg,
+                    &[coin(30, NATIVE_DENOM), coin(deposit, "juno")],
+                )
+                .unwrap();
+            let task_hash = res
+                .events
+                .iter()
+                .flat_map(|e| e.attributes.iter())
+                .find(|a| a.key == "task_hash")
+                .unwrap()
+                .value
+                .clone();
+
+            let msg = ExecuteMsg::RegisterAgent {
+                payable_account_id: Some(Addr::unchecked(AGENT1_BENEFICIARY)),
+            };
+            app.execute_contract(Addr::unchecked(AGENT0), contract_addr.clone(), &msg, &[])
+                .unwrap();
+
+            app.update_block(add_little_time);
+            let res = app
+                .execute_contract(
+                    Addr::unchecked(AGENT0),
+                    contract_addr.clone(),
+                    &ExecuteMsg::ProxyCall {},
+                    &[],
+                )
+                .unwrap();
+            let returned = res
+                .events
+                .iter()
+                .flat_map(|e| e.attributes.iter())
+                .find(|a| a.key == "returned_surplus")
+                .map(|a| a.value.clone());
+            assert_eq!(expected_surplus.map(|s| format!("{}juno", s)), returned);
+
+            // Only the next run's needs stay on the task, the rest is back with the owner
+            let task: Option<TaskResponse> = app.wrap().query_wasm_smart(
+                &contract_addr,
+                &QueryMsg::GetTask {
+                    task_hash: task_hash.clone(),
+                },
+            )?;
+            let task = task.unwrap();
+            assert!(task.total_deposit.contains(&coin(10, "juno")));
+            let owner_balance = app.wrap().query_balance(ADMIN, "juno")?;
+            assert_eq!(
+                Uint128::new(100 - deposit + expected_surplus.unwrap_or_default()),
+                owner_balance.amount
+            );
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn proxy_callback_return_surplus_balancer_invariant() -> StdResult<()> {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+        let cw20_addr = instantiate_cw20(&mut app);
+        app.sudo(SudoMsg::Bank(BankSudo::Mint {
+            to_address: ADMIN.to_string(),
+            amount: coins(100, "juno"),
+        }))
+        .unwrap();
+        let in_balance = |app: &App| -> StdResult<()> {
+            let res: GetBalancerInvariantResponse = app
+                .wrap()
+                .query_wasm_smart(&contract_addr, &QueryMsg::GetBalancerInvariant {})?;
+            assert!(res.in_balance);
+            // Tracked juno is what the contract actually holds
+            let held = app.wrap().query_balance(&contract_addr, "juno")?.amount;
+            let tracked = res
+                .available_balance
+                .native
+                .iter()
+                .find(|c| c.denom == "juno")
+                .map(|c| c.amount)
+                .unwrap_or_default();
+            assert_eq!(held, tracked);
+            Ok(())
+        };
+
+        // Keeps the agent fees of a few runs within the instantiate surplus
+        app.execute_contract(
+            Addr::unchecked(ADMIN),
+            contract_addr.clone(),
+            &settings_msg(SettingsUpdate {
+                agent_fee: Some(coin(1, NATIVE_DENOM)),
+                ..Default::default()
+            }),
+            &[],
+        )
+        .unwrap();
+        let res = app
+            .execute_contract(
+                Addr::unchecked(ADMIN),
+                contract_addr.clone(),
+                &ExecuteMsg::CreateTask {
+                    task: TaskRequest {
+                        interval: Interval::Immediate,
+                        boundary: Boundary {
+                            start: None,
+                            end: None,
+                        },
+                        stop_on_fail: false,
+                        allow_sponsored_refill: false,
+                        refund_split: None,
+                        preferred_agent: None,
+                        fail_mode: FailMode::StopOnFirstFail,
+                        agent_fee_override: None,
+                        on_success_task: None,
+                        label: None,
+                        memo: None,
+                        return_surplus: true,
+                        actions: vec![Action {
+                            msg: CosmosMsg::Wasm(WasmMsg::Execute {
+                                contract_addr: cw20_addr.to_string(),
+                                msg: to_binary(&cw20::Cw20ExecuteMsg::IncreaseAllowance {
+                                    spender: ANYONE.to_string(),
+                                    amount: Uint128::new(10),
+                                    expires: None,
+                                })?,
+                                funds: coins(10, "juno"),
+                            }),
+                            gas_limit: Some(250_000),
+                        }],
+                        rules: None,
+                    },
+                },
+                &[coin(30, NATIVE_DENOM), coin(35, "juno")],
+            )
+            .unwrap();
+        let task_hash = res
+            .events
+            .iter()
+            .flat_map(|e| e.attributes.iter())
+            .find(|a| a.key == "task_hash")
+            .unwrap()
+            .value
+            .clone();
+        app.execute_contract(
+            Addr::unchecked(AGENT0),
+            contract_addr.clone(),
+            &ExecuteMsg::RegisterAgent {
+                payable_account_id: Some(Addr::unchecked(AGENT1_BENEFICIARY)),
+            },
+            &[],
+        )
+        .unwrap();
+        in_balance(&app)?;
+
+        // Each run sends 10juno & returns what's beyond the next run, the owner tops it up again
+        for refill in [Some(15), Some(10), None] {
+            app.update_block(add_little_time);
+            app.execute_contract(
+                Addr::unchecked(AGENT0),
+                contract_addr.clone(),
+                &ExecuteMsg::ProxyCall {},
+                &[],
+            )
+            .unwrap();
+            in_balance(&app)?;
+            if let Some(refill) = refill {
+                app.execute_contract(
+                    Addr::unchecked(ADMIN),
+                    contract_addr.clone(),
+                    &ExecuteMsg::RefillTaskBalance {
+                        task_hash: task_hash.clone(),
+                        allow_new_denoms: None,
+                    },
+                    &coins(refill, "juno"),
+                )
+                .unwrap();
+                in_balance(&app)?;
+            }
+        }
+
+        // 35 + 15 + 10 deposited, 30 sent by the three runs, 10 kept for the next one, 20 returned
+        assert_eq!(
+            Uint128::new(30),
+            app.wrap().query_balance(&cw20_addr, "juno")?.amount
+        );
+        let task: Option<TaskResponse> = app
+            .wrap()
+            .query_wasm_smart(&contract_addr, &QueryMsg::GetTask { task_hash })?;
+        assert!(task.unwrap().total_deposit.contains(&coin(10, "juno")));
+        assert_eq!(
+            Uint128::new(60),
+            app.wrap().query_balance(ADMIN, "juno")?.amount
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn proxy_callback_spends_action_amounts() -> StdResult<()> {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+        let cw20_addr = instantiate_cw20(&mut app);
+        app.sudo(SudoMsg::Bank(BankSudo::Mint {
+            to_address: ADMIN.to_string(),
+            amount: coins(100, "juno"),
+        }))
+        .unwrap();
+
+        // Actions send 10juno a run, a 25juno deposit covers two runs
+        let res = app
+            .execute_contract(
+                Addr::unchecked(ADMIN),
+                contract_addr.clone(),
+                &ExecuteMsg::CreateTask {
+                    task: TaskRequest {
+                        interval: Interval::Immediate,
+                        boundary: Boundary {
+                            start: None,
+                            end: None,
+                        },
+                        stop_on_fail: false,
+                        allow_sponsored_refill: false,
+                        refund_split: None,
+                        preferred_agent: None,
+                        fail_mode: FailMode::StopOnFirstFail,
+                        agent_fee_override: None,
+                        on_success_task: None,
+                        label: None,
+                        memo: None,
+                        return_surplus: false,
+                        actions: vec![Action {
+                            msg: CosmosMsg::Wasm(WasmMsg::Execute {
+                                contract_addr: cw20_addr.to_string(),
+                                msg: to_binary(&cw20::Cw20ExecuteMsg::IncreaseAllowance {
+                                    spender: ANYONE.to_string(),
+                                    amount: Uint128::new(10),
+                                    expires: None,
+                                })?,
+                                funds: coins(10, "juno"),
+                            }),
+                            gas_limit: Some(250_000),
+                        }],
+                        rules: None,
+                    },
+                },
+                &[coin(30, NATIVE_DENOM), coin(25, "juno")],
+            )
+            .unwrap();
+        let task_hash = res
+            .events
+            .iter()
+            .flat_map(|e| e.attributes.iter())
+            .find(|a| a.key == "task_hash")
+            .unwrap()
+            .value
+            .clone();
+        app.execute_contract(
+            Addr::unchecked(AGENT0),
+            contract_addr.clone(),
+            &ExecuteMsg::RegisterAgent {
+                payable_account_id: Some(Addr::unchecked(AGENT1_BENEFICIARY)),
+            },
+            &[],
+        )
+        .unwrap();
+
+        // The first run leaves 15juno on the task & in the available balance
+        app.update_block(add_little_time);
+        app.execute_contract(
+            Addr::unchecked(AGENT0),
+            contract_addr.clone(),
+            &ExecuteMsg::ProxyCall {},
+            &[],
+        )
+        .unwrap();
+        let task: Option<TaskResponse> = app.wrap().query_wasm_smart(
+            &contract_addr,
+            &QueryMsg::GetTask {
+                task_hash: task_hash.clone(),
+            },
+        )?;
+        assert!(task.unwrap().total_deposit.contains(&coin(15, "juno")));
+        let balances: GetBalancesResponse = app
+            .wrap()
+            .query_wasm_smart(&contract_addr, &QueryMsg::GetBalances {})?;
+        assert!(balances
+            .available_balance
+            .native
+            .contains(&coin(15, "juno")));
+
+        // The second leaves 5juno, not enough for another run, so the task ends & refunds it
+        app.update_block(add_little_time);
+        let res = app
+            .execute_contract(
+                Addr::unchecked(AGENT0),
+                contract_addr.clone(),
+                &ExecuteMsg::ProxyCall {},
+                &[],
+            )
+            .unwrap();
+        assert!(res
+            .events
+            .iter()
+            .flat_map(|e| e.attributes.iter())
+            .any(|a| a.key == "ended_task" && a.value == task_hash));
+        let task: Option<TaskResponse> = app
+            .wrap()
+            .query_wasm_smart(&contract_addr, &QueryMsg::GetTask { task_hash })?;
+        assert!(task.is_none());
+        assert_eq!(
+            Uint128::new(20),
+            app.wrap().query_balance(&cw20_addr, "juno")?.amount
+        );
+        assert_eq!(
+            Uint128::new(80),
+            app.wrap().query_balance(ADMIN, "juno")?.amount
+        );
+        assert_eq!(
+            Uint128::zero(),
+            app.wrap().query_balance(&contract_addr, "juno")?.amount
+        );
+        let balances: GetBalancesResponse = app
+            .wrap()
+            .query_wasm_smart(&contract_addr, &QueryMsg::GetBalances {})?;
+        assert!(!balances
+            .available_balance
+            .native
+            .iter()
+            .any(|c| c.denom == "juno" && !c.amount.is_zero()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn proxy_callback_failure_streak() -> StdResult<()> {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+        let cw20_addr = instantiate_cw20(&mut app);
+
+        let streak_msg = |max_failure_streak| {
+            settings_msg(SettingsUpdate {
+                max_failure_streak: Some(Some(max_failure_streak)),
+                ..Default::default()
+            })
+        };
+        let res_err = app
+            .execute_contract(
+                Addr::unchecked(ADMIN),
+                contract_addr.clone(),
+                &streak_msg(0),
+                &[],
+            )
+            .unwrap_err();
+        assert_eq!(
+            ContractError::CustomError {
+                val: "Max failure streak must be at least 1".to_string()
+            },
+            res_err.downcast().unwrap()
+        );
+        app.execute_contract(
+            Addr::unchecked(ADMIN),
+            contract_addr.clone(),
+            &streak_msg(3),
+            &[],
+        )
+        .unwrap();
+
+        // Fails every run as the contract holds no tokens, like a target that no longer exists
+        let create_task_msg = ExecuteMsg::CreateTask {
+            task: TaskRequest {
+                interval: Interval::Immediate,
+                boundary: Boundary {
+                    start: None,
+                    end: None,
+                },
+                stop_on_fail: false,
+                allow_sponsored_refill: false,
+                refund_split: None,
+                preferred_agent: None,
+                fail_mode: FailMode::StopOnFirstFail,
+                agent_fee_override: None,
+                on_success_task: None,
+                label: None,
+                memo: None,
+                return_surplus: false,
+                actions: vec![Action {
+                    msg: CosmosMsg::Wasm(WasmMsg::Execute {
+                        contract_addr: cw20_addr.to_string(),
+                        msg: to_binary(&cw20::Cw20ExecuteMsg::Transfer {
+                            recipient: ANYONE.to_string(),
+                            amount: Uint128::new(10),
+                        })?,
+                        funds: vec![],
+                    }),
+                    gas_limit: Some(250_000),
+                }],
+                rules: None,
+            },
+        };
+        let res = app
+            .execute_contract(
+                Addr::unchecked(ADMIN),
+                contract_addr.clone(),
+                &create_task_msg,
+                &coins(30, NATIVE_DENOM),
+            )
+            .unwrap();
+        let task_hash = res
+            .events
+            .iter()
+            .flat_map(|e| e.attributes.iter())
+            .find(|a| a.key == "task_hash")
+            .unwrap()
+            .value
+            .clone();
+        let owner_balance = app.wrap().query_balance(ADMIN, NATIVE_DENOM)?;
+
+        let msg = ExecuteMsg::RegisterAgent {
+            payable_account_id: Some(Addr::unchecked(AGENT1_BENEFICIARY)),
+        };
+        app.execute_contract(Addr::unchecked(AGENT0), contract_addr.clone(), &msg, &[])
+            .unwrap();
+
+        for failure_streak in 1..=3u64 {
+            app.update_block(add_little_time);
+            let res = app
+                .execute_contract(
+                    Addr::unchecked(AGENT0),
+                    contract_addr.clone(),
+                    &ExecuteMsg::ProxyCall {},
+                    &[],
+                )
+                .unwrap();
+            let task: Option<TaskResponse> = app.wrap().query_wasm_smart(
+                &contract_addr,
+                &QueryMsg::GetTask {
+                    task_hash: task_hash.clone(),
+                },
+            )?;
+            if failure_streak < 3 {
+                assert_eq!(failure_streak, task.unwrap().failure_streak);
+                continue;
+            }
+
+            // The threshold ends the task even without stop_on_fail, & refunds the owner
+            assert!(task.is_none());
+            assert!(res.events.iter().any(|e| e
+                .attributes
+                .iter()
+                .any(|a| a.key == "ended_task" && a.value == task_hash)));
+            let refunded = app.wrap().query_balance(ADMIN, NATIVE_DENOM)?;
+            assert_eq!(owner_balance.amount + Uint128::new(30), refunded.amount);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn proxy_call_missed_slots_reschedule() -> StdResult<()> {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+        let cw20_addr = instantiate_cw20(&mut app);
+
+        for (interval, amount) in [
+            (Interval::Block(5), 10),
+            (Interval::Cron("0 * * * * *".to_string()), 20),
+        ] {
+            let create_task_msg = ExecuteMsg::CreateTask {
+                task: TaskRequest {
+                    interval,
+                    boundary: Boundary {
+                        start: None,
+                        end: None,
+                    },
+                    stop_on_fail: false,
+                    allow_sponsored_refill: false,
+                    refund_split: None,
+                    preferred_agent: None,
+                    fail_mode: FailMode::StopOnFirstFail,
+                    agent_fee_override: None,
+                    on_success_task: None,
+                    label: None,
+                    memo: None,
+                    return_surplus: false,
+                    actions: vec![Action {
+                        msg: CosmosMsg::Wasm(WasmMsg::Execute {
+                            contract_addr: cw20_addr.to_string(),
+                            msg: to_binary(&cw20::Cw20ExecuteMsg::IncreaseAllowance {
+                                spender: ANYONE.to_string(),
+                                amount: Uint128::new(amount),
+                                expires: None,
+                            })?,
+                            funds: vec![],
+                        }),
+                        gas_limit: Some(250_000),
+                    }],
+                    rules: None,
+                },
+            };
+            app.execute_contract(
+                Addr::unchecked(ADMIN),
+                contract_addr.clone(),
+                &create_task_msg,
+                &coins(30, NATIVE_DENOM),
+            )
+            .unwrap();
+        }
+        let msg = ExecuteMsg::RegisterAgent {
+            payable_account_id: Some(Addr::unchecked(AGENT1_BENEFICIARY)),
+        };
+        app.execute_contract(Addr::unchecked(AGENT0), contract_addr.clone(), &msg, &[])
+            .unwrap();
+
+        // agents are away for 50 blocks & 10 minutes, so many executions were missed
+        app.update_block(|block| {
+            block.height += 50;
+            block.time = block.time.plus_seconds(600);
+        });
+        let block = app.block_info();
+
+        // each task runs once for all its missed slots
+        for _ in 0..2 {
+            app.execute_contract(
+                Addr::unchecked(AGENT0),
+                contract_addr.clone(),
+                &ExecuteMsg::ProxyCall {},
+                &[],
+            )
+            .unwrap();
+        }
+        let res_err = app
+            .execute_contract(
+                Addr::unchecked(AGENT0),
+                contract_addr.clone(),
+                &ExecuteMsg::ProxyCall {},
+                &[],
+            )
+            .unwrap_err();
+        assert_eq!(
+            ContractError::CustomError {
+                val: "No Tasks For Slot".to_string()
+            },
+            res_err.downcast().unwrap()
+        );
+        let tasks: Vec<TaskResponse> = app.wrap().query_wasm_smart(
+            &contract_addr,
+            &QueryMsg::GetTasks {
+                from_index: None,
+                limit: None,
+                order_by: None,
+            },
+        )?;
+        assert!(tasks.iter().all(|t| t.executions_count == 1));
+
+        // then both are rescheduled from the current block, not from the missed slots
+        let slot_ids: GetSlotIdsResponse = app
+            .wrap()
+            .query_wasm_smart(&contract_addr, &QueryMsg::GetSlotIds {})?;
+        assert_eq!(vec![block.height + 5], slot_ids.block_ids);
+        assert_eq!(1, slot_ids.time_ids.len());
+        assert!(slot_ids.time_ids[0] > block.time.nanos());
+        assert!(slot_ids.time_ids[0] <= block.time.plus_seconds(60).nanos());
+
+        Ok(())
+    }
+
+    #[test]
+    fn proxy_call_treasury_split() -> StdResult<()> {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+        let proxy_call_msg = ExecuteMsg::ProxyCall {};
+
+        let fee_msg = |treasury_fee| {
+            settings_msg(SettingsUpdate {
+                treasury_id: Some(Some(Addr::unchecked(TREASURY))),
+                treasury_fee: Some(treasury_fee),
+                agent_fee: Some(coin(10, NATIVE_DENOM)),
+                ..Default::default()
+            })
+        };
+
+        // treasury fee must be a percentage
+        let res_err = app
+            .execute_contract(
+                Addr::unchecked(ADMIN),
+                contract_addr.clone(),
+                &fee_msg(101),
+                &[],
+            )
+            .unwrap_err();
+        assert_eq!(
+            ContractError::CustomError {
+                val: "Treasury fee must be a percentage between 0 and 100".to_string()
+            },
+            res_err.downcast().unwrap()
+        );
+        app.execute_contract(
+            Addr::unchecked(ADMIN),
+            contract_addr.clone(),
+            &fee_msg(20),
+            &[],
+        )
+        .unwrap();
+
+        let cw20_addr = instantiate_cw20(&mut app);
+        let msg = CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: cw20_addr.to_string(),
+            msg: to_binary(&cw20::Cw20ExecuteMsg::IncreaseAllowance {
+                spender: ANYONE.to_string(),
+                amount: Uint128::new(10),
+                expires: None,
+            })?,
+            funds: vec![],
+        });
+        let create_task_msg = ExecuteMsg::CreateTask {
+            task: TaskRequest {
+                interval: Interval::Immediate,
+                boundary: Boundary {
+                    start: None,
+                    end: None,
+                },
+                stop_on_fail: false,
+                allow_sponsored_refill: false,
+                refund_split: None,
+                preferred_agent: None,
+                fail_mode: FailMode::StopOnFirstFail,
+                agent_fee_override: None,
+                on_success_task: None,
+                label: None,
+                memo: None,
+                return_surplus: false,
+                actions: vec![Action {
+                    msg,
+                    gas_limit: Some(250_000),
+                }],
+                rules: None,
+            },
+        };
+        app.execute_contract(
+            Addr::unchecked(ADMIN),
+            contract_addr.clone(),
+            &create_task_msg,
+            &coins(10, NATIVE_DENOM),
+        )
+        .unwrap();
+
+        // quick agent register
+        let msg = ExecuteMsg::RegisterAgent {
+            payable_account_id: Some(Addr::unchecked(AGENT1_BENEFICIARY)),
+        };
+        app.execute_contract(Addr::unchecked(AGENT0), contract_addr.clone(), &msg, &[])
+            .unwrap();
+
+        app.update_block(add_little_time);
+        app.execute_contract(
+            Addr::unchecked(AGENT0),
+            contract_addr.clone(),
+            &proxy_call_msg,
+            &[],
+        )
+        .unwrap();
+
+        // 20% of the agent fee went to the treasury, the rest to the agent
+        let treasury_balance = app.wrap().query_balance(TREASURY, NATIVE_DENOM)?;
+        assert_eq!(coin(2, NATIVE_DENOM), treasury_balance);
+        let agent: Option<AgentResponse> = app
+            .wrap()
+            .query_wasm_smart(
+                &contract_addr,
+                &QueryMsg::GetAgent {
+                    account_id: Addr::unchecked(AGENT0),
+                },
+            )
+            .unwrap();
+        assert_eq!(coins(8, NATIVE_DENOM), agent.unwrap().balance.native);
+
+        Ok(())
+    }
+
+    #[test]
+    fn proxy_call_agent_fee_override() -> StdResult<()> {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+        let config: GetConfigResponse = app
+            .wrap()
+            .query_wasm_smart(&contract_addr, &QueryMsg::GetConfig {})?;
+        let agent_fee = config.agent_fee.amount;
+
+        let cw20_addr = instantiate_cw20(&mut app);
+        let msg = CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: cw20_addr.to_string(),
+            msg: to_binary(&cw20::Cw20ExecuteMsg::IncreaseAllowance {
+                spender: ANYONE.to_string(),
+                amount: Uint128::new(10),
+                expires: None,
+            })?,
+            funds: vec![],
+        });
+        let create_task_msg = |agent_fee_override| ExecuteMsg::CreateTask {
+            task: TaskRequest {
+                interval: Interval::Immediate,
+                boundary: Boundary {
+                    start: None,
+                    end: None,
+                },
+                stop_on_fail: false,
+                allow_sponsored_refill: false,
+                refund_split: None,
+                preferred_agent: None,
+                fail_mode: FailMode::StopOnFirstFail,
+                agent_fee_override,
+                on_success_task: None,
+                label: None,
+                memo: None,
+                return_surplus: false,
+                actions: vec![Action {
+                    msg: msg.clone(),
+                    gas_limit: Some(250_000),
+                }],
+                rules: None,
+            },
+        };
+
+        // can't undercut the global agent fee
+        let res_err = app
+            .execute_contract(
+                Addr::unchecked(ADMIN),
+                contract_addr.clone(),
+                &create_task_msg(Some(agent_fee - Uint128::new(1))),
+                &coins(30, NATIVE_DENOM),
+            )
+            .unwrap_err();
+        assert_eq!(
+            ContractError::CustomError {
+                val: "Agent fee override below agent fee".to_string()
+            },
+            res_err.downcast().unwrap()
+        );
+
+        let agent_fee_override = agent_fee + Uint128::new(20);
+        let res = app
+            .execute_contract(
+                Addr::unchecked(ADMIN),
+                contract_addr.clone(),
+                &create_task_msg(Some(agent_fee_override)),
+                &coins(30, NATIVE_DENOM),
+            )
+            .unwrap();
+        let task_hash = res
+            .events
+            .iter()
+            .flat_map(|e| e.attributes.iter())
+            .find(|a| a.key == "task_hash")
+            .unwrap()
+            .value
+            .clone();
+
+        // the override replaces the global fee in the cost
+        let cost: Option<GetTaskCostResponse> = app.wrap().query_wasm_smart(
+            &contract_addr,
+            &QueryMsg::GetTaskCost {
+                task_hash: task_hash.clone(),
+            },
+        )?;
+        let cost = cost.unwrap();
+        assert_eq!(
+            coin(agent_fee_override.u128(), NATIVE_DENOM),
+            cost.agent_fee
+        );
+        assert_eq!(
+            cost.gas_fee.amount + agent_fee_override,
+            cost.total_cost.amount
+        );
+        let task: Option<TaskResponse> = app
+            .wrap()
+            .query_wasm_smart(&contract_addr, &QueryMsg::GetTask { task_hash })?;
+        assert_eq!(Some(agent_fee_override), task.unwrap().agent_fee_override);
+
+        // quick agent register
+        let msg = ExecuteMsg::RegisterAgent {
+            payable_account_id: Some(Addr::unchecked(AGENT1_BENEFICIARY)),
+        };
+        app.execute_contract(Addr::unchecked(AGENT0), contract_addr.clone(), &msg, &[])
+            .unwrap();
+
+        app.update_block(add_little_time);
+        app.execute_contract(
+            Addr::unchecked(AGENT0),
+            contract_addr.clone(),
+            &ExecuteMsg::ProxyCall {},
+            &[],
+        )
+        .unwrap();
+
+        // and gets paid to the agent
+        let agent: Option<AgentResponse> = app.wrap().query_wasm_smart(
+            &contract_addr,
+            &QueryMsg::GetAgent {
+                account_id: Addr::unchecked(AGENT0),
+            },
+        )?;
+        assert_eq!(
+            coins(agent_fee_override.u128(), NATIVE_DENOM),
+            agent.unwrap().balance.native
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn proxy_call_several_tasks() -> StdResult<()> {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+        let proxy_call_msg = ExecuteMsg::ProxyCall {};
+
+        let cw20_addr = instantiate_cw20(&mut app);
+
+        // Doing this msg since its the easiest to guarantee success in reply
+        let msg = CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: cw20_addr.to_string(),
+            msg: to_binary(&cw20::Cw20ExecuteMsg::IncreaseAllowance {
+                spender: ANYONE.to_string(),
+                amount: Uint128::new(10),
+                expires: None,
+            })?,
+            funds: coins(1, NATIVE_DENOM),
+        });
+
+        let msg2 = CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: cw20_addr.to_string(),
+            msg: to_binary(&cw20::Cw20ExecuteMsg::IncreaseAllowance {
+                spender: ANYONE.to_string(),
+                amount: Uint128::new(10),
+                expires: None,
+            })?,
+            funds: coins(2, NATIVE_DENOM),
+        });
+
+        let msg3 = CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: cw20_addr.to_string(),
+            msg: to_binary(&cw20::Cw20ExecuteMsg::IncreaseAllowance {
+                spender: ANYONE.to_string(),
+                amount: Uint128::new(10),
+                expires: None,
+            })?,
+            funds: coins(3, NATIVE_DENOM),
+        });
+
+        let create_task_msg = ExecuteMsg::CreateTask {
+            task: TaskRequest {
+                interval: Interval::Immediate,
+                boundary: Boundary {
+                    start: None,
+                    end: None,
+                },
+                stop_on_fail: false,
+                allow_sponsored_refill: false,
+                refund_split: None,
+                preferred_agent: None,
+                fail_mode: FailMode::StopOnFirstFail,
+                agent_fee_override: None,
+                on_success_task: None,
+                label: None,
+                memo: None,
+                return_surplus: false,
                 actions: vec![Action {
                     msg,
                     gas_limit: Some(250_000),
@@ -1292,6 +3848,15 @@ mod tests {
                     end: None,
                 },
                 stop_on_fail: false,
+                allow_sponsored_refill: false,
+                refund_split: None,
+                preferred_agent: None,
+                fail_mode: FailMode::StopOnFirstFail,
+                agent_fee_override: None,
+                on_success_task: None,
+                label: None,
+                memo: None,
+                return_surplus: false,
                 actions: vec![Action {
                     msg: msg2,
                     gas_limit: Some(250_000),
@@ -1308,6 +3873,15 @@ mod tests {
                     end: None,
                 },
                 stop_on_fail: false,
+                allow_sponsored_refill: false,
+                refund_split: None,
+                preferred_agent: None,
+                fail_mode: FailMode::StopOnFirstFail,
+                agent_fee_override: None,
+                on_success_task: None,
+                label: None,
+                memo: None,
+                return_surplus: false,
                 actions: vec![Action {
                     msg: msg3,
                     gas_limit: Some(250_000),
@@ -1350,13 +3924,6 @@ mod tests {
         };
         app.execute_contract(Addr::unchecked(AGENT0), contract_addr.clone(), &msg, &[])
             .unwrap();
-        app.execute_contract(
-            Addr::unchecked(contract_addr.clone()),
-            contract_addr.clone(),
-            &msg,
-            &[],
-        )
-        .unwrap();
 
         // need block advancement
         app.update_block(add_little_time);
@@ -1387,4 +3954,133 @@ mod tests {
         assert!(res.is_ok());
         Ok(())
     }
+
+    #[test]
+    fn proxy_call_reentrancy_guard() -> StdResult<()> {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+        let reentrant_id = app.store_code(reentrant_template());
+        let reentrant_addr = app
+            .instantiate_contract(
+                reentrant_id,
+                Addr::unchecked(ADMIN),
+                &Empty {},
+                &[],
+                "reentrant",
+                None,
+            )
+            .unwrap();
+
+        let create_task_msg = ExecuteMsg::CreateTask {
+            task: TaskRequest {
+                interval: Interval::Immediate,
+                boundary: Boundary {
+                    start: None,
+                    end: None,
+                },
+                stop_on_fail: false,
+                allow_sponsored_refill: false,
+                refund_split: None,
+                preferred_agent: None,
+                fail_mode: FailMode::StopOnFirstFail,
+                agent_fee_override: None,
+                on_success_task: None,
+                label: None,
+                memo: None,
+                return_surplus: false,
+                actions: vec![Action {
+                    msg: WasmMsg::Execute {
+                        contract_addr: reentrant_addr.to_string(),
+                        msg: to_binary(&Empty {})?,
+                        funds: coins(1, NATIVE_DENOM),
+                    }
+                    .into(),
+                    gas_limit: Some(250_000),
+                }],
+                rules: None,
+            },
+        };
+        app.execute_contract(
+            Addr::unchecked(ADMIN),
+            contract_addr.clone(),
+            &create_task_msg,
+            &coins(10, NATIVE_DENOM),
+        )
+        .unwrap();
+
+        app.execute_contract(
+            Addr::unchecked(AGENT0),
+            contract_addr.clone(),
+            &ExecuteMsg::RegisterAgent {
+                payable_account_id: Some(Addr::unchecked(AGENT1_BENEFICIARY)),
+            },
+            &[],
+        )
+        .unwrap();
+        app.update_block(add_little_time);
+
+        // the target calling back in fails, which the callback sees as a failed action
+        let res = app
+            .execute_contract(
+                Addr::unchecked(AGENT0),
+                contract_addr.clone(),
+                &ExecuteMsg::ProxyCall {},
+                &[],
+            )
+            .unwrap();
+        let has_reply_failure = res.events.iter().any(|e| {
+            e.ty == "reply"
+                && e.attributes
+                    .iter()
+                    .any(|a| a.key == "mode" && a.value == "handle_failure")
+        });
+        assert!(has_reply_failure);
+
+        // no task got created by the reentrant call
+        let owner_tasks: Vec<TaskResponse> = app.wrap().query_wasm_smart(
+            contract_addr.clone(),
+            &QueryMsg::GetTasksByOwner {
+                owner_id: reentrant_addr,
+            },
+        )?;
+        assert!(owner_tasks.is_empty());
+
+        // guard is cleared once the callback is done
+        app.execute_contract(
+            Addr::unchecked(ANYONE),
+            contract_addr.clone(),
+            &ExecuteMsg::CreateTask {
+                task: TaskRequest {
+                    interval: Interval::Immediate,
+                    boundary: Boundary {
+                        start: None,
+                        end: None,
+                    },
+                    stop_on_fail: false,
+                    allow_sponsored_refill: false,
+                    refund_split: None,
+                    preferred_agent: None,
+                    fail_mode: FailMode::StopOnFirstFail,
+                    agent_fee_override: None,
+                    on_success_task: None,
+                    label: None,
+                    memo: None,
+                    return_surplus: false,
+                    actions: vec![Action {
+                        msg: StakingMsg::Delegate {
+                            validator: "you".to_string(),
+                            amount: coin(1, NATIVE_DENOM),
+                        }
+                        .into(),
+                        gas_limit: None,
+                    }],
+                    rules: None,
+                },
+            },
+            &coins(10, NATIVE_DENOM),
+        )
+        .unwrap();
+
+        Ok(())
+    }
 }
@@ -1,12 +1,21 @@
 use crate::error::ContractError;
-use crate::helpers::has_cw_coins;
+use crate::helpers::{find_denom_alias, has_cw_coins, non_zero_coins};
 use crate::state::{Config, CwCroncat};
 use cosmwasm_std::{
-    has_coins, to_binary, Addr, BankMsg, Coin, Deps, DepsMut, Env, MessageInfo, Response,
-    StdResult, SubMsg, WasmMsg,
+    has_coins, to_binary, Addr, BankMsg, Coin, Deps, DepsMut, Env, MessageInfo, Order, Response,
+    StdResult, Storage, SubMsg, Uint128, WasmMsg,
 };
-use cw20::{Balance, Cw20ExecuteMsg};
-use cw_croncat_core::msg::{ExecuteMsg, GetBalancesResponse, GetConfigResponse};
+use cw20::{Balance, Cw20CoinVerified, Cw20ExecuteMsg};
+use cw_croncat_core::msg::{
+    DenomBalance, ExecuteMsg, GetBalancerInvariantResponse, GetBalancesResponse, GetConfigResponse,
+};
+use cw_croncat_core::types::GenericBalance;
+use std::collections::BTreeSet;
+
+/// Highest gas_price UpdateSettings accepts, per unit of gas
+pub(crate) const MAX_GAS_PRICE: u32 = 100;
+/// Highest agent_fee UpdateSettings accepts, past it tasks can barely afford an execution
+pub(crate) const MAX_AGENT_FEE: u128 = 1_000_000_000;
 
 impl<'a> CwCroncat<'a> {
     pub(crate) fn query_config(&self, deps: Deps) -> StdResult<GetConfigResponse> {
@@ -14,7 +23,8 @@ impl<'a> CwCroncat<'a> {
         Ok(GetConfigResponse {
             paused: c.paused,
             owner_id: c.owner_id,
-            // treasury_id: c.treasury_id,
+            treasury_id: c.treasury_id,
+            treasury_fee: c.treasury_fee,
             min_tasks_per_agent: c.min_tasks_per_agent,
             agent_active_indices: c.agent_active_indices,
             agents_eject_threshold: c.agents_eject_threshold,
@@ -22,20 +32,117 @@ impl<'a> CwCroncat<'a> {
             agent_fee: c.agent_fee,
             gas_price: c.gas_price,
             proxy_callback_gas: c.proxy_callback_gas,
+            max_action_gas: c.max_action_gas,
+            default_gas_limit: c.default_gas_limit,
+            max_tasks_per_owner: c.max_tasks_per_owner,
+            max_tasks_per_slot: c.max_tasks_per_slot,
+            max_failure_streak: c.max_failure_streak,
+            refund_on_remove: c.refund_on_remove,
+            min_refill_amount: c.min_refill_amount,
+            dust_threshold: c.dust_threshold,
+            execution_grace_blocks: c.execution_grace_blocks,
+            execution_grace_seconds: c.execution_grace_seconds,
+            min_task_deposit: c.min_task_deposit,
+            max_boundary_horizon: c.max_boundary_horizon,
+            reject_duplicate_actions: c.reject_duplicate_actions,
+            allowed_msg_kinds: c.allowed_msg_kinds,
             slot_granularity: c.slot_granularity,
         })
     }
 
     pub(crate) fn query_balances(&self, deps: Deps) -> StdResult<GetBalancesResponse> {
         let c: Config = self.config.load(deps.storage)?;
+
+        let committed_balance = self.committed_balance(deps.storage)?;
+
         Ok(GetBalancesResponse {
             native_denom: c.native_denom,
             available_balance: c.available_balance,
+            committed_balance,
             staked_balance: c.staked_balance,
+            protocol_balance: c.protocol_balance,
             cw20_whitelist: c.cw20_whitelist,
         })
     }
 
+    /// Checks available_balance against the sum of task deposits, the protocol & surplus balances, to catch accounting drift
+    /// Zero amounts & ordering are ignored in the comparison
+    pub(crate) fn query_balancer_invariant(
+        &self,
+        deps: Deps,
+    ) -> StdResult<GetBalancerInvariantResponse> {
+        let c: Config = self.config.load(deps.storage)?;
+        let tasks_balance = self.committed_balance(deps.storage)?;
+
+        let normalize = |b: &GenericBalance| {
+            let mut native: Vec<Coin> = b
+                .native
+                .iter()
+                .filter(|c| !c.amount.is_zero())
+                .cloned()
+                .collect();
+            native.sort_by(|a, b| a.denom.cmp(&b.denom));
+            let mut cw20: Vec<Cw20CoinVerified> = b
+                .cw20
+                .iter()
+                .filter(|c| !c.amount.is_zero())
+                .cloned()
+                .collect();
+            cw20.sort_by(|a, b| a.address.cmp(&b.address));
+            (native, cw20)
+        };
+        let mut expected = tasks_balance.clone();
+        expected.add_tokens(Balance::from(c.protocol_balance.native.clone()));
+        expected.add_tokens(Balance::from(c.surplus_balance.native.clone()));
+        let in_balance = normalize(&c.available_balance) == normalize(&expected);
+
+        Ok(GetBalancerInvariantResponse {
+            available_balance: c.available_balance,
+            tasks_balance,
+            protocol_balance: c.protocol_balance,
+            surplus_balance: c.surplus_balance,
+            in_balance,
+        })
+    }
+
+    /// Per denom breakdown of the balancer invariant, to spot which denom drifted
+    pub(crate) fn query_denom_balances(&self, deps: Deps) -> StdResult<Vec<DenomBalance>> {
+        let c: Config = self.config.load(deps.storage)?;
+        let committed = self.committed_balance(deps.storage)?;
+
+        let amount_of = |coins: &[Coin], denom: &str| {
+            coins
+                .iter()
+                .filter(|c| c.denom == denom)
+                .map(|c| c.amount)
+                .sum::<Uint128>()
+        };
+        let denoms: BTreeSet<&String> = committed
+            .native
+            .iter()
+            .chain(c.available_balance.native.iter())
+            .map(|c| &c.denom)
+            .collect();
+        Ok(denoms
+            .into_iter()
+            .map(|denom| DenomBalance {
+                denom: denom.clone(),
+                committed: amount_of(&committed.native, denom),
+                available: amount_of(&c.available_balance.native, denom),
+            })
+            .collect())
+    }
+
+    /// Tally every task deposit, so committed funds can be told apart from surplus
+    fn committed_balance(&self, storage: &dyn Storage) -> StdResult<GenericBalance> {
+        let mut committed_balance = GenericBalance::default();
+        for task in self.tasks.range(storage, None, None, Order::Ascending) {
+            let (_, task) = task?;
+            committed_balance.add_tokens(Balance::from(task.total_deposit));
+        }
+        Ok(committed_balance)
+    }
+
     /// Changes core configurations
     /// Should only be updated by owner -- in best case DAO based :)
     pub fn update_settings(
@@ -57,10 +164,36 @@ impl<'a> CwCroncat<'a> {
                 agent_fee,
                 gas_price,
                 proxy_callback_gas,
+                max_action_gas,
+                default_gas_limit,
+                max_tasks_per_owner,
+                max_tasks_per_slot,
+                max_failure_streak,
+                refund_on_remove,
+                min_refill_amount,
+                dust_threshold,
+                execution_grace_blocks,
+                execution_grace_seconds,
+                min_task_deposit,
+                max_boundary_horizon,
+                reject_duplicate_actions,
+                allowed_msg_kinds,
                 min_tasks_per_agent,
                 agents_eject_threshold,
-                // treasury_id,
+                treasury_id,
+                treasury_fee,
             } => {
+                // a typo'd address would make every send to it fail
+                let owner_id = owner_id
+                    .map(|owner_id| deps.api.addr_validate(owner_id.as_str()))
+                    .transpose()?;
+                let treasury_id = treasury_id
+                    .map(|treasury_id| {
+                        treasury_id
+                            .map(|treasury_id| deps.api.addr_validate(treasury_id.as_str()))
+                            .transpose()
+                    })
+                    .transpose()?;
                 self.config
                     .update(deps.storage, |mut config| -> Result<_, ContractError> {
                         if info.sender != config.owner_id {
@@ -70,9 +203,18 @@ impl<'a> CwCroncat<'a> {
                         if let Some(owner_id) = owner_id {
                             config.owner_id = owner_id;
                         }
-                        // if let Some(treasury_id) = treasury_id {
-                        //     config.treasury_id = Some(treasury_id);
-                        // }
+                        if let Some(treasury_id) = treasury_id {
+                            config.treasury_id = treasury_id;
+                        }
+                        if let Some(treasury_fee) = treasury_fee {
+                            if treasury_fee > 100 {
+                                return Err(ContractError::CustomError {
+                                    val: "Treasury fee must be a percentage between 0 and 100"
+                                        .to_string(),
+                                });
+                            }
+                            config.treasury_fee = treasury_fee;
+                        }
 
                         if let Some(slot_granularity) = slot_granularity {
                             config.slot_granularity = slot_granularity;
@@ -81,12 +223,94 @@ impl<'a> CwCroncat<'a> {
                             config.paused = paused;
                         }
                         if let Some(gas_price) = gas_price {
+                            // a zero price would make every task free to execute
+                            if gas_price == 0 || gas_price > MAX_GAS_PRICE {
+                                return Err(ContractError::InvalidGasPrice { max: MAX_GAS_PRICE });
+                            }
                             config.gas_price = gas_price;
                         }
                         if let Some(proxy_callback_gas) = proxy_callback_gas {
                             config.proxy_callback_gas = proxy_callback_gas;
                         }
+                        if let Some(max_action_gas) = max_action_gas {
+                            config.max_action_gas = max_action_gas;
+                        }
+                        if let Some(default_gas_limit) = default_gas_limit {
+                            config.default_gas_limit = default_gas_limit;
+                        }
+                        // actions created without a gas_limit must still pass max_action_gas
+                        if config.default_gas_limit > config.max_action_gas {
+                            return Err(ContractError::CustomError {
+                                val: "Default gas limit exceeds max_action_gas".to_string(),
+                            });
+                        }
+                        if let Some(max_tasks_per_owner) = max_tasks_per_owner {
+                            config.max_tasks_per_owner = max_tasks_per_owner;
+                        }
+                        if let Some(max_tasks_per_slot) = max_tasks_per_slot {
+                            if max_tasks_per_slot == Some(0) {
+                                return Err(ContractError::CustomError {
+                                    val: "Max tasks per slot must be at least 1".to_string(),
+                                });
+                            }
+                            config.max_tasks_per_slot = max_tasks_per_slot;
+                        }
+                        if let Some(max_failure_streak) = max_failure_streak {
+                            if max_failure_streak == Some(0) {
+                                return Err(ContractError::CustomError {
+                                    val: "Max failure streak must be at least 1".to_string(),
+                                });
+                            }
+                            config.max_failure_streak = max_failure_streak;
+                        }
+                        if let Some(refund_on_remove) = refund_on_remove {
+                            config.refund_on_remove = refund_on_remove;
+                        }
+                        if let Some(min_refill_amount) = min_refill_amount {
+                            config.min_refill_amount = min_refill_amount;
+                        }
+                        if let Some(dust_threshold) = dust_threshold {
+                            config.dust_threshold = dust_threshold;
+                        }
+                        if let Some(execution_grace_blocks) = execution_grace_blocks {
+                            config.execution_grace_blocks = execution_grace_blocks;
+                        }
+                        if let Some(execution_grace_seconds) = execution_grace_seconds {
+                            config.execution_grace_seconds = execution_grace_seconds;
+                        }
+                        if let Some(min_task_deposit) = min_task_deposit {
+                            if matches!(&min_task_deposit, Some(min) if min.denom != config.agent_fee.denom)
+                            {
+                                return Err(ContractError::CustomError {
+                                    val: "Min task deposit must use the agent fee denom"
+                                        .to_string(),
+                                });
+                            }
+                            config.min_task_deposit = min_task_deposit;
+                        }
+                        if let Some(max_boundary_horizon) = max_boundary_horizon {
+                            config.max_boundary_horizon = max_boundary_horizon;
+                        }
+                        if let Some(reject_duplicate_actions) = reject_duplicate_actions {
+                            config.reject_duplicate_actions = reject_duplicate_actions;
+                        }
+                        if let Some(allowed_msg_kinds) = allowed_msg_kinds {
+                            if allowed_msg_kinds.is_empty() {
+                                return Err(ContractError::CustomError {
+                                    val: "Must allow at least one message kind".to_string(),
+                                });
+                            }
+                            config.allowed_msg_kinds = allowed_msg_kinds;
+                        }
                         if let Some(agent_fee) = agent_fee {
+                            // deposits are tallied in the current fee denom, it can't change
+                            if agent_fee.denom != config.agent_fee.denom
+                                || agent_fee.amount.u128() > MAX_AGENT_FEE
+                            {
+                                return Err(ContractError::InvalidAgentFee {
+                                    max: Coin::new(MAX_AGENT_FEE, config.agent_fee.denom),
+                                });
+                            }
                             config.agent_fee = agent_fee;
                         }
                         if let Some(min_tasks_per_agent) = min_tasks_per_agent {
@@ -111,6 +335,7 @@ impl<'a> CwCroncat<'a> {
             //         .unwrap_or_else(|| Addr::unchecked(""))
             //         .to_string(),
             // )
+            .add_attribute("treasury_fee", c.treasury_fee.to_string())
             .add_attribute("min_tasks_per_agent", c.min_tasks_per_agent.to_string())
             .add_attribute(
                 "agent_active_indices",
@@ -127,9 +352,190 @@ impl<'a> CwCroncat<'a> {
             .add_attribute("agent_fee", c.agent_fee.to_string())
             .add_attribute("gas_price", c.gas_price.to_string())
             .add_attribute("proxy_callback_gas", c.proxy_callback_gas.to_string())
+            .add_attribute("max_action_gas", c.max_action_gas.to_string())
+            .add_attribute("default_gas_limit", c.default_gas_limit.to_string())
+            .add_attribute("refund_on_remove", c.refund_on_remove.to_string())
+            .add_attribute(
+                "allowed_msg_kinds",
+                c.allowed_msg_kinds
+                    .iter()
+                    .map(|k| format!("{:?}", k))
+                    .collect::<Vec<_>>()
+                    .join(","),
+            )
             .add_attribute("slot_granularity", c.slot_granularity.to_string()))
     }
 
+    /// Admin Remove Task
+    /// Lets the owner clear out tasks that can never execute, because their deposit doesn't
+    /// cover a single execution in the agent fee denom (e.g. funded only in a junk denom)
+    /// Funded tasks can only be removed by their owner
+    pub fn admin_remove_task(
+        &self,
+        deps: DepsMut,
+        info: MessageInfo,
+        task_hash: String,
+    ) -> Result<Response, ContractError> {
+        let config = self.config.load(deps.storage)?;
+        if info.sender != config.owner_id {
+            return Err(ContractError::Unauthorized {});
+        }
+        let task = self
+            .tasks
+            .may_load(deps.storage, task_hash.clone().into_bytes())?
+            .ok_or(ContractError::NoTaskFound {})?;
+
+        if self.can_pay_execution(&config, &task) {
+            return Err(ContractError::CustomError {
+                val: "Task can still pay for an execution".to_string(),
+            });
+        }
+
+        Ok(self
+            .remove_task(deps, task_hash)?
+            .add_attribute("removed_by", info.sender))
+    }
+
+    /// Deposit Funds
+    /// Anyone can add native funds to the protocol balance, they aren't tied to any task
+    pub fn deposit_funds(
+        &self,
+        deps: DepsMut,
+        info: MessageInfo,
+    ) -> Result<Response, ContractError> {
+        let funds = non_zero_coins(&info.funds);
+        if funds.is_empty() {
+            return Err(ContractError::CustomError {
+                val: "Must attach funds".to_string(),
+            });
+        }
+        let mut config = self.config.load(deps.storage)?;
+        if let Some(denom) = find_denom_alias(&funds, &config.native_denom) {
+            return Err(ContractError::DenomAlias {
+                denom,
+                native_denom: config.native_denom,
+            });
+        }
+
+        config
+            .available_balance
+            .add_tokens(Balance::from(funds.clone()));
+        config.protocol_balance.add_tokens(Balance::from(funds));
+        self.config.save(deps.storage, &config)?;
+
+        let protocol_balance: String = config
+            .protocol_balance
+            .native
+            .iter()
+            .map(|c| c.to_string())
+            .collect();
+        Ok(Response::new()
+            .add_attribute("method", "deposit_funds")
+            .add_attribute("protocol_balance", protocol_balance))
+    }
+
+    /// Owner Withdraw
+    /// Sweeps native balance not committed to any task (junk denoms, overfunding) to the owner
+    pub fn owner_withdraw(
+        &self,
+        deps: DepsMut,
+        info: MessageInfo,
+        amount: Vec<Coin>,
+    ) -> Result<Response, ContractError> {
+        let mut config = self.config.load(deps.storage)?;
+        if info.sender != config.owner_id {
+            return Err(ContractError::Unauthorized {});
+        }
+        if amount.is_empty() || amount.iter().any(|c| c.amount.is_zero()) {
+            return Err(ContractError::EmptyBalance {});
+        }
+
+        // Free balance is whatever is available minus the task deposits
+        let committed = self.committed_balance(deps.storage)?;
+        let native_amount = |coins: &[Coin], denom: &str| {
+            coins
+                .iter()
+                .find(|c| c.denom == denom)
+                .map(|c| c.amount)
+                .unwrap_or_default()
+        };
+        for c in amount.iter() {
+            let free = native_amount(&config.available_balance.native, &c.denom)
+                .saturating_sub(native_amount(&committed.native, &c.denom));
+            if c.amount > free {
+                return Err(ContractError::CustomError {
+                    val: format!(
+                        "Not enough free {} balance, can't withdraw committed funds",
+                        c.denom
+                    ),
+                });
+            }
+        }
+
+        config
+            .available_balance
+            .minus_tokens(Balance::from(amount.clone()));
+        // The free balance is surplus & protocol funds, the surplus goes first
+        // so protocol funds keep paying agent rewards as long as possible
+        let rest = config
+            .surplus_balance
+            .saturating_minus_tokens(Balance::from(amount.clone()));
+        config
+            .protocol_balance
+            .saturating_minus_tokens(Balance::from(rest.native));
+        self.config.save(deps.storage, &config)?;
+
+        Ok(Response::new()
+            .add_attribute("method", "owner_withdraw")
+            .add_attribute("account_id", config.owner_id.to_string())
+            .add_submessage(SubMsg::new(BankMsg::Send {
+                to_address: config.owner_id.into(),
+                amount,
+            })))
+    }
+
+    /// Compact Slots
+    /// Deletes time & block slots left without any task hashes, reclaiming their storage
+    pub fn compact_slots(
+        &self,
+        deps: DepsMut,
+        info: MessageInfo,
+    ) -> Result<Response, ContractError> {
+        let config = self.config.load(deps.storage)?;
+        if info.sender != config.owner_id {
+            return Err(ContractError::Unauthorized {});
+        }
+
+        let empty_time_slots: Vec<u64> = self
+            .time_slots
+            .range(deps.storage, None, None, Order::Ascending)
+            .collect::<StdResult<Vec<_>>>()?
+            .into_iter()
+            .filter(|(_, hashes)| hashes.is_empty())
+            .map(|(slot, _)| slot)
+            .collect();
+        for slot in empty_time_slots.iter() {
+            self.time_slots.remove(deps.storage, *slot);
+        }
+
+        let empty_block_slots: Vec<u64> = self
+            .block_slots
+            .range(deps.storage, None, None, Order::Ascending)
+            .collect::<StdResult<Vec<_>>>()?
+            .into_iter()
+            .filter(|(_, hashes)| hashes.is_empty())
+            .map(|(slot, _)| slot)
+            .collect();
+        for slot in empty_block_slots.iter() {
+            self.block_slots.remove(deps.storage, *slot);
+        }
+
+        Ok(Response::new()
+            .add_attribute("method", "compact_slots")
+            .add_attribute("time_slots_removed", empty_time_slots.len().to_string())
+            .add_attribute("block_slots_removed", empty_block_slots.len().to_string()))
+    }
+
     /// Move Balance
     /// Allows owner to move balance to DAO or to let treasury transfer to itself only.
     /// This is a restricted method for moving funds utilized in growth management strategies.
@@ -188,6 +594,9 @@ impl<'a> CwCroncat<'a> {
                         }
 
                         // Update internal registry balance
+                        config
+                            .surplus_balance
+                            .saturating_minus_tokens(Balance::from(bal.clone()));
                         config
                             .available_balance
                             .minus_tokens(Balance::from(bal.clone()));
@@ -209,6 +618,9 @@ impl<'a> CwCroncat<'a> {
                         }
 
                         // Update internal registry balance
+                        config
+                            .surplus_balance
+                            .saturating_minus_tokens(Balance::from(bal.clone()));
                         config
                             .available_balance
                             .minus_tokens(Balance::from(bal.clone()));
@@ -246,14 +658,86 @@ impl<'a> CwCroncat<'a> {
 
 #[cfg(test)]
 mod tests {
+    use super::{MAX_AGENT_FEE, MAX_GAS_PRICE};
     use crate::error::ContractError;
+    use crate::helpers::test_helpers::{
+        create_task_hash, mock_init, settings_msg, task_hash_attr, task_request, SettingsUpdate,
+        ANYONE, NATIVE_DENOM,
+    };
+    use crate::helpers::CwTemplateContract;
     use crate::state::CwCroncat;
     use cosmwasm_std::testing::{mock_dependencies_with_balance, mock_env, mock_info};
-    use cosmwasm_std::{coin, coins, from_binary, Addr, MessageInfo};
+    use cosmwasm_std::{
+        coin, coins, from_binary, Addr, BankMsg, Coin, Empty, MessageInfo, Order, StakingMsg,
+        StdError, StdResult, SubMsg, Uint128,
+    };
     use cw20::Balance;
     use cw_croncat_core::msg::{
-        ExecuteMsg, GetBalancesResponse, GetConfigResponse, InstantiateMsg, QueryMsg,
+        DenomBalance, ExecuteMsg, GetBalancerInvariantResponse, GetBalancesResponse,
+        GetConfigResponse, InstantiateMsg, QueryMsg, TaskRequest, TaskResponse,
     };
+    use cw_croncat_core::types::{Action, BoundaryHorizon};
+    use cw_multi_test::{App, AppBuilder, BankSudo, Contract, ContractWrapper, Executor, SudoMsg};
+
+    const ADMIN: &str = "cosmos1sjllsnramtg3ewxqwwrwjxfgc4n4ef9u0tvx7u";
+
+    pub fn contract_template() -> Box<dyn Contract<Empty>> {
+        let contract = ContractWrapper::new(
+            crate::entry::execute,
+            crate::entry::instantiate,
+            crate::entry::query,
+        );
+        Box::new(contract)
+    }
+
+    fn mock_app() -> App {
+        AppBuilder::new().build(|router, _, storage| {
+            for address in [ADMIN, ANYONE] {
+                router
+                    .bank
+                    .init_balance(storage, &Addr::unchecked(address), coins(100, NATIVE_DENOM))
+                    .unwrap();
+            }
+        })
+    }
+
+    fn proper_instantiate() -> (App, CwTemplateContract) {
+        let mut app = mock_app();
+        let cw_template_id = app.store_code(contract_template());
+        let owner_addr = Addr::unchecked(ADMIN);
+
+        let msg = InstantiateMsg {
+            denom: NATIVE_DENOM.to_string(),
+            owner_id: Some(owner_addr.clone()),
+            agent_nomination_duration: Some(360),
+        };
+        let cw_template_contract_addr = app
+            .instantiate_contract(cw_template_id, owner_addr, &msg, &[], "Manager", None)
+            .unwrap();
+
+        (app, CwTemplateContract(cw_template_contract_addr))
+    }
+
+    /// task_request delegating `stake` atom instead
+    fn staking_task(stake: u128) -> TaskRequest {
+        TaskRequest {
+            actions: vec![Action {
+                msg: StakingMsg::Delegate {
+                    validator: "you".to_string(),
+                    amount: coin(stake, NATIVE_DENOM),
+                }
+                .into(),
+                gas_limit: Some(150_000),
+            }],
+            ..task_request()
+        }
+    }
+
+    fn query_config(app: &App, contract_addr: &Addr) -> GetConfigResponse {
+        app.wrap()
+            .query_wasm_smart(contract_addr, &QueryMsg::GetConfig {})
+            .unwrap()
+    }
 
     #[test]
     fn update_settings() {
@@ -275,17 +759,10 @@ mod tests {
             .unwrap();
         assert_eq!(0, res_init.messages.len());
 
-        let payload = ExecuteMsg::UpdateSettings {
+        let payload = settings_msg(SettingsUpdate {
             paused: Some(true),
-            owner_id: None,
-            // treasury_id: None,
-            agent_fee: None,
-            min_tasks_per_agent: None,
-            agents_eject_threshold: None,
-            gas_price: None,
-            proxy_callback_gas: None,
-            slot_granularity: None,
-        };
+            ..Default::default()
+        });
 
         // non-owner fails
         let unauth_info = MessageInfo {
@@ -326,6 +803,130 @@ mod tests {
         assert_eq!(info.sender, value.owner_id);
     }
 
+    #[test]
+    fn update_settings_fee_bounds() {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+
+        let mut update = |agent_fee: Option<Coin>, gas_price: Option<u32>| {
+            app.execute_contract(
+                Addr::unchecked(ADMIN),
+                contract_addr.clone(),
+                &settings_msg(SettingsUpdate {
+                    agent_fee,
+                    gas_price,
+                    ..Default::default()
+                }),
+                &[],
+            )
+        };
+
+        for gas_price in [0, MAX_GAS_PRICE + 1] {
+            let res_err = update(None, Some(gas_price)).unwrap_err();
+            assert_eq!(
+                ContractError::InvalidGasPrice { max: MAX_GAS_PRICE },
+                res_err.downcast().unwrap()
+            );
+        }
+        for agent_fee in [coin(MAX_AGENT_FEE + 1, "atom"), coin(5, "juno")] {
+            let res_err = update(Some(agent_fee), None).unwrap_err();
+            assert_eq!(
+                ContractError::InvalidAgentFee {
+                    max: coin(MAX_AGENT_FEE, "atom")
+                },
+                res_err.downcast().unwrap()
+            );
+        }
+
+        // the bounds themselves are fine
+        update(Some(coin(MAX_AGENT_FEE, "atom")), Some(MAX_GAS_PRICE)).unwrap();
+        let config = query_config(&app, &contract_addr);
+        assert_eq!(coin(MAX_AGENT_FEE, "atom"), config.agent_fee);
+        assert_eq!(MAX_GAS_PRICE, config.gas_price);
+    }
+
+    #[test]
+    fn update_settings_treasury_id() {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+
+        let mut update = |treasury_id: &str| {
+            app.execute_contract(
+                Addr::unchecked(ADMIN),
+                contract_addr.clone(),
+                &settings_msg(SettingsUpdate {
+                    treasury_id: Some(Some(Addr::unchecked(treasury_id))),
+                    ..Default::default()
+                }),
+                &[],
+            )
+        };
+
+        // not a valid address, refunds to it would fail
+        let res_err = update("Money_Bags").unwrap_err();
+        assert_eq!(
+            ContractError::Std(StdError::generic_err(
+                "Invalid input: address not normalized"
+            )),
+            res_err.downcast().unwrap()
+        );
+
+        update("money_bags").unwrap();
+        assert_eq!(
+            Some(Addr::unchecked("money_bags")),
+            query_config(&app, &contract_addr).treasury_id
+        );
+    }
+
+    #[test]
+    fn update_settings_clear_limits() {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+
+        let update = |app: &mut App, set: bool| {
+            app.execute_contract(
+                Addr::unchecked(ADMIN),
+                contract_addr.clone(),
+                &settings_msg(SettingsUpdate {
+                    treasury_id: Some(set.then(|| Addr::unchecked("money_bags"))),
+                    max_tasks_per_owner: Some(set.then_some(5)),
+                    max_tasks_per_slot: Some(set.then_some(10)),
+                    max_failure_streak: Some(set.then_some(3)),
+                    min_refill_amount: Some(set.then_some(Uint128::new(100))),
+                    min_task_deposit: Some(set.then(|| coin(1_000, "atom"))),
+                    max_boundary_horizon: Some(set.then_some(BoundaryHorizon {
+                        blocks: 100,
+                        seconds: 600,
+                    })),
+                    ..Default::default()
+                }),
+                &[],
+            )
+            .unwrap();
+        };
+
+        update(&mut app, true);
+        let config = query_config(&app, &contract_addr);
+        assert_eq!(Some(Addr::unchecked("money_bags")), config.treasury_id);
+        assert_eq!(Some(5), config.max_tasks_per_owner);
+        assert_eq!(Some(10), config.max_tasks_per_slot);
+        assert_eq!(Some(3), config.max_failure_streak);
+        assert_eq!(Some(Uint128::new(100)), config.min_refill_amount);
+        assert_eq!(Some(coin(1_000, "atom")), config.min_task_deposit);
+        assert!(config.max_boundary_horizon.is_some());
+
+        // back to unlimited
+        update(&mut app, false);
+        let config = query_config(&app, &contract_addr);
+        assert_eq!(None, config.treasury_id);
+        assert_eq!(None, config.max_tasks_per_owner);
+        assert_eq!(None, config.max_tasks_per_slot);
+        assert_eq!(None, config.max_failure_streak);
+        assert_eq!(None, config.min_refill_amount);
+        assert_eq!(None, config.min_task_deposit);
+        assert_eq!(None, config.max_boundary_horizon);
+    }
+
     #[test]
     fn move_balances_auth_checks() {
         let mut deps = mock_dependencies_with_balance(&coins(200000000, "atom"));
@@ -346,17 +947,10 @@ mod tests {
             .unwrap();
         assert!(res_init.messages.is_empty());
 
-        let payload = ExecuteMsg::UpdateSettings {
-            paused: None,
-            owner_id: None,
-            // treasury_id: Some(Addr::unchecked("money_bags")),
-            agent_fee: None,
-            min_tasks_per_agent: None,
-            agents_eject_threshold: None,
-            gas_price: None,
-            proxy_callback_gas: None,
-            slot_granularity: None,
-        };
+        let payload = settings_msg(SettingsUpdate {
+            treasury_id: Some(Some(Addr::unchecked("money_bags"))),
+            ..Default::default()
+        });
         let info_setting = mock_info("owner_id", &coins(0, "meow"));
         let res_exec = store
             .execute(deps.as_mut(), mock_env(), info_setting, payload)
@@ -406,17 +1000,10 @@ mod tests {
             .unwrap();
         assert!(res_init.messages.is_empty());
 
-        let payload = ExecuteMsg::UpdateSettings {
-            paused: None,
-            owner_id: None,
-            // treasury_id: Some(money_bags.clone()),
-            agent_fee: None,
-            min_tasks_per_agent: None,
-            agents_eject_threshold: None,
-            gas_price: None,
-            proxy_callback_gas: None,
-            slot_granularity: None,
-        };
+        let payload = settings_msg(SettingsUpdate {
+            treasury_id: Some(Some(money_bags.clone())),
+            ..Default::default()
+        });
         let info_settings = mock_info("owner_id", &coins(0, "meow"));
         let res_exec = store
             .execute(deps.as_mut(), mock_env(), info_settings, payload)
@@ -455,7 +1042,462 @@ mod tests {
         );
     }
 
+    #[test]
+    fn query_balances_committed() {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+        app.sudo(SudoMsg::Bank(BankSudo::Mint {
+            to_address: ANYONE.to_string(),
+            amount: coins(7, "meow"),
+        }))
+        .unwrap();
+
+        // create several tasks with different deposits
+        let deposits = vec![
+            coins(10, "atom"),
+            coins(20, "atom"),
+            vec![coin(5, "atom"), coin(7, "meow")],
+        ];
+        for (i, deposit) in deposits.into_iter().enumerate() {
+            app.execute_contract(
+                Addr::unchecked(ANYONE),
+                contract_addr.clone(),
+                &ExecuteMsg::CreateTask {
+                    task: staking_task(i as u128 + 1),
+                },
+                &deposit,
+            )
+            .unwrap();
+        }
+
+        let balances: GetBalancesResponse = app
+            .wrap()
+            .query_wasm_smart(&contract_addr, &QueryMsg::GetBalances {})
+            .unwrap();
+        assert_eq!(
+            vec![coin(35, "atom"), coin(7, "meow")],
+            balances.committed_balance.native
+        );
+
+        // without any surplus, everything deposited is committed
+        assert_eq!(
+            balances.committed_balance.native,
+            balances.available_balance.native
+        );
+    }
+
+    #[test]
+    fn query_balancer_invariant() {
+        let mut deps = mock_dependencies_with_balance(&[]);
+        let store = CwCroncat::default();
+        mock_init(&store, deps.as_mut()).unwrap();
+
+        let deposits = vec![coins(10, "atom"), vec![coin(5, "atom"), coin(7, "juno")]];
+        for (i, deposit) in deposits.into_iter().enumerate() {
+            store
+                .create_task(
+                    deps.as_mut(),
+                    mock_info(ANYONE, &deposit),
+                    mock_env(),
+                    staking_task(i as u128 + 1),
+                )
+                .unwrap();
+        }
+
+        let invariant = store.query_balancer_invariant(deps.as_ref()).unwrap();
+        assert!(invariant.in_balance);
+        assert_eq!(
+            vec![coin(15, "atom"), coin(7, "juno")],
+            invariant.tasks_balance.native
+        );
+
+        // seed some drift
+        store
+            .config
+            .update(deps.as_mut().storage, |mut c| -> StdResult<_> {
+                c.available_balance
+                    .add_tokens(Balance::from(coins(1, "atom")));
+                Ok(c)
+            })
+            .unwrap();
+        let invariant = store.query_balancer_invariant(deps.as_ref()).unwrap();
+        assert!(!invariant.in_balance);
+        // the instantiate funds are surplus
+        assert_eq!(
+            vec![coin(1000, "meow"), coin(16, "atom"), coin(7, "juno")],
+            invariant.available_balance.native
+        );
+    }
+
+    #[test]
+    fn query_denom_balances() {
+        let mut deps = mock_dependencies_with_balance(&[]);
+        let store = CwCroncat::default();
+        mock_init(&store, deps.as_mut()).unwrap();
+
+        let deposits = vec![
+            coins(10, "atom"),
+            vec![coin(5, "atom"), coin(7, "juno")],
+            vec![coin(3, "atom"), coin(4, "juno")],
+        ];
+        for (i, deposit) in deposits.into_iter().enumerate() {
+            store
+                .create_task(
+                    deps.as_mut(),
+                    mock_info(ANYONE, &deposit),
+                    mock_env(),
+                    staking_task(i as u128 + 1),
+                )
+                .unwrap();
+        }
+
+        // seed some drift in a single denom
+        store
+            .config
+            .update(deps.as_mut().storage, |mut c| -> StdResult<_> {
+                c.available_balance
+                    .add_tokens(Balance::from(coins(2, "juno")));
+                Ok(c)
+            })
+            .unwrap();
+
+        let balances = store.query_denom_balances(deps.as_ref()).unwrap();
+        assert_eq!(
+            vec![
+                DenomBalance {
+                    denom: "atom".to_string(),
+                    committed: Uint128::new(18),
+                    available: Uint128::new(18),
+                },
+                DenomBalance {
+                    denom: "juno".to_string(),
+                    committed: Uint128::new(11),
+                    available: Uint128::new(13),
+                },
+                DenomBalance {
+                    denom: "meow".to_string(),
+                    committed: Uint128::zero(),
+                    available: Uint128::new(1000),
+                },
+            ],
+            balances
+        );
+    }
+
     // // TODO: Setup CW20 logic / balances!
+    #[test]
+    fn admin_remove_underfunded_task() {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+        app.sudo(SudoMsg::Bank(BankSudo::Mint {
+            to_address: ANYONE.to_string(),
+            amount: coins(1_000_000, "junk"),
+        }))
+        .unwrap();
+        app.sudo(SudoMsg::Bank(BankSudo::Mint {
+            to_address: "bob".to_string(),
+            amount: coins(150_011, "atom"),
+        }))
+        .unwrap();
+
+        let mut create_task = |owner: &str, stake: u128, funds: &[Coin]| -> String {
+            let res = app
+                .execute_contract(
+                    Addr::unchecked(owner),
+                    contract_addr.clone(),
+                    &ExecuteMsg::CreateTask {
+                        task: staking_task(stake),
+                    },
+                    funds,
+                )
+                .unwrap();
+            task_hash_attr(&res)
+        };
+        // one execution costs 150_008atom, plus the 3atom staked
+        let underfunded = create_task(ANYONE, 1, &coins(30, "atom"));
+        let junk = create_task(ANYONE, 2, &coins(1_000_000, "junk"));
+        let funded = create_task("bob", 3, &coins(150_011, "atom"));
+        let admin_remove = |app: &mut App, sender: &str, task_hash: &String| {
+            app.execute_contract(
+                Addr::unchecked(sender),
+                contract_addr.clone(),
+                &ExecuteMsg::AdminRemoveTask {
+                    task_hash: task_hash.clone(),
+                },
+                &[],
+            )
+        };
+        let task_exists = |app: &App, task_hash: String| {
+            let task: Option<TaskResponse> = app
+                .wrap()
+                .query_wasm_smart(&contract_addr, &QueryMsg::GetTask { task_hash })
+                .unwrap();
+            task.is_some()
+        };
+
+        // only the owner can force-remove
+        let res_err = admin_remove(&mut app, "bob", &underfunded).unwrap_err();
+        assert_eq!(ContractError::Unauthorized {}, res_err.downcast().unwrap());
+
+        // tasks that can execute are left to their owner
+        let res_err = admin_remove(&mut app, ADMIN, &funded).unwrap_err();
+        assert_eq!(
+            ContractError::CustomError {
+                val: "Task can still pay for an execution".to_string()
+            },
+            res_err.downcast().unwrap()
+        );
+
+        // deposits go back to the task owner
+        for task_hash in [underfunded, junk] {
+            admin_remove(&mut app, ADMIN, &task_hash).unwrap();
+            assert!(!task_exists(&app, task_hash));
+        }
+        assert_eq!(
+            vec![coin(100, "atom"), coin(1_000_000, "junk")],
+            app.wrap().query_all_balances(ANYONE).unwrap()
+        );
+        assert!(task_exists(&app, funded));
+    }
+
+    #[test]
+    fn owner_withdraw_free_balance() {
+        let mut deps = mock_dependencies_with_balance(&[coin(100, "atom"), coin(5, "junk")]);
+        let mut store = CwCroncat::default();
+        mock_init(&store, deps.as_mut()).unwrap();
+        let info = mock_info("creator", &[]);
+
+        // commit some atom to a task
+        store
+            .create_task(
+                deps.as_mut(),
+                mock_info(ANYONE, &coins(30, "atom")),
+                mock_env(),
+                task_request(),
+            )
+            .unwrap();
+
+        // only the owner can withdraw
+        let res_err = store
+            .execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info(ANYONE, &[]),
+                ExecuteMsg::OwnerWithdraw {
+                    amount: coins(5, "junk"),
+                },
+            )
+            .unwrap_err();
+        assert_eq!(ContractError::Unauthorized {}, res_err);
+
+        // can't dip into the task deposits
+        let res_err = store
+            .execute(
+                deps.as_mut(),
+                mock_env(),
+                info.clone(),
+                ExecuteMsg::OwnerWithdraw {
+                    amount: coins(101, "atom"),
+                },
+            )
+            .unwrap_err();
+        assert_eq!(
+            ContractError::CustomError {
+                val: "Not enough free atom balance, can't withdraw committed funds".to_string()
+            },
+            res_err
+        );
+
+        // the surplus is withdrawn before the protocol funds, those keep paying agent rewards
+        store
+            .execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info("sponsor", &coins(20, "atom")),
+                ExecuteMsg::DepositFunds {},
+            )
+            .unwrap();
+        store
+            .execute(
+                deps.as_mut(),
+                mock_env(),
+                info.clone(),
+                ExecuteMsg::OwnerWithdraw {
+                    amount: coins(10, "atom"),
+                },
+            )
+            .unwrap();
+        let invariant = store.query_balancer_invariant(deps.as_ref()).unwrap();
+        assert!(invariant.in_balance);
+        assert_eq!(coins(20, "atom"), invariant.protocol_balance.native);
+
+        // sweep all free funds
+        let amount = vec![coin(110, "atom"), coin(5, "junk"), coin(1000, "meow")];
+        let res = store
+            .execute(
+                deps.as_mut(),
+                mock_env(),
+                info.clone(),
+                ExecuteMsg::OwnerWithdraw {
+                    amount: amount.clone(),
+                },
+            )
+            .unwrap();
+        assert_eq!(
+            vec![SubMsg::new(BankMsg::Send {
+                to_address: info.sender.to_string(),
+                amount,
+            })],
+            res.messages
+        );
+
+        let balances = store.query_balances(deps.as_ref()).unwrap();
+        assert_eq!(
+            vec![coin(30, "atom"), coin(0, "junk"), coin(0, "meow")],
+            balances.available_balance.native
+        );
+        assert_eq!(coins(30, "atom"), balances.committed_balance.native);
+    }
+
+    #[test]
+    fn deposit_funds() {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+        app.sudo(SudoMsg::Bank(BankSudo::Mint {
+            to_address: "sponsor".to_string(),
+            amount: vec![coin(20, "atom"), coin(3, "meow")],
+        }))
+        .unwrap();
+        create_task_hash(&mut app, &contract_addr, task_request());
+        let query_invariant = |app: &App| -> GetBalancerInvariantResponse {
+            app.wrap()
+                .query_wasm_smart(&contract_addr, &QueryMsg::GetBalancerInvariant {})
+                .unwrap()
+        };
+
+        let res_err = app
+            .execute_contract(
+                Addr::unchecked("sponsor"),
+                contract_addr.clone(),
+                &ExecuteMsg::DepositFunds {},
+                &[],
+            )
+            .unwrap_err();
+        assert_eq!(
+            ContractError::CustomError {
+                val: "Must attach funds".to_string()
+            },
+            res_err.downcast().unwrap()
+        );
+
+        // anyone can deposit, funds aren't committed to any task
+        app.execute_contract(
+            Addr::unchecked("sponsor"),
+            contract_addr.clone(),
+            &ExecuteMsg::DepositFunds {},
+            &[coin(20, "atom"), coin(3, "meow")],
+        )
+        .unwrap();
+        let balances: GetBalancesResponse = app
+            .wrap()
+            .query_wasm_smart(&contract_addr, &QueryMsg::GetBalances {})
+            .unwrap();
+        assert_eq!(
+            vec![coin(20, "atom"), coin(3, "meow")],
+            balances.protocol_balance.native
+        );
+        assert_eq!(coins(10, "atom"), balances.committed_balance.native);
+        assert_eq!(
+            vec![coin(30, "atom"), coin(3, "meow")],
+            balances.available_balance.native
+        );
+
+        // available balance reconciles with the tasks & protocol balances
+        assert!(query_invariant(&app).in_balance);
+
+        // without any surplus, withdrawals take from the protocol balance
+        app.execute_contract(
+            Addr::unchecked(ADMIN),
+            contract_addr.clone(),
+            &ExecuteMsg::OwnerWithdraw {
+                amount: coins(5, "atom"),
+            },
+            &[],
+        )
+        .unwrap();
+        let invariant = query_invariant(&app);
+        assert!(invariant.in_balance);
+        assert_eq!(
+            vec![coin(15, "atom"), coin(3, "meow")],
+            invariant.protocol_balance.native
+        );
+    }
+
+    #[test]
+    fn compact_slots() {
+        let mut deps = mock_dependencies_with_balance(&[]);
+        let mut store = CwCroncat::default();
+        mock_init(&store, deps.as_mut()).unwrap();
+        let info = mock_info("creator", &[]);
+
+        // leave empty slots behind, next to ones still in use
+        let hashes = vec![b"hash".to_vec()];
+        store
+            .time_slots
+            .save(&mut deps.storage, 1, &vec![])
+            .unwrap();
+        store
+            .time_slots
+            .save(&mut deps.storage, 2, &hashes)
+            .unwrap();
+        store
+            .block_slots
+            .save(&mut deps.storage, 3, &vec![])
+            .unwrap();
+        store
+            .block_slots
+            .save(&mut deps.storage, 4, &vec![])
+            .unwrap();
+        store
+            .block_slots
+            .save(&mut deps.storage, 5, &hashes)
+            .unwrap();
+
+        let msg = ExecuteMsg::CompactSlots {};
+        let err = store
+            .execute(
+                deps.as_mut(),
+                mock_env(),
+                mock_info("not_owner", &[]),
+                msg.clone(),
+            )
+            .unwrap_err();
+        assert_eq!(ContractError::Unauthorized {}, err);
+
+        let res = store.execute(deps.as_mut(), mock_env(), info, msg).unwrap();
+        assert!(res
+            .attributes
+            .iter()
+            .any(|a| a.key == "time_slots_removed" && a.value == "1"));
+        assert!(res
+            .attributes
+            .iter()
+            .any(|a| a.key == "block_slots_removed" && a.value == "2"));
+
+        let time_slots: Vec<u64> = store
+            .time_slots
+            .keys(&deps.storage, None, None, Order::Ascending)
+            .collect::<StdResult<_>>()
+            .unwrap();
+        assert_eq!(vec![2], time_slots);
+        let block_slots: Vec<u64> = store
+            .block_slots
+            .keys(&deps.storage, None, None, Order::Ascending)
+            .collect::<StdResult<_>>()
+            .unwrap();
+        assert_eq!(vec![5], block_slots);
+    }
+
     // #[test]
     // fn move_balances_cw() {
     //     let mut deps = mock_dependencies_with_balance(&coins(200000000, "atom"));
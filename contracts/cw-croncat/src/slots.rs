@@ -1,10 +1,8 @@
 use crate::traits::IntervalExt;
 use crate::CwCroncat;
 use cosmwasm_std::{BlockInfo, Env, Order, StdResult, Storage};
-use cron_schedule::Schedule;
 pub use cw_croncat_core::types::Interval;
-use cw_croncat_core::types::{Boundary, BoundarySpec, SlotType};
-use std::str::FromStr;
+use cw_croncat_core::types::{parse_cron, Boundary, BoundarySpec, SlotType, Task};
 
 fn get_next_block_limited(env: Env, boundary: Boundary) -> (u64, SlotType) {
     let current_block_height = env.block.height;
@@ -130,7 +128,7 @@ impl IntervalExt for Interval {
                     current_block_ts
                 };
 
-                let schedule = Schedule::from_str(crontab.as_str()).unwrap();
+                let schedule = parse_cron(crontab).unwrap();
                 let next_ts = schedule.next_after(&current_ts).unwrap();
                 (next_ts, SlotType::Cron)
             }
@@ -140,17 +138,24 @@ impl IntervalExt for Interval {
             // - Boundary specifies a start/end that block offsets can be computed from
             // - Block offset will truncate to specific modulo offsets
             Interval::Block(block) => get_next_block_by_offset(env, boundary, *block),
+            // return the time slot the delay ends in, counted from the current block.
+            // Only resolved on creation, the task doesn't repeat
+            Interval::Delay(seconds) => (
+                env.block.time.plus_seconds(*seconds).nanos(),
+                SlotType::Cron,
+            ),
+            // return the time slot of the timestamp, the task doesn't repeat
+            Interval::At(timestamp) => (timestamp.nanos(), SlotType::Cron),
         }
     }
     fn is_valid(&self) -> bool {
         match self {
             Interval::Once => true,
             Interval::Immediate => true,
-            Interval::Block(_) => true,
-            Interval::Cron(crontab) => {
-                let s = Schedule::from_str(crontab);
-                s.is_ok()
-            }
+            Interval::Block(blocks) => *blocks > 0,
+            Interval::Delay(seconds) => *seconds > 0,
+            Interval::At(_) => true,
+            Interval::Cron(crontab) => parse_cron(crontab).is_ok() && self.cron_gap(0) != Some(0),
         }
     }
 }
@@ -206,7 +211,49 @@ impl<'a> CwCroncat<'a> {
         ret
     }
 
-    /// Gets 1 slot hash item, and removes the hash from storage
+    /// Adds the task hash to a block or time slot, creating the slot if needed
+    /// Hashes are kept in ascending order, which is the order they're executed in
+    /// Shared by task creation & rescheduling after an execution
+    pub(crate) fn schedule_task(
+        &self,
+        storage: &mut dyn Storage,
+        task: &Task,
+        slot_id: u64,
+        slot_kind: &SlotType,
+    ) -> StdResult<()> {
+        let store = match slot_kind {
+            SlotType::Block => &self.block_slots,
+            SlotType::Cron => &self.time_slots,
+        };
+        let mut slot_data = store.may_load(storage, slot_id)?.unwrap_or_default();
+        let hash = task.to_hash_vec();
+        let pos = slot_data.binary_search(&hash).unwrap_or_else(|pos| pos);
+        slot_data.insert(pos, hash);
+        store.save(storage, slot_id, &slot_data)
+    }
+
+    /// The slot kind & id the task hash is scheduled in, None if it's in no slot
+    /// NOTE: Slots aren't indexed by task, so this scans every block & time slot
+    pub(crate) fn task_slot(
+        &self,
+        storage: &dyn Storage,
+        hash: &[u8],
+    ) -> StdResult<Option<(SlotType, u64)>> {
+        for (slots, kind) in [
+            (&self.block_slots, SlotType::Block),
+            (&self.time_slots, SlotType::Cron),
+        ] {
+            for slot in slots.range(storage, None, None, Order::Ascending) {
+                let (slot_id, slot_hashes) = slot?;
+                if slot_hashes.iter().any(|h| h.as_slice() == hash) {
+                    return Ok(Some((kind, slot_id)));
+                }
+            }
+        }
+        Ok(None)
+    }
+
+    /// Gets the lowest slot hash item, and removes the hash from storage
     /// Cleans up a slot if empty
     pub(crate) fn pop_slot_item(
         &mut self,
@@ -222,7 +269,13 @@ impl<'a> CwCroncat<'a> {
         let mut slot_data = store.may_load(storage, *slot).unwrap()?;
 
         // Get a single task hash, then retrieve task details
-        let hash = slot_data.pop();
+        // Slots saved before hashes were kept in order get sorted on their first pop
+        slot_data.sort();
+        let hash = if slot_data.is_empty() {
+            None
+        } else {
+            Some(slot_data.remove(0))
+        };
 
         // Need to remove this slot if no hash's left
         if slot_data.is_empty() {
@@ -250,7 +303,8 @@ impl<'a> CwCroncat<'a> {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use cosmwasm_std::{testing::{mock_env, mock_dependencies_with_balance}, coins};
+    use cosmwasm_std::{testing::{mock_env, mock_dependencies_with_balance}, coins, Addr};
+    use cw_croncat_core::types::FailMode;
 
     #[test]
     fn interval_get_next_block_limited() {
@@ -277,6 +331,32 @@ mod tests {
         }
     }
 
+    #[test]
+    fn interval_delay() {
+        let env = mock_env();
+        let (next_id, slot_kind) = Interval::Delay(86_400).next(env.clone(), Boundary { start: None, end: None });
+        assert_eq!(env.block.time.plus_seconds(86_400).nanos(), next_id);
+        assert_eq!(SlotType::Cron, slot_kind);
+
+        assert!(Interval::Delay(1).is_valid());
+        assert!(!Interval::Delay(0).is_valid());
+    }
+
+    #[test]
+    fn interval_cron_field_count() {
+        let env = mock_env();
+        let boundary = Boundary { start: None, end: None };
+        let five = Interval::Cron("30 * * * *".to_string());
+        let six = Interval::Cron("0 30 * * * *".to_string());
+        assert!(five.is_valid());
+        assert!(six.is_valid());
+        // 5 fields run at second 0
+        assert_eq!(six.next(env.clone(), boundary), five.next(env, boundary));
+
+        assert!(!Interval::Cron("0 30 * * * * 2030".to_string()).is_valid());
+        assert_eq!(Err("Cron must have 5 or 6 fields, found 7".to_string()), parse_cron("0 30 * * * * 2030").map(|_| ()));
+    }
+
     #[test]
     fn interval_get_next_block_by_offset() {
         // (input, input, outcome, outcome)
@@ -366,7 +446,7 @@ mod tests {
         ];
         store.time_slots.save(&mut deps.storage, 1, &multiple_tasks).unwrap();
         store.block_slots.save(&mut deps.storage, 1, &multiple_tasks).unwrap();
-        for task in multiple_tasks.iter().rev() {
+        for task in multiple_tasks.iter() {
             assert_eq!(*task, store.pop_slot_item(&mut deps.storage, &1, &SlotType::Cron).unwrap());
             assert_eq!(*task, store.pop_slot_item(&mut deps.storage, &1, &SlotType::Block).unwrap());
         }
@@ -375,4 +455,48 @@ mod tests {
         assert_eq!(None, store.pop_slot_item(&mut deps.storage, &1, &SlotType::Cron));
         assert_eq!(None, store.pop_slot_item(&mut deps.storage, &1, &SlotType::Block));
     }
+
+    #[test]
+    fn slot_items_schedule() {
+        let mut deps = mock_dependencies_with_balance(&coins(200, ""));
+        let store = CwCroncat::default();
+        let task = |amount: u128| Task {
+            owner_id: Addr::unchecked("bob"),
+            interval: Interval::Immediate,
+            boundary: Boundary { start: None, end: None },
+            stop_on_fail: false,
+            allow_sponsored_refill: false,
+            refund_split: None,
+            preferred_agent: None,
+            fail_mode: FailMode::StopOnFirstFail,
+            agent_fee_override: None,
+            on_success_task: None,
+            label: None,
+            memo: None,
+            return_surplus: false,
+            total_deposit: coins(amount, "atom"),
+            actions: vec![],
+            rules: None,
+            last_executed_slot: None,
+            executions_count: 0,
+            failure_streak: 0,
+            created_at_height: 0,
+            created_at_time: Default::default(),
+        };
+        let (first, second) = (task(1), task(2));
+
+        // new slots are created for either kind
+        store.schedule_task(&mut deps.storage, &first, 5, &SlotType::Block).unwrap();
+        store.schedule_task(&mut deps.storage, &first, 5, &SlotType::Cron).unwrap();
+        assert_eq!(vec![first.to_hash_vec()], store.block_slots.load(&deps.storage, 5).unwrap());
+        assert_eq!(vec![first.to_hash_vec()], store.time_slots.load(&deps.storage, 5).unwrap());
+
+        // existing slots are added to, the other kind is left alone
+        store.schedule_task(&mut deps.storage, &second, 5, &SlotType::Block).unwrap();
+        assert_eq!(
+            vec![first.to_hash_vec(), second.to_hash_vec()],
+            store.block_slots.load(&deps.storage, 5).unwrap()
+        );
+        assert_eq!(vec![first.to_hash_vec()], store.time_slots.load(&deps.storage, 5).unwrap());
+    }
 }
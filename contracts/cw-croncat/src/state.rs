@@ -1,11 +1,12 @@
-use cosmwasm_std::{Addr, Coin, StdResult, Storage, Timestamp};
+use cosmwasm_std::{Addr, Coin, StdResult, Storage, Timestamp, Uint128};
 use cw_storage_plus::{Index, IndexList, IndexedMap, Item, Map, MultiIndex};
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 
 use crate::helpers::Task;
-use cw_croncat_core::types::{Agent, GenericBalance, SlotType};
+use cw_croncat_core::types::{Agent, BoundaryHorizon, GenericBalance, MsgKind, SlotType};
 
+// Settings added after the first release default when missing, so an older config still loads
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq)]
 pub struct Config {
     // Runtime
@@ -33,14 +34,93 @@ pub struct Config {
     pub agent_fee: Coin,
     pub gas_price: u32,
     pub proxy_callback_gas: u32,
+    // Ceiling for any single action's gas_limit
+    #[serde(default = "default_max_action_gas")]
+    pub max_action_gas: u64,
+    // Filled in for actions created without a gas_limit
+    #[serde(default = "default_action_gas_limit")]
+    pub default_gas_limit: u64,
+    // Limit of tasks a single owner can have, None is unlimited
+    #[serde(default)]
+    pub max_tasks_per_owner: Option<u64>,
+    // Limit of tasks scheduled in a single slot, so it stays executable within a block, None is unlimited
+    #[serde(default)]
+    pub max_tasks_per_slot: Option<u64>,
+    // Failed executions in a row before a task is ended & refunded, None never ends it
+    #[serde(default = "default_max_failure_streak")]
+    pub max_failure_streak: Option<u64>,
+    // When false, the leftover deposit of one-shot tasks that already ran goes to the treasury
+    #[serde(default = "default_refund_on_remove")]
+    pub refund_on_remove: bool,
+    // Smallest refill accepted, in the agent fee denom, None is unrestricted
+    // Refills without the fee denom aren't limited
+    #[serde(default)]
+    pub min_refill_amount: Option<Uint128>,
+    // Non fee denom leftovers below it go to the treasury (or stay in available_balance) on removal,
+    // instead of being refunded. Zero refunds everything
+    #[serde(default)]
+    pub dust_threshold: Uint128,
+    // Blocks a block slot task with a preferred agent is reserved for it, before any active agent can execute it
+    #[serde(default = "default_execution_grace_blocks")]
+    pub execution_grace_blocks: u64,
+    // Seconds a cron slot task with a preferred agent is reserved for it, before any active agent can execute it
+    #[serde(default = "default_execution_grace_seconds")]
+    pub execution_grace_seconds: u64,
+    // Smallest deposit a task can be created with, in the agent fee denom, None is unrestricted
+    #[serde(default)]
+    pub min_task_deposit: Option<Coin>,
+    // How far ahead a task's boundary start & end may be, None is unbounded
+    #[serde(default)]
+    pub max_boundary_horizon: Option<BoundaryHorizon>,
+    // Reject tasks repeating the exact same action, otherwise they're only flagged with a warning attribute
+    #[serde(default)]
+    pub reject_duplicate_actions: bool,
+    // Message kinds task actions may use, every kind by default
+    #[serde(default = "MsgKind::all")]
+    pub allowed_msg_kinds: Vec<MsgKind>,
     pub slot_granularity: u64,
 
     // Treasury
-    // pub treasury_id: Option<Addr>,
+    #[serde(default)]
+    pub treasury_id: Option<Addr>,
+    // Percentage (0-100) of the agent fee that goes to the treasury, when one is set
+    #[serde(default)]
+    pub treasury_fee: u64,
     pub cw20_whitelist: Vec<Addr>, // TODO: Consider fee structure for whitelisted CW20s
     pub native_denom: String,
     pub available_balance: GenericBalance, // tasks + rewards balances
     pub staked_balance: GenericBalance, // surplus that is temporary staking (to be used in conjunction with external treasury)
+    // Funds deposited to the contract itself rather than to a task, part of available_balance
+    #[serde(default)]
+    pub protocol_balance: GenericBalance,
+    // Funds held by neither a task nor the protocol balance, part of available_balance
+    // The instantiate funds & unrefunded dust, less the agent fees & withdrawals paid from them
+    #[serde(default)]
+    pub surplus_balance: GenericBalance,
+}
+
+pub(crate) fn default_max_action_gas() -> u64 {
+    3_000_000
+}
+
+pub(crate) fn default_action_gas_limit() -> u64 {
+    150_000
+}
+
+pub(crate) fn default_execution_grace_blocks() -> u64 {
+    10
+}
+
+pub(crate) fn default_execution_grace_seconds() -> u64 {
+    60
+}
+
+pub(crate) fn default_max_failure_streak() -> Option<u64> {
+    Some(10)
+}
+
+pub(crate) fn default_refund_on_remove() -> bool {
+    true
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
@@ -51,6 +131,12 @@ pub struct QueueItem {
     // could help for IBC non-block bound txns
     pub prev_idx: Option<u64>,
     pub task_hash: Option<Vec<u8>>,
+    // The slot this task was executed from, recorded in task history on success
+    pub slot_id: Option<u64>,
+    // Index of the task action this reply belongs to
+    pub action_idx: u64,
+    // Indexes of the task actions that failed so far in this execution
+    pub failed_actions: Vec<u64>,
 }
 
 pub struct TaskIndexes<'a> {
@@ -84,6 +170,7 @@ pub struct CwCroncat<'a> {
     pub task_total: Item<'a, u64>,
 
     /// Timestamps can be grouped into slot buckets (1-60 second buckets) for easier agent handling
+    /// Within a slot, task hashes are kept and executed in ascending order
     pub time_slots: Map<'a, u64, Vec<Vec<u8>>>,
     /// Block slots allow for grouping of tasks at a specific block height,
     /// this is done instead of forcing a block height into a range of timestamps for reliability
@@ -93,6 +180,8 @@ pub struct CwCroncat<'a> {
     /// Keeping ordered sub messages & reply id's
     pub reply_queue: Map<'a, u64, QueueItem>,
     pub reply_index: Item<'a, u64>,
+    /// Set while a proxy call's actions are running, cleared by the proxy callback
+    pub proxy_in_progress: Item<'a, bool>,
 
     // This is a timestamp that's updated when a new task is added such that
     // the agent/task ratio allows for another agent to join.
@@ -122,6 +211,7 @@ impl<'a> CwCroncat<'a> {
             block_slots: Map::new("block_slots"),
             reply_queue: Map::new("reply_queue"),
             reply_index: Item::new("reply_index"),
+            proxy_in_progress: Item::new("proxy_in_progress"),
             agent_nomination_begin_time: Item::new("agent_nomination_begin_time"),
         }
     }
@@ -137,7 +227,7 @@ impl<'a> CwCroncat<'a> {
     }
 
     pub fn decrement_tasks(&self, storage: &mut dyn Storage) -> StdResult<u64> {
-        let val = self.task_total(storage)? - 1;
+        let val = self.task_total(storage)?.saturating_sub(1);
         self.task_total.save(storage, &val)?;
         Ok(val)
     }
@@ -166,7 +256,7 @@ mod tests {
     use crate::helpers::Task;
     use cosmwasm_std::testing::MockStorage;
     use cosmwasm_std::{coins, BankMsg, CosmosMsg, Order, StdResult};
-    use cw_croncat_core::types::{Action, Boundary, Interval};
+    use cw_croncat_core::types::{Action, Boundary, FailMode, Interval};
     use cw_storage_plus::Bound;
 
     #[test]
@@ -187,12 +277,26 @@ mod tests {
                 end: None,
             },
             stop_on_fail: false,
+            allow_sponsored_refill: false,
+            refund_split: None,
+            preferred_agent: None,
+            fail_mode: FailMode::StopOnFirstFail,
+            agent_fee_override: None,
+            on_success_task: None,
+            label: None,
+            memo: None,
+            return_surplus: false,
             total_deposit: vec![],
             actions: vec![Action {
                 msg,
                 gas_limit: Some(150_000),
             }],
             rules: None,
+            last_executed_slot: None,
+            executions_count: 0,
+            failure_streak: 0,
+            created_at_height: 0,
+            created_at_time: Default::default(),
         };
         let task_id_str = "3ccb739ea050ebbd2e08f74aeb0b7aa081b15fa78504cba44155ec774452bbee";
         let task_id = task_id_str.to_string().into_bytes();
@@ -236,6 +340,22 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn check_task_total_counter() -> StdResult<()> {
+        let mut storage = MockStorage::new();
+        let store = CwCroncat::default();
+        store.task_total.save(&mut storage, &0)?;
+
+        assert_eq!(1, store.increment_tasks(&mut storage)?);
+        assert_eq!(2, store.increment_tasks(&mut storage)?);
+        assert_eq!(1, store.decrement_tasks(&mut storage)?);
+        assert_eq!(0, store.decrement_tasks(&mut storage)?);
+        // never underflows
+        assert_eq!(0, store.decrement_tasks(&mut storage)?);
+
+        Ok(())
+    }
+
     // test for range / Ordered time slots
     #[test]
     fn check_slots_storage_structure() -> StdResult<()> {
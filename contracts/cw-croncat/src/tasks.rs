@@ -1,41 +1,368 @@
 use crate::error::ContractError;
+use crate::helpers::balance_changed_event;
+use crate::helpers::find_denom_alias;
+use crate::helpers::non_zero_coins;
+use crate::helpers::JsonObjectLen;
 use crate::slots::Interval;
 use crate::state::{Config, CwCroncat};
 use cosmwasm_std::{
-    coin, Addr, BankMsg, Coin, Deps, DepsMut, Env, MessageInfo, Order, Response, StdResult, SubMsg,
+    coin, from_slice, to_binary, Addr, Api, BankMsg, Binary, BlockInfo, Coin, CosmosMsg, Decimal,
+    Deps, DepsMut, Env, MessageInfo, Order, QueryRequest, Response, StdError, StdResult, Storage,
+    SubMsg, Timestamp, Uint128, WasmQuery,
 };
 use cw20::Balance;
-use cw_croncat_core::msg::{GetSlotHashesResponse, GetSlotIdsResponse, TaskRequest, TaskResponse};
-use cw_croncat_core::types::{SlotType, Task};
+use cw_croncat_core::msg::{
+    CheckTaskRulesResponse, CostFormula, CreateTaskResponse, GetFeeScheduleResponse,
+    GetHorizonResponse, GetOwnerSummaryResponse, GetSlotHashesResponse, GetSlotIdsResponse,
+    GetSlotStatsResponse, GetStuckTasksResponse, GetTaskCostResponse,
+    GetTasksByNextExecutionResponse, GetTasksPageResponse, OrderBy, TaskNextExecution, TaskRequest,
+    TaskResponse, ValidateTaskResponse,
+};
+use cw_croncat_core::types::{
+    parse_cron, Action, BoundarySpec, GenericBalance, Rule, RuleResponse, SlotType, Task,
+};
+use cw_storage_plus::{Bound, Map};
+use std::collections::BTreeSet;
+
+/// Most tasks a single GetTasks call returns, page with from_index for more
+pub(crate) const GET_TASKS_MAX_LIMIT: u64 = 100;
+/// Shortest hash prefix GetTasksByHashPrefix accepts, shorter ones match too many tasks to be useful
+pub(crate) const TASK_HASH_PREFIX_MIN_LEN: usize = 4;
+/// Most of an owner's tasks GetOwnerSummary reads, keeps its gas bounded
+pub(crate) const OWNER_SUMMARY_MAX_TASKS: usize = 1000;
+/// A task ending within about a day, in blocks or seconds, counts as expiring soon
+pub(crate) const EXPIRING_SOON_BLOCKS: u64 = 14_400;
+pub(crate) const EXPIRING_SOON_SECONDS: u64 = 86_400;
+/// Longest memo a task can carry, it's stored with the task and emitted on creation
+pub(crate) const TASK_MEMO_MAX_LEN: usize = 256;
+
+/// Next slot of a task at the current block, 0 once its boundary has ended
+/// Delay tasks only run once, in the slot their delay ended in
+fn next_execution(env: &Env, task: &Task) -> (u64, SlotType) {
+    match task.interval {
+        Interval::Delay(seconds) => (
+            task.created_at_time.plus_seconds(seconds).nanos(),
+            SlotType::Cron,
+        ),
+        _ => task.interval.next(env.clone(), task.boundary),
+    }
+}
+
+/// Next slot of a task at the current block, None once it ended
+/// Either its boundary passed or it's a one-shot task that already ran
+fn next_slot(env: &Env, task: &Task) -> Option<(SlotType, u64)> {
+    if task.boundary.is_past(env) || (task.interval.is_one_shot() && task.executions_count > 0) {
+        return None;
+    }
+    match next_execution(env, task) {
+        (0, _) => None,
+        (slot_id, slot_kind) => Some((slot_kind, slot_id)),
+    }
+}
+
+/// Slot a new task is first scheduled in, the id is 0 when it would never run
+/// Immediate tasks start in the current block slot, so an agent executing this block picks them up,
+/// unless the boundary starts at a later height. Following executions use the next block
+fn first_slot(env: &Env, task: &Task) -> (u64, SlotType) {
+    let (next_id, slot_kind) = task.interval.next(env.clone(), task.boundary);
+    let next_id = match (&task.interval, task.boundary.start) {
+        (Interval::Immediate, Some(BoundarySpec::Height(start))) if start > env.block.height => {
+            next_id
+        }
+        (Interval::Immediate, _) if next_id != 0 => env.block.height,
+        _ => next_id,
+    };
+    (next_id, slot_kind)
+}
+
+/// Task as returned by the task queries, with its next slot at the current block
+fn task_response(env: &Env, task: Task) -> TaskResponse {
+    TaskResponse {
+        task_hash: task.to_hash(),
+        next_slot: next_slot(env, &task),
+        owner_id: task.owner_id,
+        interval: task.interval,
+        boundary: task.boundary,
+        stop_on_fail: task.stop_on_fail,
+        allow_sponsored_refill: task.allow_sponsored_refill,
+        refund_split: task.refund_split,
+        preferred_agent: task.preferred_agent,
+        fail_mode: task.fail_mode,
+        agent_fee_override: task.agent_fee_override,
+        on_success_task: task.on_success_task,
+        label: task.label,
+        memo: task.memo,
+        return_surplus: task.return_surplus,
+        total_deposit: task.total_deposit,
+        actions: task.actions,
+        rules: task.rules,
+        last_executed_slot: task.last_executed_slot,
+        executions_count: task.executions_count,
+        failure_streak: task.failure_streak,
+        created_at_height: task.created_at_height,
+        created_at_time: task.created_at_time,
+    }
+}
 
 impl<'a> CwCroncat<'a> {
     /// Returns task data
     /// Used by the frontend for viewing tasks
+    /// NOTE: from_index is relative to order_by, defaults to ascending
     pub(crate) fn query_get_tasks(
         &self,
         deps: Deps,
+        env: Env,
+        from_index: Option<u64>,
+        limit: Option<u64>,
+        order_by: Option<OrderBy>,
+    ) -> StdResult<Vec<TaskResponse>> {
+        let size: u64 = self.task_total.load(deps.storage)?;
+        let from_index = from_index.unwrap_or_default();
+        let limit = limit
+            .unwrap_or(GET_TASKS_MAX_LIMIT)
+            .min(GET_TASKS_MAX_LIMIT)
+            .min(size.saturating_sub(from_index));
+        let order: Order = order_by.unwrap_or(OrderBy::Asc).into();
+        self.tasks
+            .range(deps.storage, None, None, order)
+            .skip(from_index as usize)
+            .take(limit as usize)
+            .map(|res| res.map(|(_k, task)| task_response(&env, task)))
+            .collect()
+    }
+
+    /// Returns task data in ascending hash order, starting after the `start_after` task hash
+    /// Seeks straight to the cursor, unlike from_index which iterates the skipped tasks
+    pub(crate) fn query_get_tasks_from(
+        &self,
+        deps: Deps,
+        env: Env,
+        start_after: Option<String>,
+        limit: Option<u64>,
+    ) -> StdResult<Vec<TaskResponse>> {
+        let limit = limit
+            .unwrap_or(GET_TASKS_MAX_LIMIT)
+            .min(GET_TASKS_MAX_LIMIT);
+        let start = start_after.map(|hash| Bound::exclusive(hash.into_bytes()));
+        self.tasks
+            .range(deps.storage, start, None, Order::Ascending)
+            .take(limit as usize)
+            .map(|res| res.map(|(_k, task)| task_response(&env, task)))
+            .collect()
+    }
+
+    /// Page of tasks after `start_after`, see `query_get_tasks_from`
+    /// `next` is the last hash of the page, only set when tasks remain after it
+    pub(crate) fn query_get_tasks_page(
+        &self,
+        deps: Deps,
+        env: Env,
+        start_after: Option<String>,
+        limit: Option<u64>,
+    ) -> StdResult<GetTasksPageResponse> {
+        let tasks = self.query_get_tasks_from(deps, env, start_after, limit)?;
+        let next = match tasks.last() {
+            Some(last) => self
+                .tasks
+                .keys(
+                    deps.storage,
+                    Some(Bound::exclusive(last.task_hash.clone().into_bytes())),
+                    None,
+                    Order::Ascending,
+                )
+                .next()
+                .map(|_| last.task_hash.clone()),
+            None => None,
+        };
+        Ok(GetTasksPageResponse { tasks, next })
+    }
+
+    /// Returns the tasks whose hash starts with `prefix`, seeking straight to the prefix
+    pub(crate) fn query_get_tasks_by_hash_prefix(
+        &self,
+        deps: Deps,
+        env: Env,
+        prefix: String,
+        limit: Option<u64>,
+    ) -> StdResult<Vec<TaskResponse>> {
+        if prefix.len() < TASK_HASH_PREFIX_MIN_LEN {
+            return Err(StdError::generic_err(format!(
+                "Hash prefix must be at least {} characters",
+                TASK_HASH_PREFIX_MIN_LEN
+            )));
+        }
+        let limit = limit
+            .unwrap_or(GET_TASKS_MAX_LIMIT)
+            .min(GET_TASKS_MAX_LIMIT);
+        let start = Some(Bound::inclusive(prefix.clone().into_bytes()));
+        self.tasks
+            .range(deps.storage, start, None, Order::Ascending)
+            .take_while(|res| {
+                res.as_ref()
+                    .map(|(k, _)| k.starts_with(prefix.as_bytes()))
+                    .unwrap_or(true)
+            })
+            .take(limit as usize)
+            .map(|res| res.map(|(_k, task)| task_response(&env, task)))
+            .collect()
+    }
+
+    /// Returns only the tasks gated by rules, for agents that can evaluate them
+    pub(crate) fn query_get_tasks_with_rules(
+        &self,
+        deps: Deps,
+        env: Env,
         from_index: Option<u64>,
         limit: Option<u64>,
     ) -> StdResult<Vec<TaskResponse>> {
-        let size: u64 = self.task_total.load(deps.storage)?.min(1000);
         let from_index = from_index.unwrap_or_default();
-        let limit = limit.unwrap_or(100).min(size);
+        let limit = limit.unwrap_or(100).min(1000);
         self.tasks
             .range(deps.storage, None, None, Order::Ascending)
+            .filter(|res| res.as_ref().map_or(true, |(_k, task)| task.rules.is_some()))
             .skip(from_index as usize)
             .take(limit as usize)
-            .map(|res| {
-                res.map(|(_k, task)| TaskResponse {
-                    task_hash: task.to_hash(),
-                    owner_id: task.owner_id,
-                    interval: task.interval,
-                    boundary: task.boundary,
-                    stop_on_fail: task.stop_on_fail,
-                    total_deposit: task.total_deposit,
-                    actions: task.actions,
-                    rules: task.rules,
-                })
+            .map(|res| res.map(|(_k, task)| task_response(&env, task)))
+            .collect()
+    }
+
+    /// Returns the tasks whose boundary end falls within the window, inclusive
+    /// so owners can see which tasks expire soon, regardless of their balance
+    pub(crate) fn query_get_tasks_by_boundary_end(
+        &self,
+        deps: Deps,
+        env: Env,
+        start: BoundarySpec,
+        end: BoundarySpec,
+        from_index: Option<u64>,
+        limit: Option<u64>,
+    ) -> StdResult<Vec<TaskResponse>> {
+        let in_window = |task_end: Option<BoundarySpec>| match (task_end, start, end) {
+            (
+                Some(BoundarySpec::Height(h)),
+                BoundarySpec::Height(start),
+                BoundarySpec::Height(end),
+            ) => start <= h && h <= end,
+            (Some(BoundarySpec::Time(t)), BoundarySpec::Time(start), BoundarySpec::Time(end)) => {
+                start <= t && t <= end
+            }
+            _ => false,
+        };
+        if std::mem::discriminant(&start) != std::mem::discriminant(&end) {
+            return Err(StdError::generic_err(
+                "Window start & end must both be heights or times",
+            ));
+        }
+
+        let from_index = from_index.unwrap_or_default();
+        let limit = limit.unwrap_or(100).min(1000);
+        self.tasks
+            .range(deps.storage, None, None, Order::Ascending)
+            .filter(|res| match res {
+                Ok((_k, task)) => in_window(task.boundary.end),
+                Err(_) => true,
+            })
+            .skip(from_index as usize)
+            .take(limit as usize)
+            .map(|res| res.map(|(_k, task)| task_response(&env, task)))
+            .collect()
+    }
+
+    /// Returns the tasks created within a time window, scanning every task as there's no index
+    pub(crate) fn query_get_tasks_created_between(
+        &self,
+        deps: Deps,
+        env: Env,
+        start: Timestamp,
+        end: Timestamp,
+        from_index: Option<u64>,
+        limit: Option<u64>,
+    ) -> StdResult<Vec<TaskResponse>> {
+        let from_index = from_index.unwrap_or_default();
+        let limit = limit.unwrap_or(100).min(1000);
+        self.tasks
+            .range(deps.storage, None, None, Order::Ascending)
+            .filter(|res| match res {
+                Ok((_k, task)) => start <= task.created_at_time && task.created_at_time <= end,
+                Err(_) => true,
+            })
+            .skip(from_index as usize)
+            .take(limit as usize)
+            .map(|res| res.map(|(_k, task)| task_response(&env, task)))
+            .collect()
+    }
+
+    /// Returns only the tasks holding a denom in their deposit, for agents paid in that token
+    pub(crate) fn query_get_tasks_by_denom(
+        &self,
+        deps: Deps,
+        env: Env,
+        denom: String,
+        from_index: Option<u64>,
+        limit: Option<u64>,
+    ) -> StdResult<Vec<TaskResponse>> {
+        let from_index = from_index.unwrap_or_default();
+        let limit = limit.unwrap_or(100).min(1000);
+        self.tasks
+            .range(deps.storage, None, None, Order::Ascending)
+            .filter(|res| match res {
+                Ok((_k, task)) => task.total_deposit.iter().any(|c| c.denom == denom),
+                Err(_) => true,
+            })
+            .skip(from_index as usize)
+            .take(limit as usize)
+            .map(|res| res.map(|(_k, task)| task_response(&env, task)))
+            .collect()
+    }
+
+    /// Returns only the tasks whose summed action gas limits fit in max_gas
+    /// Lets agents with a fixed gas budget per tx skip tasks too large to batch
+    pub(crate) fn query_get_tasks_under_gas(
+        &self,
+        deps: Deps,
+        env: Env,
+        max_gas: u64,
+        from_index: Option<u64>,
+        limit: Option<u64>,
+    ) -> StdResult<Vec<TaskResponse>> {
+        let from_index = from_index.unwrap_or_default();
+        let limit = limit.unwrap_or(100).min(1000);
+        self.tasks
+            .range(deps.storage, None, None, Order::Ascending)
+            .filter(|res| match res {
+                Ok((_k, task)) => task.to_gas_total() <= max_gas,
+                Err(_) => true,
+            })
+            .skip(from_index as usize)
+            .take(limit as usize)
+            .map(|res| res.map(|(_k, task)| task_response(&env, task)))
+            .collect()
+    }
+
+    /// Returns the tasks an agent can execute first, the ones preferring it plus the ones without a preference
+    pub(crate) fn query_get_tasks_for_agent(
+        &self,
+        deps: Deps,
+        env: Env,
+        agent_id: Addr,
+        from_index: Option<u64>,
+        limit: Option<u64>,
+    ) -> StdResult<Vec<TaskResponse>> {
+        let from_index = from_index.unwrap_or_default();
+        let limit = limit.unwrap_or(100).min(1000);
+        self.tasks
+            .range(deps.storage, None, None, Order::Ascending)
+            .filter(|res| match res {
+                Ok((_k, task)) => task
+                    .preferred_agent
+                    .as_ref()
+                    .map(|preferred_agent| *preferred_agent == agent_id)
+                    .unwrap_or(true),
+                Err(_) => true,
             })
+            .skip(from_index as usize)
+            .take(limit as usize)
+            .map(|res| res.map(|(_k, task)| task_response(&env, task)))
             .collect()
     }
 
@@ -43,6 +370,7 @@ impl<'a> CwCroncat<'a> {
     pub(crate) fn query_get_tasks_by_owner(
         &self,
         deps: Deps,
+        env: Env,
         owner_id: Addr,
     ) -> StdResult<Vec<TaskResponse>> {
         self.tasks
@@ -50,1198 +378,7295 @@ impl<'a> CwCroncat<'a> {
             .owner
             .prefix(owner_id)
             .range(deps.storage, None, None, Order::Ascending)
-            .map(|x| {
-                x.map(|(_, task)| TaskResponse {
-                    task_hash: task.to_hash(),
-                    owner_id: task.owner_id,
-                    interval: task.interval,
-                    boundary: task.boundary,
-                    stop_on_fail: task.stop_on_fail,
-                    total_deposit: task.total_deposit,
-                    actions: task.actions,
-                    rules: task.rules,
-                })
-            })
+            .map(|x| x.map(|(_, task)| task_response(&env, task)))
             .collect::<StdResult<Vec<_>>>()
     }
 
-    /// Returns single task data
-    pub(crate) fn query_get_task(
-        &self,
-        deps: Deps,
-        task_hash: String,
-    ) -> StdResult<Option<TaskResponse>> {
-        let res = self
-            .tasks
-            .may_load(deps.storage, task_hash.as_bytes().to_vec())?;
-        if res.is_none() {
-            return Ok(None);
-        }
-
-        let task: Task = res.unwrap();
-
-        Ok(Some(TaskResponse {
-            task_hash: task.to_hash(),
-            owner_id: task.owner_id,
-            interval: task.interval,
-            boundary: task.boundary,
-            stop_on_fail: task.stop_on_fail,
-            total_deposit: task.total_deposit,
-            actions: task.actions,
-            rules: task.rules,
-        }))
+    /// Returns the number of tasks currently stored
+    pub(crate) fn query_get_task_count(&self, deps: Deps) -> StdResult<u64> {
+        self.task_total(deps.storage)
     }
 
-    /// Returns a hash computed by the input task data
-    pub(crate) fn query_get_task_hash(&self, task: Task) -> StdResult<String> {
-        Ok(task.to_hash())
+    /// Returns the denoms tasks are funded in, plus the agent fee denom
+    /// Used by agents to know which denoms the contract pays in
+    pub(crate) fn query_get_denoms(&self, deps: Deps) -> StdResult<Vec<String>> {
+        let c: Config = self.config.load(deps.storage)?;
+        let mut denoms = BTreeSet::from([c.agent_fee.denom]);
+        for task in self.tasks.range(deps.storage, None, None, Order::Ascending) {
+            let (_, task) = task?;
+            denoms.extend(task.total_deposit.into_iter().map(|coin| coin.denom));
+        }
+        Ok(denoms.into_iter().collect())
     }
 
-    /// Check if interval params are valid by attempting to parse
-    pub(crate) fn query_validate_interval(&self, interval: Interval) -> StdResult<bool> {
-        Ok(interval.is_valid())
+    /// Returns the number of tasks for a specific owner, without loading them
+    pub(crate) fn query_get_owner_task_count(&self, deps: Deps, owner_id: Addr) -> StdResult<u64> {
+        Ok(self
+            .tasks
+            .idx
+            .owner
+            .prefix(owner_id)
+            .keys_raw(deps.storage, None, None, Order::Ascending)
+            .count() as u64)
     }
 
-    /// Gets a set of tasks.
-    /// Default: Returns the next executable set of tasks hashes.
-    ///
-    /// Optional Parameters:
-    /// "offset" - An unsigned integer specifying how far in the future to check for tasks that are slotted.
-    ///
-    /// Result:
-    /// (block id, block task hash's, time id, time task hash's)
-    pub(crate) fn query_slot_tasks(
+    /// Aggregates an owner's tasks in a single pass over the owner index
+    /// Gas is linear in the tasks read, up to OWNER_SUMMARY_MAX_TASKS of them
+    pub(crate) fn query_owner_summary(
         &self,
         deps: Deps,
-        slot: Option<u64>,
-    ) -> StdResult<GetSlotHashesResponse> {
-        let mut block_id: u64 = 0;
-        let mut block_hashes: Vec<Vec<u8>> = Vec::new();
-        let mut time_id: u64 = 0;
-        let mut time_hashes: Vec<Vec<u8>> = Vec::new();
+        env: Env,
+        owner_id: Addr,
+    ) -> StdResult<GetOwnerSummaryResponse> {
+        let mut summary = GetOwnerSummaryResponse {
+            task_count: 0,
+            total_deposit: vec![],
+            earliest_next_block: None,
+            latest_next_block: None,
+            earliest_next_time: None,
+            latest_next_time: None,
+            expiring_soon: 0,
+            truncated: false,
+        };
+        let mut total_deposit = GenericBalance::default();
+        let soon_height = env.block.height.saturating_add(EXPIRING_SOON_BLOCKS);
+        let soon_time = env.block.time.plus_seconds(EXPIRING_SOON_SECONDS);
+        let min = |current: Option<u64>, id: u64| Some(current.map_or(id, |c| c.min(id)));
+        let max = |current: Option<u64>, id: u64| Some(current.map_or(id, |c| c.max(id)));
 
-        // Check if slot was supplied, otherwise get the next slots for block and time
-        if let Some(id) = slot {
-            block_hashes = self
-                .block_slots
-                .may_load(deps.storage, id)?
-                .unwrap_or_default();
-            if !block_hashes.is_empty() {
-                block_id = id;
-            }
-            time_hashes = self
-                .block_slots
-                .may_load(deps.storage, id)?
-                .unwrap_or_default();
-            if !time_hashes.is_empty() {
-                time_id = id;
+        let tasks =
+            self.tasks
+                .idx
+                .owner
+                .prefix(owner_id)
+                .range(deps.storage, None, None, Order::Ascending);
+        for (i, res) in tasks.enumerate() {
+            if i == OWNER_SUMMARY_MAX_TASKS {
+                summary.truncated = true;
+                break;
             }
-        } else {
-            let time: Vec<(u64, _)> = self
-                .time_slots
-                .range(deps.storage, None, None, Order::Ascending)
-                .take(1)
-                .collect::<StdResult<Vec<(u64, _)>>>()?;
+            let (_, task) = res?;
+            summary.task_count += 1;
+            total_deposit.add_tokens(Balance::from(task.total_deposit.clone()));
 
-            if !time.is_empty() {
-                // (time_id, time_hashes) = time[0].clone();
-                let slot = time[0].clone();
-                time_id = slot.0;
-                time_hashes = slot.1;
+            match next_execution(&env, &task) {
+                (0, _) => (),
+                (id, SlotType::Block) => {
+                    summary.earliest_next_block = min(summary.earliest_next_block, id);
+                    summary.latest_next_block = max(summary.latest_next_block, id);
+                }
+                (id, SlotType::Cron) => {
+                    let earliest = summary.earliest_next_time.map(|t| t.nanos());
+                    let latest = summary.latest_next_time.map(|t| t.nanos());
+                    summary.earliest_next_time = min(earliest, id).map(Timestamp::from_nanos);
+                    summary.latest_next_time = max(latest, id).map(Timestamp::from_nanos);
+                }
             }
 
-            let block: Vec<(u64, _)> = self
-                .block_slots
-                .range(deps.storage, None, None, Order::Ascending)
-                .take(1)
-                .collect::<StdResult<Vec<(u64, _)>>>()?;
-
-            if !block.is_empty() {
-                // (block_id, block_hashes) = block[0].clone();
-                let slot = block[0].clone();
-                block_id = slot.0;
-                block_hashes = slot.1;
+            let expiring_soon = match task.boundary.end {
+                Some(BoundarySpec::Height(height)) => height <= soon_height,
+                Some(BoundarySpec::Time(time)) => time <= soon_time,
+                None => false,
+            };
+            if expiring_soon {
+                summary.expiring_soon += 1;
             }
         }
-
-        // Generate strings for all hashes
-        let block_task_hash: Vec<_> = block_hashes
-            .iter()
-            .map(|b| String::from_utf8(b.to_vec()).unwrap_or_else(|_| "".to_string()))
-            .collect();
-        let time_task_hash: Vec<_> = time_hashes
-            .iter()
-            .map(|t| String::from_utf8(t.to_vec()).unwrap_or_else(|_| "".to_string()))
-            .collect();
-
-        Ok(GetSlotHashesResponse {
-            block_id,
-            block_task_hash,
-            time_id,
-            time_task_hash,
-        })
+        summary.total_deposit = total_deposit.native;
+        Ok(summary)
     }
 
-    /// Gets list of active slot ids, for both time & block slots
-    /// (time, block)
-    pub(crate) fn query_slot_ids(&self, deps: Deps) -> StdResult<GetSlotIdsResponse> {
-        let time_ids: Vec<u64> = self
-            .time_slots
-            .keys(deps.storage, None, None, Order::Ascending)
-            .collect::<StdResult<Vec<_>>>()?;
-        let block_ids: Vec<u64> = self
-            .block_slots
-            .keys(deps.storage, None, None, Order::Ascending)
-            .collect::<StdResult<Vec<_>>>()?;
-        Ok(GetSlotIdsResponse {
-            time_ids,
-            block_ids,
+    /// Returns the soonest `limit` block & time tasks, ordered by their next execution
+    /// NOTE: Tasks aren't indexed by next slot, so this scans and sorts every task
+    pub(crate) fn query_get_tasks_by_next_execution(
+        &self,
+        deps: Deps,
+        env: Env,
+        limit: Option<u64>,
+    ) -> StdResult<GetTasksByNextExecutionResponse> {
+        let limit = limit
+            .unwrap_or(GET_TASKS_MAX_LIMIT)
+            .min(GET_TASKS_MAX_LIMIT) as usize;
+        let mut block_tasks: Vec<TaskNextExecution> = vec![];
+        let mut time_tasks: Vec<TaskNextExecution> = vec![];
+        for res in self.tasks.range(deps.storage, None, None, Order::Ascending) {
+            let (_, task) = res?;
+            let (slot_id, slot_kind) = next_execution(&env, &task);
+            // Ended tasks have no next slot
+            if slot_id == 0 {
+                continue;
+            }
+            let next = TaskNextExecution {
+                task_hash: task.to_hash(),
+                slot_id,
+            };
+            match slot_kind {
+                SlotType::Block => block_tasks.push(next),
+                SlotType::Cron => time_tasks.push(next),
+            }
+        }
+        for tasks in [&mut block_tasks, &mut time_tasks] {
+            tasks.sort_by(|a, b| (a.slot_id, &a.task_hash).cmp(&(b.slot_id, &b.task_hash)));
+            tasks.truncate(limit);
+        }
+        Ok(GetTasksByNextExecutionResponse {
+            block_tasks,
+            time_tasks,
         })
     }
 
-    /// Allows any user or contract to pay for future txns based on a specific schedule
-    /// contract, function id & other settings. When the task runs out of balance
-    /// the task is no longer executed, any additional funds will be returned to task owner.
-    pub fn create_task(
+    /// Returns single task data
+    pub(crate) fn query_get_task(
         &self,
-        deps: DepsMut,
-        info: MessageInfo,
+        deps: Deps,
         env: Env,
-        task: TaskRequest,
-    ) -> Result<Response, ContractError> {
-        if info.funds.is_empty() {
-            return Err(ContractError::CustomError {
-                val: "Must attach funds".to_string(),
-            });
-        }
-        let c: Config = self.config.load(deps.storage)?;
-        if c.paused {
-            return Err(ContractError::CustomError {
-                val: "Create task paused".to_string(),
-            });
+        task_hash: String,
+    ) -> StdResult<Option<TaskResponse>> {
+        self.get_task_response(deps, env, task_hash.into_bytes())
+    }
+
+    /// Returns the task of each hash, in the same order, None for unknown hashes
+    /// At most GET_TASKS_MAX_LIMIT hashes per query
+    pub(crate) fn query_get_tasks_by_hashes(
+        &self,
+        deps: Deps,
+        env: Env,
+        task_hashes: Vec<String>,
+    ) -> StdResult<Vec<Option<TaskResponse>>> {
+        if task_hashes.len() as u64 > GET_TASKS_MAX_LIMIT {
+            return Err(StdError::generic_err(format!(
+                "At most {} task hashes per query",
+                GET_TASKS_MAX_LIMIT
+            )));
         }
+        task_hashes
+            .into_iter()
+            .map(|task_hash| self.get_task_response(deps, env.clone(), task_hash.into_bytes()))
+            .collect()
+    }
 
-        let owner_id = info.sender;
-        let item = Task {
-            owner_id: owner_id.clone(),
-            interval: task.interval,
-            boundary: task.boundary,
-            stop_on_fail: task.stop_on_fail,
-            total_deposit: info.funds.clone(),
-            actions: task.actions,
-            rules: task.rules,
+    /// Returns the cost of one execution of a task
+    pub(crate) fn query_get_task_cost(
+        &self,
+        deps: Deps,
+        task_hash: String,
+    ) -> StdResult<Option<GetTaskCostResponse>> {
+        let task = match self.tasks.may_load(deps.storage, task_hash.into_bytes())? {
+            Some(task) => task,
+            None => return Ok(None),
         };
+        let c: Config = self.config.load(deps.storage)?;
+        self.task_cost(&c, &task)
+            .map(Some)
+            .map_err(|err| StdError::generic_err(err.to_string()))
+    }
 
-        if !item.is_valid_msg(&env.contract.address, &owner_id, &c.owner_id) {
-            return Err(ContractError::CustomError {
-                val: "Actions Message Unsupported".to_string(),
-            });
-        }
-
-        if !item.interval.is_valid() {
-            return Err(ContractError::CustomError {
-                val: "Interval invalid".to_string(),
-            });
-        }
+    /// Returns the fee settings, with the terms task_cost combines them with
+    pub(crate) fn query_fee_schedule(&self, deps: Deps) -> StdResult<GetFeeScheduleResponse> {
+        let c: Config = self.config.load(deps.storage)?;
+        Ok(GetFeeScheduleResponse {
+            gas_price: c.gas_price,
+            agent_fee: c.agent_fee.clone(),
+            proxy_callback_gas: c.proxy_callback_gas,
+            default_gas_limit: c.default_gas_limit,
+            formula: CostFormula {
+                denom: c.agent_fee.denom,
+                extra_gas: u64::from(c.proxy_callback_gas),
+                gas_multiplier: c.gas_price,
+                flat_fee: c.agent_fee.amount,
+            },
+        })
+    }
 
-        // TODO:
-        // // Check that balance is sufficient for 1 execution minimum
-        // let call_balance_used = self.task_balance_uses(&item);
-        // let min_balance_needed: u128 = if recurring == Some(true) {
-        //     call_balance_used * 2
-        // } else {
-        //     call_balance_used
-        // };
-        // assert!(
-        //     min_balance_needed <= item.total_deposit.0,
-        //     "Not enough task balance to execute job, need at least {}",
-        //     min_balance_needed
-        // );
+    /// Evaluates every rule of a task, so agents can skip tasks that would fail
+    /// Rules are queried with their stored msg & must return a RuleResponse,
+    /// a rule that errors or returns something else counts as false
+    pub(crate) fn query_check_task_rules(
+        &self,
+        deps: Deps,
+        task_hash: String,
+    ) -> StdResult<Option<CheckTaskRulesResponse>> {
+        let task = match self.tasks.may_load(deps.storage, task_hash.into_bytes())? {
+            Some(task) => task,
+            None => return Ok(None),
+        };
+        let rules: Vec<bool> = task
+            .rules
+            .unwrap_or_default()
+            .into_iter()
+            .map(|rule| {
+                deps.querier
+                    .query::<RuleResponse<Option<Binary>>>(&QueryRequest::Wasm(WasmQuery::Smart {
+                        contract_addr: rule.contract_addr.to_string(),
+                        msg: rule.msg,
+                    }))
+                    .map(|(success, _)| success)
+                    .unwrap_or(false)
+            })
+            .collect();
+        Ok(Some(CheckTaskRulesResponse {
+            success: rules.iter().all(|success| *success),
+            rules,
+        }))
+    }
 
-        let hash = item.to_hash();
+    /// Cost of one execution of a task, in the agent fee denom
+    /// gas is the actions gas plus the proxy callback, priced at gas_price, on top of the agent fee
+    /// Errors with CostOverflow when any of the sums doesn't fit
+    pub(crate) fn task_cost(
+        &self,
+        c: &Config,
+        task: &Task,
+    ) -> Result<GetTaskCostResponse, ContractError> {
+        let gas_total = task
+            .checked_gas_total()
+            .and_then(|gas| gas.checked_add(u64::from(c.proxy_callback_gas)))
+            .ok_or(ContractError::CostOverflow {})?;
+        let gas_fee = Uint128::from(gas_total)
+            .checked_mul(Uint128::from(c.gas_price))
+            .map_err(|_| ContractError::CostOverflow {})?;
+        let agent_fee = self.task_agent_fee(c, task);
+        let total_cost = gas_fee
+            .checked_add(agent_fee.amount)
+            .map_err(|_| ContractError::CostOverflow {})?;
+        Ok(GetTaskCostResponse {
+            gas_total,
+            gas_fee: Coin::new(gas_fee.u128(), c.agent_fee.denom.clone()),
+            total_cost: Coin::new(total_cost.u128(), c.agent_fee.denom.clone()),
+            agent_fee,
+            actions_cost: task.to_action_amounts(),
+        })
+    }
 
-        // Parse interval into a future timestamp, then convert to a slot
-        let (next_id, slot_kind) = item.interval.next(env.clone(), item.boundary);
+    /// Splits a task deposit into its fee balance, the fee denom left for agent fee & gas,
+    /// and its action balances, the deposit held for what the actions send
+    /// Actions sending the fee denom get their share first, so it's never spent on fees
+    pub(crate) fn task_balances(&self, c: &Config, task: &Task) -> (Coin, Vec<Coin>) {
+        let fee_denom = &c.agent_fee.denom;
+        let deposit_of = |denom: &str| {
+            task.total_deposit
+                .iter()
+                .filter(|d| d.denom == denom)
+                .map(|d| d.amount)
+                .sum::<Uint128>()
+        };
+        let fee_reserved = task
+            .to_action_amounts()
+            .into_iter()
+            .find(|a| &a.denom == fee_denom)
+            .map(|a| a.amount.min(deposit_of(fee_denom)))
+            .unwrap_or_default();
 
-        // If the next interval comes back 0, then this task should not schedule again
-        if next_id == 0 {
-            return Err(ContractError::CustomError {
-                val: "Task ended".to_string(),
-            });
+        let fee_balance = Coin::new(
+            (deposit_of(fee_denom) - fee_reserved).u128(),
+            fee_denom.clone(),
+        );
+        let mut action_balances: Vec<Coin> = task
+            .total_deposit
+            .iter()
+            .filter(|d| &d.denom != fee_denom)
+            .cloned()
+            .collect();
+        if !fee_reserved.is_zero() {
+            action_balances.push(Coin::new(fee_reserved.u128(), fee_denom.clone()));
         }
+        (fee_balance, action_balances)
+    }
 
-        // Add task to catalog
-        self.tasks
-            .update(deps.storage, item.to_hash_vec(), |old| match old {
-                Some(_) => Err(ContractError::CustomError {
-                    val: "Task already exists".to_string(),
-                }),
-                None => Ok(item.clone()),
-            })?;
+    /// Whether the action balances cover what the actions send in one execution
+    pub(crate) fn has_action_funds(&self, c: &Config, task: &Task) -> bool {
+        let (_, action_balances) = self.task_balances(c, task);
+        task.to_action_amounts().iter().all(|needed| {
+            action_balances
+                .iter()
+                .any(|b| b.denom == needed.denom && b.amount >= needed.amount)
+        })
+    }
 
-        // Increment task totals
-        let size_res = self.increment_tasks(deps.storage);
-        if size_res.is_err() {
-            return Err(ContractError::CustomError {
-                val: "Problem incrementing task total".to_string(),
-            });
-        }
-        let size = size_res.unwrap();
+    /// Action balances beyond what the actions send in one execution
+    /// The agent fee denom is kept whole, as it also pays for executions
+    pub(crate) fn action_surplus(&self, c: &Config, task: &Task) -> Vec<Coin> {
+        let (_, action_balances) = self.task_balances(c, task);
+        let needed = task.to_action_amounts();
+        action_balances
+            .into_iter()
+            .filter_map(|balance| {
+                let need = needed.iter().find(|n| n.denom == balance.denom)?;
+                let surplus = balance.amount.saturating_sub(need.amount);
+                (!surplus.is_zero()).then(|| Coin::new(surplus.u128(), balance.denom))
+            })
+            .collect()
+    }
 
-        // Get previous task hashes in slot, add as needed
-        let update_vec_data = |d: Option<Vec<Vec<u8>>>| -> StdResult<Vec<Vec<u8>>> {
-            match d {
-                // has some data, simply push new hash
-                Some(data) => {
-                    let mut s = data;
-                    s.push(item.to_hash_vec());
-                    Ok(s)
-                }
-                // No data, push new vec & hash
-                None => Ok(vec![item.to_hash_vec()]),
-            }
+    /// Agent fee paid for one execution of a task
+    /// The override only applies above the global fee, in case the owner raised it since
+    pub(crate) fn task_agent_fee(&self, c: &Config, task: &Task) -> Coin {
+        let amount = task
+            .agent_fee_override
+            .map_or(c.agent_fee.amount, |fee| fee.max(c.agent_fee.amount));
+        Coin::new(amount.u128(), c.agent_fee.denom.clone())
+    }
+
+    /// Whether the task deposit covers at least one more execution
+    /// the fee balance must cover the cost & the action balances what the actions send
+    pub(crate) fn can_pay_execution(&self, c: &Config, task: &Task) -> bool {
+        let cost = match self.task_cost(c, task) {
+            Ok(cost) => cost,
+            Err(_) => return false,
         };
+        let (fee_balance, _) = self.task_balances(c, task);
+        fee_balance.amount >= cost.total_cost.amount && self.has_action_funds(c, task)
+    }
 
-        // Based on slot kind, put into block or cron slots
-        match slot_kind {
-            SlotType::Block => {
-                self.block_slots
-                    .update(deps.storage, next_id, update_vec_data)?;
-            }
-            SlotType::Cron => {
-                self.time_slots
-                    .update(deps.storage, next_id, update_vec_data)?;
+    /// Returns single task data, looked up by the raw hash bytes
+    /// Accepts either the sha256 digest (32 bytes) or the stored hex key (64 bytes)
+    pub(crate) fn query_get_task_raw(
+        &self,
+        deps: Deps,
+        env: Env,
+        task_hash: Binary,
+    ) -> StdResult<Option<TaskResponse>> {
+        let key = match task_hash.len() {
+            32 => hex::encode(task_hash.as_slice()).into_bytes(),
+            64 => task_hash.to_vec(),
+            _ => {
+                return Err(StdError::generic_err(
+                    "Task hash must be 32 or 64 bytes long",
+                ))
             }
-        }
-
-        // Add the attached balance into available_balance
-        let mut c: Config = self.config.load(deps.storage)?;
-        c.available_balance.add_tokens(Balance::from(info.funds));
+        };
+        self.get_task_response(deps, env, key)
+    }
 
-        // If the creation of this task means we'd like another agent, update config
-        let min_tasks_per_agent = c.min_tasks_per_agent;
-        let num_active_agents = self.agent_active_queue.load(deps.storage)?.len() as u64;
-        let num_agents_to_accept =
-            self.agents_to_let_in(&min_tasks_per_agent, &num_active_agents, &size);
-        // If we should allow a new agent to take over
-        if num_agents_to_accept != 0 {
-            // Don't wipe out an older timestamp
-            let begin = self.agent_nomination_begin_time.load(deps.storage)?;
-            if begin.is_none() {
-                self.agent_nomination_begin_time
-                    .save(deps.storage, &Some(env.block.time))?;
-            }
+    /// Returns the slot kind & id the task is scheduled in, None if it's in no slot
+    /// NOTE: Slots aren't indexed by task, so this scans every block & time slot
+    pub(crate) fn query_get_task_schedule(
+        &self,
+        deps: Deps,
+        task_hash: String,
+    ) -> StdResult<Option<(SlotType, u64)>> {
+        let hash = task_hash.into_bytes();
+        if self.tasks.may_load(deps.storage, hash.clone())?.is_none() {
+            return Err(StdError::generic_err("No task found by hash"));
         }
+        self.task_slot(deps.storage, &hash)
+    }
 
-        self.config.save(deps.storage, &c)?;
+    fn get_task_response(
+        &self,
+        deps: Deps,
+        env: Env,
+        key: Vec<u8>,
+    ) -> StdResult<Option<TaskResponse>> {
+        let task = self.tasks.may_load(deps.storage, key)?;
+        Ok(task.map(|task| task_response(&env, task)))
+    }
 
-        Ok(Response::new()
-            .add_attribute("method", "create_task")
-            .add_attribute("slot_id", next_id.to_string())
-            .add_attribute("slot_kind", format!("{:?}", slot_kind))
-            .add_attribute("task_hash", hash))
+    /// Returns a hash computed by the input task data
+    pub(crate) fn query_get_task_hash(&self, task: Task) -> StdResult<String> {
+        Ok(task.to_hash())
     }
 
-    /// Deletes a task in its entirety, returning any remaining balance to task owner.
-    pub fn remove_task(&self, deps: DepsMut, task_hash: String) -> Result<Response, ContractError> {
-        let hash_vec = task_hash.clone().into_bytes();
-        let task_raw = self.tasks.may_load(deps.storage, hash_vec.clone())?;
-        if task_raw.is_none() {
-            return Err(ContractError::CustomError {
-                val: "No task found by hash".to_string(),
-            });
+    /// Check if interval params are valid by attempting to parse
+    pub(crate) fn query_validate_interval(
+        &self,
+        deps: Deps,
+        env: Env,
+        interval: Interval,
+    ) -> StdResult<bool> {
+        let c: Config = self.config.load(deps.storage)?;
+        if let Interval::Cron(crontab) = &interval {
+            parse_cron(crontab).map_err(StdError::generic_err)?;
         }
+        Ok(interval.is_valid()
+            && !interval.is_past(&env)
+            && !self.is_too_frequent(&c, &env, &interval))
+    }
 
-        // Remove all the thangs
-        self.tasks.remove(deps.storage, hash_vec)?;
+    /// Cron intervals can't fire more often than the slot granularity, to protect agents
+    fn is_too_frequent(&self, c: &Config, env: &Env, interval: &Interval) -> bool {
+        matches!(
+            interval.cron_gap(env.block.time.nanos()),
+            Some(gap) if gap < c.slot_granularity
+        )
+    }
 
-        // find any scheduled things and remove them!
-        // check which type of slot it would be in, then iterate to remove
-        // NOTE: def could use some spiffy refactor here
-        let time_ids: Vec<u64> = self
-            .time_slots
-            .keys(deps.storage, None, None, Order::Ascending)
-            .collect::<StdResult<Vec<_>>>()?;
+    /// Dry-run of create_task, lists which checks the task would pass or fail
+    /// The per owner task limit & the chained task owner are only checked when owner_id is given
+    pub(crate) fn query_validate_task(
+        &self,
+        deps: Deps,
+        env: Env,
+        task: TaskRequest,
+        funds: Vec<Coin>,
+        owner_id: Option<Addr>,
+    ) -> StdResult<ValidateTaskResponse> {
+        let c: Config = self.config.load(deps.storage)?;
+        let mut item = Task {
+            owner_id: owner_id
+                .clone()
+                .unwrap_or_else(|| env.contract.address.clone()),
+            interval: task.interval,
+            boundary: task.boundary,
+            stop_on_fail: task.stop_on_fail,
+            allow_sponsored_refill: task.allow_sponsored_refill,
+            refund_split: task.refund_split,
+            preferred_agent: task.preferred_agent,
+            fail_mode: task.fail_mode,
+            agent_fee_override: task.agent_fee_override,
+            on_success_task: task.on_success_task.clone(),
+            label: task.label.clone(),
+            memo: task.memo.clone(),
+            return_surplus: task.return_surplus,
+            total_deposit: non_zero_coins(&funds),
+            actions: task.actions,
+            rules: task.rules,
+            last_executed_slot: None,
+            executions_count: 0,
+            failure_streak: 0,
+            created_at_height: env.block.height,
+            created_at_time: env.block.time,
+        };
+        self.fill_default_gas(&c, &mut item.actions);
 
-        for tid in time_ids {
-            let mut time_hashes = self
-                .time_slots
-                .may_load(deps.storage, tid)?
+        let (res, _) = self.check_task(deps, &c, &env, &item, owner_id.is_some())?;
+        Ok(res)
+    }
+
+    /// Runs every create_task check on the task without writing anything, shared with ValidateTask
+    /// Errors are listed in the order create_task would return them, it fails with the first one
+    /// The owner limits are skipped when `check_owner` is false, the task owner is then a placeholder
+    fn check_task(
+        &self,
+        deps: Deps,
+        c: &Config,
+        env: &Env,
+        item: &Task,
+        check_owner: bool,
+    ) -> StdResult<(ValidateTaskResponse, Vec<ContractError>)> {
+        let funds_attached = !item.total_deposit.is_empty();
+        // The fee denom share is checked along with fees, on every execution
+        let funded_actions = item
+            .to_action_amounts()
+            .iter()
+            .filter(|a| a.denom != c.agent_fee.denom)
+            .all(|needed| {
+                item.total_deposit
+                    .iter()
+                    .any(|d| d.denom == needed.denom && d.amount >= needed.amount)
+            });
+        let not_paused = !c.paused;
+        let allowed_msg_kinds = item.has_allowed_msg_kinds(&c.allowed_msg_kinds);
+        let supported_actions = item.is_valid_msg(&env.contract.address) && allowed_msg_kinds;
+        let parsable_interval = item.interval.is_valid();
+        let too_frequent = parsable_interval && self.is_too_frequent(c, env, &item.interval);
+        let interval_in_past = item.interval.is_past(env);
+        let valid_interval = parsable_interval && !too_frequent && !interval_in_past;
+        // If the next interval comes back 0, then this task would never be scheduled
+        let split = item.refund_split.as_deref().unwrap_or_default();
+        let split_sums_to_one = item.refund_split.is_none()
+            || split.iter().map(|(_, share)| share).sum::<Decimal>() == Decimal::one();
+        // A zero share recipient would only ever get an empty send
+        let split_shares_non_zero = split.iter().all(|(_, share)| !share.is_zero());
+        let split_recipients_valid = split
+            .iter()
+            .all(|(recipient, _)| deps.api.addr_validate(recipient.as_str()).is_ok());
+        let valid_refund_split =
+            split_sums_to_one && split_shares_non_zero && split_recipients_valid;
+        let valid_action_gas = self.is_valid_action_gas(c, &item.actions);
+        let valid_agent_fee_override =
+            !matches!(item.agent_fee_override, Some(fee) if fee < c.agent_fee.amount);
+        // Slots can only be computed for a parsable interval within a valid boundary
+        let schedulable = parsable_interval && item.boundary.is_valid();
+        let not_ended = schedulable && item.interval.next(env.clone(), item.boundary).0 != 0;
+
+        let custom = |val: &str| ContractError::CustomError {
+            val: val.to_string(),
+        };
+        let mut errors: Vec<ContractError> = vec![];
+        if !item.boundary.is_valid() {
+            errors.push(ContractError::InvalidBoundary {});
+        }
+        if item.boundary.is_past(env) {
+            errors.push(ContractError::BoundaryInPast {});
+        } else if schedulable && !not_ended {
+            errors.push(custom("Task ended"));
+        }
+        if matches!(c.max_boundary_horizon, Some(horizon) if item.boundary.is_beyond(env, &horizon))
+        {
+            errors.push(ContractError::BoundaryBeyondHorizon {});
+        }
+        if interval_in_past {
+            errors.push(ContractError::IntervalInPast {});
+        }
+        if matches!(&item.memo, Some(memo) if memo.chars().count() > TASK_MEMO_MAX_LEN) {
+            errors.push(ContractError::MemoTooLong {
+                max: TASK_MEMO_MAX_LEN,
+            });
+        }
+        if item.actions.is_empty() {
+            errors.push(ContractError::NoActions {});
+        }
+        if c.reject_duplicate_actions && item.has_duplicate_actions() {
+            errors.push(ContractError::DuplicateActions {});
+        }
+        if let Some(Err(err)) = item
+            .rules
+            .as_ref()
+            .map(|rules| self.validate_rules(deps.api, rules))
+        {
+            errors.push(err);
+        }
+        if let Some(denom) = find_denom_alias(&item.total_deposit, &c.native_denom) {
+            errors.push(ContractError::DenomAlias {
+                denom,
+                native_denom: c.native_denom.clone(),
+            });
+        }
+        // A flat floor on deposits, on top of what executions need, discourages spam tasks
+        if let Some(min) = &c.min_task_deposit {
+            let deposit = item
+                .total_deposit
+                .iter()
+                .find(|d| d.denom == min.denom)
+                .map(|d| d.amount)
                 .unwrap_or_default();
-            if !time_hashes.is_empty() {
-                time_hashes.retain(|h| String::from_utf8(h.to_vec()).unwrap() != task_hash.clone());
+            if deposit < min.amount {
+                errors.push(ContractError::DepositBelowMinimum { min: min.clone() });
             }
-
-            // save the updates, remove if slot no longer has hashes
-            if time_hashes.is_empty() {
-                self.time_slots.remove(deps.storage, tid);
-            } else {
-                self.time_slots.save(deps.storage, tid, &time_hashes)?;
+        }
+        if let (Some(max), true) = (c.max_tasks_per_owner, check_owner) {
+            if self.query_get_owner_task_count(deps, item.owner_id.clone())? >= max {
+                errors.push(ContractError::MaxTasksPerOwner { max });
             }
         }
-        let block_ids: Vec<u64> = self
-            .block_slots
-            .keys(deps.storage, None, None, Order::Ascending)
-            .collect::<StdResult<Vec<_>>>()?;
-
-        for bid in block_ids {
-            let mut block_hashes = self
-                .block_slots
-                .may_load(deps.storage, bid)?
-                .unwrap_or_default();
-            if !block_hashes.is_empty() {
-                block_hashes
-                    .retain(|h| String::from_utf8(h.to_vec()).unwrap() != task_hash.clone());
+        if !funds_attached {
+            errors.push(custom("Must attach funds"));
+        }
+        if funds_attached && !funded_actions {
+            errors.push(custom("Not enough funds attached for the task actions"));
+        }
+        if !not_paused {
+            errors.push(custom("Create task paused"));
+        }
+        if !allowed_msg_kinds {
+            errors.push(custom("Actions Message Kind Not Allowed"));
+        } else if !supported_actions {
+            errors.push(custom("Actions Message Unsupported"));
+        }
+        if !parsable_interval {
+            errors.push(custom("Interval invalid"));
+        }
+        if too_frequent {
+            errors.push(custom("Cron interval more frequent than slot granularity"));
+        }
+        if !valid_action_gas {
+            errors.push(custom("Action gas limit exceeds max_action_gas"));
+        }
+        if !split_sums_to_one {
+            errors.push(custom("Refund split must sum to 1"));
+        }
+        if !split_shares_non_zero {
+            errors.push(custom("Refund split shares must be non-zero"));
+        }
+        if !split_recipients_valid {
+            errors.push(custom("Refund split recipient invalid"));
+        }
+        if !valid_agent_fee_override {
+            errors.push(custom("Agent fee override below agent fee"));
+        }
+        // A task whose cost doesn't fit could never be paid for
+        if let Err(err) = self.task_cost(c, item) {
+            errors.push(err);
+        }
+        if let Some(child) = &item.on_success_task {
+            let owner_id = check_owner.then_some(&item.owner_id);
+            match self.check_task_chain(deps.storage, &item.to_hash(), owner_id, child) {
+                Err(ContractError::Std(err)) => return Err(err),
+                Err(err) => errors.push(err),
+                Ok(()) => {}
             }
-
-            // save the updates, remove if slot no longer has hashes
-            if block_hashes.is_empty() {
-                self.block_slots.remove(deps.storage, bid);
-            } else {
-                self.block_slots.save(deps.storage, bid, &block_hashes)?;
+        }
+        if let (Some(max), true) = (c.max_tasks_per_slot, not_ended) {
+            let (slot_id, slot_kind) = first_slot(env, item);
+            let slots = match slot_kind {
+                SlotType::Block => &self.block_slots,
+                SlotType::Cron => &self.time_slots,
+            };
+            let slot_size = slots
+                .may_load(deps.storage, slot_id)?
+                .map_or(0, |hashes| hashes.len());
+            if slot_size as u64 >= max {
+                errors.push(ContractError::MaxTasksPerSlot { slot_id, max });
             }
         }
 
-        // setup sub-msgs for returning any remaining total_deposit to the owner
-        let task = task_raw.unwrap();
-        let submsgs = SubMsg::new(BankMsg::Send {
-            to_address: task.clone().owner_id.into(),
-            amount: task.clone().total_deposit,
-        });
-
-        // remove from the total available_balance
-        let mut c: Config = self.config.load(deps.storage)?;
-        c.available_balance
-            .minus_tokens(Balance::from(task.total_deposit));
-        self.config.save(deps.storage, &c)?;
-
-        Ok(Response::new()
-            .add_attribute("method", "remove_task")
-            .add_submessage(submsgs))
+        let res = ValidateTaskResponse {
+            is_valid: errors.is_empty(),
+            funds_attached,
+            not_paused,
+            supported_actions,
+            valid_interval,
+            not_ended,
+            valid_refund_split,
+            funded_actions,
+            errors: errors
+                .iter()
+                .map(|err| match err {
+                    ContractError::CustomError { val } => val.clone(),
+                    err => err.to_string(),
+                })
+                .collect(),
+        };
+        Ok((res, errors))
     }
 
-    /// Refill a task with more balance to continue its execution
-    /// NOTE: Restricting this to owner only, so owner can make sure the task ends
-    pub fn refill_task(
-        &self,
-        deps: DepsMut,
-        info: MessageInfo,
-        task_hash: String,
-    ) -> Result<Response, ContractError> {
-        let hash_vec = task_hash.into_bytes();
-        let task_raw = self.tasks.may_load(deps.storage, hash_vec.clone())?;
-        if task_raw.is_none() {
-            return Err(ContractError::CustomError {
-                val: "Task doesnt exist".to_string(),
-            });
-        }
-        let mut task: Task = task_raw.unwrap();
-        if task.owner_id != info.sender {
-            return Err(ContractError::CustomError {
-                val: "Only owner can refill their task".to_string(),
-            });
-        }
+    /// Gets a set of tasks.
+    /// Default: Returns the next executable set of tasks hashes.
+    ///
+    /// Optional Parameters:
+    /// "slot" - The slot type & id to get task hashes for, only that type's slot is read.
+    /// "due_at" - Only slots at or before this block's height & time are returned.
+    ///
+    /// Result:
+    /// (block id, block task hash's, time id, time task hash's)
+    /// Hashes are in ascending order, the order agents execute them in
+    pub(crate) fn query_slot_tasks(
+        &self,
+        deps: Deps,
+        slot: Option<(SlotType, u64)>,
+        from_index: Option<u64>,
+        limit: Option<u64>,
+        due_at: Option<&BlockInfo>,
+    ) -> StdResult<GetSlotHashesResponse> {
+        let block_end = due_at.map(|b| Bound::inclusive(b.height));
+        let time_end = due_at.map(|b| Bound::inclusive(b.time.nanos()));
+        let is_due = |slot_type: &SlotType, id: u64| match (due_at, slot_type) {
+            (None, _) => true,
+            (Some(b), SlotType::Block) => id <= b.height,
+            (Some(b), SlotType::Cron) => id <= b.time.nanos(),
+        };
 
-        // Add the attached balance into available_balance
-        let mut c: Config = self.config.load(deps.storage)?;
-        c.available_balance
-            .add_tokens(Balance::from(info.funds.clone()));
-        self.config.save(deps.storage, &c)?;
+        let mut block_id: u64 = 0;
+        let mut block_hashes: Vec<Vec<u8>> = Vec::new();
+        let mut time_id: u64 = 0;
+        let mut time_hashes: Vec<Vec<u8>> = Vec::new();
 
-        let mut total_balance: Vec<Coin> = vec![];
-        for t in task.total_deposit.iter() {
-            for f in info.funds.clone() {
-                if f.denom == t.denom {
-                    let amt = t.clone().amount.saturating_add(f.amount);
-                    total_balance.push(coin(amt.into(), t.clone().denom));
-                } else {
-                    total_balance.push(t.clone());
+        // Check if slot was supplied, otherwise get the next slots for block and time
+        if let Some((slot_type, id)) = slot {
+            match slot_type {
+                _ if !is_due(&slot_type, id) => {}
+                SlotType::Block => {
+                    block_hashes = self
+                        .block_slots
+                        .may_load(deps.storage, id)?
+                        .unwrap_or_default();
+                    if !block_hashes.is_empty() {
+                        block_id = id;
+                    }
+                }
+                SlotType::Cron => {
+                    time_hashes = self
+                        .time_slots
+                        .may_load(deps.storage, id)?
+                        .unwrap_or_default();
+                    if !time_hashes.is_empty() {
+                        time_id = id;
+                    }
                 }
             }
+        } else {
+            let time: Vec<(u64, _)> = self
+                .time_slots
+                .range(deps.storage, None, time_end, Order::Ascending)
+                .take(1)
+                .collect::<StdResult<Vec<(u64, _)>>>()?;
+
+            if !time.is_empty() {
+                // (time_id, time_hashes) = time[0].clone();
+                let slot = time[0].clone();
+                time_id = slot.0;
+                time_hashes = slot.1;
+            }
+
+            let block: Vec<(u64, _)> = self
+                .block_slots
+                .range(deps.storage, None, block_end, Order::Ascending)
+                .take(1)
+                .collect::<StdResult<Vec<(u64, _)>>>()?;
+
+            if !block.is_empty() {
+                // (block_id, block_hashes) = block[0].clone();
+                let slot = block[0].clone();
+                block_id = slot.0;
+                block_hashes = slot.1;
+            }
         }
-        task.total_deposit = total_balance;
 
-        // update the task
-        self.tasks.update(deps.storage, hash_vec, |old| match old {
-            Some(_) => Ok(task.clone()),
-            None => Err(ContractError::CustomError {
-                val: "Task doesnt exist".to_string(),
-            }),
-        })?;
+        // Generate strings for the requested page of hashes, in execution order
+        block_hashes.sort();
+        time_hashes.sort();
+        let from_index = from_index.unwrap_or_default() as usize;
+        let limit = limit.unwrap_or(100).min(1000) as usize;
+        let block_task_hash: Vec<_> = block_hashes
+            .iter()
+            .skip(from_index)
+            .take(limit)
+            .map(|b| String::from_utf8(b.to_vec()).unwrap_or_else(|_| "".to_string()))
+            .collect();
+        let time_task_hash: Vec<_> = time_hashes
+            .iter()
+            .skip(from_index)
+            .take(limit)
+            .map(|t| String::from_utf8(t.to_vec()).unwrap_or_else(|_| "".to_string()))
+            .collect();
 
-        // return the task total
-        let coins_total: String = task.total_deposit.iter().map(|a| a.to_string()).collect();
-        Ok(Response::new()
-            .add_attribute("method", "refill_task")
-            .add_attribute("total_deposit", coins_total))
+        Ok(GetSlotHashesResponse {
+            block_id,
+            block_task_hash,
+            time_id,
+            time_task_hash,
+        })
     }
-}
 
-#[cfg(test)]
-mod tests {
-    use super::*;
+    /// Flattens the task hashes of several slots, empty or missing slots add nothing
+    pub(crate) fn query_get_task_hashes(
+        &self,
+        deps: Deps,
+        slots: Vec<(SlotType, u64)>,
+    ) -> StdResult<Vec<String>> {
+        let mut task_hashes: Vec<String> = vec![];
+        for (slot_type, id) in slots {
+            let hashes = match slot_type {
+                SlotType::Block => self.block_slots.may_load(deps.storage, id)?,
+                SlotType::Cron => self.time_slots.may_load(deps.storage, id)?,
+            };
+            task_hashes.extend(
+                hashes
+                    .unwrap_or_default()
+                    .into_iter()
+                    .map(|h| String::from_utf8(h).unwrap_or_default()),
+            );
+        }
+        Ok(task_hashes)
+    }
 
-    use std::convert::TryInto;
-    // use cosmwasm_std::testing::MockStorage;
-    use cosmwasm_std::{
-        coin, coins, to_binary, Addr, BankMsg, CosmosMsg, Empty, StakingMsg, WasmMsg,
-    };
-    use cw_multi_test::{App, AppBuilder, Contract, ContractWrapper, Executor};
-    // use crate::error::ContractError;
-    use crate::helpers::CwTemplateContract;
-    use cw_croncat_core::msg::{ExecuteMsg, GetBalancesResponse, InstantiateMsg, QueryMsg};
-    use cw_croncat_core::types::{Action, Boundary, BoundarySpec};
+    /// Pages over the slots of one type, returning each slot id with its task hashes
+    /// At most GET_TASKS_MAX_LIMIT slots are returned per call
+    pub(crate) fn query_get_all_slots(
+        &self,
+        deps: Deps,
+        slot_type: SlotType,
+        from_index: Option<u64>,
+        limit: Option<u64>,
+    ) -> StdResult<Vec<(u64, Vec<String>)>> {
+        let slots = match slot_type {
+            SlotType::Block => &self.block_slots,
+            SlotType::Cron => &self.time_slots,
+        };
+        let limit = limit
+            .unwrap_or(GET_TASKS_MAX_LIMIT)
+            .min(GET_TASKS_MAX_LIMIT);
+        slots
+            .range(deps.storage, None, None, Order::Ascending)
+            .skip(from_index.unwrap_or_default() as usize)
+            .take(limit as usize)
+            .map(|res| {
+                res.map(|(id, hashes)| {
+                    let hashes = hashes
+                        .into_iter()
+                        .map(|h| String::from_utf8(h).unwrap_or_default())
+                        .collect();
+                    (id, hashes)
+                })
+            })
+            .collect()
+    }
 
-    pub fn contract_template() -> Box<dyn Contract<Empty>> {
-        let contract = ContractWrapper::new(
-            crate::entry::execute,
-            crate::entry::instantiate,
-            crate::entry::query,
-        );
-        Box::new(contract)
+    /// Total gas the actions of a slot's tasks may use, actions without a gas_limit count as 0
+    pub(crate) fn query_slot_gas(
+        &self,
+        deps: Deps,
+        slot_type: SlotType,
+        slot_id: u64,
+    ) -> StdResult<u64> {
+        let hashes = match slot_type {
+            SlotType::Block => self.block_slots.may_load(deps.storage, slot_id)?,
+            SlotType::Cron => self.time_slots.may_load(deps.storage, slot_id)?,
+        };
+        let mut gas: u64 = 0;
+        for hash in hashes.unwrap_or_default() {
+            if let Some(task) = self.tasks.may_load(deps.storage, hash)? {
+                gas = gas.saturating_add(task.to_gas_total());
+            }
+        }
+        Ok(gas)
     }
 
-    const ADMIN: &str = "cosmos1sjllsnramtg3ewxqwwrwjxfgc4n4ef9u0tvx7u";
-    const ANYONE: &str = "cosmos1t5u0jfg3ljsjrh2m9e47d4ny2hea7eehxrzdgd";
-    const VERY_RICH: &str = "cosmos1c3cy3wzzz3698ypklvh7shksvmefj69xhm89z2";
-    const NATIVE_DENOM: &str = "atom";
+    /// Gets list of active slot ids, for both time & block slots
+    /// (time, block)
+    pub(crate) fn query_slot_ids(&self, deps: Deps) -> StdResult<GetSlotIdsResponse> {
+        let time_ids: Vec<u64> = self
+            .time_slots
+            .keys(deps.storage, None, None, Order::Ascending)
+            .collect::<StdResult<Vec<_>>>()?;
+        let block_ids: Vec<u64> = self
+            .block_slots
+            .keys(deps.storage, None, None, Order::Ascending)
+            .collect::<StdResult<Vec<_>>>()?;
+        Ok(GetSlotIdsResponse {
+            time_ids,
+            block_ids,
+        })
+    }
 
-    fn mock_app() -> App {
-        AppBuilder::new().build(|router, _, storage| {
-            let accounts: Vec<(u128, String)> = vec![
-                (100, ADMIN.to_string()),
-                (100, ANYONE.to_string()),
-                (u128::max_value(), VERY_RICH.to_string()),
-            ];
-            for (amt, address) in accounts.iter() {
-                router
-                    .bank
-                    .init_balance(
-                        storage,
-                        &Addr::unchecked(address),
-                        vec![coin(amt.clone(), NATIVE_DENOM.to_string())],
-                    )
-                    .unwrap();
+    /// Summarizes the scheduling load, for both time & block slots
+    /// Only slot ids and hash counts are read, tasks are never loaded
+    pub(crate) fn query_slot_stats(&self, deps: Deps) -> StdResult<GetSlotStatsResponse> {
+        let mut num_task_hashes: u64 = 0;
+
+        let time_ids: Vec<u64> = self
+            .time_slots
+            .keys(deps.storage, None, None, Order::Ascending)
+            .collect::<StdResult<Vec<_>>>()?;
+        for id in time_ids.iter() {
+            let hashes = self.time_slots.load(deps.storage, *id)?;
+            num_task_hashes = num_task_hashes.saturating_add(hashes.len() as u64);
+        }
+
+        let block_ids: Vec<u64> = self
+            .block_slots
+            .keys(deps.storage, None, None, Order::Ascending)
+            .collect::<StdResult<Vec<_>>>()?;
+        for id in block_ids.iter() {
+            let hashes = self.block_slots.load(deps.storage, *id)?;
+            num_task_hashes = num_task_hashes.saturating_add(hashes.len() as u64);
+        }
+
+        Ok(GetSlotStatsResponse {
+            num_time_slots: time_ids.len() as u64,
+            num_block_slots: block_ids.len() as u64,
+            earliest_time_id: time_ids.first().copied(),
+            latest_time_id: time_ids.last().copied(),
+            earliest_block_id: block_ids.first().copied(),
+            latest_block_id: block_ids.last().copied(),
+            num_task_hashes,
+        })
+    }
+
+    /// Gets the furthest scheduled time & block slots, along with the current block
+    pub(crate) fn query_horizon(&self, deps: Deps, env: Env) -> StdResult<GetHorizonResponse> {
+        let max_time_id = self
+            .time_slots
+            .keys(deps.storage, None, None, Order::Descending)
+            .next()
+            .transpose()?;
+        let max_block_id = self
+            .block_slots
+            .keys(deps.storage, None, None, Order::Descending)
+            .next()
+            .transpose()?;
+        Ok(GetHorizonResponse {
+            max_time_id,
+            max_block_id,
+            block_height: env.block.height,
+            block_time: env.block.time,
+        })
+    }
+
+    /// Gets the task hashes of slots that are already past, i.e. missed by the agents
+    /// The slots of the current block height & time are still due, so they are left out
+    pub(crate) fn query_stuck_tasks(
+        &self,
+        deps: Deps,
+        env: Env,
+    ) -> StdResult<GetStuckTasksResponse> {
+        let stuck_hashes = |slots: &Map<u64, Vec<Vec<u8>>>, now: u64| -> StdResult<Vec<String>> {
+            let mut hashes: Vec<String> = vec![];
+            for slot in slots.range(
+                deps.storage,
+                None,
+                Some(Bound::exclusive(now)),
+                Order::Ascending,
+            ) {
+                let (_, slot_hashes) = slot?;
+                hashes.extend(
+                    slot_hashes
+                        .into_iter()
+                        .map(|h| String::from_utf8(h).unwrap_or_default()),
+                );
             }
+            Ok(hashes)
+        };
+        Ok(GetStuckTasksResponse {
+            time_task_hashes: stuck_hashes(&self.time_slots, env.block.time.nanos())?,
+            block_task_hashes: stuck_hashes(&self.block_slots, env.block.height)?,
         })
     }
 
-    fn proper_instantiate() -> (App, CwTemplateContract) {
-        let mut app = mock_app();
-        let cw_template_id = app.store_code(contract_template());
-        let owner_addr = Addr::unchecked(ADMIN);
+    /// Allows any user or contract to pay for future txns based on a specific schedule
+    /// contract, function id & other settings. When the task runs out of balance
+    /// the task is no longer executed, any additional funds will be returned to task owner.
+    pub fn create_task(
+        &self,
+        deps: DepsMut,
+        info: MessageInfo,
+        env: Env,
+        task: TaskRequest,
+    ) -> Result<Response, ContractError> {
+        let mut c: Config = self.config.load(deps.storage)?;
+        let owner_id = info.sender;
+        // Only zero coins attached is the same as no funds, rejected by check_task
+        let funds = non_zero_coins(&info.funds);
+        let mut item = Task {
+            owner_id: owner_id.clone(),
+            interval: task.interval,
+            boundary: task.boundary,
+            stop_on_fail: task.stop_on_fail,
+            allow_sponsored_refill: task.allow_sponsored_refill,
+            refund_split: task.refund_split,
+            preferred_agent: task.preferred_agent,
+            fail_mode: task.fail_mode,
+            agent_fee_override: task.agent_fee_override,
+            on_success_task: task.on_success_task,
+            label: task.label,
+            memo: task.memo,
+            return_surplus: task.return_surplus,
+            total_deposit: funds.clone(),
+            actions: task.actions,
+            rules: task.rules,
+            last_executed_slot: None,
+            executions_count: 0,
+            failure_streak: 0,
+            created_at_height: env.block.height,
+            created_at_time: env.block.time,
+        };
+        self.fill_default_gas(&c, &mut item.actions);
 
-        let msg = InstantiateMsg {
-            denom: "atom".to_string(),
-            owner_id: Some(owner_addr.clone()),
-            agent_nomination_duration: Some(360),
+        // Same checks as ValidateTask, the first failing one is returned
+        let (_, errors) = self.check_task(deps.as_ref(), &c, &env, &item, true)?;
+        if let Some(err) = errors.into_iter().next() {
+            return Err(err);
+        }
+        let duplicate_actions = item.has_duplicate_actions();
+
+        // TODO:
+        // // Check that balance is sufficient for 1 execution minimum
+        // let call_balance_used = self.task_balance_uses(&item);
+        // let min_balance_needed: u128 = if recurring == Some(true) {
+        //     call_balance_used * 2
+        // } else {
+        //     call_balance_used
+        // };
+        // assert!(
+        //     min_balance_needed <= item.total_deposit.0,
+        //     "Not enough task balance to execute job, need at least {}",
+        //     min_balance_needed
+        // );
+
+        let hash = item.to_hash();
+
+        // Parse interval into a future timestamp, then convert to a slot
+        let (next_id, slot_kind) = first_slot(&env, &item);
+
+        // Everything is loaded & validated before any state changes,
+        // so a failure can't leave a task without a slot or a stale task total
+        if self
+            .tasks
+            .may_load(deps.storage, item.to_hash_vec())?
+            .is_some()
+        {
+            return Err(ContractError::CustomError {
+                val: "Task already exists".to_string(),
+            });
+        }
+        let size = self.task_total(deps.storage)? + 1;
+
+        // Add the attached balance into available_balance
+        c.available_balance.add_tokens(Balance::from(funds));
+
+        // If the creation of this task means we'd like another agent, update config
+        let min_tasks_per_agent = c.min_tasks_per_agent;
+        let num_active_agents = self.agent_active_queue.load(deps.storage)?.len() as u64;
+        let num_agents_to_accept =
+            self.agents_to_let_in(&min_tasks_per_agent, &num_active_agents, &size);
+        // If we should allow a new agent to take over
+        // Don't wipe out an older timestamp
+        let begin_nomination = num_agents_to_accept != 0
+            && self
+                .agent_nomination_begin_time
+                .load(deps.storage)?
+                .is_none();
+
+        // Add task to catalog, then into block or cron slots
+        self.tasks.save(deps.storage, item.to_hash_vec(), &item)?;
+        self.task_total.save(deps.storage, &size)?;
+        self.schedule_task(deps.storage, &item, next_id, &slot_kind)?;
+        if begin_nomination {
+            self.agent_nomination_begin_time
+                .save(deps.storage, &Some(env.block.time))?;
+        }
+        self.config.save(deps.storage, &c)?;
+
+        let data = CreateTaskResponse {
+            task_hash: hash.clone(),
+            slot_id: next_id,
+            slot_kind: slot_kind.clone(),
         };
-        let cw_template_contract_addr = app
-            .instantiate_contract(cw_template_id, owner_addr, &msg, &[], "Manager", None)
-            .unwrap();
+        let mut response = Response::new()
+            .set_data(to_binary(&data)?)
+            .add_attribute("method", "create_task")
+            .add_attribute("slot_id", next_id.to_string())
+            .add_attribute("slot_kind", slot_kind.to_string())
+            .add_attribute("task_hash", hash.clone());
+        if let Some(memo) = item.memo.clone() {
+            response = response.add_attribute("memo", memo);
+        }
+        if duplicate_actions {
+            response = response.add_attribute("warning", "duplicate_actions");
+        }
+        Ok(response.add_event(balance_changed_event(
+            &hash,
+            &item.total_deposit,
+            &[],
+            &c.available_balance,
+        )))
+    }
+
+    /// The chained task must exist, be owned by `owner_id` when given, and following the chain from it must
+    /// never lead back to `task_hash`. Links past the first one may point to removed tasks, which just ends the chain
+    fn check_task_chain(
+        &self,
+        storage: &dyn Storage,
+        task_hash: &str,
+        owner_id: Option<&Addr>,
+        child: &str,
+    ) -> Result<(), ContractError> {
+        let mut next = self
+            .tasks
+            .may_load(storage, child.as_bytes().to_vec())?
+            .ok_or_else(|| ContractError::ChainedTaskNotFound {
+                task_hash: child.to_string(),
+            })?;
+        // Otherwise anyone could make someone else's task run, paid from its deposit
+        if matches!(owner_id, Some(owner_id) if &next.owner_id != owner_id) {
+            return Err(ContractError::Unauthorized {});
+        }
+        let mut visited = vec![task_hash.to_string(), child.to_string()];
+        while let Some(hash) = next.on_success_task {
+            if visited.contains(&hash) {
+                return Err(ContractError::TaskChainCycle {});
+            }
+            match self.tasks.may_load(storage, hash.as_bytes().to_vec())? {
+                Some(task) => next = task,
+                None => break,
+            }
+            visited.push(hash);
+        }
+        Ok(())
+    }
+
+    /// Removes a task on behalf of its owner, see `remove_task`
+    pub fn owner_remove_task(
+        &self,
+        deps: DepsMut,
+        info: MessageInfo,
+        task_hash: String,
+    ) -> Result<Response, ContractError> {
+        if let Some(task) = self
+            .tasks
+            .may_load(deps.storage, task_hash.clone().into_bytes())?
+        {
+            if task.owner_id != info.sender {
+                return Err(ContractError::Unauthorized {});
+            }
+        }
+        self.remove_task(deps, task_hash)
+    }
+
+    /// Removes several tasks on behalf of their owner, see `remove_task`
+    /// Fails without removing anything if any hash is unknown or owned by someone else,
+    /// refund sends are merged into one per recipient
+    pub fn owner_remove_tasks(
+        &self,
+        mut deps: DepsMut,
+        info: MessageInfo,
+        mut task_hashes: Vec<String>,
+    ) -> Result<Response, ContractError> {
+        let mut seen = BTreeSet::new();
+        task_hashes.retain(|hash| seen.insert(hash.clone()));
+        for task_hash in task_hashes.iter() {
+            match self
+                .tasks
+                .may_load(deps.storage, task_hash.clone().into_bytes())?
+            {
+                Some(task) if task.owner_id == info.sender => {}
+                Some(_) => return Err(ContractError::Unauthorized {}),
+                None => return Err(ContractError::NoTaskFound {}),
+            }
+        }
+
+        let mut response = Response::new().add_attribute("method", "remove_tasks");
+        let mut sends: Vec<(String, GenericBalance)> = vec![];
+        for task_hash in task_hashes {
+            let res = self.remove_task(deps.branch(), task_hash.clone())?;
+            response = response
+                .add_attribute("task_hash", task_hash)
+                .add_events(res.events);
+            for submsg in res.messages {
+                if let CosmosMsg::Bank(BankMsg::Send { to_address, amount }) = &submsg.msg {
+                    let balance = Balance::from(amount.clone());
+                    match sends.iter_mut().find(|(to, _)| to == to_address) {
+                        Some((_, sent)) => sent.add_tokens(balance),
+                        None => {
+                            let mut sent = GenericBalance::default();
+                            sent.add_tokens(balance);
+                            sends.push((to_address.clone(), sent));
+                        }
+                    }
+                } else {
+                    response = response.add_submessage(submsg);
+                }
+            }
+        }
+        Ok(
+            response.add_submessages(sends.into_iter().map(|(to_address, sent)| {
+                SubMsg::new(BankMsg::Send {
+                    to_address,
+                    amount: sent.native,
+                })
+            })),
+        )
+    }
+
+    /// Deletes a task in its entirety, returning any remaining balance to task owner.
+    pub fn remove_task(&self, deps: DepsMut, task_hash: String) -> Result<Response, ContractError> {
+        let hash_vec = task_hash.clone().into_bytes();
+        let task_raw = self.tasks.may_load(deps.storage, hash_vec.clone())?;
+        if task_raw.is_none() {
+            return Err(ContractError::CustomError {
+                val: "No task found by hash".to_string(),
+            });
+        }
+
+        // Remove all the thangs
+        self.tasks.remove(deps.storage, hash_vec)?;
+        self.decrement_tasks(deps.storage)?;
+
+        // find any scheduled things and remove them!
+        // check which type of slot it would be in, then iterate to remove
+        // NOTE: def could use some spiffy refactor here
+        let time_ids: Vec<u64> = self
+            .time_slots
+            .keys(deps.storage, None, None, Order::Ascending)
+            .collect::<StdResult<Vec<_>>>()?;
+
+        for tid in time_ids {
+            let mut time_hashes = self
+                .time_slots
+                .may_load(deps.storage, tid)?
+                .unwrap_or_default();
+            if !time_hashes.is_empty() {
+                time_hashes.retain(|h| String::from_utf8(h.to_vec()).unwrap() != task_hash.clone());
+            }
+
+            // save the updates, remove if slot no longer has hashes
+            if time_hashes.is_empty() {
+                self.time_slots.remove(deps.storage, tid);
+            } else {
+                self.time_slots.save(deps.storage, tid, &time_hashes)?;
+            }
+        }
+        let block_ids: Vec<u64> = self
+            .block_slots
+            .keys(deps.storage, None, None, Order::Ascending)
+            .collect::<StdResult<Vec<_>>>()?;
+
+        for bid in block_ids {
+            let mut block_hashes = self
+                .block_slots
+                .may_load(deps.storage, bid)?
+                .unwrap_or_default();
+            if !block_hashes.is_empty() {
+                block_hashes
+                    .retain(|h| String::from_utf8(h.to_vec()).unwrap() != task_hash.clone());
+            }
+
+            // save the updates, remove if slot no longer has hashes
+            if block_hashes.is_empty() {
+                self.block_slots.remove(deps.storage, bid);
+            } else {
+                self.block_slots.save(deps.storage, bid, &block_hashes)?;
+            }
+        }
+
+        // setup sub-msgs for returning any remaining total_deposit to the owner
+        let task = task_raw.unwrap();
+        let mut c: Config = self.config.load(deps.storage)?;
+        // one-shot tasks can opt out of the refund, sending what's left after their run to the treasury
+        let one_shot = task.interval.is_one_shot();
+        let treasury_id = match &c.treasury_id {
+            Some(treasury_id) if !c.refund_on_remove && one_shot && task.executions_count > 0 => {
+                Some(treasury_id)
+            }
+            _ => None,
+        };
+        // Non fee denom leftovers below the dust threshold aren't worth a send of their own
+        let (dust, refund): (Vec<Coin>, Vec<Coin>) = match treasury_id {
+            Some(_) => (vec![], task.total_deposit.clone()),
+            None => task
+                .total_deposit
+                .iter()
+                .cloned()
+                .partition(|d| d.denom != c.agent_fee.denom && d.amount < c.dust_threshold),
+        };
+        let refund_amount = refund
+            .iter()
+            .map(|c| c.to_string())
+            .collect::<Vec<_>>()
+            .join(",");
+        let mut submsgs = match (treasury_id, &task.refund_split) {
+            (Some(treasury_id), _) => vec![SubMsg::new(BankMsg::Send {
+                to_address: treasury_id.to_string(),
+                amount: task.clone().total_deposit,
+            })],
+            (None, Some(split)) => self.split_refund(&refund, split),
+            (None, None) if refund.is_empty() => vec![],
+            (None, None) => vec![SubMsg::new(BankMsg::Send {
+                to_address: task.clone().owner_id.into(),
+                amount: refund.clone(),
+            })],
+        };
+        // dust goes to the treasury in one send, without one it stays in available_balance as surplus
+        let mut leaving = refund;
+        match (&c.treasury_id, dust.is_empty()) {
+            (_, true) => {}
+            (Some(treasury_id), false) => {
+                submsgs.push(SubMsg::new(BankMsg::Send {
+                    to_address: treasury_id.to_string(),
+                    amount: dust.clone(),
+                }));
+                leaving.extend(dust.iter().cloned());
+            }
+            (None, false) => c.surplus_balance.add_tokens(Balance::from(dust.clone())),
+        }
+
+        // remove from the total available_balance
+        // if accounting drifted, stop at zero rather than failing the removal & report the difference
+        let shortfall = c
+            .available_balance
+            .saturating_minus_tokens(Balance::from(leaving.clone()));
+        self.config.save(deps.storage, &c)?;
+        let removed: Vec<Coin> = leaving
+            .iter()
+            .map(|d| {
+                let missing = shortfall
+                    .native
+                    .iter()
+                    .find(|s| s.denom == d.denom)
+                    .map(|s| s.amount)
+                    .unwrap_or_default();
+                Coin::new((d.amount - missing).u128(), d.denom.clone())
+            })
+            .filter(|d| !d.amount.is_zero())
+            .collect();
+
+        let mut response = Response::new()
+            .add_attribute("method", "remove_task")
+            .add_attribute("task_hash", task_hash.clone())
+            .add_event(balance_changed_event(
+                &task_hash,
+                &[],
+                &removed,
+                &c.available_balance,
+            ))
+            .add_attribute("owner_id", task.owner_id);
+        // a leftover sent to the treasury isn't a refund, so indexers can tell them apart
+        response = match treasury_id {
+            Some(_) => response.add_attribute("treasury_amount", refund_amount),
+            None => response.add_attribute("refund_amount", refund_amount),
+        };
+        if !dust.is_empty() {
+            response = response.add_attribute(
+                "dust_amount",
+                dust.iter()
+                    .map(|c| c.to_string())
+                    .collect::<Vec<_>>()
+                    .join(","),
+            );
+        }
+        if !shortfall.native.is_empty() {
+            response = response.add_attribute(
+                "balance_shortfall",
+                shortfall
+                    .native
+                    .iter()
+                    .map(|c| c.to_string())
+                    .collect::<Vec<_>>()
+                    .join(","),
+            );
+        }
+        Ok(response.add_submessages(submsgs))
+    }
+
+    /// Lets an owner reclaim the deposit of a task that will never run again,
+    /// either because its boundary has passed or it can't pay for another execution
+    pub fn claim_ended_task(
+        &self,
+        deps: DepsMut,
+        info: MessageInfo,
+        env: Env,
+        task_hash: String,
+    ) -> Result<Response, ContractError> {
+        let task = self
+            .tasks
+            .may_load(deps.storage, task_hash.clone().into_bytes())?
+            .ok_or(ContractError::NoTaskFound {})?;
+        if task.owner_id != info.sender {
+            return Err(ContractError::Unauthorized {});
+        }
+
+        let c: Config = self.config.load(deps.storage)?;
+        let ended = task.interval.next(env, task.boundary).0 == 0;
+        if !ended && self.can_pay_execution(&c, &task) {
+            return Err(ContractError::CustomError {
+                val: "Task can still be executed".to_string(),
+            });
+        }
+
+        Ok(self
+            .remove_task(deps, task_hash)?
+            .add_attribute("claimed_by", info.sender))
+    }
+
+    /// Gives actions without a gas_limit the configured default, before the task is hashed
+    fn fill_default_gas(&self, c: &Config, actions: &mut [Action]) {
+        for action in actions.iter_mut().filter(|a| a.gas_limit.is_none()) {
+            action.gas_limit = Some(c.default_gas_limit);
+        }
+    }
+
+    fn is_valid_action_gas(&self, c: &Config, actions: &[Action]) -> bool {
+        actions
+            .iter()
+            .all(|a| a.gas_limit.unwrap_or_default() <= c.max_action_gas)
+    }
+
+    /// Rules must be evaluable by agents: a valid contract to query with a json object msg
+    fn validate_rules(&self, api: &dyn Api, rules: &[Rule]) -> Result<(), ContractError> {
+        for rule in rules {
+            api.addr_validate(rule.contract_addr.as_str())
+                .map_err(|_| ContractError::InvalidRules {
+                    reason: format!("invalid contract address {}", rule.contract_addr),
+                })?;
+            let len: JsonObjectLen =
+                from_slice(&rule.msg).map_err(|_| ContractError::InvalidRules {
+                    reason: "query msg must be a json object".to_string(),
+                })?;
+            if len.0 == 0 {
+                return Err(ContractError::InvalidRules {
+                    reason: "query msg is empty".to_string(),
+                });
+            }
+        }
+        Ok(())
+    }
+
+    /// Replaces the gas_limit of every action, so gas can follow target contract changes
+    /// Gas is part of the task hash, so the task, its slots & the tasks chaining it are re-keyed
+    pub fn update_task_gas(
+        &self,
+        deps: DepsMut,
+        info: MessageInfo,
+        task_hash: String,
+        gas_limits: Vec<Option<u64>>,
+    ) -> Result<Response, ContractError> {
+        let hash_vec = task_hash.clone().into_bytes();
+        let mut task: Task = self
+            .tasks
+            .may_load(deps.storage, hash_vec.clone())?
+            .ok_or(ContractError::NoTaskFound {})?;
+        if task.owner_id != info.sender {
+            return Err(ContractError::Unauthorized {});
+        }
+        if gas_limits.len() != task.actions.len() {
+            return Err(ContractError::CustomError {
+                val: "Must provide a gas limit for every action".to_string(),
+            });
+        }
+        for (action, gas_limit) in task.actions.iter_mut().zip(gas_limits) {
+            action.gas_limit = gas_limit;
+        }
+        let c: Config = self.config.load(deps.storage)?;
+        self.fill_default_gas(&c, &mut task.actions);
+        if !self.is_valid_action_gas(&c, &task.actions) {
+            return Err(ContractError::CustomError {
+                val: "Action gas limit exceeds max_action_gas".to_string(),
+            });
+        }
+
+        let new_hash = task.to_hash();
+        self.rekey_task(deps.storage, hash_vec, &task)?;
+
+        Ok(Response::new()
+            .add_attribute("method", "update_task_gas")
+            .add_attribute("previous_task_hash", task_hash)
+            .add_attribute("task_hash", new_hash))
+    }
+
+    /// Tasks chaining the given one on success
+    /// A chain only links tasks of one owner, so only that owner's tasks are read
+    fn chained_parents(
+        &self,
+        storage: &dyn Storage,
+        owner_id: &Addr,
+        task_hash: &[u8],
+    ) -> StdResult<Vec<Task>> {
+        self.tasks
+            .idx
+            .owner
+            .prefix(owner_id.clone())
+            .range(storage, None, None, Order::Ascending)
+            .map(|item| item.map(|(_, task)| task))
+            .filter(|item| {
+                matches!(item, Ok(task)
+                    if task.on_success_task.as_deref().map(str::as_bytes) == Some(task_hash))
+            })
+            .collect()
+    }
+
+    /// Saves a task whose hashed fields changed, moving it and its scheduled slots to the new hash
+    /// Tasks chaining it on success are pointed to the new hash too
+    fn rekey_task(
+        &self,
+        storage: &mut dyn Storage,
+        hash_vec: Vec<u8>,
+        task: &Task,
+    ) -> Result<(), ContractError> {
+        let new_hash_vec = task.to_hash_vec();
+        if new_hash_vec != hash_vec {
+            if self
+                .tasks
+                .may_load(storage, new_hash_vec.clone())?
+                .is_some()
+            {
+                return Err(ContractError::CustomError {
+                    val: "Task already exists".to_string(),
+                });
+            }
+            let previous = self.tasks.load(storage, hash_vec.clone())?;
+            let parents = self.chained_parents(storage, &previous.owner_id, &hash_vec)?;
+            self.tasks.remove(storage, hash_vec.clone())?;
+
+            let new_hash = task.to_hash();
+            for mut parent in parents {
+                parent.on_success_task = Some(new_hash.clone());
+                self.tasks.save(storage, parent.to_hash_vec(), &parent)?;
+            }
+
+            // point any scheduled slots to the new hash
+            for slots in [&self.time_slots, &self.block_slots] {
+                let slot_ids: Vec<u64> = slots
+                    .keys(storage, None, None, Order::Ascending)
+                    .collect::<StdResult<Vec<_>>>()?;
+                for id in slot_ids {
+                    let mut hashes = slots.load(storage, id)?;
+                    if hashes.contains(&hash_vec) {
+                        for h in hashes.iter_mut().filter(|h| **h == hash_vec) {
+                            *h = new_hash_vec.clone();
+                        }
+                        hashes.sort();
+                        slots.save(storage, id, &hashes)?;
+                    }
+                }
+            }
+        }
+        self.tasks.save(storage, new_hash_vec, task)?;
+        Ok(())
+    }
+
+    /// Hands a task, and with it the refill & remove rights, to a new owner
+    /// The owner is part of the task hash, so the task and its slots are re-keyed
+    pub fn transfer_task_ownership(
+        &self,
+        deps: DepsMut,
+        info: MessageInfo,
+        task_hash: String,
+        new_owner: Addr,
+    ) -> Result<Response, ContractError> {
+        let hash_vec = task_hash.clone().into_bytes();
+        let mut task: Task = self
+            .tasks
+            .may_load(deps.storage, hash_vec.clone())?
+            .ok_or(ContractError::NoTaskFound {})?;
+        if task.owner_id != info.sender {
+            return Err(ContractError::Unauthorized {});
+        }
+        let new_owner = deps.api.addr_validate(new_owner.as_str())?;
+        // Chains only link tasks of one owner, after a transfer one owner's task would run the other's
+        if task.on_success_task.is_some()
+            || !self
+                .chained_parents(deps.storage, &task.owner_id, &hash_vec)?
+                .is_empty()
+        {
+            return Err(ContractError::CustomError {
+                val: "Chained tasks can't change owner".to_string(),
+            });
+        }
+
+        let c: Config = self.config.load(deps.storage)?;
+        if let Some(max) = c.max_tasks_per_owner {
+            if self.query_get_owner_task_count(deps.as_ref(), new_owner.clone())? >= max {
+                return Err(ContractError::MaxTasksPerOwner { max });
+            }
+        }
+
+        task.owner_id = new_owner.clone();
+        let new_hash = task.to_hash();
+        self.rekey_task(deps.storage, hash_vec, &task)?;
+
+        Ok(Response::new()
+            .add_attribute("method", "transfer_task_ownership")
+            .add_attribute("previous_task_hash", task_hash)
+            .add_attribute("task_hash", new_hash)
+            .add_attribute("previous_owner_id", info.sender)
+            .add_attribute("owner_id", new_owner))
+    }
+
+    /// Sends each recipient their share of the refund
+    /// Rounding leftovers go to the last recipient, so the whole deposit is returned
+    fn split_refund(&self, deposit: &[Coin], split: &[(Addr, Decimal)]) -> Vec<SubMsg> {
+        let mut remaining: Vec<Coin> = deposit.to_vec();
+        let mut submsgs: Vec<SubMsg> = vec![];
+        for (i, (recipient, share)) in split.iter().enumerate() {
+            let amount: Vec<Coin> = if i == split.len() - 1 {
+                remaining.clone()
+            } else {
+                deposit
+                    .iter()
+                    .zip(remaining.iter_mut())
+                    .map(|(d, r)| {
+                        let part = d.amount * *share;
+                        r.amount -= part;
+                        Coin::new(part.u128(), d.denom.clone())
+                    })
+                    .collect()
+            };
+            let amount: Vec<Coin> = amount.into_iter().filter(|c| !c.amount.is_zero()).collect();
+            if !amount.is_empty() {
+                submsgs.push(SubMsg::new(BankMsg::Send {
+                    to_address: recipient.to_string(),
+                    amount,
+                }));
+            }
+        }
+        submsgs
+    }
+
+    /// Refill a task with more balance to continue its execution
+    /// NOTE: Restricting this to owner only, so owner can make sure the task ends
+    /// unless the owner opted in to sponsored refills
+    /// Only denoms already in the deposit, or the fee denom, are accepted unless `allow_new_denoms`
+    pub fn refill_task(
+        &self,
+        deps: DepsMut,
+        info: MessageInfo,
+        task_hash: String,
+        allow_new_denoms: bool,
+    ) -> Result<Response, ContractError> {
+        let hash_vec = task_hash.clone().into_bytes();
+        let task_raw = self.tasks.may_load(deps.storage, hash_vec.clone())?;
+        if task_raw.is_none() {
+            return Err(ContractError::CustomError {
+                val: "Task doesnt exist".to_string(),
+            });
+        }
+        let mut task: Task = task_raw.unwrap();
+        // Sponsored tasks can be kept alive by anyone, refunds still go to the owner
+        if task.owner_id != info.sender && !task.allow_sponsored_refill {
+            return Err(ContractError::CustomError {
+                val: "Only owner can refill their task".to_string(),
+            });
+        }
+
+        let funds = non_zero_coins(&info.funds);
+        if funds.is_empty() {
+            return Err(ContractError::CustomError {
+                val: "Must attach funds".to_string(),
+            });
+        }
+
+        // Deposits only accumulate under the real denom, aliases would be tallied apart
+        let mut c: Config = self.config.load(deps.storage)?;
+        if let Some(denom) = find_denom_alias(&funds, &c.native_denom) {
+            return Err(ContractError::DenomAlias {
+                denom,
+                native_denom: c.native_denom,
+            });
+        }
+
+        // A denom no action or fee uses would just sit in the task until it's removed
+        if !allow_new_denoms {
+            if let Some(new) = funds.iter().find(|f| {
+                f.denom != c.agent_fee.denom
+                    && !task.total_deposit.iter().any(|d| d.denom == f.denom)
+            }) {
+                return Err(ContractError::RefillNewDenom {
+                    denom: new.denom.clone(),
+                });
+            }
+        }
+
+        // Tiny refills only churn the accounting, so the owner can set a floor
+        // It's in the fee denom, refills of only other denoms (e.g. for actions) aren't limited
+        let fee_refill = funds.iter().find(|f| f.denom == c.agent_fee.denom);
+        if let (Some(min), Some(refill)) = (c.min_refill_amount, fee_refill) {
+            if refill.amount < min {
+                return Err(ContractError::RefillBelowMinimum {
+                    min: coin(min.u128(), c.agent_fee.denom),
+                });
+            }
+        }
+
+        // Add the attached balance into available_balance
+        c.available_balance.add_tokens(Balance::from(funds.clone()));
+        self.config.save(deps.storage, &c)?;
+
+        for f in funds.iter() {
+            match task.total_deposit.iter_mut().find(|d| d.denom == f.denom) {
+                Some(deposit) => deposit.amount = deposit.amount.saturating_add(f.amount),
+                None => task.total_deposit.push(f.clone()),
+            }
+        }
+
+        // update the task
+        self.tasks.update(deps.storage, hash_vec, |old| match old {
+            Some(_) => Ok(task.clone()),
+            None => Err(ContractError::CustomError {
+                val: "Task doesnt exist".to_string(),
+            }),
+        })?;
+
+        // return the task total
+        let coins_total: String = task.total_deposit.iter().map(|a| a.to_string()).collect();
+        Ok(Response::new()
+            .add_attribute("method", "refill_task")
+            .add_attribute("total_deposit", coins_total)
+            .add_event(balance_changed_event(
+                &task_hash,
+                &funds,
+                &[],
+                &c.available_balance,
+            )))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use crate::state::default_action_gas_limit;
+    use std::convert::TryInto;
+    // use cosmwasm_std::testing::MockStorage;
+    use cosmwasm_std::testing::{mock_dependencies, mock_env, mock_info};
+    use cosmwasm_std::{
+        coin, coins, from_binary, from_slice, to_binary, Addr, Attribute, BankMsg, CosmosMsg,
+        Empty, IbcMsg, IbcTimeout, StakingMsg, Timestamp, WasmMsg,
+    };
+    use cw_multi_test::{
+        App, AppBuilder, AppResponse, BankSudo, Contract, ContractWrapper, Executor, SudoMsg,
+    };
+    // use crate::error::ContractError;
+    use crate::helpers::test_helpers::{
+        create_task_hash, mock_init, settings_msg, task_hash_attr, task_request, SettingsUpdate,
+        ANYONE, NATIVE_DENOM,
+    };
+    use crate::helpers::CwTemplateContract;
+    use cw_croncat_core::msg::{
+        ExecuteMsg, GetBalancesResponse, GetConfigResponse, GetTaskCostResponse, InstantiateMsg,
+        QueryMsg,
+    };
+    use cw_croncat_core::types::{
+        Action, Boundary, BoundaryHorizon, BoundarySpec, FailMode, MsgKind, Rule,
+    };
+    use schemars::JsonSchema;
+    use serde::{Deserialize, Serialize};
+
+    pub fn contract_template() -> Box<dyn Contract<Empty>> {
+        let contract = ContractWrapper::new(
+            crate::entry::execute,
+            crate::entry::instantiate,
+            crate::entry::query,
+        );
+        Box::new(contract)
+    }
+
+    const ADMIN: &str = "cosmos1sjllsnramtg3ewxqwwrwjxfgc4n4ef9u0tvx7u";
+    const VERY_RICH: &str = "cosmos1c3cy3wzzz3698ypklvh7shksvmefj69xhm89z2";
+
+    fn mock_app() -> App {
+        AppBuilder::new().build(|router, _, storage| {
+            let accounts: Vec<(u128, String)> = vec![
+                (100, ADMIN.to_string()),
+                (100, ANYONE.to_string()),
+                (u128::max_value(), VERY_RICH.to_string()),
+            ];
+            for (amt, address) in accounts.iter() {
+                router
+                    .bank
+                    .init_balance(
+                        storage,
+                        &Addr::unchecked(address),
+                        vec![coin(amt.clone(), NATIVE_DENOM.to_string())],
+                    )
+                    .unwrap();
+            }
+        })
+    }
+
+    fn proper_instantiate() -> (App, CwTemplateContract) {
+        let mut app = mock_app();
+        let cw_template_id = app.store_code(contract_template());
+        let owner_addr = Addr::unchecked(ADMIN);
+
+        let msg = InstantiateMsg {
+            denom: "atom".to_string(),
+            owner_id: Some(owner_addr.clone()),
+            agent_nomination_duration: Some(360),
+        };
+        let cw_template_contract_addr = app
+            .instantiate_contract(cw_template_id, owner_addr, &msg, &[], "Manager", None)
+            .unwrap();
+
+        let cw_template_contract = CwTemplateContract(cw_template_contract_addr);
+
+        (app, cw_template_contract)
+    }
+
+    #[test]
+    fn query_task_hash_success() {
+        let (app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+
+        let to_address = String::from("you");
+        let amount = coins(1015, "earth");
+        let bank = BankMsg::Send { to_address, amount };
+        let msg: CosmosMsg = bank.clone().into();
+
+        let task = Task {
+            owner_id: Addr::unchecked("nobody".to_string()),
+            interval: Interval::Immediate,
+            boundary: Boundary {
+                start: None,
+                end: None,
+            },
+            stop_on_fail: false,
+            allow_sponsored_refill: false,
+            refund_split: None,
+            preferred_agent: None,
+            fail_mode: FailMode::StopOnFirstFail,
+            agent_fee_override: None,
+            on_success_task: None,
+            label: None,
+            memo: None,
+            return_surplus: false,
+            total_deposit: coins(37, "atom"),
+            actions: vec![Action {
+                msg,
+                gas_limit: Some(150_000),
+            }],
+            rules: None,
+            last_executed_slot: None,
+            executions_count: 0,
+            failure_streak: 0,
+            created_at_height: 0,
+            created_at_time: Default::default(),
+        };
+
+        // HASH CHECK!
+        let task_hash: String = app
+            .wrap()
+            .query_wasm_smart(
+                &contract_addr.clone(),
+                &QueryMsg::GetTaskHash {
+                    task: Box::new(task),
+                },
+            )
+            .unwrap();
+        assert_eq!(
+            "3ccb739ea050ebbd2e08f74aeb0b7aa081b15fa78504cba44155ec774452bbee",
+            task_hash
+        );
+    }
+
+    #[test]
+    fn query_validate_interval_success() {
+        let (app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+
+        let intervals: Vec<Interval> = vec![
+            Interval::Once,
+            Interval::Immediate,
+            Interval::Block(1),
+            Interval::Block(12345),
+            Interval::Cron("0 0 * * * *".to_string()),
+        ];
+        for i in intervals.iter() {
+            let valid: bool = app
+                .wrap()
+                .query_wasm_smart(
+                    &contract_addr.clone(),
+                    &QueryMsg::ValidateInterval {
+                        interval: i.to_owned(),
+                    },
+                )
+                .unwrap();
+            assert!(valid);
+        }
+
+        // 5 field crons have no seconds field
+        let valid: bool = app
+            .wrap()
+            .query_wasm_smart(
+                contract_addr.clone(),
+                &QueryMsg::ValidateInterval {
+                    interval: Interval::Cron("0 * * * *".to_string()),
+                },
+            )
+            .unwrap();
+        assert!(valid);
+
+        // 7 fields aren't supported, the reason is returned
+        let err = app
+            .wrap()
+            .query_wasm_smart::<bool>(
+                contract_addr.clone(),
+                &QueryMsg::ValidateInterval {
+                    interval: Interval::Cron("0 0 * * * * 2030".to_string()),
+                },
+            )
+            .unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("Cron must have 5 or 6 fields, found 7"));
+
+        // every second is parsable, but too frequent for agents
+        let valid: bool = app
+            .wrap()
+            .query_wasm_smart(
+                contract_addr.clone(),
+                &QueryMsg::ValidateInterval {
+                    interval: Interval::Cron("* * * * * *".to_string()),
+                },
+            )
+            .unwrap();
+        assert!(!valid);
+
+        // every zero blocks would reschedule into the same slot forever
+        let valid: bool = app
+            .wrap()
+            .query_wasm_smart(
+                contract_addr.clone(),
+                &QueryMsg::ValidateInterval {
+                    interval: Interval::Block(0),
+                },
+            )
+            .unwrap();
+        assert!(!valid);
+    }
+
+    #[test]
+    fn query_validate_task_checks() {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+
+        let stake: CosmosMsg = StakingMsg::Delegate {
+            validator: String::from("you"),
+            amount: coin(3, "atom"),
+        }
+        .into();
+        let new_request = |interval: Interval, boundary: Boundary, msg: CosmosMsg| TaskRequest {
+            interval,
+            boundary,
+            actions: vec![Action {
+                msg,
+                gas_limit: Some(150_000),
+            }],
+            ..task_request()
+        };
+        let no_boundary = Boundary {
+            start: None,
+            end: None,
+        };
+        let validate = |app: &App, task: TaskRequest, funds: Vec<Coin>| -> ValidateTaskResponse {
+            app.wrap()
+                .query_wasm_smart(
+                    &contract_addr,
+                    &QueryMsg::ValidateTask {
+                        task: Box::new(task),
+                        funds,
+                        owner_id: None,
+                    },
+                )
+                .unwrap()
+        };
+
+        // Valid task passes every check
+        let res = validate(
+            &app,
+            new_request(Interval::Immediate, no_boundary, stake.clone()),
+            coins(13, "atom"),
+        );
+        assert_eq!(
+            ValidateTaskResponse {
+                is_valid: true,
+                funds_attached: true,
+                not_paused: true,
+                supported_actions: true,
+                valid_interval: true,
+                not_ended: true,
+                valid_refund_split: true,
+                funded_actions: true,
+                errors: vec![],
+            },
+            res
+        );
+
+        // Must attach funds
+        let res = validate(
+            &app,
+            new_request(Interval::Immediate, no_boundary, stake.clone()),
+            vec![],
+        );
+        assert!(!res.is_valid);
+        assert!(!res.funds_attached);
+        assert_eq!(vec!["Must attach funds".to_string()], res.errors);
+
+        // Actions Message Unsupported
+        let self_call = CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: contract_addr.to_string(),
+            funds: vec![],
+            msg: to_binary(&ExecuteMsg::WithdrawReward {}).unwrap(),
+        });
+        let res = validate(
+            &app,
+            new_request(Interval::Immediate, no_boundary, self_call),
+            coins(13, "atom"),
+        );
+        assert!(!res.is_valid);
+        assert!(!res.supported_actions);
+        assert_eq!(vec!["Actions Message Unsupported".to_string()], res.errors);
+
+        // Interval invalid
+        let res = validate(
+            &app,
+            new_request(
+                Interval::Cron("faux_paw".to_string()),
+                no_boundary,
+                stake.clone(),
+            ),
+            coins(13, "atom"),
+        );
+        assert!(!res.is_valid);
+        assert!(!res.valid_interval);
+        assert_eq!(vec!["Interval invalid".to_string()], res.errors);
+
+        // Task ended
+        let res = validate(
+            &app,
+            new_request(
+                Interval::Block(12346),
+                Boundary {
+                    start: None,
+                    end: Some(BoundarySpec::Height(1)),
+                },
+                stake.clone(),
+            ),
+            coins(13, "atom"),
+        );
+        assert!(!res.is_valid);
+        assert!(!res.not_ended);
+        assert_eq!(
+            vec![ContractError::BoundaryInPast {}.to_string()],
+            res.errors
+        );
+
+        // Create task paused, reported alongside any other failures
+        let change_settings_msg = settings_msg(SettingsUpdate {
+            paused: Some(true),
+            ..Default::default()
+        });
+        app.execute_contract(
+            Addr::unchecked(ADMIN),
+            contract_addr.clone(),
+            &change_settings_msg,
+            &[],
+        )
+        .unwrap();
+        let res = validate(
+            &app,
+            new_request(Interval::Immediate, no_boundary, stake),
+            vec![],
+        );
+        assert!(!res.is_valid);
+        assert!(!res.not_paused);
+        assert_eq!(
+            vec![
+                "Must attach funds".to_string(),
+                "Create task paused".to_string()
+            ],
+            res.errors
+        );
+
+        // Nothing was written while validating
+        let tasks: Vec<TaskResponse> = app
+            .wrap()
+            .query_wasm_smart(
+                &contract_addr,
+                &QueryMsg::GetTasks {
+                    from_index: None,
+                    limit: None,
+                    order_by: None,
+                },
+            )
+            .unwrap();
+        assert!(tasks.is_empty());
+    }
+
+    #[test]
+    fn query_get_tasks() {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+
+        let validator = String::from("you");
+        let amount = coin(3, "atom");
+        let stake = StakingMsg::Delegate { validator, amount };
+        let msg: CosmosMsg = stake.clone().into();
+
+        let create_task_msg = ExecuteMsg::CreateTask {
+            task: TaskRequest {
+                actions: vec![Action {
+                    msg,
+                    gas_limit: Some(150_000),
+                }],
+                ..task_request()
+            },
+        };
+
+        // create a task
+        app.execute_contract(
+            Addr::unchecked(ANYONE),
+            contract_addr.clone(),
+            &create_task_msg,
+            &coins(37, "atom"),
+        )
+        .unwrap();
+
+        // check storage has the task
+        let all_tasks: Vec<TaskResponse> = app
+            .wrap()
+            .query_wasm_smart(
+                &contract_addr.clone(),
+                &QueryMsg::GetTasks {
+                    from_index: None,
+                    limit: None,
+                    order_by: None,
+                },
+            )
+            .unwrap();
+        assert_eq!(all_tasks.len(), 1);
+
+        let owner_tasks: Vec<TaskResponse> = app
+            .wrap()
+            .query_wasm_smart(
+                &contract_addr.clone(),
+                &QueryMsg::GetTasksByOwner {
+                    owner_id: Addr::unchecked(ANYONE),
+                },
+            )
+            .unwrap();
+        assert_eq!(owner_tasks.len(), 1);
+    }
+
+    #[test]
+    fn query_slot_gas() -> StdResult<()> {
+        let mut deps = mock_dependencies();
+        let store = CwCroncat::default();
+        mock_init(&store, deps.as_mut()).unwrap();
+
+        // Immediate tasks all land in the current block slot
+        let new_request = |gas_limits: Vec<Option<u64>>| TaskRequest {
+            actions: gas_limits
+                .into_iter()
+                .enumerate()
+                .map(|(i, gas_limit)| Action {
+                    msg: StakingMsg::Delegate {
+                        validator: "you".to_string(),
+                        amount: coin(i as u128 + 1, NATIVE_DENOM),
+                    }
+                    .into(),
+                    gas_limit,
+                })
+                .collect(),
+            ..task_request()
+        };
+        for gas_limits in [
+            vec![Some(150_000)],
+            vec![Some(100_000), Some(50_000)],
+            vec![Some(75_000), None, Some(25_000)],
+        ] {
+            store
+                .create_task(
+                    deps.as_mut(),
+                    mock_info(ANYONE, &coins(37, NATIVE_DENOM)),
+                    mock_env(),
+                    new_request(gas_limits),
+                )
+                .unwrap();
+        }
+
+        // the action without a gas_limit got the default
+        let height = mock_env().block.height;
+        assert_eq!(
+            400_000 + default_action_gas_limit(),
+            store.query_slot_gas(deps.as_ref(), SlotType::Block, height)?
+        );
+        // empty or other kind of slot
+        assert_eq!(
+            0,
+            store.query_slot_gas(deps.as_ref(), SlotType::Block, height + 1)?
+        );
+        assert_eq!(
+            0,
+            store.query_slot_gas(deps.as_ref(), SlotType::Cron, height)?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn check_default_gas_limit() {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+
+        let new_request = |stake: u128| TaskRequest {
+            actions: vec![
+                Action {
+                    msg: StakingMsg::Delegate {
+                        validator: "you".to_string(),
+                        amount: coin(stake, NATIVE_DENOM),
+                    }
+                    .into(),
+                    gas_limit: None,
+                },
+                Action {
+                    msg: StakingMsg::Delegate {
+                        validator: "me".to_string(),
+                        amount: coin(stake, NATIVE_DENOM),
+                    }
+                    .into(),
+                    gas_limit: Some(50_000),
+                },
+            ],
+            ..task_request()
+        };
+        let gas_limits = |app: &App, task_hash: String| -> Vec<Option<u64>> {
+            let task: Option<TaskResponse> = app
+                .wrap()
+                .query_wasm_smart(&contract_addr, &QueryMsg::GetTask { task_hash })
+                .unwrap();
+            task.unwrap()
+                .actions
+                .into_iter()
+                .map(|a| a.gas_limit)
+                .collect()
+        };
+
+        // only the missing gas_limit is filled in
+        let task_hash = create_task_hash(&mut app, &contract_addr, new_request(1));
+        assert_eq!(
+            vec![Some(default_action_gas_limit()), Some(50_000)],
+            gas_limits(&app, task_hash)
+        );
+
+        let update_default = |app: &mut App, default_gas_limit: u64| {
+            app.execute_contract(
+                Addr::unchecked(ADMIN),
+                contract_addr.clone(),
+                &settings_msg(SettingsUpdate {
+                    default_gas_limit: Some(default_gas_limit),
+                    ..Default::default()
+                }),
+                &[],
+            )
+        };
+
+        // the default can't be above max_action_gas
+        let res_err = update_default(&mut app, 3_000_001).unwrap_err();
+        assert_eq!(
+            ContractError::CustomError {
+                val: "Default gas limit exceeds max_action_gas".to_string()
+            },
+            res_err.downcast().unwrap()
+        );
+
+        update_default(&mut app, 200_000).unwrap();
+        let task_hash = create_task_hash(&mut app, &contract_addr, new_request(2));
+        assert_eq!(
+            vec![Some(200_000), Some(50_000)],
+            gas_limits(&app, task_hash)
+        );
+    }
+
+    #[test]
+    fn query_get_all_slots() {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+
+        let new_request = |stake: u128| TaskRequest {
+            actions: vec![Action {
+                msg: StakingMsg::Delegate {
+                    validator: "you".to_string(),
+                    amount: coin(stake, NATIVE_DENOM),
+                }
+                .into(),
+                gas_limit: Some(150_000),
+            }],
+            ..task_request()
+        };
+
+        // Immediate tasks land in the block slot they're created at, two share the first one
+        let height = app.block_info().height;
+        let mut expected: Vec<(u64, Vec<String>)> = vec![];
+        for (stake, slot_id) in [(1, height), (2, height), (3, height + 1), (4, height + 2)] {
+            app.update_block(|block| block.height = slot_id);
+            let task_hash = create_task_hash(&mut app, &contract_addr, new_request(stake));
+            match expected.last_mut() {
+                Some((id, hashes)) if *id == slot_id => hashes.push(task_hash),
+                _ => expected.push((slot_id, vec![task_hash])),
+            }
+        }
+        // within a slot hashes are kept in ascending order
+        for (_, hashes) in expected.iter_mut() {
+            hashes.sort();
+        }
+
+        let all_slots = |slot_type: SlotType, from_index: Option<u64>, limit: Option<u64>| {
+            let slots: Vec<(u64, Vec<String>)> = app
+                .wrap()
+                .query_wasm_smart(
+                    &contract_addr,
+                    &QueryMsg::GetAllSlots {
+                        slot_type,
+                        from_index,
+                        limit,
+                    },
+                )
+                .unwrap();
+            slots
+        };
+        assert_eq!(expected, all_slots(SlotType::Block, None, None));
+
+        // pages follow slot order
+        assert_eq!(expected[..2], all_slots(SlotType::Block, None, Some(2))[..]);
+        assert_eq!(
+            expected[2..],
+            all_slots(SlotType::Block, Some(2), Some(2))[..]
+        );
+
+        // only the requested kind of slot is read
+        assert!(all_slots(SlotType::Cron, None, None).is_empty());
+    }
+
+    #[test]
+    fn query_get_task_hashes() -> StdResult<()> {
+        let mut deps = mock_dependencies();
+        let store = CwCroncat::default();
+
+        let hashes = |names: &[&str]| -> Vec<Vec<u8>> {
+            names.iter().map(|n| n.as_bytes().to_vec()).collect()
+        };
+        store
+            .block_slots
+            .save(deps.as_mut().storage, 10, &hashes(&["a", "b"]))?;
+        store
+            .block_slots
+            .save(deps.as_mut().storage, 11, &hashes(&["c"]))?;
+        store
+            .time_slots
+            .save(deps.as_mut().storage, 10, &hashes(&["d", "e"]))?;
+
+        let task_hashes = store.query_get_task_hashes(
+            deps.as_ref(),
+            vec![
+                (SlotType::Block, 10),
+                (SlotType::Cron, 10),
+                (SlotType::Block, 11),
+                // missing slots add nothing
+                (SlotType::Cron, 11),
+            ],
+        )?;
+        assert_eq!(vec!["a", "b", "d", "e", "c"], task_hashes);
+
+        Ok(())
+    }
+
+    #[test]
+    fn query_get_tasks_pages_past_limit() -> StdResult<()> {
+        let mut deps = mock_dependencies();
+        let store = CwCroncat::default();
+        mock_init(&store, deps.as_mut()).unwrap();
+
+        let total = GET_TASKS_MAX_LIMIT + 5;
+        for i in 0..total {
+            store
+                .create_task(
+                    deps.as_mut(),
+                    mock_info(ANYONE, &coins(37, NATIVE_DENOM)),
+                    mock_env(),
+                    TaskRequest {
+                        actions: vec![Action {
+                            msg: StakingMsg::Delegate {
+                                validator: "you".to_string(),
+                                amount: coin(u128::from(i) + 1, NATIVE_DENOM),
+                            }
+                            .into(),
+                            gas_limit: Some(150_000),
+                        }],
+                        ..task_request()
+                    },
+                )
+                .unwrap();
+        }
+
+        // the per-call limit is capped
+        let first_page =
+            store.query_get_tasks(deps.as_ref(), mock_env(), None, Some(total), None)?;
+        assert_eq!(GET_TASKS_MAX_LIMIT as usize, first_page.len());
+
+        // but the rest can still be paged to
+        let last_page = store.query_get_tasks(
+            deps.as_ref(),
+            mock_env(),
+            Some(GET_TASKS_MAX_LIMIT),
+            None,
+            None,
+        )?;
+        assert_eq!(5, last_page.len());
+        assert!(last_page
+            .iter()
+            .all(|t| !first_page.iter().any(|f| f.task_hash == t.task_hash)));
+
+        let past_end = store.query_get_tasks(deps.as_ref(), mock_env(), Some(total), None, None)?;
+        assert!(past_end.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn query_get_tasks_from_cursor() -> StdResult<()> {
+        let mut deps = mock_dependencies();
+        let store = CwCroncat::default();
+        mock_init(&store, deps.as_mut()).unwrap();
+
+        for i in 0..12u128 {
+            store
+                .create_task(
+                    deps.as_mut(),
+                    mock_info(ANYONE, &coins(37, NATIVE_DENOM)),
+                    mock_env(),
+                    TaskRequest {
+                        actions: vec![Action {
+                            msg: StakingMsg::Delegate {
+                                validator: "you".to_string(),
+                                amount: coin(i + 1, NATIVE_DENOM),
+                            }
+                            .into(),
+                            gas_limit: Some(150_000),
+                        }],
+                        ..task_request()
+                    },
+                )
+                .unwrap();
+        }
+
+        // pages by cursor match the pages by index
+        let mut start_after = None;
+        for from_index in (0..12).step_by(5) {
+            let by_cursor =
+                store.query_get_tasks_from(deps.as_ref(), mock_env(), start_after, Some(5))?;
+            let by_index = store.query_get_tasks(
+                deps.as_ref(),
+                mock_env(),
+                Some(from_index),
+                Some(5),
+                None,
+            )?;
+            assert_eq!(by_index, by_cursor);
+            start_after = by_cursor.last().map(|t| t.task_hash.clone());
+        }
+        assert!(store
+            .query_get_tasks_from(deps.as_ref(), mock_env(), start_after, None)?
+            .is_empty());
+
+        // the cursor seeks by hash, so it still works once that task is gone
+        let all = store.query_get_tasks_from(deps.as_ref(), mock_env(), None, None)?;
+        assert_eq!(12, all.len());
+        store
+            .remove_task(deps.as_mut(), all[4].task_hash.clone())
+            .unwrap();
+        let page = store.query_get_tasks_from(
+            deps.as_ref(),
+            mock_env(),
+            Some(all[4].task_hash.clone()),
+            Some(2),
+        )?;
+        assert_eq!(all[5..7].to_vec(), page);
+
+        Ok(())
+    }
+
+    #[test]
+    fn query_get_tasks_page_cursor() -> StdResult<()> {
+        let mut deps = mock_dependencies();
+        let store = CwCroncat::default();
+        mock_init(&store, deps.as_mut()).unwrap();
+
+        // no tasks, nothing to follow
+        let empty = store.query_get_tasks_page(deps.as_ref(), mock_env(), None, None)?;
+        assert!(empty.tasks.is_empty());
+        assert_eq!(None, empty.next);
+
+        for i in 0..10u128 {
+            store
+                .create_task(
+                    deps.as_mut(),
+                    mock_info(ANYONE, &coins(37, NATIVE_DENOM)),
+                    mock_env(),
+                    TaskRequest {
+                        actions: vec![Action {
+                            msg: StakingMsg::Delegate {
+                                validator: "you".to_string(),
+                                amount: coin(i + 1, NATIVE_DENOM),
+                            }
+                            .into(),
+                            gas_limit: Some(150_000),
+                        }],
+                        ..task_request()
+                    },
+                )
+                .unwrap();
+        }
+        let all = store.query_get_tasks_from(deps.as_ref(), mock_env(), None, None)?;
+
+        // walk every task by following next
+        let mut walked: Vec<TaskResponse> = vec![];
+        let mut page_sizes = vec![];
+        let mut start_after = None;
+        loop {
+            let page =
+                store.query_get_tasks_page(deps.as_ref(), mock_env(), start_after, Some(4))?;
+            page_sizes.push(page.tasks.len());
+            walked.extend(page.tasks);
+            match page.next {
+                Some(next) => start_after = Some(next),
+                None => break,
+            }
+        }
+        assert_eq!(vec![4, 4, 2], page_sizes);
+        assert_eq!(all, walked);
+
+        // a page ending exactly on the last task has no next
+        let page = store.query_get_tasks_page(deps.as_ref(), mock_env(), None, Some(10))?;
+        assert_eq!(10, page.tasks.len());
+        assert_eq!(None, page.next);
+        let page = store.query_get_tasks_page(deps.as_ref(), mock_env(), None, Some(9))?;
+        assert_eq!(Some(all[8].task_hash.clone()), page.next);
+
+        Ok(())
+    }
+
+    #[test]
+    fn query_get_tasks_by_boundary_end() -> StdResult<()> {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+
+        let block = app.block_info();
+        let height = block.height;
+        let time = block.time;
+        let ends = [
+            Some(BoundarySpec::Height(height + 50)),
+            Some(BoundarySpec::Height(height + 100)),
+            Some(BoundarySpec::Height(height + 1000)),
+            Some(BoundarySpec::Time(time.plus_seconds(100))),
+            Some(BoundarySpec::Time(time.plus_seconds(1000))),
+            None,
+        ];
+        let mut task_hashes = vec![];
+        for end in ends {
+            // block intervals can't have a time end
+            let interval = match end {
+                Some(BoundarySpec::Time(_)) => Interval::Cron("0 * * * * *".to_string()),
+                _ => Interval::Immediate,
+            };
+            task_hashes.push(create_task_hash(
+                &mut app,
+                &contract_addr,
+                TaskRequest {
+                    interval,
+                    boundary: Boundary { start: None, end },
+                    ..task_request()
+                },
+            ));
+        }
+        let ending_within = |start, end| -> StdResult<Vec<String>> {
+            let tasks: Vec<TaskResponse> = app.wrap().query_wasm_smart(
+                &contract_addr,
+                &QueryMsg::GetTasksByBoundaryEnd {
+                    start,
+                    end,
+                    from_index: None,
+                    limit: None,
+                },
+            )?;
+            let mut hashes: Vec<String> = tasks.into_iter().map(|t| t.task_hash).collect();
+            hashes.sort();
+            Ok(hashes)
+        };
+        let expected = |idxs: &[usize]| {
+            let mut hashes: Vec<String> = idxs.iter().map(|i| task_hashes[*i].clone()).collect();
+            hashes.sort();
+            hashes
+        };
+
+        // window is inclusive, heights only match height ends
+        assert_eq!(
+            expected(&[0, 1]),
+            ending_within(
+                BoundarySpec::Height(height + 50),
+                BoundarySpec::Height(height + 100)
+            )?
+        );
+        assert_eq!(
+            expected(&[0, 1, 2]),
+            ending_within(BoundarySpec::Height(0), BoundarySpec::Height(u64::MAX))?
+        );
+        // and times only time ends
+        assert_eq!(
+            expected(&[3]),
+            ending_within(
+                BoundarySpec::Time(time),
+                BoundarySpec::Time(time.plus_seconds(500))
+            )?
+        );
+        assert_eq!(
+            expected(&[]),
+            ending_within(
+                BoundarySpec::Time(time.plus_seconds(101)),
+                BoundarySpec::Time(time.plus_seconds(999))
+            )?
+        );
+
+        // can't mix both kinds
+        let err =
+            ending_within(BoundarySpec::Height(height), BoundarySpec::Time(time)).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("Window start & end must both be heights or times"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn query_get_tasks_by_hash_prefix() -> StdResult<()> {
+        let mut deps = mock_dependencies();
+        let store = CwCroncat::default();
+        mock_init(&store, deps.as_mut()).unwrap();
+
+        let new_request = |amount: u128| TaskRequest {
+            actions: vec![Action {
+                msg: StakingMsg::Delegate {
+                    validator: "you".to_string(),
+                    amount: coin(amount, NATIVE_DENOM),
+                }
+                .into(),
+                gas_limit: Some(150_000),
+            }],
+            ..task_request()
+        };
+        let task_hash = |amount: u128| {
+            let request = new_request(amount);
+            Task {
+                owner_id: Addr::unchecked(ANYONE),
+                interval: request.interval,
+                boundary: request.boundary,
+                stop_on_fail: request.stop_on_fail,
+                allow_sponsored_refill: request.allow_sponsored_refill,
+                refund_split: request.refund_split,
+                preferred_agent: request.preferred_agent,
+                fail_mode: request.fail_mode,
+                agent_fee_override: request.agent_fee_override,
+                on_success_task: request.on_success_task.clone(),
+                label: request.label.clone(),
+                memo: request.memo.clone(),
+                return_surplus: request.return_surplus,
+                total_deposit: vec![],
+                actions: request.actions,
+                rules: request.rules,
+                last_executed_slot: None,
+                executions_count: 0,
+                failure_streak: 0,
+                created_at_height: 0,
+                created_at_time: Timestamp::default(),
+            }
+            .to_hash()
+        };
+
+        // find two tasks sharing the shortest accepted prefix
+        let mut seen: std::collections::HashMap<String, u128> = Default::default();
+        let (first, second) = (1..)
+            .find_map(|amount| {
+                let prefix = task_hash(amount)[..TASK_HASH_PREFIX_MIN_LEN].to_string();
+                seen.insert(prefix, amount).map(|first| (first, amount))
+            })
+            .unwrap();
+        let other = (1..)
+            .find(|amount| {
+                task_hash(*amount)[..TASK_HASH_PREFIX_MIN_LEN]
+                    != task_hash(first)[..TASK_HASH_PREFIX_MIN_LEN]
+            })
+            .unwrap();
+        for amount in [first, second, other] {
+            store
+                .create_task(
+                    deps.as_mut(),
+                    mock_info(ANYONE, &coins(37, NATIVE_DENOM)),
+                    mock_env(),
+                    new_request(amount),
+                )
+                .unwrap();
+        }
+
+        // a longer prefix finds the unique task
+        let other_hash = task_hash(other);
+        let tasks = store.query_get_tasks_by_hash_prefix(
+            deps.as_ref(),
+            mock_env(),
+            other_hash[..8].to_string(),
+            None,
+        )?;
+        assert_eq!(
+            vec![other_hash.clone()],
+            tasks.into_iter().map(|t| t.task_hash).collect::<Vec<_>>()
+        );
+
+        // the shared one is ambiguous, limit still applies
+        let shared_prefix = task_hash(first)[..TASK_HASH_PREFIX_MIN_LEN].to_string();
+        let mut expected = vec![task_hash(first), task_hash(second)];
+        expected.sort();
+        let tasks = store.query_get_tasks_by_hash_prefix(
+            deps.as_ref(),
+            mock_env(),
+            shared_prefix.clone(),
+            None,
+        )?;
+        assert_eq!(
+            expected,
+            tasks.into_iter().map(|t| t.task_hash).collect::<Vec<_>>()
+        );
+        let tasks = store.query_get_tasks_by_hash_prefix(
+            deps.as_ref(),
+            mock_env(),
+            shared_prefix,
+            Some(1),
+        )?;
+        assert_eq!(1, tasks.len());
+
+        // too short to be useful
+        let err = store
+            .query_get_tasks_by_hash_prefix(
+                deps.as_ref(),
+                mock_env(),
+                other_hash[..3].to_string(),
+                None,
+            )
+            .unwrap_err();
+        assert_eq!(
+            StdError::generic_err("Hash prefix must be at least 4 characters"),
+            err
+        );
+
+        Ok(())
+    }
+
+    #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+    #[serde(rename_all = "snake_case")]
+    enum RulesQueryMsg {
+        Check { pass: bool },
+    }
+
+    // A rules contract that answers with whatever result it's asked for
+    fn rules_template() -> Box<dyn Contract<Empty>> {
+        let contract = ContractWrapper::new(
+            |_deps, _env, _info, _msg: Empty| -> StdResult<Response> { Ok(Response::new()) },
+            |_deps, _env, _info, _msg: Empty| -> StdResult<Response> { Ok(Response::new()) },
+            |_deps, _env, msg: RulesQueryMsg| -> StdResult<Binary> {
+                match msg {
+                    RulesQueryMsg::Check { pass } => to_binary(&(pass, None::<Binary>)),
+                }
+            },
+        );
+        Box::new(contract)
+    }
+
+    #[test]
+    fn query_check_task_rules() {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+        let rules_code_id = app.store_code(rules_template());
+        let rules_addr = app
+            .instantiate_contract(
+                rules_code_id,
+                Addr::unchecked(ADMIN),
+                &Empty {},
+                &[],
+                "rules",
+                None,
+            )
+            .unwrap();
+
+        let rule = |pass: bool| Rule {
+            contract_addr: rules_addr.clone(),
+            msg: to_binary(&RulesQueryMsg::Check { pass }).unwrap(),
+        };
+        let cases = vec![
+            (vec![rule(true), rule(true)], true, vec![true, true]),
+            (vec![rule(true), rule(false)], false, vec![true, false]),
+            // rules that can't be queried count as false
+            (
+                vec![Rule {
+                    contract_addr: Addr::unchecked("no_contract"),
+                    msg: to_binary(&RulesQueryMsg::Check { pass: true }).unwrap(),
+                }],
+                false,
+                vec![false],
+            ),
+        ];
+        for (i, (rules, success, results)) in cases.into_iter().enumerate() {
+            let res = app
+                .execute_contract(
+                    Addr::unchecked(ANYONE),
+                    contract_addr.clone(),
+                    &ExecuteMsg::CreateTask {
+                        task: TaskRequest {
+                            actions: vec![Action {
+                                msg: StakingMsg::Delegate {
+                                    validator: "you".to_string(),
+                                    amount: coin(i as u128 + 1, "atom"),
+                                }
+                                .into(),
+                                gas_limit: Some(150_000),
+                            }],
+                            rules: Some(rules),
+                            ..task_request()
+                        },
+                    },
+                    &coins(10, "atom"),
+                )
+                .unwrap();
+            let task_hash = task_hash_attr(&res);
+
+            let check: Option<CheckTaskRulesResponse> = app
+                .wrap()
+                .query_wasm_smart(
+                    contract_addr.clone(),
+                    &QueryMsg::CheckTaskRules { task_hash },
+                )
+                .unwrap();
+            assert_eq!(
+                Some(CheckTaskRulesResponse {
+                    success,
+                    rules: results,
+                }),
+                check
+            );
+        }
+    }
+
+    #[test]
+    fn query_get_tasks_with_rules() {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+
+        // 2 plain tasks and 3 rule-gated ones
+        for i in 1..=5u128 {
+            let rules = if i > 2 {
+                Some(vec![Rule {
+                    contract_addr: Addr::unchecked("rules_contract"),
+                    msg: Binary::from(br#"{"check":{}}"#.to_vec()),
+                }])
+            } else {
+                None
+            };
+            let create_task_msg = ExecuteMsg::CreateTask {
+                task: TaskRequest {
+                    actions: vec![Action {
+                        msg: StakingMsg::Delegate {
+                            validator: "you".to_string(),
+                            amount: coin(i, "atom"),
+                        }
+                        .into(),
+                        gas_limit: Some(150_000),
+                    }],
+                    rules,
+                    ..task_request()
+                },
+            };
+            app.execute_contract(
+                Addr::unchecked(ANYONE),
+                contract_addr.clone(),
+                &create_task_msg,
+                &coins(10, "atom"),
+            )
+            .unwrap();
+        }
+
+        let gated_tasks: Vec<TaskResponse> = app
+            .wrap()
+            .query_wasm_smart(
+                contract_addr.clone(),
+                &QueryMsg::GetTasksWithRules {
+                    from_index: None,
+                    limit: None,
+                },
+            )
+            .unwrap();
+        assert_eq!(gated_tasks.len(), 3);
+        assert!(gated_tasks.iter().all(|t| t.rules.is_some()));
+
+        // pagination only counts gated tasks
+        let page: Vec<TaskResponse> = app
+            .wrap()
+            .query_wasm_smart(
+                contract_addr.clone(),
+                &QueryMsg::GetTasksWithRules {
+                    from_index: Some(1),
+                    limit: Some(5),
+                },
+            )
+            .unwrap();
+        assert_eq!(page, gated_tasks[1..].to_vec());
+    }
+
+    #[test]
+    fn query_get_owner_task_count() {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+
+        let create_task_msg = |stake: u128| ExecuteMsg::CreateTask {
+            task: TaskRequest {
+                actions: vec![Action {
+                    msg: StakingMsg::Delegate {
+                        validator: String::from("you"),
+                        amount: coin(stake, "atom"),
+                    }
+                    .into(),
+                    gas_limit: Some(150_000),
+                }],
+                ..task_request()
+            },
+        };
+        let owner_task_count = |app: &App, owner: &str| -> u64 {
+            app.wrap()
+                .query_wasm_smart(
+                    contract_addr.clone(),
+                    &QueryMsg::GetOwnerTaskCount {
+                        owner_id: Addr::unchecked(owner),
+                    },
+                )
+                .unwrap()
+        };
+        assert_eq!(0, owner_task_count(&app, ANYONE));
+
+        let mut task_hashes = vec![];
+        for stake in 1..=2 {
+            let res = app
+                .execute_contract(
+                    Addr::unchecked(ANYONE),
+                    contract_addr.clone(),
+                    &create_task_msg(stake),
+                    &coins(10, "atom"),
+                )
+                .unwrap();
+            let task_hash = task_hash_attr(&res);
+            task_hashes.push(task_hash);
+        }
+        // other owners don't count
+        app.execute_contract(
+            Addr::unchecked(ADMIN),
+            contract_addr.clone(),
+            &create_task_msg(3),
+            &coins(10, "atom"),
+        )
+        .unwrap();
+        assert_eq!(2, owner_task_count(&app, ANYONE));
+        assert_eq!(1, owner_task_count(&app, ADMIN));
+        let task_count: u64 = app
+            .wrap()
+            .query_wasm_smart(contract_addr.clone(), &QueryMsg::GetTaskCount {})
+            .unwrap();
+        assert_eq!(3, task_count);
+
+        app.execute_contract(
+            Addr::unchecked(ANYONE),
+            contract_addr.clone(),
+            &ExecuteMsg::RemoveTask {
+                task_hash: task_hashes[0].clone(),
+            },
+            &[],
+        )
+        .unwrap();
+        assert_eq!(1, owner_task_count(&app, ANYONE));
+
+        // total tracks removals too
+        let task_count: u64 = app
+            .wrap()
+            .query_wasm_smart(contract_addr.clone(), &QueryMsg::GetTaskCount {})
+            .unwrap();
+        assert_eq!(2, task_count);
+    }
+
+    #[test]
+    fn query_get_task_cost() {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+
+        let actions = vec![
+            Action {
+                msg: StakingMsg::Delegate {
+                    validator: String::from("you"),
+                    amount: coin(1, "atom"),
+                }
+                .into(),
+                gas_limit: Some(150_000),
+            },
+            Action {
+                msg: StakingMsg::Delegate {
+                    validator: String::from("me"),
+                    amount: coin(2, "atom"),
+                }
+                .into(),
+                gas_limit: Some(50_000),
+            },
+        ];
+        let res = app
+            .execute_contract(
+                Addr::unchecked(ANYONE),
+                contract_addr.clone(),
+                &ExecuteMsg::CreateTask {
+                    task: TaskRequest {
+                        actions: actions.clone(),
+                        ..task_request()
+                    },
+                },
+                &coins(10, "atom"),
+            )
+            .unwrap();
+        let task_hash = task_hash_attr(&res);
+
+        let config: GetConfigResponse = app
+            .wrap()
+            .query_wasm_smart(contract_addr.clone(), &QueryMsg::GetConfig {})
+            .unwrap();
+        let gas_total = actions.iter().map(|a| a.gas_limit.unwrap()).sum::<u64>()
+            + config.proxy_callback_gas as u64;
+        let gas_fee = gas_total as u128 * config.gas_price as u128;
+
+        let cost: Option<GetTaskCostResponse> = app
+            .wrap()
+            .query_wasm_smart(contract_addr.clone(), &QueryMsg::GetTaskCost { task_hash })
+            .unwrap();
+        assert_eq!(
+            Some(GetTaskCostResponse {
+                gas_total,
+                gas_fee: coin(gas_fee, "atom"),
+                agent_fee: config.agent_fee.clone(),
+                total_cost: coin(gas_fee + config.agent_fee.amount.u128(), "atom"),
+                actions_cost: coins(3, "atom"),
+            }),
+            cost
+        );
+
+        // unknown task
+        let cost: Option<GetTaskCostResponse> = app
+            .wrap()
+            .query_wasm_smart(
+                contract_addr.clone(),
+                &QueryMsg::GetTaskCost {
+                    task_hash: "missing".to_string(),
+                },
+            )
+            .unwrap();
+        assert_eq!(None, cost);
+    }
+
+    #[test]
+    fn query_fee_schedule() -> StdResult<()> {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+
+        let assert_matches_config = |app: &App| -> StdResult<GetFeeScheduleResponse> {
+            let config: GetConfigResponse = app
+                .wrap()
+                .query_wasm_smart(contract_addr.clone(), &QueryMsg::GetConfig {})?;
+            let schedule: GetFeeScheduleResponse = app
+                .wrap()
+                .query_wasm_smart(contract_addr.clone(), &QueryMsg::GetFeeSchedule {})?;
+            assert_eq!(
+                GetFeeScheduleResponse {
+                    gas_price: config.gas_price,
+                    agent_fee: config.agent_fee.clone(),
+                    proxy_callback_gas: config.proxy_callback_gas,
+                    default_gas_limit: config.default_gas_limit,
+                    formula: CostFormula {
+                        denom: config.agent_fee.denom,
+                        extra_gas: config.proxy_callback_gas as u64,
+                        gas_multiplier: config.gas_price,
+                        flat_fee: config.agent_fee.amount,
+                    },
+                },
+                schedule
+            );
+            Ok(schedule)
+        };
+        assert_matches_config(&app)?;
+
+        app.execute_contract(
+            Addr::unchecked(ADMIN),
+            contract_addr.clone(),
+            &settings_msg(SettingsUpdate {
+                agent_fee: Some(coin(7, NATIVE_DENOM)),
+                gas_price: Some(3),
+                proxy_callback_gas: Some(5),
+                ..Default::default()
+            }),
+            &[],
+        )
+        .unwrap();
+        let schedule = assert_matches_config(&app)?;
+        assert_eq!(3, schedule.formula.gas_multiplier);
+        assert_eq!(5, schedule.formula.extra_gas);
+        assert_eq!(Uint128::new(7), schedule.formula.flat_fee);
+
+        Ok(())
+    }
+
+    #[test]
+    fn check_task_create_allowed_msg_kinds() -> StdResult<()> {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+
+        let kinds_msg = |allowed_msg_kinds: Vec<MsgKind>| {
+            settings_msg(SettingsUpdate {
+                allowed_msg_kinds: Some(allowed_msg_kinds),
+                ..Default::default()
+            })
+        };
+        let create_task_msg = ExecuteMsg::CreateTask {
+            task: TaskRequest {
+                actions: vec![Action {
+                    msg: StakingMsg::Delegate {
+                        validator: String::from("you"),
+                        amount: coin(3, "atom"),
+                    }
+                    .into(),
+                    gas_limit: Some(150_000),
+                }],
+                ..task_request()
+            },
+        };
+
+        // can't allow nothing
+        let res_err = app
+            .execute_contract(
+                Addr::unchecked(ADMIN),
+                contract_addr.clone(),
+                &kinds_msg(vec![]),
+                &[],
+            )
+            .unwrap_err();
+        assert_eq!(
+            ContractError::CustomError {
+                val: "Must allow at least one message kind".to_string()
+            },
+            res_err.downcast().unwrap()
+        );
+
+        // a bank only deployment rejects staking tasks
+        app.execute_contract(
+            Addr::unchecked(ADMIN),
+            contract_addr.clone(),
+            &kinds_msg(vec![MsgKind::Bank]),
+            &[],
+        )
+        .unwrap();
+        let config: GetConfigResponse = app
+            .wrap()
+            .query_wasm_smart(&contract_addr, &QueryMsg::GetConfig {})?;
+        assert_eq!(vec![MsgKind::Bank], config.allowed_msg_kinds);
+        let res_err = app
+            .execute_contract(
+                Addr::unchecked(ANYONE),
+                contract_addr.clone(),
+                &create_task_msg,
+                &coins(37, "atom"),
+            )
+            .unwrap_err();
+        assert_eq!(
+            ContractError::CustomError {
+                val: "Actions Message Kind Not Allowed".to_string()
+            },
+            res_err.downcast().unwrap()
+        );
+
+        // allowing staking again accepts it
+        app.execute_contract(
+            Addr::unchecked(ADMIN),
+            contract_addr.clone(),
+            &kinds_msg(vec![MsgKind::Bank, MsgKind::Staking]),
+            &[],
+        )
+        .unwrap();
+        app.execute_contract(
+            Addr::unchecked(ANYONE),
+            contract_addr.clone(),
+            &create_task_msg,
+            &coins(37, "atom"),
+        )
+        .unwrap();
+
+        Ok(())
+    }
+
+    #[test]
+    fn check_task_create_max_per_owner() {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+
+        let create_task_msg = |stake: u128| ExecuteMsg::CreateTask {
+            task: TaskRequest {
+                actions: vec![Action {
+                    msg: StakingMsg::Delegate {
+                        validator: String::from("you"),
+                        amount: coin(stake, "atom"),
+                    }
+                    .into(),
+                    gas_limit: Some(150_000),
+                }],
+                ..task_request()
+            },
+        };
+        app.execute_contract(
+            Addr::unchecked(ADMIN),
+            contract_addr.clone(),
+            &settings_msg(SettingsUpdate {
+                max_tasks_per_owner: Some(Some(2)),
+                ..Default::default()
+            }),
+            &[],
+        )
+        .unwrap();
+        let config: GetConfigResponse = app
+            .wrap()
+            .query_wasm_smart(contract_addr.clone(), &QueryMsg::GetConfig {})
+            .unwrap();
+        assert_eq!(Some(2), config.max_tasks_per_owner);
+
+        // up to the limit
+        for stake in 1..=2 {
+            app.execute_contract(
+                Addr::unchecked(ANYONE),
+                contract_addr.clone(),
+                &create_task_msg(stake),
+                &coins(10, "atom"),
+            )
+            .unwrap();
+        }
+
+        // past the limit
+        let res_err = app
+            .execute_contract(
+                Addr::unchecked(ANYONE),
+                contract_addr.clone(),
+                &create_task_msg(3),
+                &coins(10, "atom"),
+            )
+            .unwrap_err();
+        assert_eq!(
+            ContractError::MaxTasksPerOwner { max: 2 },
+            res_err.downcast().unwrap()
+        );
+
+        // limit is per owner
+        app.execute_contract(
+            Addr::unchecked(ADMIN),
+            contract_addr.clone(),
+            &create_task_msg(3),
+            &coins(10, "atom"),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn check_task_create_max_per_slot() {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+
+        let create_task_msg = |stake: u128| ExecuteMsg::CreateTask {
+            task: TaskRequest {
+                actions: vec![Action {
+                    msg: StakingMsg::Delegate {
+                        validator: String::from("you"),
+                        amount: coin(stake, "atom"),
+                    }
+                    .into(),
+                    gas_limit: Some(150_000),
+                }],
+                ..task_request()
+            },
+        };
+        let slot_cap_msg = |max_tasks_per_slot: u64| {
+            settings_msg(SettingsUpdate {
+                max_tasks_per_slot: Some(Some(max_tasks_per_slot)),
+                ..Default::default()
+            })
+        };
+
+        // an empty slot can't hold anything
+        let res_err = app
+            .execute_contract(
+                Addr::unchecked(ADMIN),
+                contract_addr.clone(),
+                &slot_cap_msg(0),
+                &[],
+            )
+            .unwrap_err();
+        assert_eq!(
+            ContractError::CustomError {
+                val: "Max tasks per slot must be at least 1".to_string()
+            },
+            res_err.downcast().unwrap()
+        );
+        app.execute_contract(
+            Addr::unchecked(ADMIN),
+            contract_addr.clone(),
+            &slot_cap_msg(2),
+            &[],
+        )
+        .unwrap();
+        let config: GetConfigResponse = app
+            .wrap()
+            .query_wasm_smart(contract_addr.clone(), &QueryMsg::GetConfig {})
+            .unwrap();
+        assert_eq!(Some(2), config.max_tasks_per_slot);
+
+        // fill the current block slot
+        let slot_id = app.block_info().height;
+        for stake in 1..=2 {
+            app.execute_contract(
+                Addr::unchecked(ANYONE),
+                contract_addr.clone(),
+                &create_task_msg(stake),
+                &coins(10, "atom"),
+            )
+            .unwrap();
+        }
+
+        // a full slot rejects the task
+        let res_err = app
+            .execute_contract(
+                Addr::unchecked(ANYONE),
+                contract_addr.clone(),
+                &create_task_msg(3),
+                &coins(10, "atom"),
+            )
+            .unwrap_err();
+        assert_eq!(
+            ContractError::MaxTasksPerSlot { slot_id, max: 2 },
+            res_err.downcast().unwrap()
+        );
+
+        // the following slot still has room
+        app.update_block(|block| block.height += 1);
+        let res = app
+            .execute_contract(
+                Addr::unchecked(ANYONE),
+                contract_addr.clone(),
+                &create_task_msg(3),
+                &coins(10, "atom"),
+            )
+            .unwrap();
+        assert!(res.events.iter().any(|e| e
+            .attributes
+            .iter()
+            .any(|a| a.key == "slot_id" && a.value == (slot_id + 1).to_string())));
+    }
+
+    #[test]
+    fn query_get_tasks_pagination() {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+
+        let validator = String::from("you");
+        let tasks_amnt: u64 = 10;
+        let from_index = 3;
+        let limit = 2;
+        let new_msg = |amount| ExecuteMsg::CreateTask {
+            task: TaskRequest {
+                actions: vec![Action {
+                    msg: StakingMsg::Delegate {
+                        validator: validator.clone(),
+                        amount: coin(amount, "atom"),
+                    }
+                    .into(),
+                    gas_limit: Some(150_000),
+                }],
+                ..task_request()
+            },
+        };
+
+        // create a tasks
+        for amount in 1..tasks_amnt as u128 + 1 {
+            app.execute_contract(
+                Addr::unchecked(VERY_RICH),
+                contract_addr.clone(),
+                &new_msg(amount),
+                &coins(37, "atom"),
+            )
+            .unwrap();
+        }
+        let mut all_tasks: Vec<TaskResponse> = app
+            .wrap()
+            .query_wasm_smart(
+                &contract_addr.clone(),
+                &QueryMsg::GetTasks {
+                    from_index: None,
+                    limit: None,
+                    order_by: None,
+                },
+            )
+            .unwrap();
+        assert_eq!(all_tasks.len(), tasks_amnt as usize);
+
+        // check we get right amount of tasks
+        let part_of_tasks: Vec<TaskResponse> = app
+            .wrap()
+            .query_wasm_smart(
+                &contract_addr.clone(),
+                &QueryMsg::GetTasks {
+                    from_index: Some(from_index),
+                    limit: None,
+                    order_by: None,
+                },
+            )
+            .unwrap();
+        let expected_amnt: usize = (tasks_amnt - from_index).try_into().unwrap();
+        assert_eq!(part_of_tasks.len(), expected_amnt);
+
+        println!(
+            "half_tasks: {:?}\n hash_vec:{:?}",
+            part_of_tasks
+                .iter()
+                .map(|t| t.task_hash.clone())
+                .collect::<Vec<String>>(),
+            all_tasks
+                .iter()
+                .map(|t| t.task_hash.clone())
+                .collect::<Vec<String>>(),
+        );
+
+        // Check it's in right order
+        for i in 0..expected_amnt {
+            assert_eq!(
+                all_tasks[from_index as usize + i].task_hash,
+                part_of_tasks[i].task_hash
+            );
+        }
+
+        // and with limit
+        let part_of_tasks: Vec<TaskResponse> = app
+            .wrap()
+            .query_wasm_smart(
+                &contract_addr.clone(),
+                &QueryMsg::GetTasks {
+                    from_index: Some(from_index),
+                    limit: Some(limit),
+                    order_by: None,
+                },
+            )
+            .unwrap();
+        let expected_amnt: usize = (limit).try_into().unwrap();
+        assert_eq!(part_of_tasks.len(), expected_amnt);
+
+        // Edge cases
+
+        // Index out of bounds, so we return nothing
+        let from_index = tasks_amnt;
+        let out_of_bounds: Vec<TaskResponse> = app
+            .wrap()
+            .query_wasm_smart(
+                &contract_addr.clone(),
+                &QueryMsg::GetTasks {
+                    from_index: Some(from_index),
+                    limit: None,
+                    order_by: None,
+                },
+            )
+            .unwrap();
+        assert!(out_of_bounds.is_empty());
+
+        // Returns as many elements as possible without a panic
+        let from_index = tasks_amnt - 2;
+        let two_last_elements: Vec<TaskResponse> = app
+            .wrap()
+            .query_wasm_smart(
+                &contract_addr.clone(),
+                &QueryMsg::GetTasks {
+                    from_index: Some(from_index),
+                    limit: Some(tasks_amnt),
+                    order_by: None,
+                },
+            )
+            .unwrap();
+        assert_eq!(two_last_elements.len(), 2);
+
+        // Removed task shouldn't reorder things
+        let removed_index = from_index as usize;
+        app.execute_contract(
+            Addr::unchecked(VERY_RICH),
+            contract_addr.clone(),
+            &ExecuteMsg::RemoveTask {
+                task_hash: all_tasks
+                    .remove(removed_index) // We removed hash from original vector to match
+                    .task_hash,
+            },
+            &vec![],
+        )
+        .unwrap();
+        let new_tasks: Vec<TaskResponse> = app
+            .wrap()
+            .query_wasm_smart(
+                &contract_addr.clone(),
+                &QueryMsg::GetTasks {
+                    from_index: None,
+                    limit: None,
+                    order_by: None,
+                },
+            )
+            .unwrap();
+        assert_eq!(new_tasks, all_tasks);
+    }
+
+    #[test]
+    fn query_get_tasks_order_by() {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+
+        let new_msg = |amount| ExecuteMsg::CreateTask {
+            task: TaskRequest {
+                actions: vec![Action {
+                    msg: StakingMsg::Delegate {
+                        validator: String::from("you"),
+                        amount: coin(amount, "atom"),
+                    }
+                    .into(),
+                    gas_limit: Some(150_000),
+                }],
+                ..task_request()
+            },
+        };
+        for amount in 1..6 {
+            app.execute_contract(
+                Addr::unchecked(VERY_RICH),
+                contract_addr.clone(),
+                &new_msg(amount),
+                &coins(37, "atom"),
+            )
+            .unwrap();
+        }
+        let get_tasks = |from_index: Option<u64>, order_by: Option<OrderBy>| -> Vec<String> {
+            let tasks: Vec<TaskResponse> = app
+                .wrap()
+                .query_wasm_smart(
+                    &contract_addr,
+                    &QueryMsg::GetTasks {
+                        from_index,
+                        limit: None,
+                        order_by,
+                    },
+                )
+                .unwrap();
+            tasks.into_iter().map(|t| t.task_hash).collect()
+        };
+
+        // Ascending is the default
+        let asc_tasks = get_tasks(None, Some(OrderBy::Asc));
+        assert_eq!(5, asc_tasks.len());
+        assert_eq!(asc_tasks, get_tasks(None, None));
+
+        // Descending reverses the list
+        let mut desc_tasks = get_tasks(None, Some(OrderBy::Desc));
+        desc_tasks.reverse();
+        assert_eq!(asc_tasks, desc_tasks);
+
+        // from_index counts from the start of the chosen order
+        let desc_from_two = get_tasks(Some(2), Some(OrderBy::Desc));
+        assert_eq!(
+            vec![
+                asc_tasks[2].clone(),
+                asc_tasks[1].clone(),
+                asc_tasks[0].clone()
+            ],
+            desc_from_two
+        );
+    }
+
+    #[test]
+    fn check_task_create_fail_cases() -> StdResult<()> {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+
+        let validator = String::from("you");
+        let amount = coin(3, "atom");
+        let stake = StakingMsg::Delegate { validator, amount };
+        let msg: CosmosMsg = stake.clone().into();
+
+        let create_task_msg = ExecuteMsg::CreateTask {
+            task: TaskRequest {
+                actions: vec![Action {
+                    msg: msg.clone(),
+                    gas_limit: Some(150_000),
+                }],
+                ..task_request()
+            },
+        };
+        // let task_id_str = "ad15b0f15010d57a51ff889d3400fe8d083a0dab2acfc752c5eb55e9e6281705".to_string();
+        // let task_id = task_id_str.clone().into_bytes();
+
+        // Must attach funds
+        let res_err = app
+            .execute_contract(
+                Addr::unchecked(ANYONE),
+                contract_addr.clone(),
+                &create_task_msg,
+                &vec![],
+            )
+            .unwrap_err();
+        assert_eq!(
+            ContractError::CustomError {
+                val: "Must attach funds".to_string()
+            },
+            res_err.downcast().unwrap()
+        );
+
+        // Create task paused
+        let change_settings_msg = settings_msg(SettingsUpdate {
+            paused: Some(true),
+            ..Default::default()
+        });
+        app.execute_contract(
+            Addr::unchecked(ADMIN),
+            contract_addr.clone(),
+            &change_settings_msg,
+            &vec![],
+        )
+        .unwrap();
+        let res_err = app
+            .execute_contract(
+                Addr::unchecked(ANYONE),
+                contract_addr.clone(),
+                &create_task_msg,
+                &coins(13, "atom"),
+            )
+            .unwrap_err();
+        assert_eq!(
+            ContractError::CustomError {
+                val: "Create task paused".to_string()
+            },
+            res_err.downcast().unwrap()
+        );
+        // Set it back
+        app.execute_contract(
+            Addr::unchecked(ADMIN),
+            contract_addr.clone(),
+            &settings_msg(SettingsUpdate {
+                paused: Some(false),
+                ..Default::default()
+            }),
+            &vec![],
+        )
+        .unwrap();
+
+        // Creator invalid
+        let action_self = CosmosMsg::Wasm(WasmMsg::Execute {
+            contract_addr: contract_addr.clone().into_string(),
+            funds: vec![],
+            msg: to_binary(&change_settings_msg.clone())?,
+        });
+        let res_err = app
+            .execute_contract(
+                Addr::unchecked(ANYONE),
+                contract_addr.clone(),
+                &ExecuteMsg::CreateTask {
+                    task: TaskRequest {
+                        interval: Interval::Once,
+                        actions: vec![Action {
+                            msg: action_self.clone(),
+                            gas_limit: Some(150_000),
+                        }],
+                        ..task_request()
+                    },
+                },
+                &coins(13, "atom"),
+            )
+            .unwrap_err();
+        assert_eq!(
+            ContractError::CustomError {
+                val: "Actions Message Unsupported".to_string()
+            },
+            res_err.downcast().unwrap()
+        );
+
+        // Owner can't schedule a migration of the contract either
+        let action_migrate = CosmosMsg::Wasm(WasmMsg::Migrate {
+            contract_addr: contract_addr.clone().into_string(),
+            new_code_id: 2,
+            msg: to_binary(&change_settings_msg)?,
+        });
+        let res_err = app
+            .execute_contract(
+                Addr::unchecked(ADMIN),
+                contract_addr.clone(),
+                &ExecuteMsg::CreateTask {
+                    task: TaskRequest {
+                        interval: Interval::Once,
+                        actions: vec![Action {
+                            msg: action_migrate,
+                            gas_limit: Some(150_000),
+                        }],
+                        ..task_request()
+                    },
+                },
+                &coins(13, "atom"),
+            )
+            .unwrap_err();
+        assert_eq!(
+            ContractError::CustomError {
+                val: "Actions Message Unsupported".to_string()
+            },
+            res_err.downcast().unwrap()
+        );
+
+        // Interval invalid, zero blocks would reschedule into the same slot forever
+        for interval in [Interval::Cron("faux_paw".to_string()), Interval::Block(0)] {
+            let res_err = app
+                .execute_contract(
+                    Addr::unchecked(ANYONE),
+                    contract_addr.clone(),
+                    &ExecuteMsg::CreateTask {
+                        task: TaskRequest {
+                            interval,
+                            actions: vec![Action {
+                                msg: msg.clone(),
+                                gas_limit: Some(150_000),
+                            }],
+                            ..task_request()
+                        },
+                    },
+                    &coins(13, "atom"),
+                )
+                .unwrap_err();
+            assert_eq!(
+                ContractError::CustomError {
+                    val: "Interval invalid".to_string()
+                },
+                res_err.downcast().unwrap()
+            );
+        }
+
+        // Inverted block & time boundaries
+        for (start, end) in [
+            (BoundarySpec::Height(100), BoundarySpec::Height(50)),
+            (
+                BoundarySpec::Time(Timestamp::from_seconds(1_700_000_000)),
+                BoundarySpec::Time(Timestamp::from_seconds(1_600_000_000)),
+            ),
+        ] {
+            let res_err = app
+                .execute_contract(
+                    Addr::unchecked(ANYONE),
+                    contract_addr.clone(),
+                    &ExecuteMsg::CreateTask {
+                        task: TaskRequest {
+                            boundary: Boundary {
+                                start: Some(start),
+                                end: Some(end),
+                            },
+                            actions: vec![Action {
+                                msg: msg.clone(),
+                                gas_limit: Some(150_000),
+                            }],
+                            ..task_request()
+                        },
+                    },
+                    &coins(13, "atom"),
+                )
+                .unwrap_err();
+            assert_eq!(
+                ContractError::InvalidBoundary {},
+                res_err.downcast().unwrap()
+            );
+        }
+
+        // Cron more frequent than slot granularity
+        let res_err = app
+            .execute_contract(
+                Addr::unchecked(ANYONE),
+                contract_addr.clone(),
+                &ExecuteMsg::CreateTask {
+                    task: TaskRequest {
+                        interval: Interval::Cron("* * * * * *".to_string()),
+                        actions: vec![Action {
+                            msg: msg.clone(),
+                            gas_limit: Some(150_000),
+                        }],
+                        ..task_request()
+                    },
+                },
+                &coins(13, "atom"),
+            )
+            .unwrap_err();
+        assert_eq!(
+            ContractError::CustomError {
+                val: "Cron interval more frequent than slot granularity".to_string()
+            },
+            res_err.downcast().unwrap()
+        );
+
+        // Task already exists
+        app.execute_contract(
+            Addr::unchecked(ANYONE),
+            contract_addr.clone(),
+            &create_task_msg,
+            &coins(13, "atom"),
+        )
+        .unwrap();
+        let res_err = app
+            .execute_contract(
+                Addr::unchecked(ANYONE),
+                contract_addr.clone(),
+                &create_task_msg,
+                &coins(13, "atom"),
+            )
+            .unwrap_err();
+        assert_eq!(
+            ContractError::CustomError {
+                val: "Task already exists".to_string()
+            },
+            res_err.downcast().unwrap()
+        );
+
+        // Boundary end already passed, by height and by time
+        let past_time = app.block_info().time.minus_seconds(60);
+        for (interval, end) in [
+            (Interval::Block(12346), BoundarySpec::Height(1)),
+            (
+                Interval::Cron("0 0 * * * *".to_string()),
+                BoundarySpec::Time(past_time),
+            ),
+        ] {
+            let res_err = app
+                .execute_contract(
+                    Addr::unchecked(ANYONE),
+                    contract_addr.clone(),
+                    &ExecuteMsg::CreateTask {
+                        task: TaskRequest {
+                            interval,
+                            boundary: Boundary {
+                                start: None,
+                                end: Some(end),
+                            },
+                            actions: vec![Action {
+                                msg: msg.clone(),
+                                gas_limit: Some(150_000),
+                            }],
+                            ..task_request()
+                        },
+                    },
+                    &coins(13, "atom"),
+                )
+                .unwrap_err();
+            assert_eq!(
+                ContractError::BoundaryInPast {},
+                res_err.downcast().unwrap()
+            );
+        }
+
+        // TODO: (needs impl!) Not enough task balance to execute job
+
+        Ok(())
+    }
+
+    #[test]
+    fn check_task_create_success() -> StdResult<()> {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+
+        let validator = String::from("you");
+        let amount = coin(3, "atom");
+        let stake = StakingMsg::Delegate { validator, amount };
+        let msg: CosmosMsg = stake.clone().into();
+
+        let create_task_msg = ExecuteMsg::CreateTask {
+            task: TaskRequest {
+                actions: vec![Action {
+                    msg,
+                    gas_limit: Some(150_000),
+                }],
+                ..task_request()
+            },
+        };
+        let task_id_str =
+            "ad15b0f15010d57a51ff889d3400fe8d083a0dab2acfc752c5eb55e9e6281705".to_string();
+
+        // create a task
+        let res = app
+            .execute_contract(
+                Addr::unchecked(ANYONE),
+                contract_addr.clone(),
+                &create_task_msg,
+                &coins(37, "atom"),
+            )
+            .unwrap();
+        // Assert task hash is returned as part of event attributes
+        let mut has_created_hash: bool = false;
+        for e in res.events {
+            for a in e.attributes {
+                if a.key == "task_hash" && a.value == task_id_str.clone() {
+                    has_created_hash = true;
+                }
+            }
+        }
+        assert!(has_created_hash);
+
+        // check storage has the task
+        let new_task: Option<TaskResponse> = app
+            .wrap()
+            .query_wasm_smart(
+                &contract_addr.clone(),
+                &QueryMsg::GetTask {
+                    task_hash: task_id_str.clone(),
+                },
+            )
+            .unwrap();
+        assert!(new_task.is_some());
+        if let Some(t) = new_task {
+            assert_eq!(Addr::unchecked(ANYONE), t.owner_id);
+            assert_eq!(Interval::Immediate, t.interval);
+            assert_eq!(
+                Boundary {
+                    start: None,
+                    end: None,
+                },
+                t.boundary
+            );
+            assert_eq!(false, t.stop_on_fail);
+            assert_eq!(coins(37, "atom"), t.total_deposit);
+            assert_eq!(task_id_str.clone(), t.task_hash);
+        }
+
+        // same task is retrievable by the raw hash bytes, both digest and stored key
+        for raw in [
+            hex::decode(&task_id_str).unwrap(),
+            task_id_str.clone().into_bytes(),
+        ] {
+            let raw_task: Option<TaskResponse> = app
+                .wrap()
+                .query_wasm_smart(
+                    contract_addr.clone(),
+                    &QueryMsg::GetTaskRaw {
+                        task_hash: Binary::from(raw),
+                    },
+                )
+                .unwrap();
+            assert_eq!(task_id_str.clone(), raw_task.unwrap().task_hash);
+        }
+        let bad_len: StdResult<Option<TaskResponse>> = app.wrap().query_wasm_smart(
+            contract_addr.clone(),
+            &QueryMsg::GetTaskRaw {
+                task_hash: Binary::from(vec![1u8; 10]),
+            },
+        );
+        assert!(bad_len.is_err());
+
+        // get slot ids
+        let slot_ids: GetSlotIdsResponse = app
+            .wrap()
+            .query_wasm_smart(&contract_addr.clone(), &QueryMsg::GetSlotIds {})
+            .unwrap();
+        let s_1: Vec<u64> = Vec::new();
+        assert_eq!(s_1, slot_ids.time_ids);
+        assert_eq!(vec![12345], slot_ids.block_ids);
+
+        // get slot hashs
+        let slot_info: GetSlotHashesResponse = app
+            .wrap()
+            .query_wasm_smart(
+                &contract_addr.clone(),
+                &QueryMsg::GetSlotHashes {
+                    slot: None,
+                    from_index: None,
+                    limit: None,
+                    due_only: false,
+                },
+            )
+            .unwrap();
+        let s_3: Vec<String> = Vec::new();
+        assert_eq!(12345, slot_info.block_id);
+        assert_eq!(vec![task_id_str.clone()], slot_info.block_task_hash);
+        assert_eq!(0, slot_info.time_id);
+        assert_eq!(s_3, slot_info.time_task_hash);
+
+        Ok(())
+    }
+
+    #[test]
+    fn check_task_create_no_partial_state() -> StdResult<()> {
+        let mut deps = mock_dependencies();
+        let store = CwCroncat::default();
+        mock_init(&store, deps.as_mut()).unwrap();
+
+        // break storage read after the catalog insert used to happen
+        store.agent_active_queue.remove(&mut deps.storage);
+
+        let task = TaskRequest {
+            actions: vec![Action {
+                msg: StakingMsg::Delegate {
+                    validator: "you".to_string(),
+                    amount: coin(3, NATIVE_DENOM),
+                }
+                .into(),
+                gas_limit: Some(150_000),
+            }],
+            ..task_request()
+        };
+        let res = store.create_task(
+            deps.as_mut(),
+            mock_info(ANYONE, &coins(37, NATIVE_DENOM)),
+            mock_env(),
+            task,
+        );
+        assert!(res.is_err());
+
+        // nothing was written
+        assert_eq!(0, store.task_total(&deps.storage)?);
+        assert_eq!(
+            0,
+            store
+                .tasks
+                .range(&deps.storage, None, None, Order::Ascending)
+                .count()
+        );
+        assert_eq!(
+            0,
+            store
+                .block_slots
+                .range(&deps.storage, None, None, Order::Ascending)
+                .count()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn check_denom_alias() {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+        app.sudo(SudoMsg::Bank(BankSudo::Mint {
+            to_address: ANYONE.to_string(),
+            amount: vec![
+                coin(37, "ATOM"),
+                coin(37, "uAtom"),
+                coin(40, "uatom"),
+                coin(5, "juno"),
+            ],
+        }))
+        .unwrap();
+
+        let task = TaskRequest {
+            actions: vec![Action {
+                msg: StakingMsg::Delegate {
+                    validator: "you".to_string(),
+                    amount: coin(3, NATIVE_DENOM),
+                }
+                .into(),
+                gas_limit: Some(150_000),
+            }],
+            ..task_request()
+        };
+
+        // case & micro prefix variants of the native denom are rejected
+        for alias in ["uatom", "ATOM", "uAtom"] {
+            let res_err = app
+                .execute_contract(
+                    Addr::unchecked(ANYONE),
+                    contract_addr.clone(),
+                    &ExecuteMsg::CreateTask { task: task.clone() },
+                    &coins(37, alias),
+                )
+                .unwrap_err();
+            assert_eq!(
+                ContractError::DenomAlias {
+                    denom: alias.to_string(),
+                    native_denom: NATIVE_DENOM.to_string(),
+                },
+                res_err.downcast().unwrap()
+            );
+        }
+        let task_count: u64 = app
+            .wrap()
+            .query_wasm_smart(&contract_addr, &QueryMsg::GetTaskCount {})
+            .unwrap();
+        assert_eq!(0, task_count);
+
+        // the native denom, or an unrelated one, is accepted
+        let res = app
+            .execute_contract(
+                Addr::unchecked(ANYONE),
+                contract_addr.clone(),
+                &ExecuteMsg::CreateTask { task },
+                &[coin(37, NATIVE_DENOM), coin(5, "juno")],
+            )
+            .unwrap();
+        let task_hash = task_hash_attr(&res);
+
+        let refill = |task_hash: String| ExecuteMsg::RefillTaskBalance {
+            task_hash,
+            allow_new_denoms: None,
+        };
+        let res_err = app
+            .execute_contract(
+                Addr::unchecked(ANYONE),
+                contract_addr.clone(),
+                &refill(task_hash.clone()),
+                &coins(3, "uatom"),
+            )
+            .unwrap_err();
+        assert_eq!(
+            ContractError::DenomAlias {
+                denom: "uatom".to_string(),
+                native_denom: NATIVE_DENOM.to_string(),
+            },
+            res_err.downcast().unwrap()
+        );
+        app.execute_contract(
+            Addr::unchecked(ANYONE),
+            contract_addr.clone(),
+            &refill(task_hash.clone()),
+            &coins(3, NATIVE_DENOM),
+        )
+        .unwrap();
+        let task: Option<TaskResponse> = app
+            .wrap()
+            .query_wasm_smart(&contract_addr, &QueryMsg::GetTask { task_hash })
+            .unwrap();
+        assert_eq!(
+            vec![coin(40, NATIVE_DENOM), coin(5, "juno")],
+            task.unwrap().total_deposit
+        );
+    }
+
+    #[test]
+    fn check_zero_amount_funds() -> StdResult<()> {
+        let mut deps = mock_dependencies();
+        let store = CwCroncat::default();
+        mock_init(&store, deps.as_mut()).unwrap();
+
+        let task = TaskRequest {
+            actions: vec![Action {
+                msg: StakingMsg::Delegate {
+                    validator: "you".to_string(),
+                    amount: coin(3, NATIVE_DENOM),
+                }
+                .into(),
+                gas_limit: Some(150_000),
+            }],
+            ..task_request()
+        };
+
+        // only zero coins is the same as no funds
+        let res_err = store
+            .create_task(
+                deps.as_mut(),
+                mock_info(ANYONE, &[coin(0, NATIVE_DENOM), coin(0, "juno")]),
+                mock_env(),
+                task.clone(),
+            )
+            .unwrap_err();
+        assert_eq!(
+            ContractError::CustomError {
+                val: "Must attach funds".to_string()
+            },
+            res_err
+        );
+
+        // zero coins are dropped from the deposit
+        let res = store
+            .create_task(
+                deps.as_mut(),
+                mock_info(ANYONE, &[coin(0, "juno"), coin(37, NATIVE_DENOM)]),
+                mock_env(),
+                task,
+            )
+            .unwrap();
+        let task_hash = from_binary::<CreateTaskResponse>(&res.data.unwrap())?.task_hash;
+        let task = store
+            .query_get_task(deps.as_ref(), mock_env(), task_hash.clone())?
+            .unwrap();
+        assert_eq!(coins(37, NATIVE_DENOM), task.total_deposit);
+
+        // same for refills
+        let res_err = store
+            .refill_task(
+                deps.as_mut(),
+                mock_info(ANYONE, &coins(0, NATIVE_DENOM)),
+                task_hash.clone(),
+                false,
+            )
+            .unwrap_err();
+        assert_eq!(
+            ContractError::CustomError {
+                val: "Must attach funds".to_string()
+            },
+            res_err
+        );
+        store
+            .refill_task(
+                deps.as_mut(),
+                mock_info(ANYONE, &[coin(0, "juno"), coin(3, NATIVE_DENOM)]),
+                task_hash.clone(),
+                false,
+            )
+            .unwrap();
+        let task = store
+            .query_get_task(deps.as_ref(), mock_env(), task_hash)?
+            .unwrap();
+        assert_eq!(coins(40, NATIVE_DENOM), task.total_deposit);
+
+        Ok(())
+    }
+
+    #[test]
+    fn check_fee_and_action_balances() -> StdResult<()> {
+        let mut deps = mock_dependencies();
+        let store = CwCroncat::default();
+        mock_init(&store, deps.as_mut()).unwrap();
+        let config = store.config.load(deps.as_ref().storage)?;
+
+        // stakes juno, pays fees in atom
+        let task = TaskRequest {
+            actions: vec![Action {
+                msg: StakingMsg::Delegate {
+                    validator: "you".to_string(),
+                    amount: coin(100, "juno"),
+                }
+                .into(),
+                gas_limit: Some(150_000),
+            }],
+            ..task_request()
+        };
+
+        // fee denom alone can't fund the stake
+        let res_err = store
+            .create_task(
+                deps.as_mut(),
+                mock_info(ANYONE, &coins(200_000, NATIVE_DENOM)),
+                mock_env(),
+                task.clone(),
+            )
+            .unwrap_err();
+        assert_eq!(
+            ContractError::CustomError {
+                val: "Not enough funds attached for the task actions".to_string()
+            },
+            res_err
+        );
+
+        let res = store
+            .create_task(
+                deps.as_mut(),
+                mock_info(ANYONE, &[coin(200_000, NATIVE_DENOM), coin(100, "juno")]),
+                mock_env(),
+                task,
+            )
+            .unwrap();
+        let task_hash = from_binary::<CreateTaskResponse>(&res.data.unwrap())?.task_hash;
+        let task = store
+            .tasks
+            .load(deps.as_ref().storage, task_hash.into_bytes())?;
+
+        let (fee_balance, action_balances) = store.task_balances(&config, &task);
+        assert_eq!(coin(200_000, NATIVE_DENOM), fee_balance);
+        assert_eq!(coins(100, "juno"), action_balances);
+        assert_eq!(
+            coins(100, "juno"),
+            store.task_cost(&config, &task).unwrap().actions_cost
+        );
+        assert!(store.can_pay_execution(&config, &task));
+
+        // once the juno is spent the task can't execute, whatever its atom balance
+        let mut spent = task.clone();
+        spent.total_deposit = coins(200_000, NATIVE_DENOM);
+        assert!(!store.can_pay_execution(&config, &spent));
+
+        // an action sending the fee denom holds its share back from fees
+        let total_cost = store
+            .task_cost(&config, &task)
+            .unwrap()
+            .total_cost
+            .amount
+            .u128();
+        let mut send_fee_denom = task;
+        send_fee_denom.actions = vec![Action {
+            msg: BankMsg::Send {
+                to_address: "you".to_string(),
+                amount: coins(10, NATIVE_DENOM),
+            }
+            .into(),
+            gas_limit: Some(150_000),
+        }];
+        send_fee_denom.total_deposit = coins(total_cost, NATIVE_DENOM);
+        let (fee_balance, action_balances) = store.task_balances(&config, &send_fee_denom);
+        assert_eq!(coin(total_cost - 10, NATIVE_DENOM), fee_balance);
+        assert_eq!(coins(10, NATIVE_DENOM), action_balances);
+        assert!(!store.can_pay_execution(&config, &send_fee_denom));
+        send_fee_denom.total_deposit = coins(total_cost + 10, NATIVE_DENOM);
+        assert!(store.can_pay_execution(&config, &send_fee_denom));
+
+        Ok(())
+    }
+
+    #[test]
+    fn check_slot_stats() -> StdResult<()> {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+
+        // nothing scheduled yet
+        let stats: GetSlotStatsResponse = app
+            .wrap()
+            .query_wasm_smart(&contract_addr, &QueryMsg::GetSlotStats {})
+            .unwrap();
+        assert_eq!(
+            GetSlotStatsResponse {
+                num_time_slots: 0,
+                num_block_slots: 0,
+                earliest_time_id: None,
+                latest_time_id: None,
+                earliest_block_id: None,
+                latest_block_id: None,
+                num_task_hashes: 0,
+            },
+            stats
+        );
+
+        // two tasks in the same block slot, one in a later block slot, one in a time slot
+        let intervals = vec![
+            (Interval::Immediate, 1),
+            (Interval::Immediate, 2),
+            (Interval::Block(5), 3),
+            (Interval::Cron("0 0 * * * *".to_string()), 4),
+        ];
+        for (interval, stake) in intervals {
+            let create_task_msg = ExecuteMsg::CreateTask {
+                task: TaskRequest {
+                    interval,
+                    actions: vec![Action {
+                        msg: StakingMsg::Delegate {
+                            validator: String::from("you"),
+                            amount: coin(stake, "atom"),
+                        }
+                        .into(),
+                        gas_limit: Some(150_000),
+                    }],
+                    ..task_request()
+                },
+            };
+            app.execute_contract(
+                Addr::unchecked(VERY_RICH),
+                contract_addr.clone(),
+                &create_task_msg,
+                &coins(37, "atom"),
+            )
+            .unwrap();
+        }
+
+        let slot_ids: GetSlotIdsResponse = app
+            .wrap()
+            .query_wasm_smart(&contract_addr, &QueryMsg::GetSlotIds {})
+            .unwrap();
+        assert_eq!(2, slot_ids.block_ids.len());
+        assert_eq!(1, slot_ids.time_ids.len());
+
+        let stats: GetSlotStatsResponse = app
+            .wrap()
+            .query_wasm_smart(&contract_addr, &QueryMsg::GetSlotStats {})
+            .unwrap();
+        assert_eq!(1, stats.num_time_slots);
+        assert_eq!(2, stats.num_block_slots);
+        assert_eq!(Some(slot_ids.time_ids[0]), stats.earliest_time_id);
+        assert_eq!(Some(slot_ids.time_ids[0]), stats.latest_time_id);
+        assert_eq!(Some(12345), stats.earliest_block_id);
+        assert_eq!(Some(12350), stats.latest_block_id);
+        assert_eq!(4, stats.num_task_hashes);
+
+        Ok(())
+    }
+
+    #[test]
+    fn check_horizon() -> StdResult<()> {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+        let block = app.block_info();
+
+        // nothing scheduled yet
+        let horizon: GetHorizonResponse = app
+            .wrap()
+            .query_wasm_smart(&contract_addr, &QueryMsg::GetHorizon {})
+            .unwrap();
+        assert_eq!(
+            GetHorizonResponse {
+                max_time_id: None,
+                max_block_id: None,
+                block_height: block.height,
+                block_time: block.time,
+            },
+            horizon
+        );
+
+        // near & far slots of each kind
+        let intervals = vec![
+            (Interval::Block(5), 1),
+            (Interval::Block(500), 2),
+            (Interval::Cron("0 * * * * *".to_string()), 3),
+            (Interval::Cron("0 0 0 1 1 *".to_string()), 4),
+        ];
+        for (interval, stake) in intervals {
+            let create_task_msg = ExecuteMsg::CreateTask {
+                task: TaskRequest {
+                    interval,
+                    actions: vec![Action {
+                        msg: StakingMsg::Delegate {
+                            validator: String::from("you"),
+                            amount: coin(stake, "atom"),
+                        }
+                        .into(),
+                        gas_limit: Some(150_000),
+                    }],
+                    ..task_request()
+                },
+            };
+            app.execute_contract(
+                Addr::unchecked(VERY_RICH),
+                contract_addr.clone(),
+                &create_task_msg,
+                &coins(37, "atom"),
+            )
+            .unwrap();
+        }
+
+        let slot_ids: GetSlotIdsResponse = app
+            .wrap()
+            .query_wasm_smart(&contract_addr, &QueryMsg::GetSlotIds {})
+            .unwrap();
+        assert_eq!(2, slot_ids.time_ids.len());
+        assert_eq!(2, slot_ids.block_ids.len());
+
+        // block intervals land on a multiple of the interval, the yearly cron on the next new year
+        let horizon: GetHorizonResponse = app
+            .wrap()
+            .query_wasm_smart(&contract_addr, &QueryMsg::GetHorizon {})
+            .unwrap();
+        assert_eq!(
+            GetHorizonResponse {
+                max_time_id: Some(Timestamp::from_seconds(1_577_836_800).nanos()),
+                max_block_id: Some(12500),
+                block_height: block.height,
+                block_time: block.time,
+            },
+            horizon
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn check_stuck_tasks() -> StdResult<()> {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+
+        let mut task_hashes: Vec<String> = vec![];
+        let intervals = vec![
+            (Interval::Immediate, 1),
+            (Interval::Block(10), 2),
+            (Interval::Cron("0 * * * * *".to_string()), 3),
+        ];
+        for (interval, stake) in intervals {
+            let create_task_msg = ExecuteMsg::CreateTask {
+                task: TaskRequest {
+                    interval,
+                    actions: vec![Action {
+                        msg: StakingMsg::Delegate {
+                            validator: String::from("you"),
+                            amount: coin(stake, "atom"),
+                        }
+                        .into(),
+                        gas_limit: Some(150_000),
+                    }],
+                    ..task_request()
+                },
+            };
+            let res = app
+                .execute_contract(
+                    Addr::unchecked(VERY_RICH),
+                    contract_addr.clone(),
+                    &create_task_msg,
+                    &coins(37, "atom"),
+                )
+                .unwrap();
+            task_hashes.push(task_hash_attr(&res));
+        }
+        let stuck_tasks = |app: &App| -> StdResult<GetStuckTasksResponse> {
+            app.wrap()
+                .query_wasm_smart(&contract_addr, &QueryMsg::GetStuckTasks {})
+        };
+
+        // the immediate task is due in the current block, so not stuck yet
+        assert_eq!(
+            GetStuckTasksResponse {
+                time_task_hashes: vec![],
+                block_task_hashes: vec![],
+            },
+            stuck_tasks(&app)?
+        );
+
+        // a block later it was missed, the others are still ahead
+        app.update_block(|block| {
+            block.height += 1;
+            block.time = block.time.plus_seconds(5);
+        });
+        assert_eq!(
+            GetStuckTasksResponse {
+                time_task_hashes: vec![],
+                block_task_hashes: vec![task_hashes[0].clone()],
+            },
+            stuck_tasks(&app)?
+        );
+
+        // past the block & time slots, everything was missed
+        app.update_block(|block| {
+            block.height += 10;
+            block.time = block.time.plus_seconds(120);
+        });
+        assert_eq!(
+            GetStuckTasksResponse {
+                time_task_hashes: vec![task_hashes[2].clone()],
+                block_task_hashes: vec![task_hashes[0].clone(), task_hashes[1].clone()],
+            },
+            stuck_tasks(&app)?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn query_slot_tasks_due_only() -> StdResult<()> {
+        let mut deps = mock_dependencies();
+        let store = CwCroncat::default();
+        let env = mock_env();
+        let height = env.block.height;
+        let now = env.block.time.nanos();
+
+        // only future slots
+        store
+            .block_slots
+            .save(&mut deps.storage, height + 1, &vec!["block_hash".into()])?;
+        store
+            .time_slots
+            .save(&mut deps.storage, now + 1, &vec!["time_hash".into()])?;
+
+        let next = store.query_slot_tasks(deps.as_ref(), None, None, None, None)?;
+        assert_eq!(
+            GetSlotHashesResponse {
+                block_id: height + 1,
+                block_task_hash: vec!["block_hash".to_string()],
+                time_id: now + 1,
+                time_task_hash: vec!["time_hash".to_string()],
+            },
+            next
+        );
+        let due = store.query_slot_tasks(deps.as_ref(), None, None, None, Some(&env.block))?;
+        assert_eq!(
+            GetSlotHashesResponse {
+                block_id: 0,
+                block_task_hash: vec![],
+                time_id: 0,
+                time_task_hash: vec![],
+            },
+            due
+        );
+        let slot = Some((SlotType::Block, height + 1));
+        assert!(store
+            .query_slot_tasks(deps.as_ref(), slot, None, None, Some(&env.block))?
+            .block_task_hash
+            .is_empty());
+
+        // slots up to the current block & time are due
+        store
+            .block_slots
+            .save(&mut deps.storage, height, &vec!["due_block_hash".into()])?;
+        store
+            .time_slots
+            .save(&mut deps.storage, now, &vec!["due_time_hash".into()])?;
+        let due = store.query_slot_tasks(deps.as_ref(), None, None, None, Some(&env.block))?;
+        assert_eq!(
+            GetSlotHashesResponse {
+                block_id: height,
+                block_task_hash: vec!["due_block_hash".to_string()],
+                time_id: now,
+                time_task_hash: vec!["due_time_hash".to_string()],
+            },
+            due
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn query_slot_tasks_by_type() -> StdResult<()> {
+        let mut deps = mock_dependencies();
+        let store = CwCroncat::default();
+
+        // a block height and a timestamp with the same id
+        store
+            .block_slots
+            .save(&mut deps.storage, 12346, &vec!["block_hash".into()])?;
+        store
+            .time_slots
+            .save(&mut deps.storage, 12346, &vec!["time_hash".into()])?;
+
+        let block_slot = store.query_slot_tasks(
+            deps.as_ref(),
+            Some((SlotType::Block, 12346)),
+            None,
+            None,
+            None,
+        )?;
+        assert_eq!(
+            GetSlotHashesResponse {
+                block_id: 12346,
+                block_task_hash: vec!["block_hash".to_string()],
+                time_id: 0,
+                time_task_hash: vec![],
+            },
+            block_slot
+        );
+
+        let time_slot = store.query_slot_tasks(
+            deps.as_ref(),
+            Some((SlotType::Cron, 12346)),
+            None,
+            None,
+            None,
+        )?;
+        assert_eq!(
+            GetSlotHashesResponse {
+                block_id: 0,
+                block_task_hash: vec![],
+                time_id: 12346,
+                time_task_hash: vec!["time_hash".to_string()],
+            },
+            time_slot
+        );
+
+        // removing one doesn't touch the other
+        store.block_slots.remove(&mut deps.storage, 12346);
+        let time_slot = store.query_slot_tasks(
+            deps.as_ref(),
+            Some((SlotType::Cron, 12346)),
+            None,
+            None,
+            None,
+        )?;
+        assert_eq!(vec!["time_hash".to_string()], time_slot.time_task_hash);
+        let block_slot = store.query_slot_tasks(
+            deps.as_ref(),
+            Some((SlotType::Block, 12346)),
+            None,
+            None,
+            None,
+        )?;
+        assert!(block_slot.block_task_hash.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn query_slot_tasks_hash_order() -> StdResult<()> {
+        let mut deps = mock_dependencies();
+        let mut store = CwCroncat::default();
+        mock_init(&store, deps.as_mut()).unwrap();
+
+        let task = |stake: u128| TaskRequest {
+            actions: vec![Action {
+                msg: StakingMsg::Delegate {
+                    validator: "you".to_string(),
+                    amount: coin(stake, NATIVE_DENOM),
+                }
+                .into(),
+                gas_limit: Some(150_000),
+            }],
+            ..task_request()
+        };
+        let mut created = vec![];
+        for stake in 1..=6 {
+            let res = store
+                .create_task(
+                    deps.as_mut(),
+                    mock_info(ANYONE, &coins(37, NATIVE_DENOM)),
+                    mock_env(),
+                    task(stake),
+                )
+                .unwrap();
+            let task_hash = from_binary::<CreateTaskResponse>(&res.data.unwrap())?.task_hash;
+            created.push(task_hash);
+        }
+        let mut sorted = created.clone();
+        sorted.sort();
+        assert_ne!(created, sorted);
+
+        let slot = store.query_slot_tasks(deps.as_ref(), None, None, None, None)?;
+        assert_eq!(sorted, slot.block_task_hash);
+        // executed in the same order
+        let height = mock_env().block.height;
+        for hash in sorted {
+            assert_eq!(
+                Some(hash.into_bytes()),
+                store.pop_slot_item(&mut deps.storage, &height, &SlotType::Block)
+            );
+        }
+
+        // slots saved in insertion order are returned sorted too
+        store.time_slots.save(
+            &mut deps.storage,
+            12345,
+            &vec!["c".into(), "a".into(), "b".into()],
+        )?;
+        let slot = store.query_slot_tasks(
+            deps.as_ref(),
+            Some((SlotType::Cron, 12345)),
+            None,
+            None,
+            None,
+        )?;
+        assert_eq!(vec!["a", "b", "c"], slot.time_task_hash);
+        assert_eq!(
+            Some(b"a".to_vec()),
+            store.pop_slot_item(&mut deps.storage, &12345, &SlotType::Cron)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn check_task_create_immediate_current_slot() {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+        let height = app.block_info().height;
+
+        let task = |stake: u128, start: Option<BoundarySpec>| TaskRequest {
+            boundary: Boundary { start, end: None },
+            actions: vec![Action {
+                msg: StakingMsg::Delegate {
+                    validator: "you".to_string(),
+                    amount: coin(stake, NATIVE_DENOM),
+                }
+                .into(),
+                gas_limit: Some(150_000),
+            }],
+            ..task_request()
+        };
+        let slot_hashes = |app: &App, slot_id: u64| {
+            let slot: GetSlotHashesResponse = app
+                .wrap()
+                .query_wasm_smart(
+                    &contract_addr,
+                    &QueryMsg::GetSlotHashes {
+                        slot: Some((SlotType::Block, slot_id)),
+                        from_index: None,
+                        limit: None,
+                        due_only: false,
+                    },
+                )
+                .unwrap();
+            slot.block_task_hash
+        };
+
+        // runs in the block it was created in, not the next one
+        let task_hash = create_task_hash(&mut app, &contract_addr, task(1, None));
+        assert_eq!(vec![task_hash], slot_hashes(&app, height));
+        assert!(slot_hashes(&app, height + 1).is_empty());
+
+        // a later boundary start still wins
+        let task_hash = create_task_hash(
+            &mut app,
+            &contract_addr,
+            task(2, Some(BoundarySpec::Height(height + 5))),
+        );
+        assert_eq!(vec![task_hash], slot_hashes(&app, height + 5));
+    }
+
+    #[test]
+    fn check_create_boundary_in_past() -> StdResult<()> {
+        let mut deps = mock_dependencies();
+        let store = CwCroncat::default();
+        mock_init(&store, deps.as_mut()).unwrap();
+
+        let env = mock_env();
+        for (interval, end) in [
+            (
+                Interval::Block(5),
+                BoundarySpec::Height(env.block.height - 1),
+            ),
+            (
+                Interval::Cron("0 0 * * * *".to_string()),
+                BoundarySpec::Time(env.block.time.minus_seconds(1)),
+            ),
+        ] {
+            let err = store
+                .create_task(
+                    deps.as_mut(),
+                    mock_info(ANYONE, &coins(20, NATIVE_DENOM)),
+                    env.clone(),
+                    TaskRequest {
+                        interval,
+                        boundary: Boundary {
+                            start: None,
+                            end: Some(end),
+                        },
+                        ..task_request()
+                    },
+                )
+                .unwrap_err();
+            assert_eq!(ContractError::BoundaryInPast {}, err);
+        }
+        assert_eq!(0, store.task_total(deps.as_ref().storage)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn check_create_ibc_transfer_task() -> StdResult<()> {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+
+        let transfer = |timeout: IbcTimeout| TaskRequest {
+            actions: vec![Action {
+                msg: IbcMsg::Transfer {
+                    channel_id: "channel-0".to_string(),
+                    to_address: "cosmos1recipient".to_string(),
+                    amount: coin(5, NATIVE_DENOM),
+                    timeout,
+                }
+                .into(),
+                gas_limit: Some(150_000),
+            }],
+            ..task_request()
+        };
+
+        // A complete transfer schedules like any other action
+        let timeout = IbcTimeout::with_timestamp(app.block_info().time.plus_seconds(600));
+        let task_hash = create_task_hash(&mut app, &contract_addr, transfer(timeout));
+        let task: Option<TaskResponse> = app
+            .wrap()
+            .query_wasm_smart(&contract_addr, &QueryMsg::GetTask { task_hash })?;
+        assert!(matches!(
+            task.unwrap().actions[0].msg,
+            CosmosMsg::Ibc(IbcMsg::Transfer { .. })
+        ));
+
+        // Without a timeout the transfer could hang forever, so it's rejected
+        let err = app
+            .execute_contract(
+                Addr::unchecked(ANYONE),
+                contract_addr.clone(),
+                &ExecuteMsg::CreateTask {
+                    task: transfer(from_slice(br#"{"block":null,"timestamp":null}"#)?),
+                },
+                &coins(20, NATIVE_DENOM),
+            )
+            .unwrap_err();
+        assert_eq!(
+            ContractError::CustomError {
+                val: "Actions Message Unsupported".to_string()
+            },
+            err.downcast().unwrap()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn check_remove_task_attributes() {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+        app.sudo(SudoMsg::Bank(BankSudo::Mint {
+            to_address: ANYONE.to_string(),
+            amount: coins(5, "juno"),
+        }))
+        .unwrap();
+
+        let res = app
+            .execute_contract(
+                Addr::unchecked(ANYONE),
+                contract_addr.clone(),
+                &ExecuteMsg::CreateTask {
+                    task: task_request(),
+                },
+                &[coin(37, NATIVE_DENOM), coin(5, "juno")],
+            )
+            .unwrap();
+        let task_hash = task_hash_attr(&res);
+        let remove_msg = ExecuteMsg::RemoveTask {
+            task_hash: task_hash.clone(),
+        };
+
+        // only the owner can remove it
+        let err = app
+            .execute_contract(
+                Addr::unchecked(ADMIN),
+                contract_addr.clone(),
+                &remove_msg,
+                &[],
+            )
+            .unwrap_err();
+        assert_eq!(ContractError::Unauthorized {}, err.downcast().unwrap());
+
+        let res = app
+            .execute_contract(
+                Addr::unchecked(ANYONE),
+                contract_addr.clone(),
+                &remove_msg,
+                &[],
+            )
+            .unwrap();
+        // first comes the _contract_addr the wasm event is tagged with
+        let wasm = res.events.iter().find(|e| e.ty == "wasm").unwrap();
+        assert_eq!(
+            vec![
+                Attribute::new("method", "remove_task"),
+                Attribute::new("task_hash", task_hash.clone()),
+                Attribute::new("owner_id", ANYONE),
+                Attribute::new("refund_amount", format!("37{},5juno", NATIVE_DENOM)),
+            ],
+            wasm.attributes[1..]
+        );
+        // the whole deposit is back with the owner
+        assert_eq!(
+            vec![coin(100, NATIVE_DENOM), coin(5, "juno")],
+            app.wrap().query_all_balances(ANYONE).unwrap()
+        );
+        let task: Option<TaskResponse> = app
+            .wrap()
+            .query_wasm_smart(&contract_addr, &QueryMsg::GetTask { task_hash })
+            .unwrap();
+        assert!(task.is_none());
+    }
+
+    #[test]
+    fn check_remove_task_balance_underflow() -> StdResult<()> {
+        let mut deps = mock_dependencies();
+        let store = CwCroncat::default();
+        mock_init(&store, deps.as_mut()).unwrap();
+
+        let res = store
+            .create_task(
+                deps.as_mut(),
+                mock_info(ANYONE, &coins(37, NATIVE_DENOM)),
+                mock_env(),
+                task_request(),
+            )
+            .unwrap();
+        let task_hash = from_binary::<CreateTaskResponse>(&res.data.unwrap())?.task_hash;
+
+        // drift the accounting, so the deposit is more than what's tracked
+        store
+            .config
+            .update(deps.as_mut().storage, |mut c| -> StdResult<_> {
+                c.available_balance.native = coins(30, NATIVE_DENOM);
+                Ok(c)
+            })?;
+
+        let res = store.remove_task(deps.as_mut(), task_hash).unwrap();
+        let config = store.config.load(deps.as_ref().storage)?;
+        assert_eq!(coins(0, NATIVE_DENOM), config.available_balance.native);
+        assert!(res
+            .attributes
+            .iter()
+            .any(|a| a.key == "balance_shortfall" && a.value == format!("7{}", NATIVE_DENOM)));
+
+        Ok(())
+    }
+
+    #[test]
+    fn check_remove_one_shot_refund_modes() {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+        app.execute_contract(
+            Addr::unchecked(ADMIN),
+            contract_addr.clone(),
+            &settings_msg(SettingsUpdate {
+                treasury_id: Some(Some(Addr::unchecked("treasury"))),
+                refund_on_remove: Some(false),
+                ..Default::default()
+            }),
+            &[],
+        )
+        .unwrap();
+
+        // Opted out, but a task that never ran is still refunded
+        // Leftovers of a run are covered by proxy_callback_one_shot_refund_modes
+        let task_hash = create_task_hash(
+            &mut app,
+            &contract_addr,
+            TaskRequest {
+                interval: Interval::Once,
+                ..task_request()
+            },
+        );
+        let res = app
+            .execute_contract(
+                Addr::unchecked(ANYONE),
+                contract_addr.clone(),
+                &ExecuteMsg::RemoveTask { task_hash },
+                &[],
+            )
+            .unwrap();
+        assert!(res
+            .events
+            .iter()
+            .flat_map(|e| e.attributes.iter())
+            .any(|a| a.key == "refund_amount" && a.value == format!("10{}", NATIVE_DENOM)));
+        assert_eq!(
+            coins(100, NATIVE_DENOM),
+            app.wrap().query_all_balances(ANYONE).unwrap()
+        );
+        assert!(app
+            .wrap()
+            .query_all_balances("treasury")
+            .unwrap()
+            .is_empty());
+    }
+
+    #[test]
+    fn check_remove_dust_threshold() {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+        app.sudo(SudoMsg::Bank(BankSudo::Mint {
+            to_address: ANYONE.to_string(),
+            amount: vec![coin(10, "osmo"), coin(100, "juno")],
+        }))
+        .unwrap();
+        app.execute_contract(
+            Addr::unchecked(ADMIN),
+            contract_addr.clone(),
+            &settings_msg(SettingsUpdate {
+                dust_threshold: Some(Uint128::new(10)),
+                ..Default::default()
+            }),
+            &[],
+        )
+        .unwrap();
+
+        let create_and_remove = |app: &mut App| {
+            let res = app
+                .execute_contract(
+                    Addr::unchecked(ANYONE),
+                    contract_addr.clone(),
+                    &ExecuteMsg::CreateTask {
+                        task: task_request(),
+                    },
+                    &[coin(7, NATIVE_DENOM), coin(5, "osmo"), coin(50, "juno")],
+                )
+                .unwrap();
+            app.execute_contract(
+                Addr::unchecked(ANYONE),
+                contract_addr.clone(),
+                &ExecuteMsg::RemoveTask {
+                    task_hash: task_hash_attr(&res),
+                },
+                &[],
+            )
+            .unwrap()
+        };
+        let dust_left = |app: &App| -> Vec<Coin> {
+            let balances: GetBalancesResponse = app
+                .wrap()
+                .query_wasm_smart(&contract_addr, &QueryMsg::GetBalances {})
+                .unwrap();
+            balances
+                .available_balance
+                .native
+                .into_iter()
+                .filter(|c| !c.amount.is_zero())
+                .collect()
+        };
+
+        // fee denom & above threshold amounts are refunded, dust stays in available_balance
+        let res = create_and_remove(&mut app);
+        assert!(res
+            .events
+            .iter()
+            .flat_map(|e| e.attributes.iter())
+            .any(|a| a.key == "dust_amount" && a.value == "5osmo"));
+        assert_eq!(
+            vec![coin(100, NATIVE_DENOM), coin(100, "juno"), coin(5, "osmo")],
+            app.wrap().query_all_balances(ANYONE).unwrap()
+        );
+        assert_eq!(vec![coin(5, "osmo")], dust_left(&app));
+
+        // with a treasury, dust is sent there instead
+        app.execute_contract(
+            Addr::unchecked(ADMIN),
+            contract_addr.clone(),
+            &settings_msg(SettingsUpdate {
+                treasury_id: Some(Some(Addr::unchecked("treasury"))),
+                ..Default::default()
+            }),
+            &[],
+        )
+        .unwrap();
+        create_and_remove(&mut app);
+        assert_eq!(
+            vec![coin(100, NATIVE_DENOM), coin(100, "juno")],
+            app.wrap().query_all_balances(ANYONE).unwrap()
+        );
+        assert_eq!(
+            coins(5, "osmo"),
+            app.wrap().query_all_balances("treasury").unwrap()
+        );
+        assert_eq!(vec![coin(5, "osmo")], dust_left(&app));
+    }
+
+    #[test]
+    fn check_remove_tasks_batch() {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+        app.sudo(SudoMsg::Bank(BankSudo::Mint {
+            to_address: ANYONE.to_string(),
+            amount: coins(5, "juno"),
+        }))
+        .unwrap();
+        app.sudo(SudoMsg::Bank(BankSudo::Mint {
+            to_address: "bob".to_string(),
+            amount: coins(40, NATIVE_DENOM),
+        }))
+        .unwrap();
+
+        let mut create_task = |owner: &str, funds: &[Coin], stake: u128| -> String {
+            let res = app
+                .execute_contract(
+                    Addr::unchecked(owner),
+                    contract_addr.clone(),
+                    &ExecuteMsg::CreateTask {
+                        task: TaskRequest {
+                            actions: vec![Action {
+                                msg: StakingMsg::Delegate {
+                                    validator: "you".to_string(),
+                                    amount: coin(stake, NATIVE_DENOM),
+                                }
+                                .into(),
+                                gas_limit: Some(150_000),
+                            }],
+                            ..task_request()
+                        },
+                    },
+                    funds,
+                )
+                .unwrap();
+            task_hash_attr(&res)
+        };
+        let owned = vec![
+            create_task(ANYONE, &coins(10, NATIVE_DENOM), 1),
+            create_task(ANYONE, &coins(20, NATIVE_DENOM), 2),
+            create_task(ANYONE, &[coin(30, NATIVE_DENOM), coin(5, "juno")], 3),
+        ];
+        let other = create_task("bob", &coins(40, NATIVE_DENOM), 4);
+        let task_count = |app: &App| -> u64 {
+            app.wrap()
+                .query_wasm_smart(&contract_addr, &QueryMsg::GetTaskCount {})
+                .unwrap()
+        };
+
+        // any hash of someone else fails the whole batch
+        let mut with_other = owned.clone();
+        with_other.push(other.clone());
+        let res_err = app
+            .execute_contract(
+                Addr::unchecked(ANYONE),
+                contract_addr.clone(),
+                &ExecuteMsg::RemoveTasks {
+                    task_hashes: with_other,
+                },
+                &[],
+            )
+            .unwrap_err();
+        assert_eq!(ContractError::Unauthorized {}, res_err.downcast().unwrap());
+        assert_eq!(4, task_count(&app));
+
+        // refunds are batched into one send per owner & denom
+        let res = app
+            .execute_contract(
+                Addr::unchecked(ANYONE),
+                contract_addr.clone(),
+                &ExecuteMsg::RemoveTasks {
+                    task_hashes: owned.clone(),
+                },
+                &[],
+            )
+            .unwrap();
+        assert_eq!(1, res.events.iter().filter(|e| e.ty == "transfer").count());
+        assert_eq!(
+            vec![coin(100, NATIVE_DENOM), coin(5, "juno")],
+            app.wrap().query_all_balances(ANYONE).unwrap()
+        );
+        assert_eq!(1, task_count(&app));
+        for task_hash in owned {
+            let task: Option<TaskResponse> = app
+                .wrap()
+                .query_wasm_smart(&contract_addr, &QueryMsg::GetTask { task_hash })
+                .unwrap();
+            assert!(task.is_none());
+        }
+
+        // only the remaining task is left in the slots
+        let slots: Vec<(u64, Vec<String>)> = app
+            .wrap()
+            .query_wasm_smart(
+                &contract_addr,
+                &QueryMsg::GetAllSlots {
+                    slot_type: SlotType::Block,
+                    from_index: None,
+                    limit: None,
+                },
+            )
+            .unwrap();
+        let slot_hashes: Vec<String> = slots.into_iter().flat_map(|(_, hashes)| hashes).collect();
+        assert_eq!(vec![other], slot_hashes);
+    }
+
+    #[test]
+    fn check_claim_ended_task() {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+        app.sudo(SudoMsg::Bank(BankSudo::Mint {
+            to_address: ANYONE.to_string(),
+            amount: coins(200_000, NATIVE_DENOM),
+        }))
+        .unwrap();
+
+        let height = app.block_info().height;
+        let res = app
+            .execute_contract(
+                Addr::unchecked(ANYONE),
+                contract_addr.clone(),
+                &ExecuteMsg::CreateTask {
+                    task: TaskRequest {
+                        interval: Interval::Block(1),
+                        boundary: Boundary {
+                            start: None,
+                            end: Some(BoundarySpec::Height(height + 5)),
+                        },
+                        ..task_request()
+                    },
+                },
+                &coins(200_000, NATIVE_DENOM),
+            )
+            .unwrap();
+        let task_hash = task_hash_attr(&res);
+        let claim_msg = ExecuteMsg::ClaimEndedTask {
+            task_hash: task_hash.clone(),
+        };
+
+        // still live & funded
+        let err = app
+            .execute_contract(
+                Addr::unchecked(ANYONE),
+                contract_addr.clone(),
+                &claim_msg,
+                &[],
+            )
+            .unwrap_err();
+        assert_eq!(
+            ContractError::CustomError {
+                val: "Task can still be executed".to_string()
+            },
+            err.downcast().unwrap()
+        );
+
+        // past the end boundary
+        app.update_block(|block| block.height += 10);
+        let err = app
+            .execute_contract(
+                Addr::unchecked(ADMIN),
+                contract_addr.clone(),
+                &claim_msg,
+                &[],
+            )
+            .unwrap_err();
+        assert_eq!(ContractError::Unauthorized {}, err.downcast().unwrap());
+
+        let res = app
+            .execute_contract(
+                Addr::unchecked(ANYONE),
+                contract_addr.clone(),
+                &claim_msg,
+                &[],
+            )
+            .unwrap();
+        assert!(res
+            .events
+            .iter()
+            .flat_map(|e| e.attributes.iter())
+            .any(|a| a.key == "claimed_by" && a.value == ANYONE));
+        assert_eq!(
+            coins(200_100, NATIVE_DENOM),
+            app.wrap().query_all_balances(ANYONE).unwrap()
+        );
+        let task: Option<TaskResponse> = app
+            .wrap()
+            .query_wasm_smart(&contract_addr, &QueryMsg::GetTask { task_hash })
+            .unwrap();
+        assert!(task.is_none());
+    }
+
+    #[test]
+    fn query_get_denoms() -> StdResult<()> {
+        let mut deps = mock_dependencies();
+        let store = CwCroncat::default();
+        mock_init(&store, deps.as_mut()).unwrap();
+        assert_eq!(
+            vec![NATIVE_DENOM.to_string()],
+            store.query_get_denoms(deps.as_ref())?
+        );
+
+        let deposits = vec![
+            vec![coin(10, "juno"), coin(10, "osmo")],
+            vec![coin(20, "juno")],
+            vec![coin(10, NATIVE_DENOM), coin(30, "osmo")],
+        ];
+        for (i, funds) in deposits.into_iter().enumerate() {
+            store
+                .create_task(
+                    deps.as_mut(),
+                    mock_info(ANYONE, &funds),
+                    mock_env(),
+                    TaskRequest {
+                        actions: vec![Action {
+                            msg: StakingMsg::Delegate {
+                                validator: "you".to_string(),
+                                amount: coin(i as u128 + 1, NATIVE_DENOM),
+                            }
+                            .into(),
+                            gas_limit: Some(150_000),
+                        }],
+                        ..task_request()
+                    },
+                )
+                .unwrap();
+        }
+
+        // every denom once, including the fee denom
+        assert_eq!(
+            vec![
+                NATIVE_DENOM.to_string(),
+                "juno".to_string(),
+                "osmo".to_string()
+            ],
+            store.query_get_denoms(deps.as_ref())?
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn check_task_create_slot_kind_attribute() -> StdResult<()> {
+        let mut deps = mock_dependencies();
+        let store = CwCroncat::default();
+        mock_init(&store, deps.as_mut()).unwrap();
+
+        let cases = vec![
+            (Interval::Immediate, "block"),
+            (Interval::Cron("0 0 * * * *".to_string()), "cron"),
+        ];
+        for (interval, slot_kind) in cases {
+            let res = store
+                .create_task(
+                    deps.as_mut(),
+                    mock_info(ANYONE, &coins(10, NATIVE_DENOM)),
+                    mock_env(),
+                    TaskRequest {
+                        interval,
+                        ..task_request()
+                    },
+                )
+                .unwrap();
+            let attr = res
+                .attributes
+                .iter()
+                .find(|a| a.key == "slot_kind")
+                .unwrap();
+            assert_eq!(slot_kind, attr.value);
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn check_task_create_min_deposit() {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+        app.sudo(SudoMsg::Bank(BankSudo::Mint {
+            to_address: ANYONE.to_string(),
+            amount: vec![coin(100, NATIVE_DENOM), coin(500, "juno")],
+        }))
+        .unwrap();
+
+        let min_deposit_msg = |min_task_deposit: Coin| {
+            settings_msg(SettingsUpdate {
+                min_task_deposit: Some(Some(min_task_deposit)),
+                ..Default::default()
+            })
+        };
+
+        // the floor must use the fee denom
+        let res_err = app
+            .execute_contract(
+                Addr::unchecked(ADMIN),
+                contract_addr.clone(),
+                &min_deposit_msg(coin(100, "juno")),
+                &[],
+            )
+            .unwrap_err();
+        assert_eq!(
+            ContractError::CustomError {
+                val: "Min task deposit must use the agent fee denom".to_string()
+            },
+            res_err.downcast().unwrap()
+        );
+        app.execute_contract(
+            Addr::unchecked(ADMIN),
+            contract_addr.clone(),
+            &min_deposit_msg(coin(100, NATIVE_DENOM)),
+            &[],
+        )
+        .unwrap();
+
+        let create_task_msg = ExecuteMsg::CreateTask {
+            task: task_request(),
+        };
+
+        // other denoms don't count towards the floor
+        let res_err = app
+            .execute_contract(
+                Addr::unchecked(ANYONE),
+                contract_addr.clone(),
+                &create_task_msg,
+                &[coin(99, NATIVE_DENOM), coin(500, "juno")],
+            )
+            .unwrap_err();
+        assert_eq!(
+            ContractError::DepositBelowMinimum {
+                min: coin(100, NATIVE_DENOM)
+            },
+            res_err.downcast().unwrap()
+        );
+
+        app.execute_contract(
+            Addr::unchecked(ANYONE),
+            contract_addr.clone(),
+            &create_task_msg,
+            &coins(100, NATIVE_DENOM),
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn check_task_create_boundary_horizon() {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+
+        // block intervals with heights, cron with times
+        let task = |start: Option<BoundarySpec>, end: Option<BoundarySpec>| TaskRequest {
+            interval: match start.or(end) {
+                Some(BoundarySpec::Time(_)) => Interval::Cron("0 0 * * * *".to_string()),
+                _ => Interval::Block(10),
+            },
+            boundary: Boundary { start, end },
+            ..task_request()
+        };
+        let block = app.block_info();
+        let far_end = Some(BoundarySpec::Time(
+            block.time.plus_seconds(100 * 31_536_000),
+        ));
+
+        // unbounded by default
+        create_task_hash(&mut app, &contract_addr, task(None, far_end));
+
+        app.execute_contract(
+            Addr::unchecked(ADMIN),
+            contract_addr.clone(),
+            &settings_msg(SettingsUpdate {
+                max_boundary_horizon: Some(Some(BoundaryHorizon {
+                    blocks: 100_000,
+                    seconds: 31_536_000,
+                })),
+                ..Default::default()
+            }),
+            &[],
+        )
+        .unwrap();
+
+        // a start or end beyond the horizon is rejected
+        for (start, end) in [
+            (None, far_end),
+            (Some(BoundarySpec::Height(block.height + 100_001)), None),
+        ] {
+            let res_err = app
+                .execute_contract(
+                    Addr::unchecked(ANYONE),
+                    contract_addr.clone(),
+                    &ExecuteMsg::CreateTask {
+                        task: task(start, end),
+                    },
+                    &coins(10, NATIVE_DENOM),
+                )
+                .unwrap_err();
+            assert_eq!(
+                ContractError::BoundaryBeyondHorizon {},
+                res_err.downcast().unwrap()
+            );
+        }
+
+        // within the horizon
+        create_task_hash(
+            &mut app,
+            &contract_addr,
+            task(None, Some(BoundarySpec::Height(block.height + 100_000))),
+        );
+        create_task_hash(
+            &mut app,
+            &contract_addr,
+            task(
+                None,
+                Some(BoundarySpec::Time(block.time.plus_seconds(31_536_000))),
+            ),
+        );
+        let task_count: u64 = app
+            .wrap()
+            .query_wasm_smart(&contract_addr, &QueryMsg::GetTaskCount {})
+            .unwrap();
+        assert_eq!(3, task_count);
+    }
+
+    #[test]
+    fn check_task_create_label() {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+
+        let task = |label: Option<&str>| TaskRequest {
+            label: label.map(String::from),
+            ..task_request()
+        };
+        let mut hashes = vec![];
+        for label in [None, Some("first"), Some("second")] {
+            let task_hash = create_task_hash(&mut app, &contract_addr, task(label));
+            let created: Option<TaskResponse> = app
+                .wrap()
+                .query_wasm_smart(
+                    &contract_addr,
+                    &QueryMsg::GetTask {
+                        task_hash: task_hash.clone(),
+                    },
+                )
+                .unwrap();
+            assert_eq!(label.map(String::from), created.unwrap().label);
+            hashes.push(task_hash);
+        }
+        let task_count: u64 = app
+            .wrap()
+            .query_wasm_smart(&contract_addr, &QueryMsg::GetTaskCount {})
+            .unwrap();
+        assert_eq!(3, task_count);
+        assert_eq!(3, hashes.iter().collect::<BTreeSet<_>>().len());
+
+        // the same label still collides
+        let res_err = app
+            .execute_contract(
+                Addr::unchecked(ANYONE),
+                contract_addr.clone(),
+                &ExecuteMsg::CreateTask {
+                    task: task(Some("first")),
+                },
+                &coins(10, NATIVE_DENOM),
+            )
+            .unwrap_err();
+        assert_eq!(
+            ContractError::CustomError {
+                val: "Task already exists".to_string()
+            },
+            res_err.downcast().unwrap()
+        );
+    }
+
+    #[test]
+    fn check_task_create_memo() -> StdResult<()> {
+        let mut deps = mock_dependencies();
+        let store = CwCroncat::default();
+        mock_init(&store, deps.as_mut()).unwrap();
+
+        let task = |memo: Option<String>| TaskRequest {
+            memo,
+            ..task_request()
+        };
+
+        // too long
+        let res_err = store
+            .create_task(
+                deps.as_mut(),
+                mock_info(ANYONE, &coins(10, NATIVE_DENOM)),
+                mock_env(),
+                task(Some("m".repeat(TASK_MEMO_MAX_LEN + 1))),
+            )
+            .unwrap_err();
+        assert_eq!(
+            ContractError::MemoTooLong {
+                max: TASK_MEMO_MAX_LEN
+            },
+            res_err
+        );
+        assert_eq!(0, store.task_total(&deps.storage)?);
+
+        let memo = "m".repeat(TASK_MEMO_MAX_LEN);
+        let res = store
+            .create_task(
+                deps.as_mut(),
+                mock_info(ANYONE, &coins(10, NATIVE_DENOM)),
+                mock_env(),
+                task(Some(memo.clone())),
+            )
+            .unwrap();
+        let attribute = |key: &str| {
+            res.attributes
+                .iter()
+                .find(|a| a.key == key)
+                .map(|a| a.value.clone())
+        };
+        assert_eq!(Some(memo.clone()), attribute("memo"));
+        let task_hash = attribute("task_hash").unwrap();
+        let created = store
+            .query_get_task(deps.as_ref(), mock_env(), task_hash)?
+            .unwrap();
+        assert_eq!(Some(memo), created.memo);
+
+        // not part of the hash, so only the memo differing still collides
+        let res_err = store
+            .create_task(
+                deps.as_mut(),
+                mock_info(ANYONE, &coins(10, NATIVE_DENOM)),
+                mock_env(),
+                task(Some("other".to_string())),
+            )
+            .unwrap_err();
+        assert_eq!(
+            ContractError::CustomError {
+                val: "Task already exists".to_string()
+            },
+            res_err
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn check_task_create_duplicate_actions() {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+
+        let action = |validator: &str, gas_limit: u64| Action {
+            msg: StakingMsg::Delegate {
+                validator: validator.to_string(),
+                amount: coin(1, NATIVE_DENOM),
+            }
+            .into(),
+            gas_limit: Some(gas_limit),
+        };
+        let create_task = |app: &mut App, actions: Vec<Action>| {
+            app.execute_contract(
+                Addr::unchecked(ANYONE),
+                contract_addr.clone(),
+                &ExecuteMsg::CreateTask {
+                    task: TaskRequest {
+                        actions,
+                        ..task_request()
+                    },
+                },
+                &coins(20, NATIVE_DENOM),
+            )
+        };
+        let warning = |res: &AppResponse| {
+            res.events
+                .iter()
+                .flat_map(|e| e.attributes.iter())
+                .find(|a| a.key == "warning")
+                .map(|a| a.value.clone())
+        };
+
+        // default policy only flags duplicates
+        let res = create_task(
+            &mut app,
+            vec![action("you", 150_000), action("you", 150_000)],
+        )
+        .unwrap();
+        assert_eq!(Some("duplicate_actions".to_string()), warning(&res));
+
+        let res = create_task(&mut app, vec![action("me", 150_000)]).unwrap();
+        assert_eq!(None, warning(&res));
+
+        app.execute_contract(
+            Addr::unchecked(ADMIN),
+            contract_addr.clone(),
+            &settings_msg(SettingsUpdate {
+                reject_duplicate_actions: Some(true),
+                ..Default::default()
+            }),
+            &[],
+        )
+        .unwrap();
+
+        // rejecting policy
+        let res_err = create_task(
+            &mut app,
+            vec![action("them", 150_000), action("them", 150_000)],
+        )
+        .unwrap_err();
+        assert_eq!(
+            ContractError::DuplicateActions {},
+            res_err.downcast().unwrap()
+        );
+        let task_count: u64 = app
+            .wrap()
+            .query_wasm_smart(&contract_addr, &QueryMsg::GetTaskCount {})
+            .unwrap();
+        assert_eq!(2, task_count);
+
+        // same msg with another gas limit is not an exact duplicate
+        let res = create_task(
+            &mut app,
+            vec![action("them", 150_000), action("them", 160_000)],
+        )
+        .unwrap();
+        assert_eq!(None, warning(&res));
+    }
+
+    #[test]
+    fn check_task_create_no_actions() -> StdResult<()> {
+        let mut deps = mock_dependencies();
+        let store = CwCroncat::default();
+        mock_init(&store, deps.as_mut()).unwrap();
+
+        let task = TaskRequest {
+            actions: vec![],
+            ..task_request()
+        };
+        let validated = store.query_validate_task(
+            deps.as_ref(),
+            mock_env(),
+            task.clone(),
+            coins(10, NATIVE_DENOM),
+            None,
+        )?;
+        assert!(!validated.is_valid);
+        assert!(validated
+            .errors
+            .contains(&ContractError::NoActions {}.to_string()));
+
+        let res_err = store
+            .create_task(
+                deps.as_mut(),
+                mock_info(ANYONE, &coins(10, NATIVE_DENOM)),
+                mock_env(),
+                task,
+            )
+            .unwrap_err();
+        assert_eq!(ContractError::NoActions {}, res_err);
+        assert_eq!(0, store.task_total(&deps.storage)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn query_validate_task_create_checks() -> StdResult<()> {
+        let mut deps = mock_dependencies();
+        let store = CwCroncat::default();
+        mock_init(&store, deps.as_mut()).unwrap();
+        store
+            .config
+            .update(&mut deps.storage, |mut c| -> StdResult<_> {
+                c.max_boundary_horizon = Some(BoundaryHorizon {
+                    blocks: 100,
+                    seconds: 600,
+                });
+                c.min_task_deposit = Some(coin(20, NATIVE_DENOM));
+                c.max_tasks_per_owner = Some(1);
+                c.max_tasks_per_slot = Some(1);
+                c.reject_duplicate_actions = true;
+                Ok(c)
+            })?;
+        // Takes the owner's only task & the current block slot's only place
+        store
+            .create_task(
+                deps.as_mut(),
+                mock_info(ANYONE, &coins(20, NATIVE_DENOM)),
+                mock_env(),
+                task_request(),
+            )
+            .unwrap();
+
+        let action = task_request().actions[0].clone();
+        let task = TaskRequest {
+            boundary: Boundary {
+                start: None,
+                end: Some(BoundarySpec::Height(mock_env().block.height + 1_000)),
+            },
+            memo: Some("m".repeat(TASK_MEMO_MAX_LEN + 1)),
+            actions: vec![action.clone(), action],
+            rules: Some(vec![Rule {
+                contract_addr: Addr::unchecked("Not_Normalized"),
+                msg: to_binary(&"{}")?,
+            }]),
+            agent_fee_override: Some(Uint128::MAX),
+            on_success_task: Some("not_a_task".to_string()),
+            ..task_request()
+        };
+        let validate = |owner_id: Option<&str>| {
+            store.query_validate_task(
+                deps.as_ref(),
+                mock_env(),
+                task.clone(),
+                coins(5, NATIVE_DENOM),
+                owner_id.map(Addr::unchecked),
+            )
+        };
+
+        let res = validate(Some(ANYONE))?;
+        assert!(!res.is_valid);
+        let errors: Vec<String> = [
+            ContractError::BoundaryBeyondHorizon {},
+            ContractError::MemoTooLong {
+                max: TASK_MEMO_MAX_LEN,
+            },
+            ContractError::DuplicateActions {},
+            ContractError::InvalidRules {
+                reason: "invalid contract address Not_Normalized".to_string(),
+            },
+            ContractError::DepositBelowMinimum {
+                min: coin(20, NATIVE_DENOM),
+            },
+            ContractError::MaxTasksPerOwner { max: 1 },
+            ContractError::CostOverflow {},
+            ContractError::ChainedTaskNotFound {
+                task_hash: "not_a_task".to_string(),
+            },
+            ContractError::MaxTasksPerSlot {
+                slot_id: mock_env().block.height,
+                max: 1,
+            },
+        ]
+        .iter()
+        .map(|err| err.to_string())
+        .collect();
+        assert_eq!(errors, res.errors);
+
+        // Without an owner, the per owner limit isn't checked
+        let res = validate(None)?;
+        let max_per_owner = ContractError::MaxTasksPerOwner { max: 1 }.to_string();
+        assert!(!res.errors.contains(&max_per_owner));
+        assert_eq!(errors.len() - 1, res.errors.len());
+
+        // create_task fails with the first of them
+        let res_err = store
+            .create_task(
+                deps.as_mut(),
+                mock_info(ANYONE, &coins(5, NATIVE_DENOM)),
+                mock_env(),
+                task,
+            )
+            .unwrap_err();
+        assert_eq!(ContractError::BoundaryBeyondHorizon {}, res_err);
+
+        Ok(())
+    }
+
+    #[test]
+    fn check_task_create_cost_overflow() -> StdResult<()> {
+        let mut deps = mock_dependencies();
+        let store = CwCroncat::default();
+        mock_init(&store, deps.as_mut()).unwrap();
+
+        let task = |gas_limits: Vec<u64>, agent_fee_override: Option<Uint128>| TaskRequest {
+            agent_fee_override,
+            actions: gas_limits
+                .into_iter()
+                .map(|gas_limit| Action {
+                    msg: StakingMsg::Delegate {
+                        validator: "you".to_string(),
+                        amount: coin(1, NATIVE_DENOM),
+                    }
+                    .into(),
+                    gas_limit: Some(gas_limit),
+                })
+                .collect(),
+            ..task_request()
+        };
+
+        // gas fee on top of a near u128 agent fee
+        let res_err = store
+            .create_task(
+                deps.as_mut(),
+                mock_info(ANYONE, &coins(10, NATIVE_DENOM)),
+                mock_env(),
+                task(vec![150_000], Some(Uint128::MAX - Uint128::new(1))),
+            )
+            .unwrap_err();
+        assert_eq!(ContractError::CostOverflow {}, res_err);
+
+        // gas sum past u64
+        store
+            .config
+            .update(&mut deps.storage, |mut c| -> StdResult<_> {
+                c.max_action_gas = u64::MAX;
+                Ok(c)
+            })?;
+        let res_err = store
+            .create_task(
+                deps.as_mut(),
+                mock_info(ANYONE, &coins(10, NATIVE_DENOM)),
+                mock_env(),
+                task(vec![u64::MAX - 1, u64::MAX - 1], None),
+            )
+            .unwrap_err();
+        assert_eq!(ContractError::CostOverflow {}, res_err);
+        assert_eq!(0, store.task_total(&deps.storage)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn check_task_create_rules() -> StdResult<()> {
+        let mut deps = mock_dependencies();
+        let store = CwCroncat::default();
+        mock_init(&store, deps.as_mut()).unwrap();
+
+        let task = |contract_addr: &str, msg: &[u8]| TaskRequest {
+            rules: Some(vec![Rule {
+                contract_addr: Addr::unchecked(contract_addr),
+                msg: Binary::from(msg.to_vec()),
+            }]),
+            ..task_request()
+        };
+
+        let malformed = vec![
+            (task("", br#"{"check":{}}"#), "invalid contract address "),
+            (
+                task("rules_contract", b"not json"),
+                "query msg must be a json object",
+            ),
+            (
+                task("rules_contract", br#"["check"]"#),
+                "query msg must be a json object",
+            ),
+            (task("rules_contract", b"{}"), "query msg is empty"),
+        ];
+        for (request, reason) in malformed {
+            let res_err = store
+                .create_task(
+                    deps.as_mut(),
+                    mock_info(ANYONE, &coins(10, NATIVE_DENOM)),
+                    mock_env(),
+                    request,
+                )
+                .unwrap_err();
+            assert_eq!(
+                ContractError::InvalidRules {
+                    reason: reason.to_string()
+                },
+                res_err
+            );
+        }
+        assert_eq!(0, store.task_total(&deps.storage)?);
+
+        store
+            .create_task(
+                deps.as_mut(),
+                mock_info(ANYONE, &coins(10, NATIVE_DENOM)),
+                mock_env(),
+                task("rules_contract", br#"{"check":{"pass":true}}"#),
+            )
+            .unwrap();
+        assert_eq!(1, store.task_total(&deps.storage)?);
+
+        Ok(())
+    }
+
+    #[test]
+    fn check_task_create_response_data() -> StdResult<()> {
+        let mut deps = mock_dependencies();
+        let store = CwCroncat::default();
+        mock_init(&store, deps.as_mut()).unwrap();
+
+        let res = store
+            .create_task(
+                deps.as_mut(),
+                mock_info(ANYONE, &coins(10, NATIVE_DENOM)),
+                mock_env(),
+                TaskRequest {
+                    interval: Interval::Cron("0 0 * * * *".to_string()),
+                    ..task_request()
+                },
+            )
+            .unwrap();
+
+        // Data matches the attributes kept for indexers
+        let data: CreateTaskResponse = from_binary(res.data.as_ref().unwrap())?;
+        let attr = |key: &str| {
+            res.attributes
+                .iter()
+                .find(|a| a.key == key)
+                .unwrap()
+                .value
+                .clone()
+        };
+        assert_eq!(attr("task_hash"), data.task_hash);
+        assert_eq!(attr("slot_id"), data.slot_id.to_string());
+        assert_eq!(SlotType::Cron, data.slot_kind);
+        assert!(store
+            .tasks
+            .may_load(&deps.storage, data.task_hash.into_bytes())?
+            .is_some());
+
+        Ok(())
+    }
+
+    #[test]
+    fn query_get_tasks_by_denom() -> StdResult<()> {
+        let mut deps = mock_dependencies();
+        let store = CwCroncat::default();
+        mock_init(&store, deps.as_mut()).unwrap();
+
+        let deposits = vec![
+            coins(10, NATIVE_DENOM),
+            coins(10, "juno"),
+            vec![coin(10, NATIVE_DENOM), coin(10, "juno")],
+            coins(10, "osmo"),
+        ];
+        for (i, funds) in deposits.into_iter().enumerate() {
+            store
+                .create_task(
+                    deps.as_mut(),
+                    mock_info(ANYONE, &funds),
+                    mock_env(),
+                    TaskRequest {
+                        actions: vec![Action {
+                            msg: StakingMsg::Delegate {
+                                validator: "you".to_string(),
+                                amount: coin(i as u128 + 1, NATIVE_DENOM),
+                            }
+                            .into(),
+                            gas_limit: Some(150_000),
+                        }],
+                        ..task_request()
+                    },
+                )
+                .unwrap();
+        }
+
+        for (denom, expected) in [(NATIVE_DENOM, 2), ("juno", 2), ("osmo", 1), ("junk", 0)] {
+            let tasks = store.query_get_tasks_by_denom(
+                deps.as_ref(),
+                mock_env(),
+                denom.to_string(),
+                None,
+                None,
+            )?;
+            assert_eq!(expected, tasks.len());
+            assert!(tasks
+                .iter()
+                .all(|t| t.total_deposit.iter().any(|c| c.denom == denom)));
+        }
+
+        // paging applies to the filtered tasks
+        let all = store.query_get_tasks_by_denom(
+            deps.as_ref(),
+            mock_env(),
+            "juno".to_string(),
+            None,
+            None,
+        )?;
+        let page = store.query_get_tasks_by_denom(
+            deps.as_ref(),
+            mock_env(),
+            "juno".to_string(),
+            Some(1),
+            Some(1),
+        )?;
+        assert_eq!(all[1..], page[..]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn query_get_tasks_under_gas() -> StdResult<()> {
+        let mut deps = mock_dependencies();
+        let store = CwCroncat::default();
+        mock_init(&store, deps.as_mut()).unwrap();
+
+        // gas sums of 100k, 200k, 250k & 300k
+        let gas_limits = vec![
+            vec![100_000],
+            vec![100_000, 100_000],
+            vec![250_000],
+            vec![150_000, 150_000],
+        ];
+        for gas_limits in gas_limits {
+            let actions = gas_limits
+                .into_iter()
+                .enumerate()
+                .map(|(i, gas)| Action {
+                    msg: StakingMsg::Delegate {
+                        validator: "you".to_string(),
+                        amount: coin(i as u128 + 1, NATIVE_DENOM),
+                    }
+                    .into(),
+                    gas_limit: Some(gas),
+                })
+                .collect();
+            store
+                .create_task(
+                    deps.as_mut(),
+                    mock_info(ANYONE, &coins(10, NATIVE_DENOM)),
+                    mock_env(),
+                    TaskRequest {
+                        actions,
+                        ..task_request()
+                    },
+                )
+                .unwrap();
+        }
+
+        let gas_totals = |tasks: Vec<TaskResponse>| -> Vec<u64> {
+            let mut totals: Vec<u64> = tasks
+                .iter()
+                .map(|t| t.actions.iter().filter_map(|a| a.gas_limit).sum())
+                .collect();
+            totals.sort_unstable();
+            totals
+        };
+        for (max_gas, expected) in [
+            (99_999, vec![]),
+            (100_000, vec![100_000]),
+            (250_000, vec![100_000, 200_000, 250_000]),
+            (1_000_000, vec![100_000, 200_000, 250_000, 300_000]),
+        ] {
+            let tasks =
+                store.query_get_tasks_under_gas(deps.as_ref(), mock_env(), max_gas, None, None)?;
+            assert_eq!(expected, gas_totals(tasks));
+        }
 
-        let cw_template_contract = CwTemplateContract(cw_template_contract_addr);
+        // paging applies to the filtered tasks
+        let all =
+            store.query_get_tasks_under_gas(deps.as_ref(), mock_env(), 250_000, None, None)?;
+        let page = store.query_get_tasks_under_gas(
+            deps.as_ref(),
+            mock_env(),
+            250_000,
+            Some(1),
+            Some(1),
+        )?;
+        assert_eq!(all[1..2], page[..]);
 
-        (app, cw_template_contract)
+        Ok(())
     }
 
     #[test]
-    fn query_task_hash_success() {
-        let (app, cw_template_contract) = proper_instantiate();
-        let contract_addr = cw_template_contract.addr();
+    fn query_owner_summary() -> StdResult<()> {
+        let mut deps = mock_dependencies();
+        let store = CwCroncat::default();
+        mock_init(&store, deps.as_mut()).unwrap();
 
-        let to_address = String::from("you");
-        let amount = coins(1015, "earth");
-        let bank = BankMsg::Send { to_address, amount };
-        let msg: CosmosMsg = bank.clone().into();
+        let env = mock_env();
+        let task = |interval: Interval, end: Option<BoundarySpec>| TaskRequest {
+            interval,
+            boundary: Boundary { start: None, end },
+            ..task_request()
+        };
+        let tasks = vec![
+            (
+                ANYONE,
+                task(Interval::Block(100), None),
+                coins(10, NATIVE_DENOM),
+            ),
+            // ends within a day
+            (
+                ANYONE,
+                task(
+                    Interval::Block(1_000),
+                    Some(BoundarySpec::Height(env.block.height + 1_500)),
+                ),
+                vec![coin(20, NATIVE_DENOM), coin(5, "juno")],
+            ),
+            // ends in two days
+            (
+                ANYONE,
+                task(
+                    Interval::Delay(60),
+                    Some(BoundarySpec::Time(env.block.time.plus_seconds(172_800))),
+                ),
+                coins(30, NATIVE_DENOM),
+            ),
+            (
+                VERY_RICH,
+                task(Interval::Block(10), None),
+                coins(40, NATIVE_DENOM),
+            ),
+        ];
+        for (owner, request, funds) in tasks {
+            store
+                .create_task(
+                    deps.as_mut(),
+                    mock_info(owner, &funds),
+                    env.clone(),
+                    request,
+                )
+                .unwrap();
+        }
 
-        let task = Task {
-            owner_id: Addr::unchecked("nobody".to_string()),
-            interval: Interval::Immediate,
-            boundary: Boundary {
-                start: None,
-                end: None,
+        let summary =
+            store.query_owner_summary(deps.as_ref(), env.clone(), Addr::unchecked(ANYONE))?;
+        let next_block = |blocks: u64| env.block.height - env.block.height % blocks + blocks;
+        assert_eq!(
+            GetOwnerSummaryResponse {
+                task_count: 3,
+                total_deposit: vec![coin(60, NATIVE_DENOM), coin(5, "juno")],
+                earliest_next_block: Some(next_block(100)),
+                latest_next_block: Some(next_block(1_000)),
+                earliest_next_time: Some(env.block.time.plus_seconds(60)),
+                latest_next_time: Some(env.block.time.plus_seconds(60)),
+                expiring_soon: 1,
+                truncated: false,
             },
-            stop_on_fail: false,
-            total_deposit: coins(37, "atom"),
-            actions: vec![Action {
-                msg,
-                gas_limit: Some(150_000),
-            }],
-            rules: None,
+            summary
+        );
+        assert_eq!(
+            store.query_get_owner_task_count(deps.as_ref(), Addr::unchecked(ANYONE))?,
+            summary.task_count
+        );
+
+        // no tasks
+        let summary = store.query_owner_summary(deps.as_ref(), env, Addr::unchecked(ADMIN))?;
+        assert_eq!(0, summary.task_count);
+        assert!(summary.total_deposit.is_empty());
+        assert_eq!(None, summary.earliest_next_block);
+        assert_eq!(None, summary.earliest_next_time);
+
+        Ok(())
+    }
+
+    #[test]
+    fn query_get_tasks_by_next_execution() -> StdResult<()> {
+        let mut deps = mock_dependencies();
+        let store = CwCroncat::default();
+        mock_init(&store, deps.as_mut()).unwrap();
+
+        let env = mock_env();
+        let task = |interval: Interval| TaskRequest {
+            interval,
+            ..task_request()
         };
+        let intervals = vec![
+            Interval::Block(1_000),
+            Interval::Delay(300),
+            Interval::Block(10),
+            Interval::At(env.block.time.plus_seconds(120)),
+            Interval::Block(100),
+            Interval::Delay(60),
+        ];
+        for interval in intervals {
+            store
+                .create_task(
+                    deps.as_mut(),
+                    mock_info(ANYONE, &coins(10, NATIVE_DENOM)),
+                    env.clone(),
+                    task(interval),
+                )
+                .unwrap();
+        }
 
-        // HASH CHECK!
-        let task_hash: String = app
-            .wrap()
-            .query_wasm_smart(
-                &contract_addr.clone(),
-                &QueryMsg::GetTaskHash {
-                    task: Box::new(task),
-                },
-            )
+        let slot_ids =
+            |tasks: &[TaskNextExecution]| -> Vec<u64> { tasks.iter().map(|t| t.slot_id).collect() };
+        let next_block = |blocks: u64| env.block.height - env.block.height % blocks + blocks;
+        let next_time = |seconds: u64| env.block.time.plus_seconds(seconds).nanos();
+
+        let res = store.query_get_tasks_by_next_execution(deps.as_ref(), env.clone(), None)?;
+        assert_eq!(
+            vec![next_block(10), next_block(100), next_block(1_000)],
+            slot_ids(&res.block_tasks)
+        );
+        assert_eq!(
+            vec![next_time(60), next_time(120), next_time(300)],
+            slot_ids(&res.time_tasks)
+        );
+        let soonest = store
+            .query_get_task(
+                deps.as_ref(),
+                mock_env(),
+                res.block_tasks[0].task_hash.clone(),
+            )?
             .unwrap();
+        assert_eq!(Interval::Block(10), soonest.interval);
+
+        // limit applies to each kind
+        let res = store.query_get_tasks_by_next_execution(deps.as_ref(), env.clone(), Some(2))?;
         assert_eq!(
-            "3ccb739ea050ebbd2e08f74aeb0b7aa081b15fa78504cba44155ec774452bbee",
-            task_hash
+            vec![next_block(10), next_block(100)],
+            slot_ids(&res.block_tasks)
+        );
+        assert_eq!(
+            vec![next_time(60), next_time(120)],
+            slot_ids(&res.time_tasks)
         );
+
+        Ok(())
     }
 
     #[test]
-    fn query_validate_interval_success() {
-        let (app, cw_template_contract) = proper_instantiate();
-        let contract_addr = cw_template_contract.addr();
+    fn query_get_task_schedule() -> StdResult<()> {
+        let mut deps = mock_dependencies();
+        let mut store = CwCroncat::default();
+        mock_init(&store, deps.as_mut()).unwrap();
 
-        let intervals: Vec<Interval> = vec![
-            Interval::Once,
-            Interval::Immediate,
-            Interval::Block(12345),
-            Interval::Cron("0 0 * * * *".to_string()),
-        ];
-        for i in intervals.iter() {
-            let valid: bool = app
-                .wrap()
-                .query_wasm_smart(
-                    &contract_addr.clone(),
-                    &QueryMsg::ValidateInterval {
-                        interval: i.to_owned(),
-                    },
+        let env = mock_env();
+        let task = |interval: Interval| TaskRequest {
+            interval,
+            ..task_request()
+        };
+        let mut hashes = vec![];
+        for interval in [Interval::Block(10), Interval::Delay(60)] {
+            let res = store
+                .create_task(
+                    deps.as_mut(),
+                    mock_info(ANYONE, &coins(10, NATIVE_DENOM)),
+                    env.clone(),
+                    task(interval),
                 )
                 .unwrap();
-            assert!(valid);
+            let task_hash = from_binary::<CreateTaskResponse>(&res.data.unwrap())?.task_hash;
+            hashes.push(task_hash);
         }
+
+        let next_block = env.block.height - env.block.height % 10 + 10;
+        assert_eq!(
+            Some((SlotType::Block, next_block)),
+            store.query_get_task_schedule(deps.as_ref(), hashes[0].clone())?
+        );
+        let next_time = env.block.time.plus_seconds(60).nanos();
+        assert_eq!(
+            Some((SlotType::Cron, next_time)),
+            store.query_get_task_schedule(deps.as_ref(), hashes[1].clone())?
+        );
+
+        // taken out of its slot, as during an execution, the task is still around
+        store.pop_slot_item(&mut deps.storage, &next_block, &SlotType::Block);
+        assert_eq!(
+            None,
+            store.query_get_task_schedule(deps.as_ref(), hashes[0].clone())?
+        );
+
+        // unknown task
+        let err = store
+            .query_get_task_schedule(deps.as_ref(), "not_a_task".to_string())
+            .unwrap_err();
+        assert_eq!(StdError::generic_err("No task found by hash"), err);
+
+        Ok(())
     }
 
     #[test]
-    fn query_get_tasks() {
+    fn query_task_next_slot() {
         let (mut app, cw_template_contract) = proper_instantiate();
         let contract_addr = cw_template_contract.addr();
 
-        let validator = String::from("you");
-        let amount = coin(3, "atom");
-        let stake = StakingMsg::Delegate { validator, amount };
-        let msg: CosmosMsg = stake.clone().into();
-
-        let create_task_msg = ExecuteMsg::CreateTask {
-            task: TaskRequest {
-                interval: Interval::Immediate,
-                boundary: Boundary {
-                    start: None,
-                    end: None,
-                },
-                stop_on_fail: false,
-                actions: vec![Action {
-                    msg,
-                    gas_limit: Some(150_000),
-                }],
-                rules: None,
-            },
+        let task = |interval: Interval, end: Option<BoundarySpec>| TaskRequest {
+            interval,
+            boundary: Boundary { start: None, end },
+            ..task_request()
         };
+        let end_height = app.block_info().height + 20;
+        let mut hashes = vec![];
+        for (interval, end) in [
+            (Interval::Block(10), None),
+            (Interval::Cron("0 * * * * *".to_string()), None),
+            (Interval::Delay(60), None),
+            (Interval::Block(10), Some(BoundarySpec::Height(end_height))),
+        ] {
+            hashes.push(create_task_hash(
+                &mut app,
+                &contract_addr,
+                task(interval, end),
+            ));
+        }
 
-        // create a task
-        app.execute_contract(
-            Addr::unchecked(ANYONE),
-            contract_addr.clone(),
-            &create_task_msg,
-            &coins(37, "atom"),
-        )
-        .unwrap();
+        // matches the slot each task was scheduled in
+        for task_hash in hashes.iter() {
+            let task: Option<TaskResponse> = app
+                .wrap()
+                .query_wasm_smart(
+                    &contract_addr,
+                    &QueryMsg::GetTask {
+                        task_hash: task_hash.clone(),
+                    },
+                )
+                .unwrap();
+            let scheduled: Option<(SlotType, u64)> = app
+                .wrap()
+                .query_wasm_smart(
+                    &contract_addr,
+                    &QueryMsg::GetTaskSchedule {
+                        task_hash: task_hash.clone(),
+                    },
+                )
+                .unwrap();
+            assert!(scheduled.is_some());
+            assert_eq!(scheduled, task.unwrap().next_slot);
+        }
 
-        // check storage has the task
-        let all_tasks: Vec<TaskResponse> = app
+        // past its end height the bounded task has no next slot
+        app.update_block(|block| block.height = end_height + 1);
+        let tasks: Vec<TaskResponse> = app
             .wrap()
             .query_wasm_smart(
-                &contract_addr.clone(),
+                &contract_addr,
                 &QueryMsg::GetTasks {
                     from_index: None,
                     limit: None,
+                    order_by: None,
                 },
             )
             .unwrap();
-        assert_eq!(all_tasks.len(), 1);
+        for task in tasks {
+            if task.task_hash == hashes[3] {
+                assert_eq!(None, task.next_slot);
+            } else {
+                assert!(task.next_slot.is_some());
+            }
+        }
+    }
 
-        let owner_tasks: Vec<TaskResponse> = app
-            .wrap()
-            .query_wasm_smart(
-                &contract_addr.clone(),
-                &QueryMsg::GetTasksByOwner {
-                    owner_id: Addr::unchecked(ANYONE),
-                },
-            )
-            .unwrap();
-        assert_eq!(owner_tasks.len(), 1);
+    #[test]
+    fn query_get_tasks_by_hashes() {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+
+        let task = |validator: &str| TaskRequest {
+            interval: Interval::Block(10),
+            actions: vec![Action {
+                msg: StakingMsg::Delegate {
+                    validator: validator.to_string(),
+                    amount: coin(1, NATIVE_DENOM),
+                }
+                .into(),
+                gas_limit: Some(150_000),
+            }],
+            ..task_request()
+        };
+        let mut hashes = vec![];
+        for validator in ["alice", "bob", "carol"] {
+            hashes.push(create_task_hash(&mut app, &contract_addr, task(validator)));
+        }
+        let tasks_by_hashes = |task_hashes: Vec<String>| -> StdResult<Vec<Option<TaskResponse>>> {
+            app.wrap()
+                .query_wasm_smart(&contract_addr, &QueryMsg::GetTasksByHashes { task_hashes })
+        };
+
+        // same order as asked, the missing hash in its place
+        let task_hashes = vec![
+            hashes[2].clone(),
+            "not_a_task".to_string(),
+            hashes[0].clone(),
+            hashes[1].clone(),
+        ];
+        let tasks = tasks_by_hashes(task_hashes.clone()).unwrap();
+        assert_eq!(4, tasks.len());
+        assert!(tasks[1].is_none());
+        for (task_hash, task) in task_hashes.iter().zip(tasks.iter()) {
+            if let Some(task) = task {
+                assert_eq!(task_hash, &task.task_hash);
+                let single: Option<TaskResponse> = app
+                    .wrap()
+                    .query_wasm_smart(
+                        &contract_addr,
+                        &QueryMsg::GetTask {
+                            task_hash: task_hash.clone(),
+                        },
+                    )
+                    .unwrap();
+                assert_eq!(Some(task.clone()), single);
+            }
+        }
+        assert_eq!(3, tasks.iter().flatten().count());
+
+        // capped input
+        let err =
+            tasks_by_hashes(vec![hashes[0].clone(); GET_TASKS_MAX_LIMIT as usize + 1]).unwrap_err();
+        assert!(err.to_string().contains(&format!(
+            "At most {} task hashes per query",
+            GET_TASKS_MAX_LIMIT
+        )));
     }
 
     #[test]
-    fn query_get_tasks_pagination() {
+    fn query_get_tasks_created_between() -> StdResult<()> {
+        let mut deps = mock_dependencies();
+        let store = CwCroncat::default();
+        mock_init(&store, deps.as_mut()).unwrap();
+
+        // one task every 100 seconds
+        let start = mock_env().block.time;
+        for i in 0..4u64 {
+            let mut env = mock_env();
+            env.block.time = start.plus_seconds(i * 100);
+            store
+                .create_task(
+                    deps.as_mut(),
+                    mock_info(ANYONE, &coins(10, NATIVE_DENOM)),
+                    env,
+                    TaskRequest {
+                        actions: vec![Action {
+                            msg: StakingMsg::Delegate {
+                                validator: "you".to_string(),
+                                amount: coin(i as u128 + 1, NATIVE_DENOM),
+                            }
+                            .into(),
+                            gas_limit: Some(150_000),
+                        }],
+                        ..task_request()
+                    },
+                )
+                .unwrap();
+        }
+
+        // window bounds are inclusive
+        for (from, to, expected) in [(50, 200, 2), (0, 300, 4), (100, 100, 1), (350, 500, 0)] {
+            let (from, to) = (start.plus_seconds(from), start.plus_seconds(to));
+            let tasks = store.query_get_tasks_created_between(
+                deps.as_ref(),
+                mock_env(),
+                from,
+                to,
+                None,
+                None,
+            )?;
+            assert_eq!(expected, tasks.len());
+            assert!(tasks
+                .iter()
+                .all(|t| from <= t.created_at_time && t.created_at_time <= to));
+        }
+
+        // paging applies to the filtered tasks
+        let end = start.plus_seconds(300);
+        let all = store.query_get_tasks_created_between(
+            deps.as_ref(),
+            mock_env(),
+            start,
+            end,
+            None,
+            None,
+        )?;
+        let page = store.query_get_tasks_created_between(
+            deps.as_ref(),
+            mock_env(),
+            start,
+            end,
+            Some(1),
+            Some(2),
+        )?;
+        assert_eq!(all[1..3], page[..]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn query_get_tasks_for_agent() -> StdResult<()> {
+        let mut deps = mock_dependencies();
+        let store = CwCroncat::default();
+        mock_init(&store, deps.as_mut()).unwrap();
+
+        let preferred_agents = vec![None, Some("alice"), Some("bob"), Some("bob")];
+        for (i, preferred_agent) in preferred_agents.into_iter().enumerate() {
+            store
+                .create_task(
+                    deps.as_mut(),
+                    mock_info(ANYONE, &coins(10, NATIVE_DENOM)),
+                    mock_env(),
+                    TaskRequest {
+                        preferred_agent: preferred_agent.map(Addr::unchecked),
+                        actions: vec![Action {
+                            msg: StakingMsg::Delegate {
+                                validator: "you".to_string(),
+                                amount: coin(i as u128 + 1, NATIVE_DENOM),
+                            }
+                            .into(),
+                            gas_limit: Some(150_000),
+                        }],
+                        ..task_request()
+                    },
+                )
+                .unwrap();
+        }
+
+        // preferred tasks plus the general ones
+        for (agent, expected) in [("alice", 2), ("bob", 3), ("carol", 1)] {
+            let tasks = store.query_get_tasks_for_agent(
+                deps.as_ref(),
+                mock_env(),
+                Addr::unchecked(agent),
+                None,
+                None,
+            )?;
+            assert_eq!(expected, tasks.len());
+            assert!(tasks.iter().all(|t| t.preferred_agent.is_none()
+                || t.preferred_agent == Some(Addr::unchecked(agent))));
+        }
+
+        // pages through the same matching tasks
+        let all = store.query_get_tasks_for_agent(
+            deps.as_ref(),
+            mock_env(),
+            Addr::unchecked("bob"),
+            None,
+            None,
+        )?;
+        let page = store.query_get_tasks_for_agent(
+            deps.as_ref(),
+            mock_env(),
+            Addr::unchecked("bob"),
+            Some(1),
+            Some(1),
+        )?;
+        assert_eq!(all[1..2], page[..]);
+
+        Ok(())
+    }
+
+    #[test]
+    fn query_slot_tasks_pagination() -> StdResult<()> {
+        let mut deps = mock_dependencies();
+        let store = CwCroncat::default();
+        mock_init(&store, deps.as_mut()).unwrap();
+
+        // every immediate task in the same block shares a slot
+        for i in 1..=200u128 {
+            store
+                .create_task(
+                    deps.as_mut(),
+                    mock_info(ANYONE, &coins(10, NATIVE_DENOM)),
+                    mock_env(),
+                    TaskRequest {
+                        actions: vec![Action {
+                            msg: StakingMsg::Delegate {
+                                validator: "you".to_string(),
+                                amount: coin(i, NATIVE_DENOM),
+                            }
+                            .into(),
+                            gas_limit: Some(150_000),
+                        }],
+                        ..task_request()
+                    },
+                )
+                .unwrap();
+        }
+        let slot_id = mock_env().block.height;
+        let all_hashes: Vec<String> = store
+            .block_slots
+            .load(&deps.storage, slot_id)?
+            .into_iter()
+            .map(|h| String::from_utf8(h).unwrap())
+            .collect();
+        assert_eq!(200, all_hashes.len());
+
+        let mut paged_hashes: Vec<String> = vec![];
+        for page in 0..5u64 {
+            let res = store.query_slot_tasks(
+                deps.as_ref(),
+                Some((SlotType::Block, slot_id)),
+                Some(page * 50),
+                Some(50),
+                None,
+            )?;
+            // id stays accurate, even past the last page
+            assert_eq!(slot_id, res.block_id);
+            assert_eq!(if page < 4 { 50 } else { 0 }, res.block_task_hash.len());
+            paged_hashes.extend(res.block_task_hash);
+        }
+        assert_eq!(all_hashes, paged_hashes);
+
+        // default page size
+        let res = store.query_slot_tasks(deps.as_ref(), None, None, None, None)?;
+        assert_eq!(slot_id, res.block_id);
+        assert_eq!(all_hashes[..100].to_vec(), res.block_task_hash);
+
+        Ok(())
+    }
+
+    #[test]
+    fn check_remove_create() -> StdResult<()> {
         let (mut app, cw_template_contract) = proper_instantiate();
         let contract_addr = cw_template_contract.addr();
 
         let validator = String::from("you");
-        let tasks_amnt: u64 = 10;
-        let from_index = 3;
-        let limit = 2;
-        let new_msg = |amount| ExecuteMsg::CreateTask {
+        let amount = coin(3, "atom");
+        let stake = StakingMsg::Delegate { validator, amount };
+        let msg: CosmosMsg = stake.clone().into();
+
+        let create_task_msg = ExecuteMsg::CreateTask {
             task: TaskRequest {
-                interval: Interval::Immediate,
-                boundary: Boundary {
-                    start: None,
-                    end: None,
-                },
-                stop_on_fail: false,
                 actions: vec![Action {
-                    msg: StakingMsg::Delegate {
-                        validator: validator.clone(),
-                        amount: coin(amount, "atom"),
-                    }
-                    .into(),
+                    msg,
                     gas_limit: Some(150_000),
                 }],
-                rules: None,
+                ..task_request()
             },
         };
+        let task_id_str =
+            "ad15b0f15010d57a51ff889d3400fe8d083a0dab2acfc752c5eb55e9e6281705".to_string();
 
-        // create a tasks
-        for amount in 1..tasks_amnt as u128 + 1 {
-            app.execute_contract(
-                Addr::unchecked(VERY_RICH),
-                contract_addr.clone(),
-                &new_msg(amount),
-                &coins(37, "atom"),
-            )
-            .unwrap();
-        }
-        let mut all_tasks: Vec<TaskResponse> = app
+        // create a task
+        app.execute_contract(
+            Addr::unchecked(ANYONE),
+            contract_addr.clone(),
+            &create_task_msg,
+            &coins(37, "atom"),
+        )
+        .unwrap();
+
+        // check storage DOES have the task
+        let new_task: Option<TaskResponse> = app
             .wrap()
             .query_wasm_smart(
                 &contract_addr.clone(),
-                &QueryMsg::GetTasks {
-                    from_index: None,
-                    limit: None,
+                &QueryMsg::GetTask {
+                    task_hash: task_id_str.clone(),
                 },
             )
             .unwrap();
-        assert_eq!(all_tasks.len(), tasks_amnt as usize);
+        assert!(new_task.is_some());
 
-        // check we get right amount of tasks
-        let part_of_tasks: Vec<TaskResponse> = app
+        // Confirm slot exists, proving task was scheduled
+        let slot_ids: GetSlotIdsResponse = app
             .wrap()
-            .query_wasm_smart(
-                &contract_addr.clone(),
-                &QueryMsg::GetTasks {
-                    from_index: Some(from_index),
-                    limit: None,
-                },
-            )
+            .query_wasm_smart(&contract_addr.clone(), &QueryMsg::GetSlotIds {})
             .unwrap();
-        let expected_amnt: usize = (tasks_amnt - from_index).try_into().unwrap();
-        assert_eq!(part_of_tasks.len(), expected_amnt);
-
-        println!(
-            "half_tasks: {:?}\n hash_vec:{:?}",
-            part_of_tasks
-                .iter()
-                .map(|t| t.task_hash.clone())
-                .collect::<Vec<String>>(),
-            all_tasks
-                .iter()
-                .map(|t| t.task_hash.clone())
-                .collect::<Vec<String>>(),
-        );
-
-        // Check it's in right order
-        for i in 0..expected_amnt {
-            assert_eq!(
-                all_tasks[from_index as usize + i].task_hash,
-                part_of_tasks[i].task_hash
-            );
-        }
+        let s_1: Vec<u64> = Vec::new();
+        assert_eq!(s_1, slot_ids.time_ids);
+        assert_eq!(vec![12345], slot_ids.block_ids);
 
-        // and with limit
-        let part_of_tasks: Vec<TaskResponse> = app
-            .wrap()
-            .query_wasm_smart(
-                &contract_addr.clone(),
-                &QueryMsg::GetTasks {
-                    from_index: Some(from_index),
-                    limit: Some(limit),
+        // Remove the Task
+        let res = app
+            .execute_contract(
+                Addr::unchecked(ANYONE),
+                contract_addr.clone(),
+                &ExecuteMsg::RemoveTask {
+                    task_hash: task_id_str.clone(),
                 },
+                &[],
             )
             .unwrap();
-        let expected_amnt: usize = (limit).try_into().unwrap();
-        assert_eq!(part_of_tasks.len(), expected_amnt);
 
-        // Edge cases
+        // Assert removed hash, owner & refund are part of event attributes
+        let mut has_removed_hash = false;
+        let mut has_owner = false;
+        let mut has_refund_amount = false;
+        for e in res.events {
+            for a in e.attributes {
+                if a.key == "task_hash" && a.value == task_id_str.clone() {
+                    has_removed_hash = true;
+                }
+                if a.key == "owner_id" && a.value == ANYONE {
+                    has_owner = true;
+                }
+                if a.key == "refund_amount" && a.value == "37atom" {
+                    has_refund_amount = true;
+                }
+            }
+        }
+        assert!(has_removed_hash);
+        assert!(has_owner);
+        assert!(has_refund_amount);
 
-        // Index out of bounds, so we return nothing
-        let from_index = tasks_amnt;
-        let out_of_bounds: Vec<TaskResponse> = app
+        // check storage DOESNT have the task
+        let rem_task: Option<TaskResponse> = app
             .wrap()
             .query_wasm_smart(
                 &contract_addr.clone(),
-                &QueryMsg::GetTasks {
-                    from_index: Some(from_index),
-                    limit: None,
+                &QueryMsg::GetTask {
+                    task_hash: task_id_str.clone(),
                 },
             )
             .unwrap();
-        assert!(out_of_bounds.is_empty());
+        assert!(rem_task.is_none());
 
-        // Returns as many elements as possible without a panic
-        let from_index = tasks_amnt - 2;
-        let two_last_elements: Vec<TaskResponse> = app
+        // Check the contract total balance has decreased from the removed task
+        let balances: GetBalancesResponse = app
             .wrap()
-            .query_wasm_smart(
-                &contract_addr.clone(),
-                &QueryMsg::GetTasks {
-                    from_index: Some(from_index),
-                    limit: Some(tasks_amnt),
-                },
-            )
+            .query_wasm_smart(&contract_addr.clone(), &QueryMsg::GetBalances {})
             .unwrap();
-        assert_eq!(two_last_elements.len(), 2);
+        assert_eq!(coins(0, "atom"), balances.available_balance.native);
 
-        // Removed task shouldn't reorder things
-        let removed_index = from_index as usize;
-        app.execute_contract(
-            Addr::unchecked(ANYONE),
-            contract_addr.clone(),
-            &ExecuteMsg::RemoveTask {
-                task_hash: all_tasks
-                    .remove(removed_index) // We removed hash from original vector to match
-                    .task_hash,
-            },
-            &vec![],
-        )
-        .unwrap();
-        let new_tasks: Vec<TaskResponse> = app
+        // Check the slots correctly removed the task
+        let slot_ids: GetSlotIdsResponse = app
             .wrap()
-            .query_wasm_smart(
-                &contract_addr.clone(),
-                &QueryMsg::GetTasks {
-                    from_index: None,
-                    limit: None,
-                },
-            )
+            .query_wasm_smart(&contract_addr.clone(), &QueryMsg::GetSlotIds {})
             .unwrap();
-        assert_eq!(new_tasks, all_tasks);
+        let s: Vec<u64> = Vec::new();
+        assert_eq!(s.clone(), slot_ids.time_ids);
+        assert_eq!(s, slot_ids.block_ids);
+
+        Ok(())
     }
 
     #[test]
-    fn check_task_create_fail_cases() -> StdResult<()> {
+    fn check_remove_refund_split() -> StdResult<()> {
         let (mut app, cw_template_contract) = proper_instantiate();
         let contract_addr = cw_template_contract.addr();
+        let alice = Addr::unchecked("alice");
+        let bob = Addr::unchecked("bob");
 
-        let validator = String::from("you");
-        let amount = coin(3, "atom");
-        let stake = StakingMsg::Delegate { validator, amount };
-        let msg: CosmosMsg = stake.clone().into();
-
-        let create_task_msg = ExecuteMsg::CreateTask {
+        let create_task_msg = |refund_split: Vec<(Addr, Decimal)>| ExecuteMsg::CreateTask {
             task: TaskRequest {
-                interval: Interval::Immediate,
-                boundary: Boundary {
-                    start: None,
-                    end: None,
-                },
-                stop_on_fail: false,
+                refund_split: Some(refund_split),
                 actions: vec![Action {
-                    msg: msg.clone(),
+                    msg: StakingMsg::Delegate {
+                        validator: String::from("you"),
+                        amount: coin(3, "atom"),
+                    }
+                    .into(),
                     gas_limit: Some(150_000),
                 }],
-                rules: None,
+                ..task_request()
             },
         };
-        // let task_id_str = "ad15b0f15010d57a51ff889d3400fe8d083a0dab2acfc752c5eb55e9e6281705".to_string();
-        // let task_id = task_id_str.clone().into_bytes();
 
-        // Must attach funds
+        // split doesn't sum to one
         let res_err = app
             .execute_contract(
                 Addr::unchecked(ANYONE),
                 contract_addr.clone(),
-                &create_task_msg,
-                &vec![],
+                &create_task_msg(vec![
+                    (alice.clone(), Decimal::percent(50)),
+                    (bob.clone(), Decimal::percent(40)),
+                ]),
+                &coins(37, "atom"),
             )
             .unwrap_err();
         assert_eq!(
             ContractError::CustomError {
-                val: "Must attach funds".to_string()
+                val: "Refund split must sum to 1".to_string()
             },
             res_err.downcast().unwrap()
         );
 
-        // Create task paused
-        let change_settings_msg = ExecuteMsg::UpdateSettings {
-            paused: Some(true),
-            owner_id: None,
-            // treasury_id: None,
-            agent_fee: None,
-            agents_eject_threshold: None,
-            gas_price: None,
-            proxy_callback_gas: None,
-            slot_granularity: None,
-            min_tasks_per_agent: None,
-        };
-        app.execute_contract(
-            Addr::unchecked(ADMIN),
-            contract_addr.clone(),
-            &change_settings_msg,
-            &vec![],
-        )
-        .unwrap();
+        // zero share
         let res_err = app
             .execute_contract(
                 Addr::unchecked(ANYONE),
                 contract_addr.clone(),
-                &create_task_msg,
-                &coins(13, "atom"),
+                &create_task_msg(vec![
+                    (alice.clone(), Decimal::one()),
+                    (bob.clone(), Decimal::zero()),
+                ]),
+                &coins(37, "atom"),
             )
             .unwrap_err();
         assert_eq!(
             ContractError::CustomError {
-                val: "Create task paused".to_string()
+                val: "Refund split shares must be non-zero".to_string()
             },
             res_err.downcast().unwrap()
         );
-        // Set it back
-        app.execute_contract(
-            Addr::unchecked(ADMIN),
-            contract_addr.clone(),
-            &ExecuteMsg::UpdateSettings {
-                paused: Some(false),
-                owner_id: None,
-                // treasury_id: None,
-                agent_fee: None,
-                agents_eject_threshold: None,
-                gas_price: None,
-                proxy_callback_gas: None,
-                slot_granularity: None,
-                min_tasks_per_agent: None,
-            },
-            &vec![],
-        )
-        .unwrap();
 
-        // Creator invalid
-        let action_self = CosmosMsg::Wasm(WasmMsg::Execute {
-            contract_addr: contract_addr.clone().into_string(),
-            funds: vec![],
-            msg: to_binary(&change_settings_msg.clone())?,
-        });
+        // recipient that isn't a valid address
         let res_err = app
             .execute_contract(
                 Addr::unchecked(ANYONE),
                 contract_addr.clone(),
-                &ExecuteMsg::CreateTask {
-                    task: TaskRequest {
-                        interval: Interval::Once,
-                        boundary: Boundary {
-                            start: None,
-                            end: None,
-                        },
-                        stop_on_fail: false,
-                        actions: vec![Action {
-                            msg: action_self.clone(),
-                            gas_limit: Some(150_000),
-                        }],
-                        rules: None,
-                    },
-                },
-                &coins(13, "atom"),
+                &create_task_msg(vec![
+                    (alice.clone(), Decimal::percent(50)),
+                    (Addr::unchecked("Bob"), Decimal::percent(50)),
+                ]),
+                &coins(37, "atom"),
             )
             .unwrap_err();
         assert_eq!(
             ContractError::CustomError {
-                val: "Actions Message Unsupported".to_string()
+                val: "Refund split recipient invalid".to_string()
             },
             res_err.downcast().unwrap()
         );
 
-        // Interval invalid
-        let res_err = app
+        // two-way split
+        let res = app
+            .execute_contract(
+                Addr::unchecked(ANYONE),
+                contract_addr.clone(),
+                &create_task_msg(vec![
+                    (alice.clone(), Decimal::percent(50)),
+                    (bob.clone(), Decimal::percent(50)),
+                ]),
+                &coins(37, "atom"),
+            )
+            .unwrap();
+        let task_hash = task_hash_attr(&res);
+
+        app.execute_contract(
+            Addr::unchecked(ANYONE),
+            contract_addr.clone(),
+            &ExecuteMsg::RemoveTask { task_hash },
+            &[],
+        )
+        .unwrap();
+
+        // rounding leftover goes to the last recipient
+        assert_eq!(
+            coin(18, "atom"),
+            app.wrap().query_balance(&alice, "atom").unwrap()
+        );
+        assert_eq!(
+            coin(19, "atom"),
+            app.wrap().query_balance(&bob, "atom").unwrap()
+        );
+        assert_eq!(
+            coin(63, "atom"),
+            app.wrap().query_balance(ANYONE, "atom").unwrap()
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn check_rekey_task_updates_chain() -> StdResult<()> {
+        let mut deps = mock_dependencies();
+        let store = CwCroncat::default();
+        mock_init(&store, deps.as_mut()).unwrap();
+        let mut create_task = |task: TaskRequest| -> String {
+            let res = store
+                .create_task(
+                    deps.as_mut(),
+                    mock_info(ANYONE, &coins(37, NATIVE_DENOM)),
+                    mock_env(),
+                    task,
+                )
+                .unwrap();
+            from_binary::<CreateTaskResponse>(&res.data.unwrap())
+                .unwrap()
+                .task_hash
+        };
+        let child_hash = create_task(task_request());
+        let parent_hash = create_task(TaskRequest {
+            interval: Interval::Block(10),
+            on_success_task: Some(child_hash.clone()),
+            ..task_request()
+        });
+
+        // A change to the child's hashed fields moves the parent's link along
+        let mut child = store
+            .tasks
+            .load(deps.as_ref().storage, child_hash.clone().into_bytes())?;
+        child.actions[0].gas_limit = Some(200_000);
+        store
+            .rekey_task(deps.as_mut().storage, child_hash.into_bytes(), &child)
+            .unwrap();
+        let parent = store
+            .tasks
+            .load(deps.as_ref().storage, parent_hash.into_bytes())?;
+        assert_eq!(Some(child.to_hash()), parent.on_success_task);
+
+        Ok(())
+    }
+
+    #[test]
+    fn check_update_task_gas() -> StdResult<()> {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+
+        let res = app
             .execute_contract(
                 Addr::unchecked(ANYONE),
                 contract_addr.clone(),
                 &ExecuteMsg::CreateTask {
                     task: TaskRequest {
-                        interval: Interval::Cron("faux_paw".to_string()),
-                        boundary: Boundary {
-                            start: None,
-                            end: None,
-                        },
-                        stop_on_fail: false,
                         actions: vec![Action {
-                            msg: msg.clone(),
+                            msg: StakingMsg::Delegate {
+                                validator: String::from("you"),
+                                amount: coin(3, "atom"),
+                            }
+                            .into(),
                             gas_limit: Some(150_000),
                         }],
-                        rules: None,
+                        ..task_request()
                     },
                 },
-                &coins(13, "atom"),
+                &coins(37, "atom"),
+            )
+            .unwrap();
+        let task_hash = task_hash_attr(&res);
+        let update_gas_msg = |gas_limit: u64| ExecuteMsg::UpdateTaskGas {
+            task_hash: task_hash.clone(),
+            gas_limits: vec![Some(gas_limit)],
+        };
+
+        // only the owner can update
+        let res_err = app
+            .execute_contract(
+                Addr::unchecked(ADMIN),
+                contract_addr.clone(),
+                &update_gas_msg(200_000),
+                &[],
             )
             .unwrap_err();
-        assert_eq!(
-            ContractError::CustomError {
-                val: "Interval invalid".to_string()
-            },
-            res_err.downcast().unwrap()
-        );
+        assert_eq!(ContractError::Unauthorized {}, res_err.downcast().unwrap());
 
-        // Task already exists
-        app.execute_contract(
-            Addr::unchecked(ANYONE),
-            contract_addr.clone(),
-            &create_task_msg,
-            &coins(13, "atom"),
-        )
-        .unwrap();
+        // can't go past the configured ceiling
         let res_err = app
             .execute_contract(
                 Addr::unchecked(ANYONE),
                 contract_addr.clone(),
-                &create_task_msg,
-                &coins(13, "atom"),
+                &update_gas_msg(3_000_001),
+                &[],
             )
             .unwrap_err();
         assert_eq!(
             ContractError::CustomError {
-                val: "Task already exists".to_string()
+                val: "Action gas limit exceeds max_action_gas".to_string()
             },
             res_err.downcast().unwrap()
         );
 
-        // Task ended
-        let res_err = app
+        let res = app
             .execute_contract(
                 Addr::unchecked(ANYONE),
                 contract_addr.clone(),
-                &ExecuteMsg::CreateTask {
-                    task: TaskRequest {
-                        interval: Interval::Block(12346),
-                        boundary: Boundary {
-                            start: None,
-                            end: Some(BoundarySpec::Height(1)),
-                        },
-                        stop_on_fail: false,
-                        actions: vec![Action {
-                            msg,
-                            gas_limit: Some(150_000),
-                        }],
-                        rules: None,
-                    },
-                },
-                &coins(13, "atom"),
+                &update_gas_msg(200_000),
+                &[],
             )
-            .unwrap_err();
-        assert_eq!(
-            ContractError::CustomError {
-                val: "Task ended".to_string()
-            },
-            res_err.downcast().unwrap()
-        );
+            .unwrap();
+        let new_hash = task_hash_attr(&res);
+        assert_ne!(task_hash, new_hash);
 
-        // TODO: (needs impl!) Not enough task balance to execute job
+        // task was re-keyed, along with its slot
+        let old_task: Option<TaskResponse> = app.wrap().query_wasm_smart(
+            contract_addr.clone(),
+            &QueryMsg::GetTask {
+                task_hash: task_hash.clone(),
+            },
+        )?;
+        assert!(old_task.is_none());
+        let new_task: Option<TaskResponse> = app.wrap().query_wasm_smart(
+            contract_addr.clone(),
+            &QueryMsg::GetTask {
+                task_hash: new_hash.clone(),
+            },
+        )?;
+        assert_eq!(Some(200_000), new_task.unwrap().actions[0].gas_limit);
+        let slot_info: GetSlotHashesResponse = app.wrap().query_wasm_smart(
+            contract_addr.clone(),
+            &QueryMsg::GetSlotHashes {
+                slot: None,
+                from_index: None,
+                limit: None,
+                due_only: false,
+            },
+        )?;
+        assert_eq!(vec![new_hash], slot_info.block_task_hash);
 
         Ok(())
     }
 
     #[test]
-    fn check_task_create_success() -> StdResult<()> {
+    fn check_update_task_gas_chained() -> StdResult<()> {
         let (mut app, cw_template_contract) = proper_instantiate();
         let contract_addr = cw_template_contract.addr();
-
-        let validator = String::from("you");
-        let amount = coin(3, "atom");
-        let stake = StakingMsg::Delegate { validator, amount };
-        let msg: CosmosMsg = stake.clone().into();
-
-        let create_task_msg = ExecuteMsg::CreateTask {
-            task: TaskRequest {
-                interval: Interval::Immediate,
-                boundary: Boundary {
-                    start: None,
-                    end: None,
-                },
-                stop_on_fail: false,
-                actions: vec![Action {
-                    msg,
-                    gas_limit: Some(150_000),
-                }],
-                rules: None,
-            },
+        let mut create_task = |task: TaskRequest| -> String {
+            let res = app
+                .execute_contract(
+                    Addr::unchecked(ANYONE),
+                    contract_addr.clone(),
+                    &ExecuteMsg::CreateTask { task },
+                    &coins(37, "atom"),
+                )
+                .unwrap();
+            task_hash_attr(&res)
         };
-        let task_id_str =
-            "ad15b0f15010d57a51ff889d3400fe8d083a0dab2acfc752c5eb55e9e6281705".to_string();
+        let child_hash = create_task(task_request());
+        let parent_hash = create_task(TaskRequest {
+            interval: Interval::Block(10),
+            on_success_task: Some(child_hash.clone()),
+            ..task_request()
+        });
 
-        // create a task
         let res = app
             .execute_contract(
                 Addr::unchecked(ANYONE),
                 contract_addr.clone(),
-                &create_task_msg,
-                &coins(37, "atom"),
-            )
-            .unwrap();
-        // Assert task hash is returned as part of event attributes
-        let mut has_created_hash: bool = false;
-        for e in res.events {
-            for a in e.attributes {
-                if a.key == "task_hash" && a.value == task_id_str.clone() {
-                    has_created_hash = true;
-                }
-            }
-        }
-        assert!(has_created_hash);
-
-        // check storage has the task
-        let new_task: Option<TaskResponse> = app
-            .wrap()
-            .query_wasm_smart(
-                &contract_addr.clone(),
-                &QueryMsg::GetTask {
-                    task_hash: task_id_str.clone(),
+                &ExecuteMsg::UpdateTaskGas {
+                    task_hash: child_hash.clone(),
+                    gas_limits: vec![Some(200_000)],
                 },
+                &[],
             )
             .unwrap();
-        assert!(new_task.is_some());
-        if let Some(t) = new_task {
-            assert_eq!(Addr::unchecked(ANYONE), t.owner_id);
-            assert_eq!(Interval::Immediate, t.interval);
-            assert_eq!(
-                Boundary {
-                    start: None,
-                    end: None,
-                },
-                t.boundary
-            );
-            assert_eq!(false, t.stop_on_fail);
-            assert_eq!(coins(37, "atom"), t.total_deposit);
-            assert_eq!(task_id_str.clone(), t.task_hash);
-        }
-
-        // get slot ids
-        let slot_ids: GetSlotIdsResponse = app
-            .wrap()
-            .query_wasm_smart(&contract_addr.clone(), &QueryMsg::GetSlotIds {})
-            .unwrap();
-        let s_1: Vec<u64> = Vec::new();
-        assert_eq!(s_1, slot_ids.time_ids);
-        assert_eq!(vec![12346], slot_ids.block_ids);
+        let new_hash = task_hash_attr(&res);
+        assert_ne!(child_hash, new_hash);
 
-        // get slot hashs
-        let slot_info: GetSlotHashesResponse = app
-            .wrap()
-            .query_wasm_smart(
-                &contract_addr.clone(),
-                &QueryMsg::GetSlotHashes { slot: None },
-            )
-            .unwrap();
-        let s_3: Vec<String> = Vec::new();
-        assert_eq!(12346, slot_info.block_id);
-        assert_eq!(vec![task_id_str.clone()], slot_info.block_task_hash);
-        assert_eq!(0, slot_info.time_id);
-        assert_eq!(s_3, slot_info.time_task_hash);
+        // The parent still chains the child, under its new hash
+        let parent: Option<TaskResponse> = app.wrap().query_wasm_smart(
+            contract_addr.clone(),
+            &QueryMsg::GetTask {
+                task_hash: parent_hash,
+            },
+        )?;
+        assert_eq!(Some(new_hash), parent.unwrap().on_success_task);
 
         Ok(())
     }
 
     #[test]
-    fn check_remove_create() -> StdResult<()> {
+    fn check_transfer_task_ownership_chained() -> StdResult<()> {
         let (mut app, cw_template_contract) = proper_instantiate();
         let contract_addr = cw_template_contract.addr();
+        let mut create_task = |task: TaskRequest| -> String {
+            let res = app
+                .execute_contract(
+                    Addr::unchecked(ANYONE),
+                    contract_addr.clone(),
+                    &ExecuteMsg::CreateTask { task },
+                    &coins(37, "atom"),
+                )
+                .unwrap();
+            task_hash_attr(&res)
+        };
+        let child_hash = create_task(task_request());
+        let parent_hash = create_task(TaskRequest {
+            interval: Interval::Block(10),
+            on_success_task: Some(child_hash.clone()),
+            ..task_request()
+        });
 
-        let validator = String::from("you");
-        let amount = coin(3, "atom");
-        let stake = StakingMsg::Delegate { validator, amount };
-        let msg: CosmosMsg = stake.clone().into();
-
-        let create_task_msg = ExecuteMsg::CreateTask {
-            task: TaskRequest {
-                interval: Interval::Immediate,
-                boundary: Boundary {
-                    start: None,
-                    end: None,
+        // Neither end of a chain can move to another owner
+        for task_hash in [child_hash.clone(), parent_hash.clone()] {
+            let res_err = app
+                .execute_contract(
+                    Addr::unchecked(ANYONE),
+                    contract_addr.clone(),
+                    &ExecuteMsg::TransferTaskOwnership {
+                        task_hash,
+                        new_owner: Addr::unchecked(ADMIN),
+                    },
+                    &[],
+                )
+                .unwrap_err();
+            assert_eq!(
+                ContractError::CustomError {
+                    val: "Chained tasks can't change owner".to_string()
                 },
-                stop_on_fail: false,
-                actions: vec![Action {
-                    msg,
-                    gas_limit: Some(150_000),
-                }],
-                rules: None,
+                res_err.downcast().unwrap()
+            );
+        }
+        let parent: Option<TaskResponse> = app.wrap().query_wasm_smart(
+            contract_addr.clone(),
+            &QueryMsg::GetTask {
+                task_hash: parent_hash,
             },
-        };
-        let task_id_str =
-            "ad15b0f15010d57a51ff889d3400fe8d083a0dab2acfc752c5eb55e9e6281705".to_string();
+        )?;
+        assert_eq!(Some(child_hash), parent.unwrap().on_success_task);
 
-        // create a task
-        app.execute_contract(
-            Addr::unchecked(ANYONE),
-            contract_addr.clone(),
-            &create_task_msg,
-            &coins(37, "atom"),
-        )
-        .unwrap();
+        Ok(())
+    }
 
-        // check storage DOES have the task
-        let new_task: Option<TaskResponse> = app
-            .wrap()
-            .query_wasm_smart(
-                &contract_addr.clone(),
-                &QueryMsg::GetTask {
-                    task_hash: task_id_str.clone(),
+    #[test]
+    fn check_transfer_task_ownership() -> StdResult<()> {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+
+        let res = app
+            .execute_contract(
+                Addr::unchecked(ANYONE),
+                contract_addr.clone(),
+                &ExecuteMsg::CreateTask {
+                    task: TaskRequest {
+                        actions: vec![Action {
+                            msg: StakingMsg::Delegate {
+                                validator: String::from("you"),
+                                amount: coin(3, "atom"),
+                            }
+                            .into(),
+                            gas_limit: Some(150_000),
+                        }],
+                        ..task_request()
+                    },
                 },
+                &coins(37, "atom"),
             )
             .unwrap();
-        assert!(new_task.is_some());
+        let task_hash = task_hash_attr(&res);
+        let transfer_msg = ExecuteMsg::TransferTaskOwnership {
+            task_hash: task_hash.clone(),
+            new_owner: Addr::unchecked(ADMIN),
+        };
 
-        // Confirm slot exists, proving task was scheduled
-        let slot_ids: GetSlotIdsResponse = app
-            .wrap()
-            .query_wasm_smart(&contract_addr.clone(), &QueryMsg::GetSlotIds {})
+        // only the owner can transfer
+        let res_err = app
+            .execute_contract(
+                Addr::unchecked(ADMIN),
+                contract_addr.clone(),
+                &transfer_msg,
+                &[],
+            )
+            .unwrap_err();
+        assert_eq!(ContractError::Unauthorized {}, res_err.downcast().unwrap());
+
+        let res = app
+            .execute_contract(
+                Addr::unchecked(ANYONE),
+                contract_addr.clone(),
+                &transfer_msg,
+                &[],
+            )
             .unwrap();
-        let s_1: Vec<u64> = Vec::new();
-        assert_eq!(s_1, slot_ids.time_ids);
-        assert_eq!(vec![12346], slot_ids.block_ids);
+        let new_hash = task_hash_attr(&res);
+        assert_ne!(task_hash, new_hash);
 
-        // Remove the Task
-        app.execute_contract(
-            Addr::unchecked(ANYONE),
+        // task was re-keyed, along with its slot & owner index
+        let old_task: Option<TaskResponse> = app.wrap().query_wasm_smart(
             contract_addr.clone(),
-            &ExecuteMsg::RemoveTask {
-                task_hash: task_id_str.clone(),
+            &QueryMsg::GetTask {
+                task_hash: task_hash.clone(),
             },
-            &vec![],
-        )
-        .unwrap();
+        )?;
+        assert!(old_task.is_none());
+        let slot_info: GetSlotHashesResponse = app.wrap().query_wasm_smart(
+            contract_addr.clone(),
+            &QueryMsg::GetSlotHashes {
+                slot: None,
+                from_index: None,
+                limit: None,
+                due_only: false,
+            },
+        )?;
+        assert_eq!(vec![new_hash.clone()], slot_info.block_task_hash);
+        let old_owner_tasks: Vec<TaskResponse> = app.wrap().query_wasm_smart(
+            contract_addr.clone(),
+            &QueryMsg::GetTasksByOwner {
+                owner_id: Addr::unchecked(ANYONE),
+            },
+        )?;
+        assert!(old_owner_tasks.is_empty());
+        let new_owner_tasks: Vec<TaskResponse> = app.wrap().query_wasm_smart(
+            contract_addr.clone(),
+            &QueryMsg::GetTasksByOwner {
+                owner_id: Addr::unchecked(ADMIN),
+            },
+        )?;
+        assert_eq!(1, new_owner_tasks.len());
+        assert_eq!(new_hash, new_owner_tasks[0].task_hash);
+        assert_eq!(Addr::unchecked(ADMIN), new_owner_tasks[0].owner_id);
 
-        // check storage DOESNT have the task
-        let rem_task: Option<TaskResponse> = app
-            .wrap()
-            .query_wasm_smart(
-                &contract_addr.clone(),
-                &QueryMsg::GetTask {
-                    task_hash: task_id_str.clone(),
+        // the old owner lost the refill & remove rights
+        let res_err = app
+            .execute_contract(
+                Addr::unchecked(ANYONE),
+                contract_addr.clone(),
+                &ExecuteMsg::RefillTaskBalance {
+                    task_hash: new_hash.clone(),
+                    allow_new_denoms: None,
                 },
+                &coins(3, "atom"),
+            )
+            .unwrap_err();
+        assert_eq!(
+            ContractError::CustomError {
+                val: "Only owner can refill their task".to_string()
+            },
+            res_err.downcast().unwrap()
+        );
+        let res_err = app
+            .execute_contract(
+                Addr::unchecked(ANYONE),
+                contract_addr.clone(),
+                &ExecuteMsg::RemoveTask {
+                    task_hash: new_hash.clone(),
+                },
+                &[],
+            )
+            .unwrap_err();
+        assert_eq!(ContractError::Unauthorized {}, res_err.downcast().unwrap());
+
+        // the new owner has them, and gets the refund
+        app.execute_contract(
+            Addr::unchecked(ADMIN),
+            contract_addr.clone(),
+            &ExecuteMsg::RefillTaskBalance {
+                task_hash: new_hash.clone(),
+                allow_new_denoms: None,
+            },
+            &coins(3, "atom"),
+        )
+        .unwrap();
+        let res = app
+            .execute_contract(
+                Addr::unchecked(ADMIN),
+                contract_addr.clone(),
+                &ExecuteMsg::RemoveTask {
+                    task_hash: new_hash,
+                },
+                &[],
             )
             .unwrap();
-        assert!(rem_task.is_none());
-
-        // Check the contract total balance has decreased from the removed task
-        let balances: GetBalancesResponse = app
-            .wrap()
-            .query_wasm_smart(&contract_addr.clone(), &QueryMsg::GetBalances {})
-            .unwrap();
-        assert_eq!(coins(0, "atom"), balances.available_balance.native);
-
-        // Check the slots correctly removed the task
-        let slot_ids: GetSlotIdsResponse = app
-            .wrap()
-            .query_wasm_smart(&contract_addr.clone(), &QueryMsg::GetSlotIds {})
-            .unwrap();
-        let s: Vec<u64> = Vec::new();
-        assert_eq!(s.clone(), slot_ids.time_ids);
-        assert_eq!(s, slot_ids.block_ids);
+        assert!(res
+            .events
+            .iter()
+            .flat_map(|e| e.attributes.iter())
+            .any(|a| a.key == "refund_amount" && a.value == "40atom"));
+        // 100 to start, minus the refill, plus the whole deposit
+        assert_eq!(coin(137, "atom"), app.wrap().query_balance(ADMIN, "atom")?);
 
         Ok(())
     }
@@ -1258,23 +7683,18 @@ mod tests {
 
         let create_task_msg = ExecuteMsg::CreateTask {
             task: TaskRequest {
-                interval: Interval::Immediate,
-                boundary: Boundary {
-                    start: None,
-                    end: None,
-                },
-                stop_on_fail: false,
                 actions: vec![Action {
                     msg,
                     gas_limit: Some(150_000),
                 }],
-                rules: None,
+                ..task_request()
             },
         };
         let task_id_str =
             "ad15b0f15010d57a51ff889d3400fe8d083a0dab2acfc752c5eb55e9e6281705".to_string();
 
         // create a task
+        let created_at = app.block_info();
         app.execute_contract(
             Addr::unchecked(ANYONE),
             contract_addr.clone(),
@@ -1282,6 +7702,10 @@ mod tests {
             &coins(37, "atom"),
         )
         .unwrap();
+        app.update_block(|block| {
+            block.height += 5;
+            block.time = block.time.plus_seconds(30);
+        });
         // refill task
         let res = app
             .execute_contract(
@@ -1289,6 +7713,7 @@ mod tests {
                 contract_addr.clone(),
                 &ExecuteMsg::RefillTaskBalance {
                     task_hash: task_id_str.clone(),
+                    allow_new_denoms: None,
                 },
                 &coins(3, "atom"),
             )
@@ -1319,6 +7744,9 @@ mod tests {
         if let Some(t) = new_task {
             assert_eq!(Addr::unchecked(ANYONE), t.owner_id);
             assert_eq!(coins(40, "atom"), t.total_deposit);
+            // creation metadata stays the same after refill
+            assert_eq!(created_at.height, t.created_at_height);
+            assert_eq!(created_at.time, t.created_at_time);
         }
 
         // Check the balance has increased to include the new refilled total
@@ -1330,4 +7758,425 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn check_balance_changed_events() -> StdResult<()> {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+
+        let create_task_msg = ExecuteMsg::CreateTask {
+            task: TaskRequest {
+                actions: vec![Action {
+                    msg: StakingMsg::Delegate {
+                        validator: "you".to_string(),
+                        amount: coin(3, "atom"),
+                    }
+                    .into(),
+                    gas_limit: Some(150_000),
+                }],
+                ..task_request()
+            },
+        };
+        let task_hash =
+            "ad15b0f15010d57a51ff889d3400fe8d083a0dab2acfc752c5eb55e9e6281705".to_string();
+
+        let responses = [
+            app.execute_contract(
+                Addr::unchecked(ANYONE),
+                contract_addr.clone(),
+                &create_task_msg,
+                &coins(37, "atom"),
+            )
+            .unwrap(),
+            app.execute_contract(
+                Addr::unchecked(ANYONE),
+                contract_addr.clone(),
+                &ExecuteMsg::RefillTaskBalance {
+                    task_hash: task_hash.clone(),
+                    allow_new_denoms: None,
+                },
+                &coins(3, "atom"),
+            )
+            .unwrap(),
+            app.execute_contract(
+                Addr::unchecked(ANYONE),
+                contract_addr.clone(),
+                &ExecuteMsg::RemoveTask { task_hash },
+                &[],
+            )
+            .unwrap(),
+        ];
+
+        // one event per mutation, reporting the resulting balance
+        let events: Vec<Vec<(String, String)>> = responses
+            .iter()
+            .map(|res| {
+                let event = res
+                    .events
+                    .iter()
+                    .find(|e| e.ty == "wasm-balance_changed")
+                    .unwrap();
+                event
+                    .attributes
+                    .iter()
+                    .map(|a| (a.key.clone(), a.value.clone()))
+                    .collect()
+            })
+            .collect();
+        let attr = |event: &[(String, String)], key: &str| {
+            event.iter().find(|a| a.0 == key).unwrap().1.clone()
+        };
+        let deltas: Vec<String> = events.iter().map(|e| attr(e, "delta")).collect();
+        let available: Vec<String> = events
+            .iter()
+            .map(|e| attr(e, "available_balance"))
+            .collect();
+        assert_eq!(vec!["37atom", "3atom", "-40atom"], deltas);
+        assert_eq!(vec!["37atom", "40atom", "0atom"], available);
+
+        // deltas net to zero once the task is gone
+        let total: i128 = deltas
+            .iter()
+            .map(|d| d.trim_end_matches("atom").parse::<i128>().unwrap())
+            .sum();
+        assert_eq!(0, total);
+
+        Ok(())
+    }
+
+    #[test]
+    fn check_task_created_at() {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+
+        app.update_block(|block| {
+            block.height += 100;
+            block.time = block.time.plus_seconds(600);
+        });
+        let created = app.block_info();
+        let task_hash = create_task_hash(&mut app, &contract_addr, task_request());
+        let created_at = |app: &App| -> (u64, Timestamp) {
+            let task: Option<TaskResponse> = app
+                .wrap()
+                .query_wasm_smart(
+                    &contract_addr,
+                    &QueryMsg::GetTask {
+                        task_hash: task_hash.clone(),
+                    },
+                )
+                .unwrap();
+            let task = task.unwrap();
+            (task.created_at_height, task.created_at_time)
+        };
+
+        // The block the task was created in, not the block it's queried in
+        app.update_block(|block| {
+            block.height += 5;
+            block.time = block.time.plus_seconds(30);
+        });
+        let expected = (created.height, created.time);
+        assert_eq!(expected, created_at(&app));
+
+        // A refill in a later block leaves them unchanged
+        app.execute_contract(
+            Addr::unchecked(ANYONE),
+            contract_addr.clone(),
+            &ExecuteMsg::RefillTaskBalance {
+                task_hash: task_hash.clone(),
+                allow_new_denoms: None,
+            },
+            &coins(3, NATIVE_DENOM),
+        )
+        .unwrap();
+        assert_eq!(expected, created_at(&app));
+    }
+
+    #[test]
+    fn query_get_task_raw_matches_get_task() {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+        let task_hash = create_task_hash(&mut app, &contract_addr, task_request());
+
+        let by_string: Option<TaskResponse> = app
+            .wrap()
+            .query_wasm_smart(
+                &contract_addr,
+                &QueryMsg::GetTask {
+                    task_hash: task_hash.clone(),
+                },
+            )
+            .unwrap();
+        assert!(by_string.is_some());
+        let by_raw = |task_hash: Binary| -> StdResult<Option<TaskResponse>> {
+            app.wrap()
+                .query_wasm_smart(&contract_addr, &QueryMsg::GetTaskRaw { task_hash })
+        };
+
+        // Stored hex key (64 bytes) and decoded digest (32 bytes) both resolve to the same task
+        let hex_key = Binary::from(task_hash.clone().into_bytes());
+        let digest = Binary::from(hex::decode(&task_hash).unwrap());
+        assert_eq!(64, hex_key.len());
+        assert_eq!(32, digest.len());
+        for raw in [hex_key, digest] {
+            assert_eq!(by_string, by_raw(raw).unwrap());
+        }
+
+        // Unknown hash of a valid length is just not found
+        assert_eq!(None, by_raw(Binary::from([7u8; 32])).unwrap());
+
+        // Any other length is rejected
+        let err = by_raw(Binary::from([7u8; 10])).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("Task hash must be 32 or 64 bytes long"));
+    }
+
+    #[test]
+    fn check_refill_min_amount() {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+        app.sudo(SudoMsg::Bank(BankSudo::Mint {
+            to_address: ANYONE.to_string(),
+            amount: coins(10, "juno"),
+        }))
+        .unwrap();
+        app.execute_contract(
+            Addr::unchecked(ADMIN),
+            contract_addr.clone(),
+            &settings_msg(SettingsUpdate {
+                min_refill_amount: Some(Some(Uint128::new(10))),
+                ..Default::default()
+            }),
+            &[],
+        )
+        .unwrap();
+
+        let res = app
+            .execute_contract(
+                Addr::unchecked(ANYONE),
+                contract_addr.clone(),
+                &ExecuteMsg::CreateTask {
+                    task: task_request(),
+                },
+                &[coin(37, NATIVE_DENOM), coin(5, "juno")],
+            )
+            .unwrap();
+        let task_hash = task_hash_attr(&res);
+        let mut refill = |funds: &[Coin]| {
+            app.execute_contract(
+                Addr::unchecked(ANYONE),
+                contract_addr.clone(),
+                &ExecuteMsg::RefillTaskBalance {
+                    task_hash: task_hash.clone(),
+                    allow_new_denoms: None,
+                },
+                funds,
+            )
+        };
+
+        // below the minimum, alone or along with another denom
+        for funds in [
+            coins(9, NATIVE_DENOM),
+            vec![coin(9, NATIVE_DENOM), coin(1, "juno")],
+        ] {
+            let err = refill(&funds).unwrap_err();
+            assert_eq!(
+                ContractError::RefillBelowMinimum {
+                    min: coin(10, NATIVE_DENOM)
+                },
+                err.downcast().unwrap()
+            );
+        }
+
+        // the minimum is in the fee denom, other denoms refill any amount
+        refill(&coins(1, "juno")).unwrap();
+
+        // at the minimum
+        refill(&coins(10, NATIVE_DENOM)).unwrap();
+        let task: Option<TaskResponse> = app
+            .wrap()
+            .query_wasm_smart(&contract_addr, &QueryMsg::GetTask { task_hash })
+            .unwrap();
+        assert_eq!(
+            vec![coin(47, NATIVE_DENOM), coin(6, "juno")],
+            task.unwrap().total_deposit
+        );
+    }
+
+    #[test]
+    fn check_refill_new_denoms() {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+        app.sudo(SudoMsg::Bank(BankSudo::Mint {
+            to_address: ANYONE.to_string(),
+            amount: vec![coin(10, "juno"), coin(10, "osmo")],
+        }))
+        .unwrap();
+
+        let task = TaskRequest {
+            actions: vec![Action {
+                msg: StakingMsg::Delegate {
+                    validator: "you".to_string(),
+                    amount: coin(3, NATIVE_DENOM),
+                }
+                .into(),
+                gas_limit: Some(150_000),
+            }],
+            ..task_request()
+        };
+        let res = app
+            .execute_contract(
+                Addr::unchecked(ANYONE),
+                contract_addr.clone(),
+                &ExecuteMsg::CreateTask { task },
+                &[coin(37, NATIVE_DENOM), coin(5, "juno")],
+            )
+            .unwrap();
+        let task_hash = task_hash_attr(&res);
+        let refill = |app: &mut App, funds: &[Coin], allow_new_denoms: Option<bool>| {
+            app.execute_contract(
+                Addr::unchecked(ANYONE),
+                contract_addr.clone(),
+                &ExecuteMsg::RefillTaskBalance {
+                    task_hash: task_hash.clone(),
+                    allow_new_denoms,
+                },
+                funds,
+            )
+        };
+        let deposit = |app: &App| -> Vec<Coin> {
+            let task: Option<TaskResponse> = app
+                .wrap()
+                .query_wasm_smart(
+                    &contract_addr,
+                    &QueryMsg::GetTask {
+                        task_hash: task_hash.clone(),
+                    },
+                )
+                .unwrap();
+            task.unwrap().total_deposit
+        };
+
+        // denoms already in the deposit are added up
+        refill(&mut app, &[coin(3, NATIVE_DENOM), coin(2, "juno")], None).unwrap();
+        assert_eq!(vec![coin(40, NATIVE_DENOM), coin(7, "juno")], deposit(&app));
+
+        // a new denom is rejected by default
+        let res_err =
+            refill(&mut app, &[coin(3, NATIVE_DENOM), coin(2, "osmo")], None).unwrap_err();
+        assert_eq!(
+            ContractError::RefillNewDenom {
+                denom: "osmo".to_string()
+            },
+            res_err.downcast().unwrap()
+        );
+        assert_eq!(vec![coin(40, NATIVE_DENOM), coin(7, "juno")], deposit(&app));
+
+        // unless explicitly allowed
+        refill(&mut app, &coins(2, "osmo"), Some(true)).unwrap();
+        assert_eq!(
+            vec![coin(40, NATIVE_DENOM), coin(7, "juno"), coin(2, "osmo")],
+            deposit(&app)
+        );
+    }
+
+    #[test]
+    fn check_sponsored_refill() -> StdResult<()> {
+        let (mut app, cw_template_contract) = proper_instantiate();
+        let contract_addr = cw_template_contract.addr();
+
+        let create_task_msg = |stake: u128, allow_sponsored_refill: bool| ExecuteMsg::CreateTask {
+            task: TaskRequest {
+                allow_sponsored_refill,
+                actions: vec![Action {
+                    msg: StakingMsg::Delegate {
+                        validator: String::from("you"),
+                        amount: coin(stake, "atom"),
+                    }
+                    .into(),
+                    gas_limit: Some(150_000),
+                }],
+                ..task_request()
+            },
+        };
+
+        // create a regular task and a sponsored one
+        let res = app
+            .execute_contract(
+                Addr::unchecked(ANYONE),
+                contract_addr.clone(),
+                &create_task_msg(3, false),
+                &coins(37, "atom"),
+            )
+            .unwrap();
+        let owner_only_hash = task_hash_attr(&res);
+        let res = app
+            .execute_contract(
+                Addr::unchecked(ANYONE),
+                contract_addr.clone(),
+                &create_task_msg(4, true),
+                &coins(37, "atom"),
+            )
+            .unwrap();
+        let sponsored_hash = task_hash_attr(&res);
+
+        // non-owner can't refill a regular task
+        let res_err = app
+            .execute_contract(
+                Addr::unchecked(VERY_RICH),
+                contract_addr.clone(),
+                &ExecuteMsg::RefillTaskBalance {
+                    task_hash: owner_only_hash,
+                    allow_new_denoms: None,
+                },
+                &coins(3, "atom"),
+            )
+            .unwrap_err();
+        assert_eq!(
+            ContractError::CustomError {
+                val: "Only owner can refill their task".to_string()
+            },
+            res_err.downcast().unwrap()
+        );
+
+        // anyone can refill a sponsored task
+        app.execute_contract(
+            Addr::unchecked(VERY_RICH),
+            contract_addr.clone(),
+            &ExecuteMsg::RefillTaskBalance {
+                task_hash: sponsored_hash.clone(),
+                allow_new_denoms: None,
+            },
+            &coins(3, "atom"),
+        )
+        .unwrap();
+        let task: Option<TaskResponse> = app
+            .wrap()
+            .query_wasm_smart(
+                &contract_addr,
+                &QueryMsg::GetTask {
+                    task_hash: sponsored_hash.clone(),
+                },
+            )
+            .unwrap();
+        let task = task.unwrap();
+        assert!(task.allow_sponsored_refill);
+        assert_eq!(Addr::unchecked(ANYONE), task.owner_id);
+        assert_eq!(coins(40, "atom"), task.total_deposit);
+
+        // refund on removal still goes to the owner
+        app.execute_contract(
+            Addr::unchecked(ANYONE),
+            contract_addr.clone(),
+            &ExecuteMsg::RemoveTask {
+                task_hash: sponsored_hash,
+            },
+            &[],
+        )
+        .unwrap();
+        let owner_balance = app.wrap().query_balance(ANYONE, "atom")?;
+        assert_eq!(coin(100 - 37 - 37 + 40, "atom"), owner_balance);
+
+        Ok(())
+    }
 }
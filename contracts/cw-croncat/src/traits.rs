@@ -15,6 +15,6 @@ pub trait IntervalExt {
 pub trait TaskHash {
     fn to_hash(&self) -> String;
     fn to_hash_vec(&self) -> Vec<u8>;
-    fn is_valid_msg(&self, self_addr: &Addr, sender: &Addr, owner_id: &Addr) -> bool;
+    fn is_valid_msg(&self, self_addr: &Addr) -> bool;
     fn to_gas_total(&self) -> u64;
 }
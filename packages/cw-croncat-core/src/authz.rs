@@ -0,0 +1,115 @@
+//! Authz support for task actions
+//!
+//! A task can act on behalf of its owner by wrapping messages in an authz `MsgExec`,
+//! sent as a `CosmosMsg::Stargate`. The croncat contract is the grantee that executes it,
+//! so before creating such a task the owner must grant the contract every inner msg type, e.g.:
+//!
+//! ```bash
+//! junod tx authz grant $CONTRACT generic \
+//!   --msg-type /cosmos.distribution.v1beta1.MsgWithdrawDelegatorReward --from $USER
+//! ```
+//!
+//! Only inner msgs whose signer is the task owner are accepted, see `SUPPORTED_EXEC_MSGS`.
+use cosmwasm_std::Addr;
+
+pub const MSG_EXEC_TYPE_URL: &str = "/cosmos.authz.v1beta1.MsgExec";
+
+/// Inner msgs allowed in a MsgExec, all of them carry the signer (granter) as field 1
+pub const SUPPORTED_EXEC_MSGS: [&str; 5] = [
+    "/cosmos.bank.v1beta1.MsgSend",
+    "/cosmos.staking.v1beta1.MsgDelegate",
+    "/cosmos.staking.v1beta1.MsgUndelegate",
+    "/cosmos.staking.v1beta1.MsgBeginRedelegate",
+    "/cosmos.distribution.v1beta1.MsgWithdrawDelegatorReward",
+];
+
+/// Checks an encoded MsgExec is executed by `grantee` and every inner msg is signed by `granter`
+pub fn is_valid_exec(value: &[u8], grantee: &Addr, granter: &Addr) -> bool {
+    let fields = match proto_fields(value) {
+        Some(fields) => fields,
+        None => return false,
+    };
+
+    let mut has_grantee = false;
+    let mut msgs: Vec<&[u8]> = vec![];
+    for (num, bytes) in fields {
+        match num {
+            // MsgExec.grantee
+            1 => {
+                if bytes != grantee.as_bytes() {
+                    return false;
+                }
+                has_grantee = true;
+            }
+            // MsgExec.msgs
+            2 => msgs.push(bytes),
+            _ => (),
+        }
+    }
+    if !has_grantee || msgs.is_empty() {
+        return false;
+    }
+
+    msgs.into_iter().all(|any| {
+        let fields = match proto_fields(any) {
+            Some(fields) => fields,
+            None => return false,
+        };
+        let type_url = fields.iter().find(|(num, _)| *num == 1).map(|f| f.1);
+        let msg = fields.iter().find(|(num, _)| *num == 2).map(|f| f.1);
+        match (type_url, msg) {
+            (Some(type_url), Some(msg)) => {
+                SUPPORTED_EXEC_MSGS
+                    .iter()
+                    .any(|supported| supported.as_bytes() == type_url)
+                    && proto_fields(msg)
+                        .and_then(|f| f.into_iter().find(|(num, _)| *num == 1))
+                        .map(|(_, signer)| signer)
+                        == Some(granter.as_bytes())
+            }
+            _ => false,
+        }
+    })
+}
+
+/// Reads a varint, returning it along with the number of bytes used
+fn read_varint(bytes: &[u8]) -> Option<(u64, usize)> {
+    let mut value: u64 = 0;
+    for (i, b) in bytes.iter().enumerate().take(10) {
+        value |= u64::from(b & 0x7f) << (7 * i);
+        if b & 0x80 == 0 {
+            return Some((value, i + 1));
+        }
+    }
+    None
+}
+
+/// Splits a protobuf message into its length-delimited fields, other wire types are skipped
+/// Returns None for malformed input
+fn proto_fields(mut bytes: &[u8]) -> Option<Vec<(u64, &[u8])>> {
+    let mut fields = vec![];
+    while !bytes.is_empty() {
+        let (key, used) = read_varint(bytes)?;
+        bytes = &bytes[used..];
+        match key & 0x7 {
+            // varint
+            0 => {
+                let (_, used) = read_varint(bytes)?;
+                bytes = &bytes[used..];
+            }
+            // 64-bit
+            1 => bytes = bytes.get(8..)?,
+            // length-delimited
+            2 => {
+                let (len, used) = read_varint(bytes)?;
+                let end = used.checked_add(usize::try_from(len).ok()?)?;
+                fields.push((key >> 3, bytes.get(used..end)?));
+                bytes = &bytes[end..];
+            }
+            // 32-bit
+            5 => bytes = bytes.get(4..)?,
+            _ => return None,
+        }
+    }
+    Some(fields)
+}
@@ -1,3 +1,4 @@
+pub mod authz;
 pub mod msg;
 pub mod traits;
 pub mod types;
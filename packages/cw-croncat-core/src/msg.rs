@@ -1,9 +1,9 @@
 use crate::types::{Action, AgentResponse, Boundary, GenericBalance, Interval, Rule, Task};
-use crate::types::{Agent, SlotType};
-use cosmwasm_std::{Addr, Coin, Uint64};
+use crate::types::{Agent, BoundaryHorizon, BoundarySpec, FailMode, MsgKind, SlotType};
+use cosmwasm_std::{Addr, Binary, Coin, Decimal, Order, Timestamp, Uint128, Uint64};
 use cw20::Balance;
 use schemars::JsonSchema;
-use serde::{Deserialize, Serialize};
+use serde::{Deserialize, Deserializer, Serialize};
 
 // NOTE: Which version is more practical?
 // // Exporting a nice schema
@@ -38,15 +38,31 @@ pub struct Croncat {
     get_agent_ids_response: Option<GetAgentIdsResponse>,
     get_agent_tasks_response: Option<AgentTaskResponse>,
     task_request: Option<TaskRequest>,
+    create_task_response: Option<CreateTaskResponse>,
     task_response: Option<TaskResponse>,
     validate_interval_response: Option<bool>,
+    validate_task_response: Option<ValidateTaskResponse>,
     get_agent_response: Option<Option<AgentResponse>>,
     get_tasks_response: Option<Vec<TaskResponse>>,
+    get_tasks_page_response: Option<GetTasksPageResponse>,
     get_tasks_by_owner_response: Option<Vec<TaskResponse>>,
     get_task_response: Option<Option<TaskResponse>>,
     get_task_hash_response: Option<String>,
     get_slot_hashes_response: Option<GetSlotHashesResponse>,
     get_slot_ids_response: Option<GetSlotIdsResponse>,
+    get_slot_stats_response: Option<GetSlotStatsResponse>,
+    get_horizon_response: Option<GetHorizonResponse>,
+    get_stuck_tasks_response: Option<GetStuckTasksResponse>,
+    get_task_cost_response: Option<Option<GetTaskCostResponse>>,
+    get_denoms_response: Option<Vec<String>>,
+    check_task_rules_response: Option<Option<CheckTaskRulesResponse>>,
+    get_balancer_invariant_response: Option<GetBalancerInvariantResponse>,
+    get_denom_balances_response: Option<Vec<DenomBalance>>,
+    get_fee_schedule_response: Option<GetFeeScheduleResponse>,
+    get_owner_summary_response: Option<GetOwnerSummaryResponse>,
+    get_tasks_by_next_execution_response: Option<GetTasksByNextExecutionResponse>,
+    get_task_schedule_response: Option<Option<(SlotType, u64)>>,
+    get_tasks_by_hashes_response: Option<Vec<Option<TaskResponse>>>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
@@ -57,9 +73,14 @@ pub struct InstantiateMsg {
     pub agent_nomination_duration: Option<u16>,
 }
 
+/// Stored state is brought to the current shape, new settings get their defaults
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct MigrateMsg {}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum ExecuteMsg {
+    /// Settings left out stay unchanged, the optional limits are cleared with an explicit null
     UpdateSettings {
         owner_id: Option<Addr>,
         slot_granularity: Option<u64>,
@@ -67,14 +88,85 @@ pub enum ExecuteMsg {
         agent_fee: Option<Coin>,
         gas_price: Option<u32>,
         proxy_callback_gas: Option<u32>,
+        max_action_gas: Option<u64>,
+        default_gas_limit: Option<u64>,
+        #[serde(
+            default,
+            deserialize_with = "clearable",
+            skip_serializing_if = "Option::is_none"
+        )]
+        max_tasks_per_owner: Option<Option<u64>>,
+        #[serde(
+            default,
+            deserialize_with = "clearable",
+            skip_serializing_if = "Option::is_none"
+        )]
+        max_tasks_per_slot: Option<Option<u64>>,
+        #[serde(
+            default,
+            deserialize_with = "clearable",
+            skip_serializing_if = "Option::is_none"
+        )]
+        max_failure_streak: Option<Option<u64>>,
+        refund_on_remove: Option<bool>,
+        #[serde(
+            default,
+            deserialize_with = "clearable",
+            skip_serializing_if = "Option::is_none"
+        )]
+        min_refill_amount: Option<Option<Uint128>>,
+        /// Non fee denom leftovers below it aren't refunded on task removal
+        dust_threshold: Option<Uint128>,
+        /// Blocks a task stays reserved for its preferred agent, once its block slot is due
+        execution_grace_blocks: Option<u64>,
+        /// Seconds a task stays reserved for its preferred agent, once its cron slot is due
+        execution_grace_seconds: Option<u64>,
+        /// Must use the agent fee denom
+        #[serde(
+            default,
+            deserialize_with = "clearable",
+            skip_serializing_if = "Option::is_none"
+        )]
+        min_task_deposit: Option<Option<Coin>>,
+        /// Tasks whose boundary start or end is further ahead are rejected
+        #[serde(
+            default,
+            deserialize_with = "clearable",
+            skip_serializing_if = "Option::is_none"
+        )]
+        max_boundary_horizon: Option<Option<BoundaryHorizon>>,
+        /// Reject tasks repeating an identical action, instead of only flagging them
+        reject_duplicate_actions: Option<bool>,
+        /// Replaces the allowed message kinds, must not be empty
+        allowed_msg_kinds: Option<Vec<MsgKind>>,
         min_tasks_per_agent: Option<u64>,
         agents_eject_threshold: Option<u64>,
-        // treasury_id: Option<Addr>,
+        #[serde(
+            default,
+            deserialize_with = "clearable",
+            skip_serializing_if = "Option::is_none"
+        )]
+        treasury_id: Option<Option<Addr>>,
+        treasury_fee: Option<u64>,
     },
     MoveBalances {
         balances: Vec<Balance>,
         account_id: Addr,
     },
+    /// Withdraws native balance not committed to any task, to the owner
+    /// Surplus is withdrawn first, protocol balance once it's used up
+    OwnerWithdraw {
+        amount: Vec<Coin>,
+    },
+    /// Adds the attached funds to the protocol balance, agent rewards are paid from it first
+    /// They aren't committed to any task
+    DepositFunds {},
+    /// Cleanup of tasks that can't pay for a single execution, the deposit is refunded to the task owner
+    AdminRemoveTask {
+        task_hash: String,
+    },
+    /// Deletes time & block slots that no longer hold any task
+    CompactSlots {},
 
     RegisterAgent {
         payable_account_id: Option<Addr>,
@@ -92,57 +184,259 @@ pub enum ExecuteMsg {
     RemoveTask {
         task_hash: String,
     },
+    /// Removes several tasks of the sender, refunds are batched into one send per recipient
+    /// Nothing is removed if any hash is unknown or owned by someone else
+    RemoveTasks {
+        task_hashes: Vec<String>,
+    },
+    /// Denoms the task deposit doesn't hold yet, other than the fee denom, need allow_new_denoms
     RefillTaskBalance {
         task_hash: String,
+        allow_new_denoms: Option<bool>,
+    },
+    /// Removes a task that ended or can't pay for an execution, refunding the owner
+    ClaimEndedTask {
+        task_hash: String,
+    },
+    /// One gas limit per action, in the same order as the task actions
+    UpdateTaskGas {
+        task_hash: String,
+        gas_limits: Vec<Option<u64>>,
+    },
+    /// Current owner only, the task is re-keyed as the owner is part of its hash
+    /// Tasks chained with on_success_task, either end, stay with their owner
+    TransferTaskOwnership {
+        task_hash: String,
+        new_owner: Addr,
     },
     ProxyCall {},
 }
 
+/// Tells an explicit null apart from a missing field, for settings that can be unset
+/// A missing field is None, null is Some(None)
+fn clearable<'de, T, D>(deserializer: D) -> Result<Option<Option<T>>, D::Error>
+where
+    T: Deserialize<'de>,
+    D: Deserializer<'de>,
+{
+    Option::<T>::deserialize(deserializer).map(Some)
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 #[serde(rename_all = "snake_case")]
 pub enum QueryMsg {
     GetConfig {},
     GetBalances {},
+    /// Agent details & status (active, pending or nominated), None if not registered
     GetAgent {
         account_id: Addr,
     },
+    /// Active and pending agent queues, in queue order
     GetAgentIds {},
     GetAgentTasks {
         account_id: Addr,
     },
+    /// Task hashes of the slot proxy_call executes next, that the agent can execute now
+    GetEligibleTasks {
+        agent_id: Addr,
+    },
+    /// from_index is relative to the chosen order, so it counts from the end for Desc
     GetTasks {
         from_index: Option<u64>,
         limit: Option<u64>,
+        order_by: Option<OrderBy>,
+    },
+    /// Ascending by task hash, pass the last returned task_hash as start_after for the next page
+    GetTasksFrom {
+        start_after: Option<String>,
+        limit: Option<u64>,
+    },
+    /// Same order as GetTasksFrom, with the cursor for the next page when more tasks remain
+    GetTasksPage {
+        start_after: Option<String>,
+        limit: Option<u64>,
+    },
+    /// Tasks whose hex hash starts with prefix, for looking up a truncated hash
+    GetTasksByHashPrefix {
+        prefix: String,
+        limit: Option<u64>,
+    },
+    GetTasksWithRules {
+        from_index: Option<u64>,
+        limit: Option<u64>,
+    },
+    GetTasksByDenom {
+        denom: String,
+        from_index: Option<u64>,
+        limit: Option<u64>,
+    },
+    /// Tasks whose actions' gas limits add up to max_gas or less
+    GetTasksUnderGas {
+        max_gas: u64,
+        from_index: Option<u64>,
+        limit: Option<u64>,
+    },
+    /// Tasks whose boundary end is within start & end (inclusive), both must be heights or times
+    /// A height window only matches height ends, a time window only time ends
+    GetTasksByBoundaryEnd {
+        start: BoundarySpec,
+        end: BoundarySpec,
+        from_index: Option<u64>,
+        limit: Option<u64>,
+    },
+    /// Tasks created within start & end (inclusive)
+    /// NOTE: Tasks aren't indexed by creation time, so this scans every task
+    GetTasksCreatedBetween {
+        start: Timestamp,
+        end: Timestamp,
+        from_index: Option<u64>,
+        limit: Option<u64>,
     },
     GetTasksByOwner {
         owner_id: Addr,
     },
+    /// Tasks preferring the agent, plus the ones without a preferred agent
+    GetTasksForAgent {
+        agent_id: Addr,
+        from_index: Option<u64>,
+        limit: Option<u64>,
+    },
+    GetTaskCount {},
+    GetOwnerTaskCount {
+        owner_id: Addr,
+    },
+    /// Task count, deposits & upcoming executions of an owner's tasks
+    /// NOTE: Reads up to 1000 of the owner's tasks, gas grows linearly with them
+    GetOwnerSummary {
+        owner_id: Addr,
+    },
+    /// Soonest block & time tasks by next execution, up to limit of each
+    /// NOTE: Tasks aren't indexed by next slot, so this scans and sorts every task
+    GetTasksByNextExecution {
+        limit: Option<u64>,
+    },
     GetTask {
         task_hash: String,
     },
+    /// Tasks for a batch of hashes, e.g. a slot's from GetSlotHashes
+    /// Same order as task_hashes, None where no task is found
+    GetTasksByHashes {
+        task_hashes: Vec<String>,
+    },
+    /// Same as GetTask, for callers already holding the hash bytes
+    GetTaskRaw {
+        task_hash: Binary,
+    },
     GetTaskHash {
         task: Box<Task>,
     },
+    /// Slot kind & id the task is scheduled in, None if it isn't in any slot
+    /// Errors if the task doesn't exist
+    /// NOTE: Slots aren't indexed by task, so this scans every slot
+    GetTaskSchedule {
+        task_hash: String,
+    },
+    /// Cost of a single execution of the task, as charged by the contract
+    GetTaskCost {
+        task_hash: String,
+    },
+    /// Evaluates the task rules against the current chain state
+    CheckTaskRules {
+        task_hash: String,
+    },
+    /// Crons that can't be parsed error with the reason, instead of returning false
     ValidateInterval {
         interval: Interval,
     },
+    /// Runs the create task checks, owner_id adds the ones depending on who creates it
+    ValidateTask {
+        task: Box<TaskRequest>,
+        funds: Vec<Coin>,
+        owner_id: Option<Addr>,
+    },
+    /// Block & time slot ids can overlap, so a specific slot needs its type
+    /// from_index & limit page within the block and time hashes of the slot
+    /// due_only skips slots after the current block height & time
+    /// Hashes come in ascending order, the order agents execute them in
     GetSlotHashes {
-        slot: Option<u64>,
+        slot: Option<(SlotType, u64)>,
+        from_index: Option<u64>,
+        limit: Option<u64>,
+        #[serde(default)]
+        due_only: bool,
+    },
+    /// All task hashes in the given slots, in order, so a batch can be fetched in one call
+    GetTaskHashes {
+        slots: Vec<(SlotType, u64)>,
+    },
+    /// Slot ids with their task hashes, in slot order, for scheduling overviews
+    GetAllSlots {
+        slot_type: SlotType,
+        from_index: Option<u64>,
+        limit: Option<u64>,
     },
     GetSlotIds {},
+    GetSlotStats {},
+    /// Sum of the action gas limits of every task in the slot, so agents can split a batch
+    GetSlotGas {
+        slot_type: SlotType,
+        slot_id: u64,
+    },
+    /// Furthest scheduled slots, with the current block for reference
+    GetHorizon {},
+    /// Task hashes still sitting in slots before the current block height & time
+    GetStuckTasks {},
+    /// Distinct denoms across all task deposits, plus the agent fee denom
+    GetDenoms {},
+    /// Recomputes the task deposits, to check available_balance hasn't drifted from them & the protocol balance
+    GetBalancerInvariant {},
+    /// Committed (sum of task deposits) & available totals for every native denom
+    GetDenomBalances {},
+    /// Config values that price an execution, with how they add up to a task's cost
+    GetFeeSchedule {},
+}
+
+#[derive(Serialize, Deserialize, Clone, Copy, Debug, PartialEq, Eq, JsonSchema)]
+pub enum OrderBy {
+    Asc,
+    Desc,
+}
+
+impl From<OrderBy> for Order {
+    fn from(order_by: OrderBy) -> Self {
+        match order_by {
+            OrderBy::Asc => Order::Ascending,
+            OrderBy::Desc => Order::Descending,
+        }
+    }
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct GetConfigResponse {
     pub paused: bool,
     pub owner_id: Addr,
-    // pub treasury_id: Option<Addr>,
+    pub treasury_id: Option<Addr>,
+    pub treasury_fee: u64,
     pub min_tasks_per_agent: u64,
     pub agent_active_indices: Vec<(SlotType, u32, u32)>,
     pub agents_eject_threshold: u64,
     pub agent_fee: Coin,
     pub gas_price: u32,
     pub proxy_callback_gas: u32,
+    pub max_action_gas: u64,
+    pub default_gas_limit: u64,
+    pub max_tasks_per_owner: Option<u64>,
+    pub max_tasks_per_slot: Option<u64>,
+    pub max_failure_streak: Option<u64>,
+    pub refund_on_remove: bool,
+    pub min_refill_amount: Option<Uint128>,
+    pub dust_threshold: Uint128,
+    pub execution_grace_blocks: u64,
+    pub execution_grace_seconds: u64,
+    pub min_task_deposit: Option<Coin>,
+    pub max_boundary_horizon: Option<BoundaryHorizon>,
+    pub reject_duplicate_actions: bool,
+    pub allowed_msg_kinds: Vec<MsgKind>,
     pub slot_granularity: u64,
     pub native_denom: String,
 }
@@ -151,10 +445,63 @@ pub struct GetConfigResponse {
 pub struct GetBalancesResponse {
     pub native_denom: String,
     pub available_balance: GenericBalance,
+    /// Sum of all task deposits, the rest of available_balance is surplus
+    pub committed_balance: GenericBalance,
     pub staked_balance: GenericBalance,
+    /// Deposited through DepositFunds, held by the contract rather than any task
+    pub protocol_balance: GenericBalance,
     pub cw20_whitelist: Vec<Addr>,
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct GetBalancerInvariantResponse {
+    pub available_balance: GenericBalance,
+    /// Sum of all task deposits
+    pub tasks_balance: GenericBalance,
+    pub protocol_balance: GenericBalance,
+    /// Instantiate funds & kept dust, less the agent fees paid from them
+    pub surplus_balance: GenericBalance,
+    /// available_balance holds the same non-zero amounts as the tasks, protocol & surplus balances together
+    pub in_balance: bool,
+}
+
+/// Next executions are computed from each task's interval at the current block,
+/// block heights & times are kept apart as they aren't comparable
+/// truncated is set when the owner has more tasks than the summary reads
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct GetOwnerSummaryResponse {
+    pub task_count: u64,
+    pub total_deposit: Vec<Coin>,
+    pub earliest_next_block: Option<u64>,
+    pub latest_next_block: Option<u64>,
+    pub earliest_next_time: Option<Timestamp>,
+    pub latest_next_time: Option<Timestamp>,
+    /// Tasks whose boundary end is about a day away or less
+    pub expiring_soon: u64,
+    pub truncated: bool,
+}
+
+/// slot_id is a block height or a time in nanos, depending on the list it's in
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct TaskNextExecution {
+    pub task_hash: String,
+    pub slot_id: u64,
+}
+
+/// Block heights & times aren't comparable, so each kind is sorted on its own
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct GetTasksByNextExecutionResponse {
+    pub block_tasks: Vec<TaskNextExecution>,
+    pub time_tasks: Vec<TaskNextExecution>,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct DenomBalance {
+    pub denom: String,
+    pub committed: Uint128,
+    pub available: Uint128,
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
 pub struct GetAgentIdsResponse {
     pub active: Vec<Addr>,
@@ -174,6 +521,15 @@ pub struct TaskRequest {
     pub interval: Interval,
     pub boundary: Boundary,
     pub stop_on_fail: bool,
+    pub allow_sponsored_refill: bool,
+    pub refund_split: Option<Vec<(Addr, Decimal)>>,
+    pub preferred_agent: Option<Addr>,
+    pub fail_mode: FailMode,
+    pub agent_fee_override: Option<Uint128>,
+    pub on_success_task: Option<String>,
+    pub label: Option<String>,
+    pub memo: Option<String>,
+    pub return_surplus: bool,
     pub actions: Vec<Action>,
     pub rules: Option<Vec<Rule>>,
 }
@@ -181,13 +537,97 @@ pub struct TaskRequest {
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct TaskResponse {
     pub task_hash: String,
+    /// Slot the interval resolves to from the current block, None once the task ended
+    pub next_slot: Option<(SlotType, u64)>,
     pub owner_id: Addr,
     pub interval: Interval,
     pub boundary: Boundary,
     pub stop_on_fail: bool,
+    pub allow_sponsored_refill: bool,
+    pub refund_split: Option<Vec<(Addr, Decimal)>>,
+    pub preferred_agent: Option<Addr>,
+    pub fail_mode: FailMode,
+    pub agent_fee_override: Option<Uint128>,
+    pub on_success_task: Option<String>,
+    pub label: Option<String>,
+    pub memo: Option<String>,
+    pub return_surplus: bool,
     pub total_deposit: Vec<Coin>,
     pub actions: Vec<Action>,
     pub rules: Option<Vec<Rule>>,
+    pub last_executed_slot: Option<u64>,
+    pub executions_count: u64,
+    pub failure_streak: u64,
+    pub created_at_height: u64,
+    pub created_at_time: Timestamp,
+}
+
+/// Set as the CreateTask response data, so a SubMsg reply can parse the new task
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct CreateTaskResponse {
+    pub task_hash: String,
+    pub slot_id: u64,
+    pub slot_kind: SlotType,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct GetTasksPageResponse {
+    pub tasks: Vec<TaskResponse>,
+    /// Last task hash of the page, None once there are no more tasks
+    pub next: Option<String>,
+}
+
+/// Result of every create task check, errors match the ones returned by CreateTask
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct ValidateTaskResponse {
+    pub is_valid: bool,
+    pub funds_attached: bool,
+    pub not_paused: bool,
+    pub supported_actions: bool,
+    pub valid_interval: bool,
+    pub not_ended: bool,
+    pub valid_refund_split: bool,
+    pub funded_actions: bool,
+    pub errors: Vec<String>,
+}
+
+/// gas_total includes proxy_callback_gas, total_cost is gas_fee plus agent_fee
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct GetTaskCostResponse {
+    pub gas_total: u64,
+    pub gas_fee: Coin,
+    pub agent_fee: Coin,
+    pub total_cost: Coin,
+    /// Coins the actions send, paid from the task deposit on top of total_cost
+    pub actions_cost: Vec<Coin>,
+}
+
+/// Per execution fee settings, `formula` spells out how GetTaskCost combines them
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct GetFeeScheduleResponse {
+    pub gas_price: u32,
+    pub agent_fee: Coin,
+    pub proxy_callback_gas: u32,
+    /// Gas limit given to actions created without one
+    pub default_gas_limit: u64,
+    pub formula: CostFormula,
+}
+
+/// total_cost = (sum of action gas limits + extra_gas) * gas_multiplier + max(flat_fee, agent_fee_override)
+/// in `denom`, the coins actions send are paid on top of it
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
+pub struct CostFormula {
+    pub denom: String,
+    pub extra_gas: u64,
+    pub gas_multiplier: u32,
+    pub flat_fee: Uint128,
+}
+
+/// success is true when every rule passed, rules holds each rule's result in order
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct CheckTaskRulesResponse {
+    pub success: bool,
+    pub rules: Vec<bool>,
 }
 
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
@@ -204,6 +644,32 @@ pub struct GetSlotIdsResponse {
     pub block_ids: Vec<u64>,
 }
 
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct GetSlotStatsResponse {
+    pub num_time_slots: u64,
+    pub num_block_slots: u64,
+    pub earliest_time_id: Option<u64>,
+    pub latest_time_id: Option<u64>,
+    pub earliest_block_id: Option<u64>,
+    pub latest_block_id: Option<u64>,
+    pub num_task_hashes: u64,
+}
+
+/// Slot ids are None when nothing is scheduled of that kind
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct GetHorizonResponse {
+    pub max_time_id: Option<u64>,
+    pub max_block_id: Option<u64>,
+    pub block_height: u64,
+    pub block_time: Timestamp,
+}
+
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct GetStuckTasksResponse {
+    pub time_task_hashes: Vec<String>,
+    pub block_task_hashes: Vec<String>,
+}
+
 #[cfg(test)]
 mod tests {
     use cosmwasm_std::{coin, coins, BankMsg, CosmosMsg, Timestamp};
@@ -229,6 +695,7 @@ mod tests {
             balance: generic_balance.clone(),
             total_tasks_executed: 0,
             last_missed_slot: 3,
+            missed_slots: 1,
             register_start: Timestamp::from_nanos(5),
         }
         .into();
@@ -246,24 +713,54 @@ mod tests {
                 end: None,
             },
             stop_on_fail: false,
+            allow_sponsored_refill: false,
+            refund_split: None,
+            preferred_agent: None,
+            fail_mode: FailMode::StopOnFirstFail,
+            agent_fee_override: None,
+            on_success_task: None,
+            label: None,
+            memo: None,
+            return_surplus: false,
             total_deposit: vec![],
             actions: vec![Action {
                 msg,
                 gas_limit: Some(150_000),
             }],
             rules: None,
+            last_executed_slot: None,
+            executions_count: 0,
+            failure_streak: 0,
+            created_at_height: 0,
+            created_at_time: Default::default(),
         }
         .into();
 
         let config_response = GetConfigResponse {
             paused: true,
             owner_id: Addr::unchecked("bob"),
+            treasury_id: Some(Addr::unchecked("treasury")),
+            treasury_fee: 10,
             min_tasks_per_agent: 5,
             agent_active_indices: vec![(SlotType::Block, 10, 5)],
             agents_eject_threshold: 5,
             agent_fee: coin(5, "earth"),
             gas_price: 2,
             proxy_callback_gas: 3,
+            max_action_gas: 3_000_000,
+            default_gas_limit: 150_000,
+            max_tasks_per_owner: None,
+            max_tasks_per_slot: None,
+            max_failure_streak: None,
+            refund_on_remove: true,
+            min_refill_amount: None,
+            dust_threshold: Uint128::zero(),
+            execution_grace_blocks: 10,
+            execution_grace_seconds: 60,
+            min_task_deposit: None,
+            max_boundary_horizon: None,
+            reject_duplicate_actions: false,
+            allowed_msg_kinds: MsgKind::all(),
             slot_granularity: 1,
             native_denom: "juno".to_string(),
         }
@@ -271,7 +768,9 @@ mod tests {
         let balance_response = GetBalancesResponse {
             native_denom: "some".to_string(),
             available_balance: generic_balance.clone(),
+            committed_balance: generic_balance.clone(),
             staked_balance: generic_balance.clone(),
+            protocol_balance: generic_balance.clone(),
             cw20_whitelist: vec![Addr::unchecked("bob")],
         }
         .into();
@@ -294,12 +793,28 @@ mod tests {
                 end: Some(BoundarySpec::Time(Timestamp::from_nanos(64))),
             },
             stop_on_fail: true,
+            allow_sponsored_refill: false,
+            refund_split: None,
+            preferred_agent: None,
+            fail_mode: FailMode::StopOnFirstFail,
+            agent_fee_override: None,
+            on_success_task: None,
+            label: None,
+            memo: None,
+            return_surplus: false,
             actions: vec![],
             rules: None, // TODO
         }
         .into();
+        let create_task_response = CreateTaskResponse {
+            task_hash: "test".to_string(),
+            slot_id: 5,
+            slot_kind: SlotType::Block,
+        }
+        .into();
         let task_response_raw = TaskResponse {
             task_hash: "test".to_string(),
+            next_slot: Some((SlotType::Cron, 5)),
             owner_id: Addr::unchecked("bob"),
             interval: Interval::Cron("blah-blah".to_string()),
             boundary: Boundary {
@@ -307,24 +822,56 @@ mod tests {
                 end: None,
             },
             stop_on_fail: true,
+            allow_sponsored_refill: false,
+            refund_split: None,
+            preferred_agent: None,
+            fail_mode: FailMode::StopOnFirstFail,
+            agent_fee_override: None,
+            on_success_task: None,
+            label: None,
+            memo: None,
+            return_surplus: false,
             total_deposit: vec![coin(5, "earth")],
             actions: vec![],
             rules: None,
+            last_executed_slot: None,
+            executions_count: 0,
+            failure_streak: 0,
+            created_at_height: 0,
+            created_at_time: Default::default(),
         };
         let task_response = task_response_raw.clone().into();
         let validate_interval_response = false.into();
+        let validate_task_response = ValidateTaskResponse {
+            is_valid: false,
+            funds_attached: false,
+            not_paused: true,
+            supported_actions: true,
+            valid_interval: true,
+            not_ended: true,
+            valid_refund_split: true,
+            funded_actions: true,
+            errors: vec!["Must attach funds".to_string()],
+        }
+        .into();
         let get_agent_response = Some(AgentResponse {
             status: AgentStatus::Active,
             payable_account_id: Addr::unchecked("bob"),
             balance: generic_balance.clone(),
             total_tasks_executed: 2,
             last_missed_slot: 2,
+            missed_slots: 1,
             register_start: Timestamp::from_nanos(5),
         })
         .into();
         let get_tasks_response = vec![task_response_raw.clone()].into();
+        let get_tasks_page_response = GetTasksPageResponse {
+            tasks: vec![task_response_raw.clone()],
+            next: None,
+        }
+        .into();
         let get_tasks_by_owner_response = vec![task_response_raw.clone()].into();
-        let get_task_response = Some(task_response_raw).into();
+        let get_task_response = Some(task_response_raw.clone()).into();
         let get_task_hash_response = ("asd".to_string()).into();
         let get_slot_hashes_response = GetSlotHashesResponse {
             block_id: 5,
@@ -338,6 +885,86 @@ mod tests {
             block_ids: vec![3],
         }
         .into();
+        let get_slot_stats_response = GetSlotStatsResponse {
+            num_time_slots: 1,
+            num_block_slots: 1,
+            earliest_time_id: Some(1),
+            latest_time_id: Some(1),
+            earliest_block_id: Some(3),
+            latest_block_id: Some(3),
+            num_task_hashes: 2,
+        }
+        .into();
+        let get_horizon_response = GetHorizonResponse {
+            max_time_id: Some(1),
+            max_block_id: None,
+            block_height: 5,
+            block_time: Timestamp::from_seconds(1),
+        }
+        .into();
+        let get_stuck_tasks_response = GetStuckTasksResponse {
+            time_task_hashes: vec!["alice".to_string()],
+            block_task_hashes: vec![],
+        }
+        .into();
+        let get_task_cost_response = Some(GetTaskCostResponse {
+            gas_total: 150_003,
+            gas_fee: coin(150_003, "juno"),
+            agent_fee: coin(5, "juno"),
+            total_cost: coin(150_008, "juno"),
+            actions_cost: coins(3, "atom"),
+        })
+        .into();
+        let get_denoms_response = Some(vec!["atom".to_string(), "juno".to_string()]);
+        let check_task_rules_response = Some(CheckTaskRulesResponse {
+            success: false,
+            rules: vec![true, false],
+        })
+        .into();
+        let get_balancer_invariant_response = GetBalancerInvariantResponse {
+            available_balance: generic_balance.clone(),
+            tasks_balance: generic_balance.clone(),
+            protocol_balance: generic_balance.clone(),
+            surplus_balance: generic_balance.clone(),
+            in_balance: true,
+        }
+        .into();
+        let get_denom_balances_response = Some(vec![DenomBalance {
+            denom: "atom".to_string(),
+            committed: Uint128::new(5),
+            available: Uint128::new(6),
+        }]);
+        let get_fee_schedule_response = Some(GetFeeScheduleResponse {
+            gas_price: 2,
+            agent_fee: coin(5, "atom"),
+            proxy_callback_gas: 3,
+            default_gas_limit: 150_000,
+            formula: CostFormula {
+                denom: "atom".to_string(),
+                extra_gas: 3,
+                gas_multiplier: 2,
+                flat_fee: Uint128::new(5),
+            },
+        });
+        let get_owner_summary_response = Some(GetOwnerSummaryResponse {
+            task_count: 2,
+            total_deposit: coins(5, "atom"),
+            earliest_next_block: Some(100),
+            latest_next_block: Some(200),
+            earliest_next_time: None,
+            latest_next_time: None,
+            expiring_soon: 1,
+            truncated: false,
+        });
+        let get_tasks_by_next_execution_response = Some(GetTasksByNextExecutionResponse {
+            block_tasks: vec![TaskNextExecution {
+                task_hash: "alice".to_string(),
+                slot_id: 100,
+            }],
+            time_tasks: vec![],
+        });
+        let get_task_schedule_response = Some(Some((SlotType::Block, 100)));
+        let get_tasks_by_hashes_response = Some(vec![Some(task_response_raw.clone()), None]);
         let croncat = Croncat {
             agent,
             task,
@@ -346,15 +973,31 @@ mod tests {
             get_agent_ids_response,
             get_agent_tasks_response,
             task_request,
+            create_task_response,
             task_response,
             validate_interval_response,
+            validate_task_response,
             get_agent_response,
             get_tasks_response,
+            get_tasks_page_response,
             get_tasks_by_owner_response,
             get_task_response,
             get_task_hash_response,
             get_slot_hashes_response,
             get_slot_ids_response,
+            get_slot_stats_response,
+            get_horizon_response,
+            get_stuck_tasks_response,
+            get_task_cost_response,
+            get_denoms_response,
+            check_task_rules_response,
+            get_balancer_invariant_response,
+            get_denom_balances_response,
+            get_fee_schedule_response,
+            get_owner_summary_response,
+            get_tasks_by_next_execution_response,
+            get_task_schedule_response,
+            get_tasks_by_hashes_response,
         };
 
         let ser = serde_json_wasm::to_string(&croncat);
@@ -363,4 +1006,33 @@ mod tests {
         let deser: Result<Croncat, _> = serde_json_wasm::from_str(&ser.unwrap());
         assert!(deser.is_ok());
     }
+
+    #[test]
+    fn update_settings_clearable() {
+        let max_tasks_per_owner = |json: &str| match serde_json_wasm::from_str(json).unwrap() {
+            ExecuteMsg::UpdateSettings {
+                max_tasks_per_owner,
+                ..
+            } => max_tasks_per_owner,
+            _ => unreachable!(),
+        };
+        // left out, set & cleared
+        assert_eq!(None, max_tasks_per_owner(r#"{"update_settings":{}}"#));
+        assert_eq!(
+            Some(Some(5)),
+            max_tasks_per_owner(r#"{"update_settings":{"max_tasks_per_owner":5}}"#)
+        );
+        assert_eq!(
+            Some(None),
+            max_tasks_per_owner(r#"{"update_settings":{"max_tasks_per_owner":null}}"#)
+        );
+
+        // unchanged settings aren't serialized, they'd read back as cleared
+        let msg: ExecuteMsg =
+            serde_json_wasm::from_str(r#"{"update_settings":{"treasury_id":null}}"#).unwrap();
+        let ser = serde_json_wasm::to_string(&msg).unwrap();
+        assert!(ser.contains(r#""treasury_id":null"#));
+        assert!(!ser.contains("max_tasks_per_owner"));
+        assert_eq!(msg, serde_json_wasm::from_str(&ser).unwrap());
+    }
 }
@@ -1,5 +1,8 @@
+use crate::authz::{is_valid_exec, MSG_EXEC_TYPE_URL};
+use cosmwasm_std::StakingMsg;
 use cosmwasm_std::{
-    Addr, BankMsg, Binary, Coin, CosmosMsg, Empty, Env, GovMsg, IbcMsg, Timestamp, WasmMsg,
+    Addr, BankMsg, Binary, Coin, CosmosMsg, Decimal, Empty, Env, GovMsg, IbcMsg, Timestamp,
+    Uint128, WasmMsg,
 };
 use cron_schedule::Schedule;
 use cw20::{Balance, Cw20CoinVerified};
@@ -7,6 +10,7 @@ use hex::encode;
 use schemars::JsonSchema;
 use serde::{Deserialize, Serialize};
 use sha2::{Digest, Sha256};
+use std::fmt;
 use std::str::FromStr;
 
 #[derive(Serialize, Deserialize, Clone, PartialEq, JsonSchema, Debug, Default)]
@@ -44,6 +48,11 @@ pub struct Agent {
     // Example data: 1633890060000000000 or 0
     pub last_missed_slot: u64,
 
+    // Slots missed in a row, reset once the agent executes again
+    // Past the config's agents_eject_threshold, the agent is moved back to pending
+    #[serde(default)]
+    pub missed_slots: u64,
+
     // Timestamp of when agent first registered
     // Useful for rewarding agents for their patience while they are pending and operating service
     // Agent will be responsible to constantly monitor when it is their turn to join in active agent set (done as part of agent code loops)
@@ -59,13 +68,14 @@ pub struct AgentResponse {
     pub balance: GenericBalance,
     pub total_tasks_executed: u64,
     pub last_missed_slot: u64,
+    pub missed_slots: u64,
     pub register_start: Timestamp,
 }
 
 /// Defines the spacing of execution
 /// NOTE:S
 /// - Block Height Based: Once, Immediate, Block
-/// - Timestamp Based: Cron
+/// - Timestamp Based: Cron, Delay, At
 /// - No Epoch support directly, advised to use block heights instead
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
 pub enum Interval {
@@ -73,13 +83,22 @@ pub enum Interval {
     Once,
 
     /// The ugly batch schedule type, in case you need to exceed single TXN gas limits, within fewest block(s)
+    /// First runs in the block it was created in, then every following block
     Immediate,
 
     /// Allows timing based on block intervals rather than timestamps
     Block(u64),
 
     /// Crontab Spec String
+    /// Either 5 fields (minute hour day-of-month month day-of-week),
+    /// or 6 fields with a leading seconds field, e.g. "0 0 * * * *"
     Cron(String),
+
+    /// Runs once, this many seconds after the task was created
+    Delay(u64),
+
+    /// Runs once, at this absolute time
+    At(Timestamp),
 }
 
 #[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Eq, JsonSchema)]
@@ -99,12 +118,60 @@ pub struct Boundary {
     pub end: Option<BoundarySpec>,
 }
 
+impl Boundary {
+    /// Start can't come after end, only compared when both use the same units
+    pub fn is_valid(&self) -> bool {
+        match (self.start, self.end) {
+            (Some(BoundarySpec::Height(start)), Some(BoundarySpec::Height(end))) => start <= end,
+            (Some(BoundarySpec::Time(start)), Some(BoundarySpec::Time(end))) => start <= end,
+            _ => true,
+        }
+    }
+
+    /// End is already behind the current block, in whichever units it uses
+    pub fn is_past(&self, env: &Env) -> bool {
+        match self.end {
+            Some(BoundarySpec::Height(end)) => end < env.block.height,
+            Some(BoundarySpec::Time(end)) => end < env.block.time,
+            None => false,
+        }
+    }
+
+    /// Start or end is further ahead of the current block than the horizon allows
+    pub fn is_beyond(&self, env: &Env, horizon: &BoundaryHorizon) -> bool {
+        let max_height = env.block.height.saturating_add(horizon.blocks);
+        let max_time = env.block.time.plus_seconds(horizon.seconds);
+        [self.start, self.end].iter().any(|spec| match spec {
+            Some(BoundarySpec::Height(height)) => *height > max_height,
+            Some(BoundarySpec::Time(time)) => *time > max_time,
+            None => false,
+        })
+    }
+}
+
+/// How far ahead of the current block boundaries may be, per unit
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub struct BoundaryHorizon {
+    pub blocks: u64,
+    pub seconds: u64,
+}
+
 #[derive(Debug, PartialEq, Eq, std::hash::Hash, Deserialize, Serialize, Clone, JsonSchema)]
 pub enum SlotType {
     Block,
     Cron,
 }
 
+/// Stable lowercase names, used as the `slot_kind` attribute value
+impl fmt::Display for SlotType {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            SlotType::Block => write!(f, "block"),
+            SlotType::Cron => write!(f, "cron"),
+        }
+    }
+}
+
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, JsonSchema)]
 pub struct Rule {
     /// TBD: Interchain query support (See ibc::IbcMsg)
@@ -127,9 +194,79 @@ pub struct Action<T = Empty> {
     pub gas_limit: Option<u64>,
 }
 
+impl Action {
+    /// Coins this action sends when it runs
+    /// Only msgs moving funds count: bank sends, delegations, wasm funds & ibc transfers
+    pub fn to_sent_amounts(&self) -> Vec<Coin> {
+        match &self.msg {
+            CosmosMsg::Bank(BankMsg::Send { amount, .. }) => amount.clone(),
+            CosmosMsg::Staking(StakingMsg::Delegate { amount, .. }) => vec![amount.clone()],
+            CosmosMsg::Wasm(WasmMsg::Execute { funds, .. })
+            | CosmosMsg::Wasm(WasmMsg::Instantiate { funds, .. }) => funds.clone(),
+            CosmosMsg::Ibc(IbcMsg::Transfer { amount, .. }) => vec![amount.clone()],
+            _ => vec![],
+        }
+    }
+}
+
+/// How the remaining actions of a task are handled once one of them fails
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, Default, PartialEq, Eq, JsonSchema)]
+pub enum FailMode {
+    /// Skip the actions after the failed one
+    #[default]
+    StopOnFirstFail,
+    /// Run every action, failed ones are recorded in the callback attributes
+    ContinueOnFail,
+}
+
+/// Message kinds a deployment can allow in task actions, one per CosmosMsg variant
+#[derive(Serialize, Deserialize, Copy, Clone, Debug, PartialEq, Eq, JsonSchema)]
+pub enum MsgKind {
+    Bank,
+    Custom,
+    Staking,
+    Distribution,
+    Stargate,
+    Ibc,
+    Wasm,
+    Gov,
+}
+
+impl MsgKind {
+    /// Every kind, the default so no message is restricted by kind
+    pub fn all() -> Vec<MsgKind> {
+        vec![
+            MsgKind::Bank,
+            MsgKind::Custom,
+            MsgKind::Staking,
+            MsgKind::Distribution,
+            MsgKind::Stargate,
+            MsgKind::Ibc,
+            MsgKind::Wasm,
+            MsgKind::Gov,
+        ]
+    }
+
+    /// None for kinds added to CosmosMsg after this list
+    pub fn of<T>(msg: &CosmosMsg<T>) -> Option<MsgKind> {
+        match msg {
+            CosmosMsg::Bank(_) => Some(MsgKind::Bank),
+            CosmosMsg::Custom(_) => Some(MsgKind::Custom),
+            CosmosMsg::Staking(_) => Some(MsgKind::Staking),
+            CosmosMsg::Distribution(_) => Some(MsgKind::Distribution),
+            CosmosMsg::Stargate { .. } => Some(MsgKind::Stargate),
+            CosmosMsg::Ibc(_) => Some(MsgKind::Ibc),
+            CosmosMsg::Wasm(_) => Some(MsgKind::Wasm),
+            CosmosMsg::Gov(_) => Some(MsgKind::Gov),
+            _ => None,
+        }
+    }
+}
+
 /// The response required by all rule queries. Bool is needed for croncat, T allows flexible rule engine
 pub type RuleResponse<T> = (bool, T);
 
+/// Fields added after the first release default when missing, so tasks stored by older versions still load
 #[derive(Serialize, Deserialize, Clone, Debug, PartialEq, JsonSchema)]
 pub struct Task {
     /// Entity responsible for this task, can change task details
@@ -142,6 +279,44 @@ pub struct Task {
     /// Defines if this task can continue until balance runs out
     pub stop_on_fail: bool,
 
+    /// Allows anyone to refill this task, refunds still go to the owner
+    #[serde(default)]
+    pub allow_sponsored_refill: bool,
+
+    /// Shares of the refund on removal, instead of sending it all to the owner
+    /// NOTE: Shares must sum to 1
+    #[serde(default)]
+    pub refund_split: Option<Vec<(Addr, Decimal)>>,
+
+    /// Agent that gets to execute this task first, any agent can after a grace period
+    #[serde(default)]
+    pub preferred_agent: Option<Addr>,
+
+    /// What happens to the remaining actions when one fails
+    #[serde(default)]
+    pub fail_mode: FailMode,
+
+    /// Agent fee paid for this task instead of the global one, to get it prioritized
+    /// NOTE: Can't be below the global agent fee, the higher of both is paid
+    #[serde(default)]
+    pub agent_fee_override: Option<Uint128>,
+
+    /// Hash of a task scheduled into the current slot every time this one runs successfully
+    #[serde(default)]
+    pub on_success_task: Option<String>,
+
+    /// Client chosen label, part of the hash so otherwise identical tasks can coexist
+    #[serde(default)]
+    pub label: Option<String>,
+
+    /// Free text note for the owner, unlike the label it's left out of the hash
+    #[serde(default)]
+    pub memo: Option<String>,
+
+    /// After a successful run, action denom balance beyond the next run's needs is sent back to the owner
+    #[serde(default)]
+    pub return_surplus: bool,
+
     /// NOTE: Only tally native balance here, manager can maintain token/balances outside of tasks
     pub total_deposit: Vec<Coin>,
 
@@ -152,12 +327,31 @@ pub struct Task {
     /// Rules MUST return the ResolverResponse type
     pub rules: Option<Vec<Rule>>,
     // TODO: funds! should we support funds being attached?
+    /// Execution history, updated after each successful run
+    /// The slot id (block height or timestamp) of the last successful execution
+    #[serde(default)]
+    pub last_executed_slot: Option<u64>,
+    /// Total number of successful executions
+    #[serde(default)]
+    pub executions_count: u64,
+    /// Number of failed executions in a row, reset by a successful one
+    #[serde(default)]
+    pub failure_streak: u64,
+
+    /// Block height & time the task was created at
+    #[serde(default)]
+    pub created_at_height: u64,
+    #[serde(default)]
+    pub created_at_time: Timestamp,
 }
 
 impl Task {
     /// Get the hash of a task based on parameters
+    /// The hash is the storage key, so only fields that never change once created feed it:
+    /// owner_id, interval, boundary, actions & rules.
+    /// total_deposit (changes on refill), execution history & creation info are left out
     pub fn to_hash(&self) -> String {
-        let message = format!(
+        let mut message = format!(
             "{:?}{:?}{:?}{:?}{:?}",
             self.owner_id,
             self.interval,
@@ -165,6 +359,10 @@ impl Task {
             self.actions,
             self.rules
         );
+        // Only labelled tasks hash it, so existing hashes stay the same
+        if let Some(label) = &self.label {
+            message.push_str(&format!("{:?}", label));
+        }
 
         let hash = Sha256::digest(message.as_bytes());
         encode(hash)
@@ -180,22 +378,21 @@ impl Task {
     // }
 
     /// Validate the task actions only use the supported messages
-    pub fn is_valid_msg(&self, self_addr: &Addr, sender: &Addr, owner_id: &Addr) -> bool {
+    pub fn is_valid_msg(&self, self_addr: &Addr) -> bool {
         // TODO: Chagne to default FALSE, once all messages are covered in tests
         let mut valid = true;
 
         for action in self.actions.iter() {
             match action.clone().msg {
-                CosmosMsg::Wasm(WasmMsg::Execute {
-                    contract_addr,
-                    funds: _,
-                    msg: _,
-                }) => {
-                    // TODO: Is there any way sender can be "self" creating a malicious task?
-                    // cannot be THIS contract id, unless predecessor is owner of THIS contract
-                    if &contract_addr == self_addr && sender != owner_id {
-                        valid = false;
-                    }
+                // Cannot target THIS contract with any wasm msg, otherwise a task could
+                // pause the contract, change settings or migrate it through the scheduler
+                CosmosMsg::Wasm(WasmMsg::Execute { contract_addr, .. })
+                | CosmosMsg::Wasm(WasmMsg::Migrate { contract_addr, .. })
+                | CosmosMsg::Wasm(WasmMsg::UpdateAdmin { contract_addr, .. })
+                | CosmosMsg::Wasm(WasmMsg::ClearAdmin { contract_addr })
+                    if &contract_addr == self_addr =>
+                {
+                    valid = false;
                 }
                 // TODO: Allow send, as long as coverage of assets is correctly handled
                 CosmosMsg::Bank(BankMsg::Send { .. }) => {
@@ -210,12 +407,30 @@ impl Task {
                     // Restrict bank msg for time being, so contract doesnt get drained, however could allow an escrow type setup
                     valid = false;
                 }
-                // TODO: Setup better support for IBC
-                CosmosMsg::Ibc(IbcMsg::Transfer { .. }) => {
-                    // Restrict bank msg for time being, so contract doesnt get drained, however could allow an escrow type setup
+                // Transfers need a channel, a recipient, something to send & a timeout to give up at
+                CosmosMsg::Ibc(IbcMsg::Transfer {
+                    channel_id,
+                    to_address,
+                    amount,
+                    timeout,
+                }) => {
+                    let has_timeout = matches!(timeout.block(), Some(b) if b.height > 0)
+                        || matches!(timeout.timestamp(), Some(t) if t.nanos() > 0);
+                    if channel_id.is_empty()
+                        || to_address.is_empty()
+                        || amount.amount.is_zero()
+                        || !has_timeout
+                    {
+                        valid = false;
+                    }
+                }
+                // Authz exec must be run by this contract, on behalf of the task owner only
+                CosmosMsg::Stargate { type_url, value }
+                    if type_url == MSG_EXEC_TYPE_URL
+                        && !is_valid_exec(value.as_slice(), self_addr, &self.owner_id) =>
+                {
                     valid = false;
                 }
-                // TODO: Check authZ messages
                 _ => (),
             }
         }
@@ -223,17 +438,49 @@ impl Task {
         valid
     }
 
+    /// Checks every action is one of the allowed message kinds
+    pub fn has_allowed_msg_kinds(&self, allowed: &[MsgKind]) -> bool {
+        self.actions
+            .iter()
+            .all(|action| matches!(MsgKind::of(&action.msg), Some(kind) if allowed.contains(&kind)))
+    }
+
+    /// Checks if any action is repeated exactly, msg and gas limit included
+    pub fn has_duplicate_actions(&self) -> bool {
+        self.actions
+            .iter()
+            .enumerate()
+            .any(|(i, action)| self.actions[i + 1..].contains(action))
+    }
+
     /// Get task gas total
     /// helper for getting total configured gas for this tasks actions
+    /// saturates at u64::MAX, see checked_gas_total
     pub fn to_gas_total(&self) -> u64 {
-        let mut gas: u64 = 0;
+        self.checked_gas_total().unwrap_or(u64::MAX)
+    }
 
+    /// Total configured gas for this tasks actions, None if it doesn't fit in a u64
+    pub fn checked_gas_total(&self) -> Option<u64> {
         // tally all the gases
+        self.actions.iter().try_fold(0u64, |gas, action| {
+            gas.checked_add(action.gas_limit.unwrap_or(0))
+        })
+    }
+
+    /// Coins the actions send on each execution, merged by denom
+    /// Only msgs moving funds count, see Action::to_sent_amounts
+    pub fn to_action_amounts(&self) -> Vec<Coin> {
+        let mut amounts: Vec<Coin> = vec![];
         for action in self.actions.iter() {
-            gas = gas.saturating_add(action.gas_limit.unwrap_or(0));
+            for coin in action.to_sent_amounts() {
+                match amounts.iter_mut().find(|a| a.denom == coin.denom) {
+                    Some(a) => a.amount = a.amount.saturating_add(coin.amount),
+                    None => amounts.push(coin),
+                }
+            }
         }
-
-        gas
+        amounts
     }
 }
 
@@ -300,6 +547,48 @@ impl GenericBalance {
             }
         };
     }
+
+    /// Same as minus_tokens, but stops at zero instead of underflowing
+    /// Returns the part of `minus` that wasn't covered, empty when the balance was sufficient
+    pub fn saturating_minus_tokens(&mut self, minus: Balance) -> GenericBalance {
+        let mut shortfall = GenericBalance::default();
+        match minus {
+            Balance::Native(balance) => {
+                for token in balance.0 {
+                    let exist = self
+                        .native
+                        .iter_mut()
+                        .find(|exist| exist.denom == token.denom);
+                    let available = exist.as_ref().map(|e| e.amount).unwrap_or_default();
+                    if let Some(exist) = exist {
+                        exist.amount = available.saturating_sub(token.amount);
+                    }
+                    if token.amount > available {
+                        shortfall
+                            .native
+                            .push(Coin::new((token.amount - available).u128(), token.denom));
+                    }
+                }
+            }
+            Balance::Cw20(token) => {
+                let exist = self
+                    .cw20
+                    .iter_mut()
+                    .find(|exist| exist.address == token.address);
+                let available = exist.as_ref().map(|e| e.amount).unwrap_or_default();
+                if let Some(exist) = exist {
+                    exist.amount = available.saturating_sub(token.amount);
+                }
+                if token.amount > available {
+                    shortfall.cw20.push(Cw20CoinVerified {
+                        address: token.address,
+                        amount: token.amount - available,
+                    });
+                }
+            }
+        };
+        shortfall
+    }
 }
 
 fn get_next_block_limited(env: Env, boundary: Boundary) -> (u64, SlotType) {
@@ -395,6 +684,17 @@ fn get_next_block_by_offset(env: Env, boundary: Boundary, block: u64) -> (u64, S
     (next_block_height, SlotType::Block)
 }
 
+/// Parses a crontab with 5 fields (minute, hour, day of month, month, day of week)
+/// or 6 fields, where the extra leading one is seconds. 5 field crons run at second 0
+pub fn parse_cron(crontab: &str) -> Result<Schedule, String> {
+    let expression = match crontab.split_whitespace().count() {
+        5 => format!("0 {}", crontab),
+        6 => crontab.to_string(),
+        n => return Err(format!("Cron must have 5 or 6 fields, found {}", n)),
+    };
+    Schedule::from_str(&expression).map_err(|e| e.to_string())
+}
+
 impl Interval {
     pub fn next(&self, env: Env, boundary: Boundary) -> (u64, SlotType) {
         match self {
@@ -426,7 +726,7 @@ impl Interval {
                     current_block_ts
                 };
 
-                let schedule = Schedule::from_str(crontab.as_str()).unwrap();
+                let schedule = parse_cron(crontab).unwrap();
                 let next_ts = schedule.next_after(&current_ts).unwrap();
                 (next_ts, SlotType::Cron)
             }
@@ -436,17 +736,47 @@ impl Interval {
             // - Boundary specifies a start/end that block offsets can be computed from
             // - Block offset will truncate to specific modulo offsets
             Interval::Block(block) => get_next_block_by_offset(env, boundary, *block),
+            // return the time slot the delay ends in, counted from the current block.
+            // Only resolved on creation, the task doesn't repeat
+            Interval::Delay(seconds) => (
+                env.block.time.plus_seconds(*seconds).nanos(),
+                SlotType::Cron,
+            ),
+            // return the time slot of the timestamp, the task doesn't repeat
+            Interval::At(timestamp) => (timestamp.nanos(), SlotType::Cron),
+        }
+    }
+    /// Gap between the next two occurrences of a cron interval after a timestamp, in nanos
+    /// Returns None for non-cron intervals or crontabs that can't be parsed
+    pub fn cron_gap(&self, after: u64) -> Option<u64> {
+        if let Interval::Cron(crontab) = self {
+            let schedule = parse_cron(crontab).ok()?;
+            let first = schedule.next_after(&after)?;
+            let second = schedule.next_after(&first)?;
+            Some(second.saturating_sub(first))
+        } else {
+            None
         }
     }
+    /// Once, Delay & At tasks end after their single run, the others reschedule
+    pub fn is_one_shot(&self) -> bool {
+        matches!(self, Interval::Once | Interval::Delay(_) | Interval::At(_))
+    }
+    /// An At interval whose time already passed would never run on time
+    pub fn is_past(&self, env: &Env) -> bool {
+        matches!(self, Interval::At(timestamp) if *timestamp < env.block.time)
+    }
     pub fn is_valid(&self) -> bool {
         match self {
             Interval::Once => true,
             Interval::Immediate => true,
-            Interval::Block(_) => true,
-            Interval::Cron(crontab) => {
-                let s = Schedule::from_str(crontab);
-                s.is_ok()
-            }
+            // Every zero blocks would keep rescheduling into the same slot
+            Interval::Block(blocks) => *blocks > 0,
+            Interval::Delay(seconds) => *seconds > 0,
+            // Whether it's still ahead is checked with the block, see is_past
+            Interval::At(_) => true,
+            // Same for a crontab firing twice at the same time
+            Interval::Cron(crontab) => parse_cron(crontab).is_ok() && self.cron_gap(0) != Some(0),
         }
     }
 }
@@ -454,9 +784,106 @@ impl Interval {
 #[cfg(test)]
 mod tests {
     use super::*;
-    use cosmwasm_std::{IbcTimeout, VoteOption};
+    use cosmwasm_std::testing::mock_env;
+    use cosmwasm_std::{coin, coins, from_slice, IbcTimeout, IbcTimeoutBlock, VoteOption};
     use hex::ToHex;
 
+    #[test]
+    fn boundary_is_valid() {
+        let boundary = |start, end| Boundary { start, end };
+        assert!(boundary(None, None).is_valid());
+        assert!(boundary(
+            Some(BoundarySpec::Height(50)),
+            Some(BoundarySpec::Height(100))
+        )
+        .is_valid());
+        assert!(!boundary(
+            Some(BoundarySpec::Height(100)),
+            Some(BoundarySpec::Height(50))
+        )
+        .is_valid());
+        assert!(!boundary(
+            Some(BoundarySpec::Time(Timestamp::from_seconds(100))),
+            Some(BoundarySpec::Time(Timestamp::from_seconds(50)))
+        )
+        .is_valid());
+        // mixed units aren't comparable
+        assert!(boundary(
+            Some(BoundarySpec::Height(100)),
+            Some(BoundarySpec::Time(Timestamp::from_nanos(50)))
+        )
+        .is_valid());
+    }
+
+    #[test]
+    fn boundary_is_beyond() {
+        let env = mock_env();
+        let horizon = BoundaryHorizon {
+            blocks: 100,
+            seconds: 600,
+        };
+        let boundary = |start, end| Boundary { start, end };
+        assert!(!boundary(None, None).is_beyond(&env, &horizon));
+        assert!(!boundary(
+            Some(BoundarySpec::Height(env.block.height + 100)),
+            Some(BoundarySpec::Time(env.block.time.plus_seconds(600)))
+        )
+        .is_beyond(&env, &horizon));
+        assert!(
+            boundary(Some(BoundarySpec::Height(env.block.height + 101)), None)
+                .is_beyond(&env, &horizon)
+        );
+        assert!(boundary(
+            None,
+            Some(BoundarySpec::Time(env.block.time.plus_seconds(601)))
+        )
+        .is_beyond(&env, &horizon));
+    }
+
+    #[test]
+    fn interval_is_valid() {
+        assert!(!Interval::Block(0).is_valid());
+        assert!(Interval::Block(1).is_valid());
+        assert!(!Interval::Delay(0).is_valid());
+        assert!(Interval::Cron("0 0 * * * *".to_string()).is_valid());
+        assert!(!Interval::Cron("faux_paw".to_string()).is_valid());
+    }
+
+    #[test]
+    fn interval_at() {
+        let env = mock_env();
+        let at = Interval::At(env.block.time.plus_seconds(60));
+        assert_eq!(
+            (env.block.time.plus_seconds(60).nanos(), SlotType::Cron),
+            at.next(
+                env.clone(),
+                Boundary {
+                    start: None,
+                    end: None
+                }
+            )
+        );
+        assert!(at.is_valid());
+        assert!(!at.is_past(&env));
+        assert!(!Interval::At(env.block.time).is_past(&env));
+        assert!(Interval::At(env.block.time.minus_seconds(1)).is_past(&env));
+    }
+
+    #[test]
+    fn interval_cron_gap() {
+        let after = 1_600_000_000_000_000_000;
+        assert_eq!(
+            Some(1_000_000_000),
+            Interval::Cron("* * * * * *".to_string()).cron_gap(after)
+        );
+        assert_eq!(
+            Some(3_600_000_000_000),
+            Interval::Cron("0 0 * * * *".to_string()).cron_gap(after)
+        );
+        assert_eq!(None, Interval::Cron("faux_paw".to_string()).cron_gap(after));
+        assert_eq!(None, Interval::Block(5).cron_gap(after));
+    }
+
     #[test]
     fn is_valid_msg_once_block_based() {
         let task = Task {
@@ -467,6 +894,15 @@ mod tests {
                 end: Some(BoundarySpec::Height(8)),
             },
             stop_on_fail: false,
+            allow_sponsored_refill: false,
+            refund_split: None,
+            preferred_agent: None,
+            fail_mode: FailMode::StopOnFirstFail,
+            agent_fee_override: None,
+            on_success_task: None,
+            label: None,
+            memo: None,
+            return_surplus: false,
             total_deposit: Default::default(),
             actions: vec![Action {
                 msg: CosmosMsg::Wasm(WasmMsg::Execute {
@@ -480,12 +916,13 @@ mod tests {
                 contract_addr: Addr::unchecked("foo"),
                 msg: Binary("bar".into()),
             }]),
+            last_executed_slot: None,
+            executions_count: 0,
+            failure_streak: 0,
+            created_at_height: 0,
+            created_at_time: Default::default(),
         };
-        assert!(task.is_valid_msg(
-            &Addr::unchecked("alice2"),
-            &Addr::unchecked("bob"),
-            &Addr::unchecked("bob")
-        ));
+        assert!(task.is_valid_msg(&Addr::unchecked("alice2")));
     }
 
     #[test]
@@ -498,6 +935,15 @@ mod tests {
                 end: Some(BoundarySpec::Time(Timestamp::from_nanos(2_000_000_000))),
             },
             stop_on_fail: false,
+            allow_sponsored_refill: false,
+            refund_split: None,
+            preferred_agent: None,
+            fail_mode: FailMode::StopOnFirstFail,
+            agent_fee_override: None,
+            on_success_task: None,
+            label: None,
+            memo: None,
+            return_surplus: false,
             total_deposit: Default::default(),
             actions: vec![Action {
                 msg: CosmosMsg::Wasm(WasmMsg::Execute {
@@ -511,12 +957,13 @@ mod tests {
                 contract_addr: Addr::unchecked("foo"),
                 msg: Binary("bar".into()),
             }]),
+            last_executed_slot: None,
+            executions_count: 0,
+            failure_streak: 0,
+            created_at_height: 0,
+            created_at_time: Default::default(),
         };
-        assert!(task.is_valid_msg(
-            &Addr::unchecked("alice2"),
-            &Addr::unchecked("bob"),
-            &Addr::unchecked("bob")
-        ));
+        assert!(task.is_valid_msg(&Addr::unchecked("alice2")));
     }
 
     #[test]
@@ -529,6 +976,15 @@ mod tests {
                 end: None,
             },
             stop_on_fail: false,
+            allow_sponsored_refill: false,
+            refund_split: None,
+            preferred_agent: None,
+            fail_mode: FailMode::StopOnFirstFail,
+            agent_fee_override: None,
+            on_success_task: None,
+            label: None,
+            memo: None,
+            return_surplus: false,
             total_deposit: Default::default(),
             actions: vec![Action {
                 msg: CosmosMsg::Wasm(WasmMsg::Execute {
@@ -542,17 +998,18 @@ mod tests {
                 contract_addr: Addr::unchecked("foo"),
                 msg: Binary("bar".into()),
             }]),
+            last_executed_slot: None,
+            executions_count: 0,
+            failure_streak: 0,
+            created_at_height: 0,
+            created_at_time: Default::default(),
         };
-        assert!(task.is_valid_msg(
-            &Addr::unchecked("alice2"),
-            &Addr::unchecked("bob"),
-            &Addr::unchecked("bob")
-        ));
+        assert!(task.is_valid_msg(&Addr::unchecked("alice2")));
     }
 
     #[test]
     fn is_valid_msg_wrong_account() {
-        // Cannot create a task to execute on the cron manager when not the owner
+        // Cannot create a task to execute on the cron manager
         let task = Task {
             owner_id: Addr::unchecked("alice"),
             interval: Interval::Block(5),
@@ -561,6 +1018,15 @@ mod tests {
                 end: None,
             },
             stop_on_fail: false,
+            allow_sponsored_refill: false,
+            refund_split: None,
+            preferred_agent: None,
+            fail_mode: FailMode::StopOnFirstFail,
+            agent_fee_override: None,
+            on_success_task: None,
+            label: None,
+            memo: None,
+            return_surplus: false,
             total_deposit: Default::default(),
             actions: vec![Action {
                 msg: CosmosMsg::Wasm(WasmMsg::Execute {
@@ -574,12 +1040,207 @@ mod tests {
                 contract_addr: Addr::unchecked("foo"),
                 msg: Binary("bar".into()),
             }]),
+            last_executed_slot: None,
+            executions_count: 0,
+            failure_streak: 0,
+            created_at_height: 0,
+            created_at_time: Default::default(),
+        };
+        assert!(!task.is_valid_msg(&Addr::unchecked("alice")));
+    }
+
+    #[test]
+    fn is_valid_msg_self_migrate() {
+        // Cannot create a task to migrate the cron manager
+        let task = Task {
+            owner_id: Addr::unchecked("alice"),
+            interval: Interval::Block(5),
+            boundary: Boundary {
+                start: Some(BoundarySpec::Height(4)),
+                end: None,
+            },
+            stop_on_fail: false,
+            allow_sponsored_refill: false,
+            refund_split: None,
+            preferred_agent: None,
+            fail_mode: FailMode::StopOnFirstFail,
+            agent_fee_override: None,
+            on_success_task: None,
+            label: None,
+            memo: None,
+            return_surplus: false,
+            total_deposit: Default::default(),
+            actions: vec![Action {
+                msg: CosmosMsg::Wasm(WasmMsg::Migrate {
+                    contract_addr: "croncat".to_string(),
+                    new_code_id: 2,
+                    msg: Binary::from(vec![]),
+                }),
+                gas_limit: Some(5),
+            }],
+            rules: None,
+            last_executed_slot: None,
+            executions_count: 0,
+            failure_streak: 0,
+            created_at_height: 0,
+            created_at_time: Default::default(),
+        };
+        assert!(!task.is_valid_msg(&Addr::unchecked("croncat")));
+    }
+
+    #[test]
+    fn is_valid_msg_self_update_admin() {
+        // Cannot create a task to change the admin of the cron manager
+        let task = Task {
+            owner_id: Addr::unchecked("alice"),
+            interval: Interval::Block(5),
+            boundary: Boundary {
+                start: Some(BoundarySpec::Height(4)),
+                end: None,
+            },
+            stop_on_fail: false,
+            allow_sponsored_refill: false,
+            refund_split: None,
+            preferred_agent: None,
+            fail_mode: FailMode::StopOnFirstFail,
+            agent_fee_override: None,
+            on_success_task: None,
+            label: None,
+            memo: None,
+            return_surplus: false,
+            total_deposit: Default::default(),
+            actions: vec![Action {
+                msg: CosmosMsg::Wasm(WasmMsg::UpdateAdmin {
+                    contract_addr: "croncat".to_string(),
+                    admin: "alice".to_string(),
+                }),
+                gas_limit: Some(5),
+            }],
+            rules: None,
+            last_executed_slot: None,
+            executions_count: 0,
+            failure_streak: 0,
+            created_at_height: 0,
+            created_at_time: Default::default(),
         };
-        assert!(!task.is_valid_msg(
-            &Addr::unchecked("alice"),
-            &Addr::unchecked("sender"),
-            &Addr::unchecked("bob")
-        ));
+        assert!(!task.is_valid_msg(&Addr::unchecked("croncat")));
+    }
+
+    #[test]
+    fn is_valid_msg_self_clear_admin() {
+        // Cannot create a task to clear the admin of the cron manager
+        let task = Task {
+            owner_id: Addr::unchecked("alice"),
+            interval: Interval::Block(5),
+            boundary: Boundary {
+                start: Some(BoundarySpec::Height(4)),
+                end: None,
+            },
+            stop_on_fail: false,
+            allow_sponsored_refill: false,
+            refund_split: None,
+            preferred_agent: None,
+            fail_mode: FailMode::StopOnFirstFail,
+            agent_fee_override: None,
+            on_success_task: None,
+            label: None,
+            memo: None,
+            return_surplus: false,
+            total_deposit: Default::default(),
+            actions: vec![Action {
+                msg: CosmosMsg::Wasm(WasmMsg::ClearAdmin {
+                    contract_addr: "croncat".to_string(),
+                }),
+                gas_limit: Some(5),
+            }],
+            rules: None,
+            last_executed_slot: None,
+            executions_count: 0,
+            failure_streak: 0,
+            created_at_height: 0,
+            created_at_time: Default::default(),
+        };
+        assert!(!task.is_valid_msg(&Addr::unchecked("croncat")));
+    }
+
+    // Minimal protobuf encoding of a length-delimited field, enough for short test msgs
+    fn proto_field(num: u8, bytes: &[u8]) -> Vec<u8> {
+        let mut field = vec![num << 3 | 2, bytes.len() as u8];
+        field.extend_from_slice(bytes);
+        field
+    }
+
+    fn authz_exec_task(grantee: &str, granter: &str, inner_type_url: &str) -> Task {
+        let withdraw = [
+            proto_field(1, granter.as_bytes()),
+            proto_field(2, b"validator"),
+        ]
+        .concat();
+        let any = [
+            proto_field(1, inner_type_url.as_bytes()),
+            proto_field(2, &withdraw),
+        ]
+        .concat();
+        let exec = [proto_field(1, grantee.as_bytes()), proto_field(2, &any)].concat();
+        Task {
+            owner_id: Addr::unchecked("alice"),
+            interval: Interval::Block(5),
+            boundary: Boundary {
+                start: None,
+                end: None,
+            },
+            stop_on_fail: false,
+            allow_sponsored_refill: false,
+            refund_split: None,
+            preferred_agent: None,
+            fail_mode: FailMode::StopOnFirstFail,
+            agent_fee_override: None,
+            on_success_task: None,
+            label: None,
+            memo: None,
+            return_surplus: false,
+            total_deposit: Default::default(),
+            actions: vec![Action {
+                msg: CosmosMsg::Stargate {
+                    type_url: MSG_EXEC_TYPE_URL.to_string(),
+                    value: Binary::from(exec),
+                },
+                gas_limit: Some(5),
+            }],
+            rules: None,
+            last_executed_slot: None,
+            executions_count: 0,
+            failure_streak: 0,
+            created_at_height: 0,
+            created_at_time: Default::default(),
+        }
+    }
+
+    #[test]
+    fn is_valid_msg_authz_exec() {
+        let withdraw_url = "/cosmos.distribution.v1beta1.MsgWithdrawDelegatorReward";
+        let task = authz_exec_task("croncat", "alice", withdraw_url);
+        assert!(task.is_valid_msg(&Addr::unchecked("croncat")));
+
+        // granter must be the task owner
+        let task = authz_exec_task("croncat", "bob", withdraw_url);
+        assert!(!task.is_valid_msg(&Addr::unchecked("croncat")));
+
+        // grantee must be this contract
+        let task = authz_exec_task("someone", "alice", withdraw_url);
+        assert!(!task.is_valid_msg(&Addr::unchecked("croncat")));
+
+        // only supported inner msgs
+        let task = authz_exec_task("croncat", "alice", "/cosmos.gov.v1beta1.MsgVote");
+        assert!(!task.is_valid_msg(&Addr::unchecked("croncat")));
+
+        // malformed exec
+        let mut task = authz_exec_task("croncat", "alice", withdraw_url);
+        task.actions[0].msg = CosmosMsg::Stargate {
+            type_url: MSG_EXEC_TYPE_URL.to_string(),
+            value: Binary::from(vec![10, 50, 1]),
+        };
+        assert!(!task.is_valid_msg(&Addr::unchecked("croncat")));
     }
 
     #[test]
@@ -593,6 +1254,15 @@ mod tests {
                 end: None,
             },
             stop_on_fail: false,
+            allow_sponsored_refill: false,
+            refund_split: None,
+            preferred_agent: None,
+            fail_mode: FailMode::StopOnFirstFail,
+            agent_fee_override: None,
+            on_success_task: None,
+            label: None,
+            memo: None,
+            return_surplus: false,
             total_deposit: Default::default(),
             actions: vec![Action {
                 msg: CosmosMsg::Gov(GovMsg::Vote {
@@ -605,18 +1275,177 @@ mod tests {
                 contract_addr: Addr::unchecked("foo"),
                 msg: Binary("bar".into()),
             }]),
+            last_executed_slot: None,
+            executions_count: 0,
+            failure_streak: 0,
+            created_at_height: 0,
+            created_at_time: Default::default(),
+        };
+        assert!(!task.is_valid_msg(&Addr::unchecked("alice")));
+    }
+
+    #[test]
+    fn has_allowed_msg_kinds() {
+        let mut task = Task {
+            owner_id: Addr::unchecked("bob"),
+            interval: Interval::Once,
+            boundary: Boundary {
+                start: None,
+                end: None,
+            },
+            stop_on_fail: false,
+            allow_sponsored_refill: false,
+            refund_split: None,
+            preferred_agent: None,
+            fail_mode: FailMode::StopOnFirstFail,
+            agent_fee_override: None,
+            on_success_task: None,
+            label: None,
+            memo: None,
+            return_surplus: false,
+            total_deposit: Default::default(),
+            actions: vec![Action {
+                msg: CosmosMsg::Wasm(WasmMsg::ClearAdmin {
+                    contract_addr: "alice".to_string(),
+                }),
+                gas_limit: Some(5),
+            }],
+            rules: None,
+            last_executed_slot: None,
+            executions_count: 0,
+            failure_streak: 0,
+            created_at_height: 0,
+            created_at_time: Default::default(),
+        };
+        assert!(task.has_allowed_msg_kinds(&MsgKind::all()));
+        assert!(task.has_allowed_msg_kinds(&[MsgKind::Wasm]));
+        assert!(!task.has_allowed_msg_kinds(&[MsgKind::Bank, MsgKind::Staking]));
+
+        // every action must be allowed
+        task.actions.push(Action {
+            msg: CosmosMsg::Gov(GovMsg::Vote {
+                proposal_id: 1,
+                vote: VoteOption::Yes,
+            }),
+            gas_limit: Some(5),
+        });
+        assert!(!task.has_allowed_msg_kinds(&[MsgKind::Wasm]));
+        assert!(task.has_allowed_msg_kinds(&[MsgKind::Wasm, MsgKind::Gov]));
+    }
+
+    #[test]
+    fn checked_gas_total() {
+        let action = |gas_limit: Option<u64>| Action {
+            msg: CosmosMsg::Wasm(WasmMsg::ClearAdmin {
+                contract_addr: "alice".to_string(),
+            }),
+            gas_limit,
+        };
+        let mut task = Task {
+            owner_id: Addr::unchecked("bob"),
+            interval: Interval::Once,
+            boundary: Boundary {
+                start: None,
+                end: None,
+            },
+            stop_on_fail: false,
+            allow_sponsored_refill: false,
+            refund_split: None,
+            preferred_agent: None,
+            fail_mode: FailMode::StopOnFirstFail,
+            agent_fee_override: None,
+            on_success_task: None,
+            label: None,
+            memo: None,
+            return_surplus: false,
+            total_deposit: Default::default(),
+            actions: vec![action(Some(5)), action(None), action(Some(10))],
+            rules: None,
+            last_executed_slot: None,
+            executions_count: 0,
+            failure_streak: 0,
+            created_at_height: 0,
+            created_at_time: Default::default(),
         };
-        assert!(!task.is_valid_msg(
-            &Addr::unchecked("alice"),
-            &Addr::unchecked("sender"),
-            &Addr::unchecked("bob")
-        ));
+        assert_eq!(Some(15), task.checked_gas_total());
+        assert_eq!(15, task.to_gas_total());
+
+        // past u64 the checked sum fails, the other saturates
+        task.actions = vec![action(Some(u64::MAX - 1)), action(Some(2))];
+        assert_eq!(None, task.checked_gas_total());
+        assert_eq!(u64::MAX, task.to_gas_total());
+    }
+
+    #[test]
+    fn to_action_amounts() {
+        let mut task = Task {
+            owner_id: Addr::unchecked("bob"),
+            interval: Interval::Once,
+            boundary: Boundary {
+                start: None,
+                end: None,
+            },
+            stop_on_fail: false,
+            allow_sponsored_refill: false,
+            refund_split: None,
+            preferred_agent: None,
+            fail_mode: FailMode::StopOnFirstFail,
+            agent_fee_override: None,
+            on_success_task: None,
+            label: None,
+            memo: None,
+            return_surplus: false,
+            total_deposit: Default::default(),
+            actions: vec![Action {
+                msg: CosmosMsg::Wasm(WasmMsg::ClearAdmin {
+                    contract_addr: "alice".to_string(),
+                }),
+                gas_limit: Some(5),
+            }],
+            rules: None,
+            last_executed_slot: None,
+            executions_count: 0,
+            failure_streak: 0,
+            created_at_height: 0,
+            created_at_time: Default::default(),
+        };
+        assert!(task.to_action_amounts().is_empty());
+
+        // amounts are merged by denom
+        task.actions.push(Action {
+            msg: BankMsg::Send {
+                to_address: "alice".to_string(),
+                amount: vec![coin(3, "atom"), coin(5, "juno")],
+            }
+            .into(),
+            gas_limit: Some(5),
+        });
+        task.actions.push(Action {
+            msg: StakingMsg::Delegate {
+                validator: "val".to_string(),
+                amount: coin(10, "juno"),
+            }
+            .into(),
+            gas_limit: Some(5),
+        });
+        task.actions.push(Action {
+            msg: CosmosMsg::Wasm(WasmMsg::Execute {
+                contract_addr: "alice".to_string(),
+                msg: Binary::default(),
+                funds: coins(1, "atom"),
+            }),
+            gas_limit: Some(5),
+        });
+        assert_eq!(
+            vec![coin(4, "atom"), coin(15, "juno")],
+            task.to_action_amounts()
+        );
     }
 
     #[test]
     fn is_valid_msg_transfer() {
-        // A task with CosmosMsg::Ibc Transfer should return false
-        let task = Task {
+        // A task with a complete CosmosMsg::Ibc Transfer should return true
+        let mut task = Task {
             owner_id: Addr::unchecked("bob"),
             interval: Interval::Block(5),
             boundary: Boundary {
@@ -624,6 +1453,15 @@ mod tests {
                 end: None,
             },
             stop_on_fail: false,
+            allow_sponsored_refill: false,
+            refund_split: None,
+            preferred_agent: None,
+            fail_mode: FailMode::StopOnFirstFail,
+            agent_fee_override: None,
+            on_success_task: None,
+            label: None,
+            memo: None,
+            return_surplus: false,
             total_deposit: Default::default(),
             actions: vec![Action {
                 msg: CosmosMsg::Ibc(IbcMsg::Transfer {
@@ -638,12 +1476,52 @@ mod tests {
                 contract_addr: Addr::unchecked("foo"),
                 msg: Binary("bar".into()),
             }]),
+            last_executed_slot: None,
+            executions_count: 0,
+            failure_streak: 0,
+            created_at_height: 0,
+            created_at_time: Default::default(),
         };
-        assert!(!task.is_valid_msg(
-            &Addr::unchecked("alice"),
-            &Addr::unchecked("sender"),
-            &Addr::unchecked("bob")
-        ));
+        assert!(task.is_valid_msg(&Addr::unchecked("alice")));
+
+        // block timeouts work too
+        task.actions[0].msg = CosmosMsg::Ibc(IbcMsg::Transfer {
+            channel_id: "id".to_string(),
+            to_address: "address".to_string(),
+            amount: Coin::new(10, "coin"),
+            timeout: IbcTimeout::with_block(IbcTimeoutBlock {
+                revision: 1,
+                height: 100,
+            }),
+        });
+        assert!(task.is_valid_msg(&Addr::unchecked("alice")));
+
+        // missing timeout
+        task.actions[0].msg = CosmosMsg::Ibc(IbcMsg::Transfer {
+            channel_id: "id".to_string(),
+            to_address: "address".to_string(),
+            amount: Coin::new(10, "coin"),
+            timeout: from_slice(br#"{"block":null,"timestamp":null}"#).unwrap(),
+        });
+        assert!(!task.is_valid_msg(&Addr::unchecked("alice")));
+
+        // missing channel
+        task.actions[0].msg = CosmosMsg::Ibc(IbcMsg::Transfer {
+            channel_id: "".to_string(),
+            to_address: "address".to_string(),
+            amount: Coin::new(10, "coin"),
+            timeout: IbcTimeout::with_timestamp(Timestamp::from_nanos(1_000_000_000)),
+        });
+        assert!(!task.is_valid_msg(&Addr::unchecked("alice")));
+
+        // nothing to send
+        task.actions[0].msg = CosmosMsg::Ibc(IbcMsg::Transfer {
+            channel_id: "id".to_string(),
+            to_address: "address".to_string(),
+            amount: Coin::new(0, "coin"),
+            timeout: IbcTimeout::with_timestamp(Timestamp::from_nanos(1_000_000_000)),
+        });
+        assert!(!task.is_valid_msg(&Addr::unchecked("alice")));
     }
 
     #[test]
@@ -657,6 +1535,15 @@ mod tests {
                 end: None,
             },
             stop_on_fail: false,
+            allow_sponsored_refill: false,
+            refund_split: None,
+            preferred_agent: None,
+            fail_mode: FailMode::StopOnFirstFail,
+            agent_fee_override: None,
+            on_success_task: None,
+            label: None,
+            memo: None,
+            return_surplus: false,
             total_deposit: Default::default(),
             actions: vec![Action {
                 msg: CosmosMsg::Bank(BankMsg::Burn {
@@ -668,12 +1555,13 @@ mod tests {
                 contract_addr: Addr::unchecked("foo"),
                 msg: Binary("bar".into()),
             }]),
+            last_executed_slot: None,
+            executions_count: 0,
+            failure_streak: 0,
+            created_at_height: 0,
+            created_at_time: Default::default(),
         };
-        assert!(!task.is_valid_msg(
-            &Addr::unchecked("alice"),
-            &Addr::unchecked("sender"),
-            &Addr::unchecked("bob")
-        ));
+        assert!(!task.is_valid_msg(&Addr::unchecked("alice")));
     }
 
     #[test]
@@ -687,6 +1575,15 @@ mod tests {
                 end: None,
             },
             stop_on_fail: false,
+            allow_sponsored_refill: false,
+            refund_split: None,
+            preferred_agent: None,
+            fail_mode: FailMode::StopOnFirstFail,
+            agent_fee_override: None,
+            on_success_task: None,
+            label: None,
+            memo: None,
+            return_surplus: false,
             total_deposit: Default::default(),
             actions: vec![Action {
                 msg: CosmosMsg::Bank(BankMsg::Send {
@@ -699,12 +1596,13 @@ mod tests {
                 contract_addr: Addr::unchecked("foo"),
                 msg: Binary("bar".into()),
             }]),
+            last_executed_slot: None,
+            executions_count: 0,
+            failure_streak: 0,
+            created_at_height: 0,
+            created_at_time: Default::default(),
         };
-        assert!(!task.is_valid_msg(
-            &Addr::unchecked("alice"),
-            &Addr::unchecked("sender"),
-            &Addr::unchecked("bob")
-        ));
+        assert!(!task.is_valid_msg(&Addr::unchecked("alice")));
     }
 
     #[test]
@@ -872,6 +1770,45 @@ mod tests {
         coins.minus_tokens(minus_cw20);
     }
 
+    #[test]
+    fn test_saturating_minus_tokens() {
+        let mut coins: GenericBalance = GenericBalance::default();
+        coins.add_tokens(Balance::from(vec![Coin::new(100, "native")]));
+        coins.add_tokens(Balance::Cw20(Cw20CoinVerified {
+            address: Addr::unchecked("cw20"),
+            amount: 100u128.into(),
+        }));
+
+        // Enough balance, nothing uncovered
+        let shortfall = coins.saturating_minus_tokens(Balance::from(vec![Coin::new(10, "native")]));
+        assert_eq!(coins.native, vec![Coin::new(90, "native")]);
+        assert_eq!(GenericBalance::default(), shortfall);
+
+        // Stops at zero, reporting the difference
+        let shortfall = coins.saturating_minus_tokens(Balance::from(vec![
+            Coin::new(91, "native"),
+            Coin::new(5, "missing"),
+        ]));
+        assert_eq!(coins.native, vec![Coin::new(0, "native")]);
+        assert_eq!(
+            shortfall.native,
+            vec![Coin::new(1, "native"), Coin::new(5, "missing")]
+        );
+
+        let shortfall = coins.saturating_minus_tokens(Balance::Cw20(Cw20CoinVerified {
+            address: Addr::unchecked("cw20"),
+            amount: 101u128.into(),
+        }));
+        assert!(coins.cw20[0].amount.is_zero());
+        assert_eq!(
+            shortfall.cw20,
+            vec![Cw20CoinVerified {
+                address: Addr::unchecked("cw20"),
+                amount: 1u128.into(),
+            }]
+        );
+    }
+
     #[test]
     fn hashing() {
         let task = Task {
@@ -882,6 +1819,15 @@ mod tests {
                 end: None,
             },
             stop_on_fail: false,
+            allow_sponsored_refill: false,
+            refund_split: None,
+            preferred_agent: None,
+            fail_mode: FailMode::StopOnFirstFail,
+            agent_fee_override: None,
+            on_success_task: None,
+            label: None,
+            memo: None,
+            return_surplus: false,
             total_deposit: Default::default(),
             actions: vec![Action {
                 msg: CosmosMsg::Wasm(WasmMsg::ClearAdmin {
@@ -893,6 +1839,11 @@ mod tests {
                 contract_addr: Addr::unchecked("foo"),
                 msg: Binary("bar".into()),
             }]),
+            last_executed_slot: None,
+            executions_count: 0,
+            failure_streak: 0,
+            created_at_height: 0,
+            created_at_time: Default::default(),
         };
 
         let message = format!(
@@ -909,4 +1860,93 @@ mod tests {
         assert_eq!(encoded, task.to_hash());
         assert_eq!(bytes, task.to_hash_vec());
     }
+
+    #[test]
+    fn hashing_ignores_mutable_fields() {
+        let task = Task {
+            owner_id: Addr::unchecked("bob"),
+            interval: Interval::Block(5),
+            boundary: Boundary {
+                start: None,
+                end: None,
+            },
+            stop_on_fail: false,
+            allow_sponsored_refill: false,
+            refund_split: None,
+            preferred_agent: None,
+            fail_mode: FailMode::StopOnFirstFail,
+            agent_fee_override: None,
+            on_success_task: None,
+            label: None,
+            memo: None,
+            return_surplus: false,
+            total_deposit: coins(10, "atom"),
+            actions: vec![Action {
+                msg: CosmosMsg::Wasm(WasmMsg::ClearAdmin {
+                    contract_addr: "alice".to_string(),
+                }),
+                gas_limit: Some(5),
+            }],
+            rules: None,
+            last_executed_slot: None,
+            executions_count: 0,
+            failure_streak: 0,
+            created_at_height: 0,
+            created_at_time: Default::default(),
+        };
+
+        // refills & executions keep the same key
+        let refilled = Task {
+            total_deposit: vec![coin(25, "atom"), coin(5, "juno")],
+            last_executed_slot: Some(12345),
+            executions_count: 3,
+            failure_streak: 0,
+            created_at_height: 100,
+            created_at_time: Timestamp::from_seconds(100),
+            ..task.clone()
+        };
+        assert_eq!(task.to_hash(), refilled.to_hash());
+
+        // any change to what the task does is a different task
+        let other_actions = Task {
+            actions: vec![Action {
+                msg: CosmosMsg::Wasm(WasmMsg::ClearAdmin {
+                    contract_addr: "carol".to_string(),
+                }),
+                gas_limit: Some(5),
+            }],
+            ..task.clone()
+        };
+        let other_owner = Task {
+            owner_id: Addr::unchecked("alice"),
+            ..task.clone()
+        };
+        let other_interval = Task {
+            interval: Interval::Block(6),
+            ..task.clone()
+        };
+        let other_boundary = Task {
+            boundary: Boundary {
+                start: Some(BoundarySpec::Height(4)),
+                end: None,
+            },
+            ..task.clone()
+        };
+        let other_rules = Task {
+            rules: Some(vec![Rule {
+                contract_addr: Addr::unchecked("foo"),
+                msg: Binary("bar".into()),
+            }]),
+            ..task.clone()
+        };
+        for other in [
+            other_actions,
+            other_owner,
+            other_interval,
+            other_boundary,
+            other_rules,
+        ] {
+            assert_ne!(task.to_hash(), other.to_hash());
+        }
+    }
 }